@@ -1,7 +1,12 @@
 use bencher::{Bencher, benchmark_group, benchmark_main};
-use blaze_db::prelude::{EmbeddingStore, Ingestor};
+use blaze_db::prelude::{EmbeddingStore, Ingestor, Kernel, Metrics, SearchQuery, VectorData};
+use blaze_db::utils::EmbeddingData;
 use tokio::runtime::Runtime;
 
+/// Shape of the synthetic store used by the search benches (Qwen3-sized vectors)
+const VECTORS: usize = 10_000;
+const DIMENSIONS: usize = 1024;
+
 fn read(b: &mut Bencher) {
     let ingest = Ingestor::new("./sample/War_and_peace.txt", 512);
     b.iter(|| {
@@ -18,5 +23,91 @@ fn load(b: &mut Bencher) {
     });
 }
 
-benchmark_group!(benches, read, load);
+fn random_values(count: usize, seed: u64) -> Vec<f32> {
+    let mut state = seed;
+    (0..count)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((state >> 33) as f32 / (1u64 << 31) as f32) * 2.0 - 1.0
+        })
+        .collect()
+}
+
+fn synthetic_store() -> VectorData {
+    let items = (0..VECTORS)
+        .map(|i| EmbeddingData {
+            index: i,
+            chunk: format!("chunk {}", i),
+            embedding: random_values(DIMENSIONS, i as u64),
+            dimensions: DIMENSIONS,
+            ..Default::default()
+        })
+        .collect();
+    let mut data = VectorData::default();
+    data.apply(EmbeddingStore::new(0, items));
+    data
+}
+
+/// Brute-force search over the whole store; throughput is vector bytes scanned per second
+fn search(b: &mut Bencher, metric: Metrics) {
+    let data = synthetic_store();
+    let query = SearchQuery::new(10, random_values(DIMENSIONS, u64::MAX), metric);
+    b.bytes = (VECTORS * DIMENSIONS * size_of::<f32>()) as u64;
+    b.iter(|| query.search(&data));
+}
+
+fn search_cosine(b: &mut Bencher) {
+    search(b, Metrics::Cosine);
+}
+
+fn search_euclidean(b: &mut Bencher) {
+    search(b, Metrics::Euclidean);
+}
+
+fn search_dot_product(b: &mut Bencher) {
+    search(b, Metrics::DotProduct);
+}
+
+/// Single-threaded kernel throughput over one row of the store
+fn kernel(b: &mut Bencher, kernel: Kernel, squared_l2: bool) {
+    let a = random_values(DIMENSIONS, 1);
+    let c = random_values(DIMENSIONS, 2);
+    b.bytes = (2 * DIMENSIONS * size_of::<f32>()) as u64;
+    if squared_l2 {
+        b.iter(|| kernel.squared_l2(&a, &c));
+    } else {
+        b.iter(|| kernel.dot(&a, &c));
+    }
+}
+
+fn kernel_dot_scalar(b: &mut Bencher) {
+    kernel(b, Kernel::scalar(), false);
+}
+
+fn kernel_dot_detected(b: &mut Bencher) {
+    kernel(b, Kernel::detect(), false);
+}
+
+fn kernel_l2_scalar(b: &mut Bencher) {
+    kernel(b, Kernel::scalar(), true);
+}
+
+fn kernel_l2_detected(b: &mut Bencher) {
+    kernel(b, Kernel::detect(), true);
+}
+
+benchmark_group!(
+    benches,
+    read,
+    load,
+    search_cosine,
+    search_euclidean,
+    search_dot_product,
+    kernel_dot_scalar,
+    kernel_dot_detected,
+    kernel_l2_scalar,
+    kernel_l2_detected
+);
 benchmark_main!(benches);
@@ -0,0 +1,80 @@
+use blaze_db::config::Config;
+use blaze_db::server::{AppState, serve};
+use clap::Parser;
+use colored::Colorize;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+#[derive(Parser)]
+#[command(name = "blaze-server", about = "HTTP API server for a Blaze-DB store")]
+struct Args {
+    /// Configuration file, defaults to `$BLAZE_CONFIG` or `config.toml`
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Directory holding the .bin batches, overrides `storage.path`
+    #[arg(long)]
+    store: Option<String>,
+    /// Address to listen on, overrides `server.bind`
+    #[arg(long)]
+    bind: Option<String>,
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+    let mut config = match Config::resolve(args.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", "Failed to load config".red().bold());
+            eprintln!("Error: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(store) = args.store {
+        config.storage.path = store;
+    }
+    if let Some(bind) = args.bind {
+        config.server.bind = bind;
+    }
+
+    let state = match AppState::from_config(&config).await {
+        Ok(state) => Arc::new(state),
+        Err(e) => {
+            eprintln!("{}", "Failed to load store".red().bold());
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    {
+        let data = state.data.read().await;
+        println!(
+            "Loaded {} vectors ({} dimensions) from {}",
+            data.total_vectors.to_string().cyan(),
+            data.dimensions.to_string().cyan(),
+            config.storage.path
+        );
+        if data.discarded_log_bytes > 0 {
+            eprintln!(
+                "{} dropped {} bytes of an incomplete write-ahead log record",
+                "Warning:".yellow().bold(),
+                data.discarded_log_bytes
+            );
+        }
+    }
+
+    let listener = match TcpListener::bind(&config.server.bind).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Failed to bind {}: {}", config.server.bind, e);
+            std::process::exit(1);
+        }
+    };
+    println!("Listening on {}", config.server.bind.green().bold());
+
+    if let Err(e) = serve(listener, state).await {
+        eprintln!("Server error: {}", e);
+        std::process::exit(1);
+    }
+}
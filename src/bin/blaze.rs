@@ -0,0 +1,9 @@
+use colored::Colorize;
+
+#[tokio::main]
+async fn main() {
+    if let Err(e) = blaze_db::cli::run().await {
+        eprintln!("{} {:#}", "Error:".red().bold(), e);
+        std::process::exit(1);
+    }
+}
@@ -1,3 +1,4 @@
+use blaze_db::config::Config;
 use blaze_db::prelude::EmbeddingStore;
 use colored::Colorize;
 
@@ -5,7 +6,15 @@ use colored::Colorize;
 async fn main() {
     println!();
 
-    match EmbeddingStore::read_binary("./embeddings").await {
+    let config = match Config::resolve(None) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error loading config: {:#}", e);
+            return;
+        }
+    };
+
+    match EmbeddingStore::read_binary(&config.storage.path).await {
         Ok(vector_data) => {
             println!("{}", "Successfully loaded embeddings".green().bold());
             println!();
@@ -1,3 +1,4 @@
+use blaze_db::config::Config;
 use blaze_db::prelude::*;
 use tokio::time::Instant;
 #[tokio::main]
@@ -5,10 +6,14 @@ pub async fn main() {
     let sample_text = "There is no Peace without War,\nWars should be celebrated,\nBecause it is the win against the evil.";
     let chunks = vec![sample_text.to_string()];
 
-    let provider = Provider::new(
-        "http://localhost:1234/v1/embeddings",
-        "text-embedding-qwen3-embedding-0.6b",
-    );
+    let config = match Config::resolve(None) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error loading config: {:#}", e);
+            return;
+        }
+    };
+    let provider = config.provider.embedder();
 
     match provider.fetch_embeddings(&chunks).await {
         Ok(embeddings) => {
@@ -19,12 +24,17 @@ pub async fn main() {
 
             let start = Instant::now();
 
-            let vector_data = EmbeddingStore::read_binary("./embeddings").await.unwrap();
+            let vector_data = EmbeddingStore::read_binary(&config.storage.path)
+                .await
+                .unwrap();
 
-            let search_query =
-                SearchQuery::new(5, embeddings.data[0].embedding.clone(), Metrics::Cosine);
+            let search_query = SearchQuery::new(
+                config.search.top_k,
+                embeddings.data[0].embedding.clone(),
+                config.search.metric,
+            );
 
-            let result = search_query.search(&vector_data);
+            let result = search_query.search(&vector_data).unwrap();
 
             println!("\nTop {} similar chunks:", search_query.top_k);
             for (i, item) in result.iter().enumerate() {
@@ -1,16 +1,25 @@
-use blaze_db::prelude::{EmbeddingStore, Ingestor, Provider};
+use blaze_db::config::Config;
+use blaze_db::prelude::{EmbeddingStore, Ingestor};
+use blaze_db::utils::{BatchOutcome, Pipeline};
 use colored::Colorize;
 use indicatif::{ProgressBar, ProgressStyle};
 use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
 
+const SOURCE: &str = "./sample/War_and_peace.txt";
+
 #[tokio::main]
 async fn main() {
-    let url = "http://localhost:1234/v1/embeddings";
-    let model = "text-embedding-qwen3-embedding-0.6b";
-    let provider = Provider::new(url, model);
+    let config = match Config::resolve(None) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error loading config: {:#}", e);
+            return;
+        }
+    };
+    let provider = config.provider.embedder();
 
-    let batch_size = 512;
-    let ingestor = Ingestor::new("./sample/War_and_peace.txt", batch_size);
+    let batch_size = config.ingest.batch_size;
+    let ingestor = Ingestor::new(SOURCE, batch_size);
 
     match ingestor.read_line() {
         Ok(batched_data) => {
@@ -41,21 +50,35 @@ async fn main() {
                     .progress_chars("##>-"),
             );
 
-            for (index, chunk) in batched_data.iter().enumerate() {
-                match provider.fetch_embeddings(chunk).await {
-                    Ok(embeddings) => {
-                        let embedding_store = EmbeddingStore::new(index, embeddings.data);
-                        embedding_store.debug_print();
-                        let filename = format!("./embeddings/embeddings_batch_{}", index);
-                        if let Err(e) = embedding_store.write_binary(&filename).await {
-                            eprintln!("Failed to write embeddings to file: {}", e);
-                        }
-                        progress_bar.inc(1);
-                    }
-                    Err(e) => {
-                        eprintln!("Error fetching embeddings: {}", e);
-                    }
+            let mut data = match EmbeddingStore::open(&config.storage.path).await {
+                Ok(data) => data,
+                Err(e) => {
+                    eprintln!("Error opening store: {:#}", e);
+                    return;
                 }
+            };
+            // A rerun after a failure only embeds the batches that are still missing
+            let pipeline = Pipeline::new(provider, &config.storage.path)
+                .with_concurrency(config.ingest.concurrency);
+            let job = format!("{}:{}", SOURCE, batch_size);
+            let result = pipeline
+                .run(&job, &batched_data, &mut data, |outcome| {
+                    if let BatchOutcome::Failed { batch, error } = outcome {
+                        progress_bar.println(format!("Error fetching batch {}: {}", batch, error));
+                    }
+                    progress_bar.inc(1);
+                })
+                .await;
+            progress_bar.finish();
+
+            match result {
+                Ok(report) => println!(
+                    "Stored {} batches, skipped {}, failed {}",
+                    report.stored.to_string().green(),
+                    report.skipped.to_string().cyan(),
+                    report.failed.len().to_string().red()
+                ),
+                Err(e) => eprintln!("Failed to write embeddings: {:#}", e),
             }
         }
 
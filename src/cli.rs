@@ -1,5 +1,589 @@
-#[allow(unused)]
+use crate::config::Config;
+use crate::core::{Filter, IndexType, Metrics, SearchQuery};
+use crate::server::{AppState, serve};
+use crate::utils::{
+    BatchOutcome, Chunker, CollectionSchema, Collections, DedupMethod, DedupPolicy,
+    EmbeddingProvider, EmbeddingStore, FileEntry, FileOutcome, Format, Ingestor, MappedStore,
+    Metadata, MetadataValue, Pipeline, STORE_SEGMENT_FILE_NAME, VectorData, checksum,
+};
+use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
-fn _run() {
-    todo!("Implement a CLI client for interacting with the database server");
+use colored::Colorize;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+#[derive(Parser)]
+#[command(name = "blaze", about = "Command line client for Blaze-DB")]
+pub struct Cli {
+    /// Configuration file, defaults to `$BLAZE_CONFIG` or `config.toml`
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+    /// Store directory, overrides `storage.path`
+    #[arg(long, global = true)]
+    pub store: Option<String>,
+    /// Named collection inside the store, overrides `storage.collection`
+    #[arg(long, global = true)]
+    pub collection: Option<String>,
+    /// Embedding endpoint, overrides `provider.url`
+    #[arg(long, global = true)]
+    pub url: Option<String>,
+    /// Embedding model, overrides `provider.model`
+    #[arg(long, global = true)]
+    pub model: Option<String>,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Embed a text, markdown, HTML, JSONL or CSV file and append it to the store, or
+    /// sync a directory: embed new and changed files, delete vectors of removed ones
+    Ingest {
+        file: PathBuf,
+        /// Chunks per provider request, overrides `ingest.batch_size`
+        #[arg(long)]
+        batch_size: Option<usize>,
+        /// Provider requests in flight, overrides `ingest.concurrency`
+        #[arg(long)]
+        concurrency: Option<usize>,
+        /// line, paragraph, sentence, window, recursive or markdown with default sizes,
+        /// overrides `ingest.chunker`
+        #[arg(long)]
+        chunker: Option<Chunker>,
+        /// text, markdown, html, jsonl or csv, overrides `ingest.format` and the extension
+        #[arg(long)]
+        format: Option<Format>,
+        /// JSONL key or CSV column holding text, repeatable; overrides `ingest.text_fields`
+        #[arg(long = "text-field")]
+        text_fields: Vec<String>,
+        /// JSONL key or CSV column kept as metadata, repeatable; overrides
+        /// `ingest.metadata_fields`
+        #[arg(long = "metadata-field")]
+        metadata_fields: Vec<String>,
+        /// Glob of directory files to ingest, e.g. '**/*.md', repeatable; overrides
+        /// `ingest.include`
+        #[arg(long)]
+        include: Vec<String>,
+        /// Glob of directory files or subdirectories to skip, repeatable; overrides
+        /// `ingest.exclude`
+        #[arg(long)]
+        exclude: Vec<String>,
+        /// none, exact, minhash, simhash or cosine with default thresholds, overrides
+        /// `ingest.dedup.method`
+        #[arg(long)]
+        dedup: Option<DedupMethod>,
+        /// keep_first, merge_metadata or skip, overrides `ingest.dedup.policy`. Metadata
+        /// is merged and skip drops every copy only within one file, or one block with
+        /// --stream; copies of an earlier file or block are just dropped
+        #[arg(long)]
+        dedup_policy: Option<DedupPolicy>,
+        /// Read a text or JSONL file a block at a time instead of whole, for files
+        /// larger than memory; dedups against recent chunks only, and not with cosine
+        #[arg(long)]
+        stream: bool,
+    },
+    /// Embed a query and print the most similar chunks
+    Search {
+        text: String,
+        /// Number of results, overrides `search.top_k`
+        #[arg(long)]
+        top_k: Option<usize>,
+        /// cosine, euclidean or dot, overrides `search.metric`
+        #[arg(long)]
+        metric: Option<Metrics>,
+        /// Metadata filter as JSON, e.g. '{"eq": {"field": "source", "value": "a.txt"}}'
+        #[arg(long)]
+        filter: Option<String>,
+    },
+    /// Print store statistics
+    Stats,
+    /// Dump every vector as JSON lines
+    Export {
+        /// Output file, stdout when omitted
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Write the store into a memory-mapped segment that search maps instead of loading
+    /// every batch; batches written later are read on top of it
+    Seal,
+    /// Delete vectors by id
+    Delete {
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+    /// Run the HTTP API server
+    Serve {
+        /// Address to listen on, overrides `server.bind`
+        #[arg(long)]
+        bind: Option<String>,
+    },
+    /// Manage named collections
+    #[command(subcommand)]
+    Collection(CollectionCommand),
+}
+
+#[derive(Subcommand)]
+pub enum CollectionCommand {
+    /// Declare a new collection
+    Create {
+        name: String,
+        #[arg(long)]
+        dimensions: usize,
+        /// Default search metric, overrides `search.metric`
+        #[arg(long)]
+        metric: Option<Metrics>,
+        /// flat, hnsw or ivf
+        #[arg(long, default_value = "flat")]
+        index: IndexType,
+    },
+    /// List collections and their schemas
+    List,
+    /// Delete a collection and all of its vectors
+    Drop { name: String },
+    /// Rename a collection
+    Rename { from: String, to: String },
+}
+
+/// Settings resolved from the config file, the environment and command line flags
+struct Settings {
+    /// Directory of the selected collection, or `storage.path` when there is none
+    store: String,
+    provider: Arc<dyn EmbeddingProvider>,
+    config: Config,
+    collection: Option<CollectionSchema>,
+}
+
+impl Cli {
+    async fn settings(&self) -> Result<Settings> {
+        let mut config = Config::resolve(self.config.as_deref())?;
+        if let Some(store) = &self.store {
+            config.storage.path = store.clone();
+        }
+        if let Some(collection) = &self.collection {
+            config.storage.collection = Some(collection.clone());
+        }
+        if let Some(url) = &self.url {
+            config.provider.url = url.clone();
+        }
+        if let Some(model) = &self.model {
+            config.provider.model = model.clone();
+        }
+
+        // A collection's schema supplies the model and the default metric; collection
+        // management itself always works on the root
+        let (store, collection) = if matches!(self.command, Command::Collection(_)) {
+            (config.storage.path.clone(), None)
+        } else {
+            config.select_collection().await?
+        };
+        if let (Some(schema), Some(model)) = (&collection, &self.model)
+            && *model != schema.model
+        {
+            anyhow::bail!(
+                "Collection {} was embedded with {}, not {}",
+                schema.name,
+                schema.model,
+                model
+            );
+        }
+
+        Ok(Settings {
+            store,
+            provider: config.provider.embedder(),
+            config,
+            collection,
+        })
+    }
+}
+
+/// Parse the process arguments and run the selected command
+pub async fn run() -> Result<()> {
+    run_with(Cli::parse()).await
+}
+
+pub async fn run_with(cli: Cli) -> Result<()> {
+    let settings = cli.settings().await?;
+
+    match cli.command {
+        Command::Ingest {
+            file,
+            batch_size,
+            concurrency,
+            chunker,
+            format,
+            text_fields,
+            metadata_fields,
+            include,
+            exclude,
+            dedup,
+            dedup_policy,
+            stream,
+        } => {
+            let batch_size = batch_size.unwrap_or(settings.config.ingest.batch_size);
+            let concurrency = concurrency.unwrap_or(settings.config.ingest.concurrency);
+            let chunker = chunker.unwrap_or(settings.config.ingest.chunker.clone());
+            let mut reader = settings.config.ingest.reader.clone();
+            if let Some(format) = format {
+                reader.format = Some(format);
+            }
+            if !text_fields.is_empty() {
+                reader.text_fields = text_fields;
+            }
+            if !metadata_fields.is_empty() {
+                reader.metadata_fields = metadata_fields;
+            }
+            if !file.is_file() && !file.is_dir() {
+                anyhow::bail!("Source file must exist or be a directory: {:?}", file);
+            }
+            if !batch_size.is_multiple_of(8) {
+                anyhow::bail!("Batch size must be a multiple of 8");
+            }
+            let mut deduplication = settings.config.ingest.dedup;
+            if let Some(method) = dedup {
+                deduplication.method = method;
+            }
+            if let Some(policy) = dedup_policy {
+                deduplication.policy = policy;
+            }
+            let ingestor = Ingestor::new(&file, batch_size)
+                .with_chunker(chunker)
+                .with_reader(reader)
+                .with_dedup(deduplication);
+            if file.is_dir() {
+                let ingestor = ingestor
+                    .with_include(non_empty_or(include, &settings.config.ingest.include))
+                    .with_exclude(non_empty_or(exclude, &settings.config.ingest.exclude));
+                sync(&settings, ingestor, concurrency).await
+            } else {
+                ingest(&settings, ingestor, concurrency, stream).await
+            }
+        }
+        Command::Search {
+            text,
+            top_k,
+            metric,
+            filter,
+        } => {
+            let top_k = top_k.unwrap_or(settings.config.search.top_k);
+            let metric = metric.unwrap_or(settings.config.search.metric);
+            search(&settings, &text, top_k, metric, filter.as_deref()).await
+        }
+        Command::Stats => stats(&settings).await,
+        Command::Export { output } => export(&settings, output).await,
+        Command::Seal => seal(&settings).await,
+        Command::Delete { ids } => delete(&settings, ids).await,
+        Command::Serve { bind } => {
+            let bind = bind.unwrap_or(settings.config.server.bind.clone());
+            let state = AppState::from_config(&settings.config).await?;
+            let listener = TcpListener::bind(&bind)
+                .await
+                .with_context(|| format!("Failed to bind {}", bind))?;
+            println!("Listening on {}", bind.green().bold());
+            serve(listener, Arc::new(state)).await
+        }
+        Command::Collection(command) => collection(&settings, command).await,
+    }
+}
+
+async fn collection(settings: &Settings, command: CollectionCommand) -> Result<()> {
+    let mut collections = Collections::open(&settings.config.storage.path).await?;
+    match command {
+        CollectionCommand::Create {
+            name,
+            dimensions,
+            metric,
+            index,
+        } => {
+            let schema = CollectionSchema::new(
+                &name,
+                dimensions,
+                metric.unwrap_or(settings.config.search.metric),
+                settings.provider.model(),
+            )
+            .with_index(index);
+            collections.create(schema).await?;
+            println!("Created collection {}", name.cyan());
+        }
+        CollectionCommand::List => {
+            for schema in collections.list() {
+                println!(
+                    "{} {} dimensions, {:?}, {:?} index, model {}",
+                    schema.name.cyan(),
+                    schema.dimensions,
+                    schema.metric,
+                    schema.index,
+                    schema.model
+                );
+            }
+        }
+        CollectionCommand::Drop { name } => {
+            collections.drop(&name).await?;
+            println!("Dropped collection {}", name.cyan());
+        }
+        CollectionCommand::Rename { from, to } => {
+            collections.rename(&from, &to).await?;
+            println!("Renamed collection {} to {}", from.cyan(), to.cyan());
+        }
+    }
+    Ok(())
+}
+
+/// Globs from the command line, or from the config file when none were given
+fn non_empty_or(globs: Vec<String>, config: &[String]) -> Vec<String> {
+    if globs.is_empty() {
+        config.to_vec()
+    } else {
+        globs
+    }
+}
+
+fn pipeline(settings: &Settings, concurrency: usize) -> Pipeline {
+    let pipeline =
+        Pipeline::new(settings.provider.clone(), &settings.store).with_concurrency(concurrency);
+    match &settings.collection {
+        Some(schema) => pipeline.with_schema(schema.clone()),
+        None => pipeline,
+    }
+}
+
+async fn ingest(
+    settings: &Settings,
+    ingestor: Ingestor,
+    concurrency: usize,
+    stream: bool,
+) -> Result<()> {
+    let mut data = EmbeddingStore::open(&settings.store).await?;
+    warn_discarded(&data, &settings.store);
+    // Keyed by the canonical path like a sync, so `a.txt` and `./a.txt` are one source
+    let source = std::fs::canonicalize(&ingestor.source)
+        .with_context(|| format!("Failed to resolve {:?}", ingestor.source))?
+        .to_string_lossy()
+        .to_string();
+    let mut job = format!(
+        "{}:{}:{}",
+        source,
+        ingestor.batch_size,
+        serde_json::to_string(&(&ingestor.chunker, &ingestor.reader, &ingestor.dedup))?
+    );
+
+    let mut metadata = Metadata::new();
+    metadata.insert("source".to_string(), MetadataValue::from(source.clone()));
+    let pipeline = pipeline(settings, concurrency)
+        .with_id_prefix(format!("{}:", source))
+        .with_metadata(metadata);
+
+    let progress_bar = if stream {
+        ProgressBar::new_spinner()
+    } else {
+        ProgressBar::new(0)
+    };
+    progress_bar.set_style(
+        ProgressStyle::with_template(if stream {
+            "{spinner} {pos} batches"
+        } else {
+            "[{bar:40.cyan/blue}] {pos}/{len} batches"
+        })
+        .expect("Invalid progress template")
+        .progress_chars("##>-"),
+    );
+    let on_batch = |outcome: &BatchOutcome| {
+        if let BatchOutcome::Failed { batch, error } = outcome {
+            progress_bar.println(format!("Error fetching batch {}: {}", batch, error));
+        }
+        progress_bar.inc(1);
+    };
+
+    // Batches stored by an interrupted run of the same file and chunking are skipped
+    let report = if stream {
+        // Streamed batches are only numbered, so any change to the file starts over
+        let (size, modified) = FileEntry::stat(&ingestor.source)?;
+        job.push_str(&format!(":{}:{}", size, modified));
+        let batches = ingestor.stream(concurrency)?;
+        pipeline
+            .run_stream(&job, batches, &mut data, on_batch)
+            .await?
+    } else {
+        // The content hash keeps a checkpoint of an edited file from being resumed
+        let bytes = tokio::fs::read(&ingestor.source)
+            .await
+            .with_context(|| format!("Failed to read {:?}", ingestor.source))?;
+        job.push_str(&format!(":{:016x}", checksum(&bytes)));
+        drop(bytes);
+        let batches = ingestor.read_chunks()?;
+        progress_bar.set_length(batches.len().max(1) as u64);
+        pipeline
+            .run_chunks(&job, &batches, &mut data, on_batch)
+            .await?
+    };
+    progress_bar.finish();
+
+    println!(
+        "Ingested {} batches into {} ({} already done, {} failed)",
+        report.stored.to_string().green(),
+        settings.store,
+        report.skipped.to_string().cyan(),
+        report.failed.len().to_string().red()
+    );
+    if !report.is_complete() {
+        println!("Run the same command again to retry the failed batches");
+    }
+    Ok(())
+}
+
+async fn sync(settings: &Settings, ingestor: Ingestor, concurrency: usize) -> Result<()> {
+    let mut data = EmbeddingStore::open(&settings.store).await?;
+    warn_discarded(&data, &settings.store);
+    let report = pipeline(settings, concurrency)
+        .sync(&ingestor, &mut data, |outcome| match outcome {
+            FileOutcome::Unchanged { .. } => {}
+            FileOutcome::Added { path, chunks } => {
+                println!("{} {} ({} chunks)", "Added".green(), path, chunks)
+            }
+            FileOutcome::Updated { path, chunks } => {
+                println!("{} {} ({} chunks)", "Updated".cyan(), path, chunks)
+            }
+            FileOutcome::Removed { path, vectors } => {
+                println!("{} {} ({} vectors)", "Removed".yellow(), path, vectors)
+            }
+            FileOutcome::Failed { path, error } => {
+                println!("{} {}: {}", "Failed".red(), path, error)
+            }
+        })
+        .await?;
+
+    println!(
+        "Synced {} into {} ({} added, {} updated, {} unchanged, {} removed, {} failed)",
+        ingestor.source.display(),
+        settings.store,
+        report.added.to_string().green(),
+        report.updated.to_string().cyan(),
+        report.unchanged,
+        report.removed.to_string().yellow(),
+        report.failed.len().to_string().red()
+    );
+    if !report.is_complete() {
+        println!("Run the same command again to retry the failed files");
+    }
+    Ok(())
+}
+
+async fn search(
+    settings: &Settings,
+    text: &str,
+    top_k: usize,
+    metric: Metrics,
+    filter: Option<&str>,
+) -> Result<()> {
+    let embeddings = settings.provider.fetch_query(text).await?;
+    let query_vector = embeddings
+        .data
+        .into_iter()
+        .next()
+        .map(|e| e.embedding)
+        .context("Provider returned no embedding")?;
+
+    let mut query = SearchQuery::new(top_k, query_vector, metric);
+    if let Some(filter) = filter {
+        let filter: Filter = serde_json::from_str(filter).context("Invalid filter")?;
+        query = query.with_filter(filter);
+    }
+
+    // A collection is searched through its declared index, built on first use; a
+    // metric other than the collection's is ranked exactly
+    let index_type = match &settings.collection {
+        Some(schema) if schema.metric == metric => schema.index,
+        _ => IndexType::Flat,
+    };
+    let sealed = Path::new(&settings.store).join(STORE_SEGMENT_FILE_NAME);
+    let results = if index_type == IndexType::Flat && sealed.exists() {
+        // An exact scan of a sealed store runs straight off the memory map
+        let store = MappedStore::open(&settings.store).await?;
+        warn_discarded(store.delta(), &settings.store);
+        query.search_source(&store)?
+    } else {
+        let data = EmbeddingStore::read_binary(&settings.store).await?;
+        warn_discarded(&data, &settings.store);
+        query.check_dimensions(data.dimensions)?;
+        let index = index_type.open(&settings.store, &data, metric).await?;
+        query.search_in(&data, index.as_deref())?
+    };
+
+    for (i, item) in results.iter().enumerate() {
+        println!();
+        println!("{} {} ({})", "Result".blue(), i + 1, item.id.cyan());
+        println!("Chunk: {}", item.chunk);
+        println!("Score: {:.4}", item.score);
+    }
+    Ok(())
+}
+
+/// Warn when the write-ahead log of `store` ended in an incomplete record
+fn warn_discarded(data: &VectorData, store: &str) {
+    if data.discarded_log_bytes > 0 {
+        eprintln!(
+            "{} ignored {} bytes of an incomplete write-ahead log record in {}",
+            "Warning:".yellow().bold(),
+            data.discarded_log_bytes,
+            store
+        );
+    }
+}
+
+async fn stats(settings: &Settings) -> Result<()> {
+    let data = EmbeddingStore::read_binary(&settings.store).await?;
+    warn_discarded(&data, &settings.store);
+    println!("{}", "Stats:".yellow().bold());
+    println!(" Total vectors: {}", data.total_vectors.to_string().cyan());
+    println!(" Dimensions: {}", data.dimensions.to_string().cyan());
+    println!(" Memory Usage: {:.2}MB", data.memory_usage_mb());
+    Ok(())
+}
+
+async fn export(settings: &Settings, output: Option<PathBuf>) -> Result<()> {
+    let data = EmbeddingStore::read_binary(&settings.store).await?;
+    warn_discarded(&data, &settings.store);
+    let writer: Box<dyn Write> = match &output {
+        Some(path) => Box::new(
+            std::fs::File::create(path).with_context(|| format!("Failed to create {:?}", path))?,
+        ),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    let mut writer = BufWriter::new(writer);
+
+    for idx in (0..data.embedding.len()).filter(|idx| !data.is_deleted(*idx)) {
+        let line = serde_json::json!({
+            "id": data.get_id(idx),
+            "chunk": data.get_chunk(idx),
+            "metadata": data.get_metadata(idx),
+            "embedding": data.get_vector(idx),
+        });
+        writeln!(writer, "{}", line)?;
+    }
+    writer.flush()?;
+
+    if let Some(path) = output {
+        eprintln!("Exported {} vectors to {:?}", data.total_vectors, path);
+    }
+    Ok(())
+}
+
+async fn seal(settings: &Settings) -> Result<()> {
+    let sealed = EmbeddingStore::seal(&settings.store).await?;
+    println!(
+        "Sealed {} vectors into {:?}",
+        sealed.to_string().green(),
+        Path::new(&settings.store).join(STORE_SEGMENT_FILE_NAME)
+    );
+    Ok(())
+}
+
+async fn delete(settings: &Settings, ids: Vec<String>) -> Result<()> {
+    let mut data = EmbeddingStore::open(&settings.store).await?;
+    warn_discarded(&data, &settings.store);
+    let deleted = EmbeddingStore::delete(&settings.store, &mut data, ids).await?;
+    println!("Deleted {} vectors", deleted.to_string().cyan());
+    Ok(())
 }
@@ -0,0 +1,398 @@
+use crate::core::Metrics;
+use crate::utils::{
+    Chunker, CollectionSchema, Collections, DEFAULT_CONCURRENCY, DEFAULT_CONNECT_TIMEOUT,
+    DEFAULT_HASHING_DIMENSIONS, DEFAULT_INPUT_TYPE, DEFAULT_TIMEOUT, Dedup, DedupMethod,
+    EmbeddingProvider, HashingProvider, Provider, ProviderKind, Reader, RetryPolicy,
+};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Config file used when neither a flag nor `BLAZE_CONFIG` names one
+pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+/// Settings read from `config.toml`; every section and key is optional.
+///
+/// Every value but the chunker's `separators` and window `unit` can be overridden with
+/// a `BLAZE_<SECTION>_<KEY>` environment variable, e.g. `BLAZE_PROVIDER_URL` or
+/// `BLAZE_SEARCH_TOP_K`. The chunker and dedup method are picked by name with
+/// `BLAZE_INGEST_CHUNKER` and `BLAZE_INGEST_DEDUP` and tuned with keys such as
+/// `BLAZE_INGEST_CHUNKER_SIZE` or `BLAZE_INGEST_DEDUP_THRESHOLD`; lists are
+/// comma-separated.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Config {
+    pub storage: StorageConfig,
+    pub provider: ProviderConfig,
+    pub ingest: IngestConfig,
+    pub search: SearchConfig,
+    pub server: ServerConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct StorageConfig {
+    /// Directory holding the `.bin` batches, or the collections when one is selected
+    pub path: String,
+    /// Named collection under `path` to use instead of `path` itself
+    pub collection: Option<String>,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            path: "./embeddings".to_string(),
+            collection: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ProviderConfig {
+    /// API format of `url`: `openai`, `ollama`, `tei` or `cohere`
+    pub kind: ProviderKind,
+    /// Embed offline with a `HashingProvider` instead of calling `url`
+    pub offline: bool,
+    pub url: String,
+    pub model: String,
+    pub api_key: Option<String>,
+    /// Embedding size requested from OpenAI models that support shortening, and the
+    /// size of hashing embeddings
+    pub dimensions: Option<usize>,
+    /// Cohere input type of ingested text
+    pub input_type: String,
+    /// Whole-request timeout in seconds
+    pub timeout_secs: u64,
+    pub connect_timeout_secs: u64,
+    /// Retries for 429 and 5xx responses, timeouts and refused connections
+    pub max_retries: u32,
+    /// Client-side limits; unlimited when unset
+    pub requests_per_minute: Option<u32>,
+    pub tokens_per_minute: Option<u32>,
+}
+
+impl Default for ProviderConfig {
+    fn default() -> Self {
+        Self {
+            kind: ProviderKind::default(),
+            offline: false,
+            url: "http://localhost:1234/v1/embeddings".to_string(),
+            model: "text-embedding-qwen3-embedding-0.6b".to_string(),
+            api_key: None,
+            dimensions: None,
+            input_type: DEFAULT_INPUT_TYPE.to_string(),
+            timeout_secs: DEFAULT_TIMEOUT.as_secs(),
+            connect_timeout_secs: DEFAULT_CONNECT_TIMEOUT.as_secs(),
+            max_retries: RetryPolicy::default().max_retries,
+            requests_per_minute: None,
+            tokens_per_minute: None,
+        }
+    }
+}
+
+impl ProviderConfig {
+    /// The offline provider when `offline` is set, otherwise the HTTP client
+    pub fn embedder(&self) -> Arc<dyn EmbeddingProvider> {
+        if self.offline {
+            Arc::new(HashingProvider::new(
+                self.dimensions.unwrap_or(DEFAULT_HASHING_DIMENSIONS),
+            ))
+        } else {
+            Arc::new(self.build())
+        }
+    }
+
+    /// HTTP client for `url`, whatever `offline` says
+    pub fn build(&self) -> Provider {
+        let mut provider = Provider::new(&self.url, &self.model)
+            .with_kind(self.kind)
+            .with_input_type(&self.input_type)
+            .with_timeouts(
+                Duration::from_secs(self.connect_timeout_secs),
+                Duration::from_secs(self.timeout_secs),
+            )
+            .with_retry(RetryPolicy {
+                max_retries: self.max_retries,
+                ..Default::default()
+            })
+            .with_rate_limit(self.requests_per_minute, self.tokens_per_minute);
+        if let Some(dimensions) = self.dimensions {
+            provider = provider.with_dimensions(dimensions);
+        }
+        match &self.api_key {
+            Some(api_key) => provider.with_api_key(api_key),
+            None => provider,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct IngestConfig {
+    pub batch_size: usize,
+    /// Provider requests in flight during ingestion
+    pub concurrency: usize,
+    /// How files are cut into chunks, e.g. `{ kind = "recursive", size = 800 }`
+    pub chunker: Chunker,
+    /// `format`, `text_fields`, `metadata_fields` and `delimiter` of source files
+    #[serde(flatten)]
+    pub reader: Reader,
+    /// Duplicate chunks dropped before embedding, e.g.
+    /// `{ method = "minhash", threshold = 0.9, policy = "skip" }`
+    pub dedup: Dedup,
+    /// Globs selecting files of a source directory, every file when empty
+    pub include: Vec<String>,
+    /// Globs of files and directories to skip in a source directory
+    pub exclude: Vec<String>,
+}
+
+impl Default for IngestConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 512,
+            concurrency: DEFAULT_CONCURRENCY,
+            chunker: Chunker::default(),
+            reader: Reader::default(),
+            dedup: Dedup::default(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SearchConfig {
+    pub metric: Metrics,
+    pub top_k: usize,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            metric: Metrics::Cosine,
+            top_k: 5,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub bind: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: "127.0.0.1:8080".to_string(),
+        }
+    }
+}
+
+impl Config {
+    /// Parse a TOML configuration file
+    pub fn load(path: impl AsRef<Path>) -> Result<Config> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config: {:?}", path))?;
+        Self::parse(&contents).with_context(|| format!("Failed to parse config: {:?}", path))
+    }
+
+    /// Parse a configuration file, falling back to defaults when it does not exist
+    pub fn load_or_default(path: impl AsRef<Path>) -> Result<Config> {
+        if path.as_ref().exists() {
+            Self::load(path)
+        } else {
+            Ok(Config::default())
+        }
+    }
+
+    pub fn parse(contents: &str) -> Result<Config> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    /// Load `path` (or the default config file) and apply environment overrides
+    pub fn resolve(path: Option<&Path>) -> Result<Config> {
+        let path = path
+            .map(Path::to_path_buf)
+            .unwrap_or_else(Self::default_path);
+        let mut config = Self::load_or_default(path)?;
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    /// Directory and schema of the collection named by `storage.collection`, whose model
+    /// and metric replace `provider.model` and `search.metric`; without a collection,
+    /// `storage.path` itself and no schema
+    pub async fn select_collection(&mut self) -> Result<(String, Option<CollectionSchema>)> {
+        let Some(name) = &self.storage.collection else {
+            return Ok((self.storage.path.clone(), None));
+        };
+        let collections = Collections::open(&self.storage.path).await?;
+        let schema = collections.schema(name)?.clone();
+        self.provider.model = schema.model.clone();
+        self.search.metric = schema.metric;
+        let store = collections.path(name).to_string_lossy().to_string();
+        Ok((store, Some(schema)))
+    }
+
+    /// Config file named by `BLAZE_CONFIG`, or `config.toml` in the working directory
+    pub fn default_path() -> PathBuf {
+        std::env::var("BLAZE_CONFIG")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from(DEFAULT_CONFIG_PATH))
+    }
+
+    /// Override values from `BLAZE_*` environment variables
+    pub fn apply_env(&mut self) -> Result<()> {
+        self.apply_overrides(|key| std::env::var(key).ok())
+    }
+
+    /// Override values from any variable source, keyed like the environment variables
+    pub fn apply_overrides(&mut self, vars: impl Fn(&str) -> Option<String>) -> Result<()> {
+        if let Some(path) = vars("BLAZE_STORAGE_PATH") {
+            self.storage.path = path;
+        }
+        if let Some(collection) = vars("BLAZE_STORAGE_COLLECTION") {
+            self.storage.collection = Some(collection);
+        }
+        if let Some(kind) = vars("BLAZE_PROVIDER_KIND") {
+            self.provider.kind = kind.parse()?;
+        }
+        if let Some(offline) = parse_var(&vars, "BLAZE_PROVIDER_OFFLINE")? {
+            self.provider.offline = offline;
+        }
+        if let Some(url) = vars("BLAZE_PROVIDER_URL") {
+            self.provider.url = url;
+        }
+        if let Some(model) = vars("BLAZE_PROVIDER_MODEL") {
+            self.provider.model = model;
+        }
+        if let Some(api_key) = vars("BLAZE_PROVIDER_API_KEY") {
+            self.provider.api_key = Some(api_key);
+        }
+        if let Some(dimensions) = parse_var(&vars, "BLAZE_PROVIDER_DIMENSIONS")? {
+            self.provider.dimensions = Some(dimensions);
+        }
+        if let Some(input_type) = vars("BLAZE_PROVIDER_INPUT_TYPE") {
+            self.provider.input_type = input_type;
+        }
+        if let Some(timeout) = parse_var(&vars, "BLAZE_PROVIDER_TIMEOUT_SECS")? {
+            self.provider.timeout_secs = timeout;
+        }
+        if let Some(timeout) = parse_var(&vars, "BLAZE_PROVIDER_CONNECT_TIMEOUT_SECS")? {
+            self.provider.connect_timeout_secs = timeout;
+        }
+        if let Some(retries) = parse_var(&vars, "BLAZE_PROVIDER_MAX_RETRIES")? {
+            self.provider.max_retries = retries;
+        }
+        if let Some(limit) = parse_var(&vars, "BLAZE_PROVIDER_REQUESTS_PER_MINUTE")? {
+            self.provider.requests_per_minute = Some(limit);
+        }
+        if let Some(limit) = parse_var(&vars, "BLAZE_PROVIDER_TOKENS_PER_MINUTE")? {
+            self.provider.tokens_per_minute = Some(limit);
+        }
+        if let Some(batch_size) = parse_var(&vars, "BLAZE_INGEST_BATCH_SIZE")? {
+            self.ingest.batch_size = batch_size;
+        }
+        if let Some(concurrency) = parse_var(&vars, "BLAZE_INGEST_CONCURRENCY")? {
+            self.ingest.concurrency = concurrency;
+        }
+        if let Some(chunker) = vars("BLAZE_INGEST_CHUNKER") {
+            self.ingest.chunker = chunker.parse()?;
+        }
+        if let Some(value) = parse_var(&vars, "BLAZE_INGEST_CHUNKER_SIZE")? {
+            match &mut self.ingest.chunker {
+                Chunker::Window { size, .. }
+                | Chunker::Recursive { size, .. }
+                | Chunker::Markdown { size } => *size = value,
+                other => anyhow::bail!("The {:?} chunker has no size", other),
+            }
+        }
+        if let Some(value) = parse_var(&vars, "BLAZE_INGEST_CHUNKER_OVERLAP")? {
+            match &mut self.ingest.chunker {
+                Chunker::Window { overlap, .. } | Chunker::Recursive { overlap, .. } => {
+                    *overlap = value
+                }
+                other => anyhow::bail!("The {:?} chunker has no overlap", other),
+            }
+        }
+        if let Some(format) = vars("BLAZE_INGEST_FORMAT") {
+            self.ingest.reader.format = Some(format.parse()?);
+        }
+        if let Some(fields) = vars("BLAZE_INGEST_TEXT_FIELDS") {
+            self.ingest.reader.text_fields = split_list(&fields);
+        }
+        if let Some(fields) = vars("BLAZE_INGEST_METADATA_FIELDS") {
+            self.ingest.reader.metadata_fields = split_list(&fields);
+        }
+        if let Some(delimiter) = parse_var(&vars, "BLAZE_INGEST_DELIMITER")? {
+            self.ingest.reader.delimiter = Some(delimiter);
+        }
+        if let Some(method) = vars("BLAZE_INGEST_DEDUP") {
+            self.ingest.dedup.method = method.parse()?;
+        }
+        if let Some(value) = parse_var(&vars, "BLAZE_INGEST_DEDUP_THRESHOLD")? {
+            match &mut self.ingest.dedup.method {
+                DedupMethod::MinHash { threshold } | DedupMethod::Cosine { threshold } => {
+                    *threshold = value
+                }
+                other => anyhow::bail!("The {:?} dedup method has no threshold", other),
+            }
+        }
+        if let Some(value) = parse_var(&vars, "BLAZE_INGEST_DEDUP_MAX_DISTANCE")? {
+            match &mut self.ingest.dedup.method {
+                DedupMethod::SimHash { max_distance } => *max_distance = value,
+                other => anyhow::bail!("The {:?} dedup method has no max_distance", other),
+            }
+        }
+        if let Some(policy) = vars("BLAZE_INGEST_DEDUP_POLICY") {
+            self.ingest.dedup.policy = policy.parse()?;
+        }
+        if let Some(globs) = vars("BLAZE_INGEST_INCLUDE") {
+            self.ingest.include = split_list(&globs);
+        }
+        if let Some(globs) = vars("BLAZE_INGEST_EXCLUDE") {
+            self.ingest.exclude = split_list(&globs);
+        }
+        if let Some(metric) = vars("BLAZE_SEARCH_METRIC") {
+            self.search.metric = metric.parse()?;
+        }
+        if let Some(top_k) = parse_var(&vars, "BLAZE_SEARCH_TOP_K")? {
+            self.search.top_k = top_k;
+        }
+        if let Some(bind) = vars("BLAZE_SERVER_BIND") {
+            self.server.bind = bind;
+        }
+        Ok(())
+    }
+}
+
+/// Comma-separated list of names
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Parse the variable `key` when it is set
+fn parse_var<T: FromStr>(vars: impl Fn(&str) -> Option<String>, key: &str) -> Result<Option<T>>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    vars(key)
+        .map(|value| {
+            value
+                .parse()
+                .with_context(|| format!("Invalid {}: {}", key, value))
+        })
+        .transpose()
+}
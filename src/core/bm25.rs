@@ -0,0 +1,284 @@
+use crate::core::search::{SearchQuery, VectorSource};
+use crate::core::topk::top_k;
+use crate::utils::write_atomic;
+use anyhow::{Context, Result};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use tokio::fs;
+use tokio::task::spawn_blocking;
+
+/// File name of the persisted BM25 index, stored next to the `.bin` batches
+const BM25_FILE_NAME: &str = "index.bm25";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Bm25Config {
+    /// Term frequency saturation
+    pub k1: f32,
+    /// Document length normalization, from 0 (none) to 1 (full)
+    pub b: f32,
+}
+
+impl Default for Bm25Config {
+    fn default() -> Self {
+        Self { k1: 1.2, b: 0.75 }
+    }
+}
+
+/// Inverted index over the chunk text of a vector store, scored with Okapi BM25.
+///
+/// Documents are positions in the source it was built from, like the vector indexes,
+/// so deleted positions are skipped through `SearchQuery::accepts` at query time.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Bm25Index {
+    pub config: Bm25Config,
+    /// Term -> `(position, term frequency)` in ascending position order
+    postings: HashMap<String, Vec<(u32, u32)>>,
+    /// Token count of every document
+    lengths: Vec<u32>,
+    total_length: u64,
+}
+
+impl Bm25Index {
+    pub fn new(config: Bm25Config) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Index the chunk of every position in `data`
+    pub fn build<S: VectorSource + ?Sized>(data: &S, config: Bm25Config) -> Self {
+        let mut index = Self::new(config);
+        index.update(data);
+        index
+    }
+
+    /// Index positions of `data` added since the last build or update
+    pub fn update<S: VectorSource + ?Sized>(&mut self, data: &S) -> usize {
+        let start = self.len();
+        let tokenized: Vec<Vec<String>> = (start..data.len())
+            .into_par_iter()
+            .map(|idx| tokenize(data.chunk(idx)))
+            .collect();
+        for tokens in tokenized {
+            self.insert_tokens(tokens);
+        }
+        self.len() - start
+    }
+
+    /// Append `text` as the next document
+    pub fn insert(&mut self, text: &str) {
+        self.insert_tokens(tokenize(text));
+    }
+
+    fn insert_tokens(&mut self, tokens: Vec<String>) {
+        let doc = self.lengths.len() as u32;
+        self.lengths.push(tokens.len() as u32);
+        self.total_length += tokens.len() as u64;
+
+        let mut frequencies: HashMap<String, u32> = HashMap::new();
+        for token in tokens {
+            *frequencies.entry(token).or_default() += 1;
+        }
+        for (token, tf) in frequencies {
+            self.postings.entry(token).or_default().push((doc, tf));
+        }
+    }
+
+    /// Number of indexed documents
+    pub fn len(&self) -> usize {
+        self.lengths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lengths.is_empty()
+    }
+
+    /// Number of documents containing `term`, after tokenization
+    pub fn document_frequency(&self, term: &str) -> usize {
+        tokenize(term)
+            .first()
+            .and_then(|token| self.postings.get(token))
+            .map_or(0, Vec::len)
+    }
+
+    /// BM25 score of every document matching at least one query term
+    pub fn scores(&self, text: &str) -> HashMap<usize, f32> {
+        let mut scores: HashMap<usize, f32> = HashMap::new();
+        if self.is_empty() {
+            return scores;
+        }
+
+        let docs = self.len() as f32;
+        let average_length = self.total_length as f32 / docs;
+        let Bm25Config { k1, b } = self.config;
+
+        let mut terms = tokenize(text);
+        terms.sort();
+        terms.dedup();
+        for term in terms {
+            let Some(postings) = self.postings.get(&term) else {
+                continue;
+            };
+            let df = postings.len() as f32;
+            let idf = ((docs - df + 0.5) / (df + 0.5) + 1.0).ln();
+            for &(doc, tf) in postings {
+                let tf = tf as f32;
+                let length = self.lengths[doc as usize] as f32;
+                let norm = k1 * (1.0 - b + b * length / average_length.max(f32::EPSILON));
+                *scores.entry(doc as usize).or_default() += idf * tf * (k1 + 1.0) / (tf + norm);
+            }
+        }
+        scores
+    }
+
+    /// The `limit` best `(position, score)` pairs for `text` that `query` accepts in `data`
+    pub fn ranked<S: VectorSource + ?Sized>(
+        &self,
+        text: &str,
+        query: &SearchQuery,
+        data: &S,
+        limit: usize,
+    ) -> Vec<(usize, f32)> {
+        let candidates: Vec<(usize, f32)> = self
+            .scores(text)
+            .into_iter()
+            .filter(|&(idx, _)| idx < data.len() && query.accepts(data, idx))
+            .collect();
+        top_k(candidates.into_par_iter(), limit)
+    }
+
+    /// Save the postings as `index.bm25` inside `dir_path`
+    pub async fn write_binary(&self, dir_path: &str) -> Result<()> {
+        let self_clone = self.clone();
+        let path = Path::new(dir_path).join(BM25_FILE_NAME);
+        spawn_blocking(move || {
+            let encoded = bincode::serialize(&self_clone)?;
+            write_atomic(&path, |writer| Ok(writer.write_all(&encoded)?))
+                .with_context(|| format!("Failed to write index: {:?}", path))
+        })
+        .await?
+    }
+
+    /// Load `index.bm25` from `dir_path`
+    pub async fn read_binary(dir_path: &str) -> Result<Bm25Index> {
+        let path = Path::new(dir_path).join(BM25_FILE_NAME);
+        let bytes = fs::read(&path)
+            .await
+            .with_context(|| format!("Failed to read index: {:?}", path))?;
+
+        let index: Bm25Index = spawn_blocking(move || bincode::deserialize(&bytes))
+            .await?
+            .with_context(|| format!("Failed to deserialize index: {:?}", path))?;
+
+        Ok(index)
+    }
+}
+
+/// How a hybrid query combines its vector and keyword rankings
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Fusion {
+    /// Reciprocal rank fusion: each ranking adds `1 / (k + rank)`, ranks starting at 1.
+    /// Only ranks matter, so the two score scales never need to agree.
+    Rrf { k: f32 },
+    /// `alpha * vector + (1 - alpha) * keyword` after min-max normalizing each ranking's
+    /// scores to [0, 1]; a position missing from a ranking contributes 0 there
+    Linear { alpha: f32 },
+}
+
+impl Default for Fusion {
+    fn default() -> Self {
+        Fusion::Rrf { k: 60.0 }
+    }
+}
+
+impl Fusion {
+    /// Fuse two best-first `(position, score)` rankings and keep the `k` best
+    pub fn fuse(
+        &self,
+        vector: &[(usize, f32)],
+        keyword: &[(usize, f32)],
+        k: usize,
+    ) -> Vec<(usize, f32)> {
+        let mut fused: HashMap<usize, f32> = HashMap::new();
+        match *self {
+            Fusion::Rrf { k } => {
+                for ranking in [vector, keyword] {
+                    for (rank, &(idx, _)) in ranking.iter().enumerate() {
+                        *fused.entry(idx).or_default() += 1.0 / (k + rank as f32 + 1.0);
+                    }
+                }
+            }
+            Fusion::Linear { alpha } => {
+                for (ranking, weight) in [(vector, alpha), (keyword, 1.0 - alpha)] {
+                    for (idx, score) in min_max(ranking) {
+                        *fused.entry(idx).or_default() += weight * score;
+                    }
+                }
+            }
+        }
+        top_k(fused.into_par_iter(), k)
+    }
+}
+
+/// Scale the scores of a ranking to [0, 1]; all 1.0 when they are equal
+fn min_max(ranking: &[(usize, f32)]) -> impl Iterator<Item = (usize, f32)> + '_ {
+    let scores = ranking
+        .iter()
+        .map(|&(_, score)| score)
+        .filter(|s| s.is_finite());
+    let min = scores.clone().fold(f32::INFINITY, f32::min);
+    let max = scores.fold(f32::NEG_INFINITY, f32::max);
+    let range = max - min;
+    ranking.iter().map(move |&(idx, score)| {
+        let normalized = if !score.is_finite() {
+            0.0
+        } else if range > f32::EPSILON {
+            (score - min) / range
+        } else {
+            1.0
+        };
+        (idx, normalized)
+    })
+}
+
+/// Split `text` into lowercase terms.
+///
+/// Runs of letters and digits form a term; scripts written without spaces (CJK
+/// ideographs, kana, Hangul syllables) yield one term per character so they can still be
+/// matched. Punctuation, symbols and emoji separate terms and are dropped.
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    for c in text.chars() {
+        if is_unspaced_script(c) {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+            tokens.push(c.to_string());
+        } else if c.is_alphanumeric() {
+            current.extend(c.to_lowercase());
+        } else if !current.is_empty() {
+            tokens.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+fn is_unspaced_script(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30FF}'   // Hiragana and Katakana
+        | '\u{3400}'..='\u{4DBF}' // CJK Extension A
+        | '\u{4E00}'..='\u{9FFF}' // CJK Unified Ideographs
+        | '\u{AC00}'..='\u{D7AF}' // Hangul syllables
+        | '\u{F900}'..='\u{FAFF}' // CJK Compatibility Ideographs
+        | '\u{20000}'..='\u{2FA1F}' // CJK Extensions B onwards
+    )
+}
@@ -0,0 +1,192 @@
+use crate::utils::{Metadata, MetadataValue};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// Predicate over vector metadata, evaluated for every candidate during search.
+///
+/// Fields may address nested maps with dots, e.g. `"source.file"`. Comparisons against
+/// a list field match when any element of the list satisfies them, which makes
+/// `Filter::eq("tags", "rust")` test tag membership.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Filter {
+    Eq {
+        field: String,
+        value: MetadataValue,
+    },
+    Ne {
+        field: String,
+        value: MetadataValue,
+    },
+    Gt {
+        field: String,
+        value: MetadataValue,
+    },
+    Gte {
+        field: String,
+        value: MetadataValue,
+    },
+    Lt {
+        field: String,
+        value: MetadataValue,
+    },
+    Lte {
+        field: String,
+        value: MetadataValue,
+    },
+    In {
+        field: String,
+        values: Vec<MetadataValue>,
+    },
+    Exists {
+        field: String,
+    },
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+    Not(Box<Filter>),
+}
+
+impl Filter {
+    pub fn eq(field: impl Into<String>, value: impl Into<MetadataValue>) -> Self {
+        Filter::Eq {
+            field: field.into(),
+            value: value.into(),
+        }
+    }
+
+    pub fn ne(field: impl Into<String>, value: impl Into<MetadataValue>) -> Self {
+        Filter::Ne {
+            field: field.into(),
+            value: value.into(),
+        }
+    }
+
+    pub fn gt(field: impl Into<String>, value: impl Into<MetadataValue>) -> Self {
+        Filter::Gt {
+            field: field.into(),
+            value: value.into(),
+        }
+    }
+
+    pub fn gte(field: impl Into<String>, value: impl Into<MetadataValue>) -> Self {
+        Filter::Gte {
+            field: field.into(),
+            value: value.into(),
+        }
+    }
+
+    pub fn lt(field: impl Into<String>, value: impl Into<MetadataValue>) -> Self {
+        Filter::Lt {
+            field: field.into(),
+            value: value.into(),
+        }
+    }
+
+    pub fn lte(field: impl Into<String>, value: impl Into<MetadataValue>) -> Self {
+        Filter::Lte {
+            field: field.into(),
+            value: value.into(),
+        }
+    }
+
+    pub fn is_in<V: Into<MetadataValue>>(field: impl Into<String>, values: Vec<V>) -> Self {
+        Filter::In {
+            field: field.into(),
+            values: values.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    pub fn exists(field: impl Into<String>) -> Self {
+        Filter::Exists {
+            field: field.into(),
+        }
+    }
+
+    /// Inclusive range `low <= field <= high`
+    pub fn between(
+        field: impl Into<String>,
+        low: impl Into<MetadataValue>,
+        high: impl Into<MetadataValue>,
+    ) -> Self {
+        let field = field.into();
+        Filter::And(vec![
+            Filter::gte(field.clone(), low),
+            Filter::lte(field, high),
+        ])
+    }
+
+    pub fn and(self, other: Filter) -> Self {
+        match self {
+            Filter::And(mut filters) => {
+                filters.push(other);
+                Filter::And(filters)
+            }
+            filter => Filter::And(vec![filter, other]),
+        }
+    }
+
+    pub fn or(self, other: Filter) -> Self {
+        match self {
+            Filter::Or(mut filters) => {
+                filters.push(other);
+                Filter::Or(filters)
+            }
+            filter => Filter::Or(vec![filter, other]),
+        }
+    }
+
+    pub fn negate(self) -> Self {
+        Filter::Not(Box::new(self))
+    }
+
+    /// Evaluate the filter against one vector's metadata
+    pub fn matches(&self, metadata: &Metadata) -> bool {
+        match self {
+            Filter::Eq { field, value } => any_value(metadata, field, |v| v.loosely_equals(value)),
+            Filter::Ne { field, value } => !any_value(metadata, field, |v| v.loosely_equals(value)),
+            Filter::Gt { field, value } => any_value(metadata, field, |v| {
+                v.compare(value) == Some(Ordering::Greater)
+            }),
+            Filter::Gte { field, value } => any_value(metadata, field, |v| {
+                matches!(v.compare(value), Some(Ordering::Greater | Ordering::Equal))
+            }),
+            Filter::Lt { field, value } => any_value(metadata, field, |v| {
+                v.compare(value) == Some(Ordering::Less)
+            }),
+            Filter::Lte { field, value } => any_value(metadata, field, |v| {
+                matches!(v.compare(value), Some(Ordering::Less | Ordering::Equal))
+            }),
+            Filter::In { field, values } => any_value(metadata, field, |v| {
+                values.iter().any(|candidate| v.loosely_equals(candidate))
+            }),
+            Filter::Exists { field } => lookup(metadata, field).is_some(),
+            Filter::And(filters) => filters.iter().all(|f| f.matches(metadata)),
+            Filter::Or(filters) => filters.iter().any(|f| f.matches(metadata)),
+            Filter::Not(filter) => !filter.matches(metadata),
+        }
+    }
+}
+
+/// Resolve a dotted field path inside the metadata map
+fn lookup<'a>(metadata: &'a Metadata, field: &str) -> Option<&'a MetadataValue> {
+    let mut parts = field.split('.');
+    let mut current = metadata.get(parts.next()?)?;
+    for part in parts {
+        match current {
+            MetadataValue::Map(map) => current = map.get(part)?,
+            _ => return None,
+        }
+    }
+    Some(current)
+}
+
+/// Test `predicate` against the field value, or against each element when it is a list
+fn any_value(metadata: &Metadata, field: &str, predicate: impl Fn(&MetadataValue) -> bool) -> bool {
+    match lookup(metadata, field) {
+        Some(value @ MetadataValue::List(values)) => {
+            values.iter().any(&predicate) || predicate(value)
+        }
+        Some(value) => predicate(value),
+        None => false,
+    }
+}
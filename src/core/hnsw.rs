@@ -0,0 +1,439 @@
+use crate::core::search::{
+    Metrics, SearchQuery, SearchResult, VectorIndex, VectorSource, sort_by_score,
+};
+use crate::core::simd;
+use crate::utils::{SPLITMIX_GAMMA, SplitMix64, VectorData, write_atomic};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashSet};
+use std::io::Write;
+use std::path::Path;
+use tokio::fs;
+use tokio::task::spawn_blocking;
+
+/// File name of the persisted index, stored next to the `.bin` batches
+const HNSW_FILE_NAME: &str = "index.hnsw";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct HnswConfig {
+    /// Maximum number of links per node on the upper layers (layer 0 keeps `2 * m`)
+    pub m: usize,
+    /// Size of the dynamic candidate list while building the graph
+    pub ef_construction: usize,
+    /// Size of the dynamic candidate list while querying
+    pub ef_search: usize,
+}
+
+impl Default for HnswConfig {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 200,
+            ef_search: 64,
+        }
+    }
+}
+
+/// Hierarchical Navigable Small World graph over the vectors of a `VectorData`.
+///
+/// Nodes are identified by their position in `VectorData.embedding`, so the index
+/// must be queried against the same data it was built from.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HnswIndex {
+    pub config: HnswConfig,
+    pub metric: Metrics,
+    entry_point: Option<usize>,
+    max_level: usize,
+    /// `links[node][level]` holds the neighbours of `node` on `level`
+    links: Vec<Vec<Vec<u32>>>,
+    rng_state: u64,
+}
+
+/// A node id paired with its similarity to the current target
+#[derive(Clone, Copy, PartialEq)]
+struct Candidate {
+    score: f32,
+    id: usize,
+}
+
+impl Eq for Candidate {}
+
+/// The vector the graph is walked towards, with its norm worked out once
+#[derive(Clone, Copy)]
+struct Target<'a> {
+    vector: &'a [f32],
+    norm: f32,
+}
+
+impl<'a> Target<'a> {
+    fn new(vector: &'a [f32]) -> Self {
+        Self {
+            vector,
+            norm: simd::norm(vector),
+        }
+    }
+
+    /// The stored vector at `idx`, reusing its cached norm
+    fn stored(data: &'a VectorData, idx: usize) -> Self {
+        let vector = &data.embedding[idx];
+        Self {
+            vector,
+            norm: data.norm(idx).unwrap_or_else(|| simd::norm(vector)),
+        }
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then_with(|| other.id.cmp(&self.id))
+    }
+}
+
+impl HnswIndex {
+    pub fn new(config: HnswConfig, metric: Metrics) -> Self {
+        assert!(config.m >= 2, "HNSW parameter m must be at least 2");
+        Self {
+            config,
+            metric,
+            entry_point: None,
+            max_level: 0,
+            links: Vec::new(),
+            rng_state: SPLITMIX_GAMMA,
+        }
+    }
+
+    /// Build an index over every vector currently held in `data`
+    pub fn build(data: &VectorData, metric: Metrics, config: HnswConfig) -> Self {
+        let mut index = Self::new(config, metric);
+        for idx in 0..data.embedding.len() {
+            index.insert(data, idx);
+        }
+        index
+    }
+
+    /// Number of nodes in the graph
+    pub fn len(&self) -> usize {
+        self.links.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    /// Insert the vector at position `idx` of `data` into the graph.
+    ///
+    /// Vectors must be inserted in position order, starting right after the last indexed one.
+    pub fn insert(&mut self, data: &VectorData, idx: usize) {
+        assert_eq!(idx, self.links.len(), "Vectors must be inserted in order");
+        let target = Target::stored(data, idx);
+        let level = self.random_level();
+        self.links.push(vec![Vec::new(); level + 1]);
+
+        let Some(mut entry) = self.entry_point else {
+            self.entry_point = Some(idx);
+            self.max_level = level;
+            return;
+        };
+
+        // Greedy descent through the layers above the new node's level
+        for layer in (level + 1..=self.max_level).rev() {
+            entry = self.greedy_closest(data, target, entry, layer);
+        }
+
+        let mut entry_points = vec![entry];
+        for layer in (0..=level.min(self.max_level)).rev() {
+            let candidates = self.search_layer(
+                data,
+                target,
+                &entry_points,
+                self.config.ef_construction,
+                layer,
+                None,
+            );
+            let neighbours = self.select_neighbours(data, &candidates, self.config.m);
+            self.links[idx][layer] = neighbours.iter().map(|&n| n as u32).collect();
+
+            let max_links = self.max_links(layer);
+            for &neighbour in &neighbours {
+                self.links[neighbour][layer].push(idx as u32);
+                if self.links[neighbour][layer].len() > max_links {
+                    self.shrink_links(data, neighbour, layer, max_links);
+                }
+            }
+
+            entry_points = candidates.iter().map(|c| c.id).collect();
+        }
+
+        if level > self.max_level {
+            self.max_level = level;
+            self.entry_point = Some(idx);
+        }
+    }
+
+    /// Approximate top-k search returning positions and similarities, best first
+    pub fn search_nearest(
+        &self,
+        data: &VectorData,
+        query: &[f32],
+        top_k: usize,
+    ) -> Vec<(usize, f32)> {
+        self.search_filtered(data, query, top_k, None)
+    }
+
+    fn search_filtered(
+        &self,
+        data: &VectorData,
+        query: &[f32],
+        top_k: usize,
+        accept: Option<&dyn Fn(usize) -> bool>,
+    ) -> Vec<(usize, f32)> {
+        let Some(mut entry) = self.entry_point else {
+            return Vec::new();
+        };
+        if top_k == 0 {
+            return Vec::new();
+        }
+
+        let target = Target::new(query);
+        for layer in (1..=self.max_level).rev() {
+            entry = self.greedy_closest(data, target, entry, layer);
+        }
+
+        let ef = self.config.ef_search.max(top_k);
+        self.search_layer(data, target, &[entry], ef, 0, accept)
+            .into_iter()
+            .take(top_k)
+            .map(|c| (c.id, c.score))
+            .collect()
+    }
+
+    /// Save the index as `index.hnsw` inside `dir_path`
+    pub async fn write_binary(&self, dir_path: &str) -> Result<()> {
+        let self_clone = self.clone();
+        let path = Path::new(dir_path).join(HNSW_FILE_NAME);
+        spawn_blocking(move || {
+            let encoded = bincode::serialize(&self_clone)?;
+            write_atomic(&path, |writer| Ok(writer.write_all(&encoded)?))
+                .with_context(|| format!("Failed to write index: {:?}", path))
+        })
+        .await?
+    }
+
+    /// Load `index.hnsw` from `dir_path`
+    pub async fn read_binary(dir_path: &str) -> Result<HnswIndex> {
+        let path = Path::new(dir_path).join(HNSW_FILE_NAME);
+        let bytes = fs::read(&path)
+            .await
+            .with_context(|| format!("Failed to read index: {:?}", path))?;
+
+        let index: HnswIndex = spawn_blocking(move || bincode::deserialize(&bytes))
+            .await?
+            .with_context(|| format!("Failed to deserialize index: {:?}", path))?;
+
+        Ok(index)
+    }
+
+    fn similarity(&self, data: &VectorData, target: Target, id: usize) -> f32 {
+        let score = self.metric.calculate_with_norms(
+            target.vector,
+            &data.embedding[id],
+            Some(target.norm),
+            data.norm(id),
+        );
+        if score.is_nan() { f32::MIN } else { score }
+    }
+
+    fn max_links(&self, layer: usize) -> usize {
+        if layer == 0 {
+            self.config.m * 2
+        } else {
+            self.config.m
+        }
+    }
+
+    /// Draw a level from the exponentially decaying distribution `floor(-ln(u) / ln(m))`
+    fn random_level(&mut self) -> usize {
+        let mut rng = SplitMix64::new(self.rng_state);
+        let z = rng.next_u64();
+        self.rng_state = rng.state();
+
+        let uniform = ((z >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+        let level_mult = 1.0 / (self.config.m as f64).ln();
+        (-uniform.ln() * level_mult).floor() as usize
+    }
+
+    fn greedy_closest(
+        &self,
+        data: &VectorData,
+        target: Target,
+        start: usize,
+        layer: usize,
+    ) -> usize {
+        let mut current = start;
+        let mut best = self.similarity(data, target, current);
+        loop {
+            let mut improved = false;
+            for &neighbour in &self.links[current][layer] {
+                let neighbour = neighbour as usize;
+                let score = self.similarity(data, target, neighbour);
+                if score > best {
+                    best = score;
+                    current = neighbour;
+                    improved = true;
+                }
+            }
+            if !improved {
+                return current;
+            }
+        }
+    }
+
+    /// Beam search on a single layer, returning up to `ef` candidates sorted best first.
+    ///
+    /// Nodes rejected by `accept` are still traversed so the filter cannot disconnect the
+    /// graph, but only accepted nodes make it into the results.
+    fn search_layer(
+        &self,
+        data: &VectorData,
+        target: Target,
+        entry_points: &[usize],
+        ef: usize,
+        layer: usize,
+        accept: Option<&dyn Fn(usize) -> bool>,
+    ) -> Vec<Candidate> {
+        let accepted = |id: usize| accept.is_none_or(|f| f(id));
+        let mut visited: HashSet<usize> = HashSet::with_capacity(ef * 4);
+        let mut candidates: BinaryHeap<Candidate> = BinaryHeap::new();
+        let mut results: BinaryHeap<Reverse<Candidate>> = BinaryHeap::new();
+
+        for &entry in entry_points {
+            if visited.insert(entry) {
+                let candidate = Candidate {
+                    score: self.similarity(data, target, entry),
+                    id: entry,
+                };
+                candidates.push(candidate);
+                if accepted(entry) {
+                    results.push(Reverse(candidate));
+                    if results.len() > ef {
+                        results.pop();
+                    }
+                }
+            }
+        }
+
+        while let Some(current) = candidates.pop() {
+            let worst = results.peek().map(|r| r.0.score).unwrap_or(f32::MIN);
+            if current.score < worst && results.len() >= ef {
+                break;
+            }
+
+            for &neighbour in &self.links[current.id][layer] {
+                let neighbour = neighbour as usize;
+                if !visited.insert(neighbour) {
+                    continue;
+                }
+
+                let score = self.similarity(data, target, neighbour);
+                let worst = results.peek().map(|r| r.0.score).unwrap_or(f32::MIN);
+                if results.len() < ef || score > worst {
+                    let candidate = Candidate {
+                        score,
+                        id: neighbour,
+                    };
+                    candidates.push(candidate);
+                    if accepted(neighbour) {
+                        results.push(Reverse(candidate));
+                        if results.len() > ef {
+                            results.pop();
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut sorted: Vec<Candidate> = results.into_iter().map(|r| r.0).collect();
+        sorted.sort_by(|a, b| b.cmp(a));
+        sorted
+    }
+
+    /// Neighbour selection heuristic from the HNSW paper: prefer candidates that are
+    /// closer to the target than to any already selected neighbour, then fill up with
+    /// the best of the pruned ones so nodes stay well connected.
+    fn select_neighbours(
+        &self,
+        data: &VectorData,
+        candidates: &[Candidate],
+        max_links: usize,
+    ) -> Vec<usize> {
+        let mut selected: Vec<usize> = Vec::with_capacity(max_links);
+        let mut pruned: Vec<usize> = Vec::new();
+
+        for candidate in candidates {
+            if selected.len() >= max_links {
+                break;
+            }
+            let target = Target::stored(data, candidate.id);
+            let diverse = selected
+                .iter()
+                .all(|&s| self.similarity(data, target, s) < candidate.score);
+            if diverse {
+                selected.push(candidate.id);
+            } else {
+                pruned.push(candidate.id);
+            }
+        }
+
+        for id in pruned {
+            if selected.len() >= max_links {
+                break;
+            }
+            selected.push(id);
+        }
+
+        selected
+    }
+
+    fn shrink_links(&mut self, data: &VectorData, node: usize, layer: usize, max_links: usize) {
+        let target = Target::stored(data, node);
+        let mut candidates: Vec<Candidate> = self.links[node][layer]
+            .iter()
+            .map(|&n| Candidate {
+                score: self.similarity(data, target, n as usize),
+                id: n as usize,
+            })
+            .collect();
+        candidates.sort_by(|a, b| b.cmp(a));
+
+        let kept = self.select_neighbours(data, &candidates, max_links);
+        self.links[node][layer] = kept.into_iter().map(|n| n as u32).collect();
+    }
+}
+
+impl VectorIndex for HnswIndex {
+    fn search(&self, query: &SearchQuery, data: &VectorData) -> Result<Vec<SearchResult>> {
+        query.check_dimensions(data.dimensions)?;
+        let ef = self.config.ef_search.max(query.top_k);
+        let accept = |idx: usize| idx < data.embedding.len() && query.accepts(data, idx);
+        let query_norm = query.norm();
+        let mut results: Vec<SearchResult> = self
+            .search_filtered(data, &query.query_vector, ef, Some(&accept))
+            .into_iter()
+            .map(|(idx, _)| query.result(data, idx, query.score(data, idx, query_norm)))
+            .collect();
+
+        // The graph is navigated with the build metric; rank by the metric asked for
+        sort_by_score(&mut results);
+        results.truncate(query.top_k);
+        Ok(results)
+    }
+}
@@ -0,0 +1,410 @@
+use crate::core::kmeans::{kmeans, nearest, squared_distance};
+use crate::core::search::{
+    Metrics, SearchQuery, SearchResult, VectorIndex, VectorSource, sort_candidates,
+};
+use crate::core::simd;
+use crate::core::topk::top_k;
+use crate::utils::{SplitMix64, VectorData, checksum, write_atomic};
+use anyhow::{Context, Result};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+use tokio::fs;
+use tokio::task::spawn_blocking;
+
+/// File name of the IVF centroids, stored next to the `.bin` batches
+const IVF_FILE_NAME: &str = "index.ivf";
+
+/// Extension of the centroids file and of the posting list segments next to it
+const IVF_EXTENSION: &str = "ivf";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IvfConfig {
+    /// Number of k-means centroids, i.e. posting lists
+    pub lists: usize,
+    /// Number of nearest lists scanned per query
+    pub nprobe: usize,
+    /// Maximum number of live vectors sampled to train the centroids
+    pub sample_size: usize,
+    /// Maximum k-means iterations
+    pub iterations: usize,
+    pub seed: u64,
+}
+
+impl Default for IvfConfig {
+    fn default() -> Self {
+        Self {
+            lists: 256,
+            nprobe: 16,
+            sample_size: 50_000,
+            iterations: 25,
+            seed: 0x5EED,
+        }
+    }
+}
+
+/// Inverted file index: vectors are bucketed under their nearest k-means centroid and a
+/// query only scans the `nprobe` buckets whose centroids are closest to it.
+///
+/// Lists hold positions in the `VectorData` the index was built from. Positions past
+/// the indexed prefix are scanned exhaustively, so freshly ingested vectors are found
+/// before `update` files them into lists. The coarse quantizer works on Euclidean
+/// distance, over unit-normalized vectors when the index metric is cosine.
+///
+/// On disk the centroids live in `index.ivf` and the posting lists in segments, one per
+/// `update`, so appending a batch of vectors writes only the lists of that batch.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IvfIndex {
+    pub config: IvfConfig,
+    pub metric: Metrics,
+    dimensions: usize,
+    /// Row-major `lists * dimensions` centroid matrix
+    centroids: Vec<f32>,
+    lists: Vec<Vec<u32>>,
+    /// Number of leading positions already filed into a list
+    indexed: usize,
+}
+
+impl IvfIndex {
+    /// Train centroids on a sample of `data` and file every vector into its list
+    pub fn build<S: VectorSource + ?Sized>(
+        data: &S,
+        metric: Metrics,
+        config: IvfConfig,
+    ) -> Result<Self> {
+        let mut index = Self::train(data, metric, config)?;
+        index.update(data);
+        Ok(index)
+    }
+
+    /// Train centroids without filing any vectors
+    pub fn train<S: VectorSource + ?Sized>(
+        data: &S,
+        metric: Metrics,
+        config: IvfConfig,
+    ) -> Result<Self> {
+        if config.lists == 0 {
+            anyhow::bail!("IVF needs at least one list");
+        }
+        let live: Vec<usize> = (0..data.len()).filter(|&i| !data.is_deleted(i)).collect();
+        let Some(&first) = live.first() else {
+            anyhow::bail!("Cannot train an IVF index without vectors");
+        };
+        let dimensions = data.vector(first).len();
+
+        let mut rng = SplitMix64::new(config.seed);
+        let sample: Vec<f32> = rng
+            .sample(live.len(), config.sample_size)
+            .into_iter()
+            .map(|i| live[i])
+            .filter(|&idx| data.vector(idx).len() == dimensions)
+            .flat_map(|idx| coarse_vector(metric, data.vector(idx), data.norm(idx)))
+            .collect();
+        let lists = config.lists.min(live.len());
+        let centroids = kmeans(&sample, dimensions, lists, config.iterations, &mut rng);
+
+        Ok(Self {
+            config,
+            metric,
+            dimensions,
+            centroids,
+            lists: vec![Vec::new(); lists],
+            indexed: 0,
+        })
+    }
+
+    /// File every position of `data` that is not yet in a list; returns how many were added
+    pub fn update<S: VectorSource + ?Sized>(&mut self, data: &S) -> usize {
+        let start = self.indexed;
+        let assignments: Vec<Option<usize>> = (start..data.len())
+            .into_par_iter()
+            .map(|idx| {
+                let vector = data.vector(idx);
+                (vector.len() == self.dimensions).then(|| self.nearest_list(vector, data.norm(idx)))
+            })
+            .collect();
+
+        for (offset, list) in assignments.into_iter().enumerate() {
+            if let Some(list) = list {
+                self.lists[list].push((start + offset) as u32);
+            }
+        }
+        self.indexed = data.len().max(start);
+        self.indexed - start
+    }
+
+    /// Drop deleted positions from the posting lists
+    pub fn prune<S: VectorSource + ?Sized>(&mut self, data: &S) {
+        self.lists
+            .iter_mut()
+            .for_each(|list| list.retain(|&idx| !data.is_deleted(idx as usize)));
+    }
+
+    pub fn len(&self) -> usize {
+        self.lists.iter().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of leading positions covered by the lists
+    pub fn indexed(&self) -> usize {
+        self.indexed
+    }
+
+    pub fn list_count(&self) -> usize {
+        self.lists.len()
+    }
+
+    pub fn list(&self, list: usize) -> &[u32] {
+        &self.lists[list]
+    }
+
+    pub fn centroid(&self, list: usize) -> &[f32] {
+        &self.centroids[list * self.dimensions..(list + 1) * self.dimensions]
+    }
+
+    fn nearest_list(&self, vector: &[f32], norm: Option<f32>) -> usize {
+        nearest(
+            &self.centroids,
+            self.dimensions,
+            &coarse_vector(self.metric, vector, norm),
+        )
+    }
+
+    /// The `nprobe` lists whose centroids are closest to `query`
+    pub fn probe(&self, query: &[f32], nprobe: usize) -> Vec<usize> {
+        let query = coarse_vector(self.metric, query, None);
+        let mut lists: Vec<(usize, f32)> = (0..self.lists.len())
+            .map(|list| (list, -squared_distance(self.centroid(list), &query)))
+            .collect();
+        sort_candidates(&mut lists);
+        lists
+            .into_iter()
+            .take(nprobe)
+            .map(|(list, _)| list)
+            .collect()
+    }
+
+    /// Search `data` scanning the configured number of lists
+    pub fn search_source<S: VectorSource + ?Sized>(
+        &self,
+        query: &SearchQuery,
+        data: &S,
+    ) -> Result<Vec<SearchResult>> {
+        self.search_nprobe(query, data, self.config.nprobe)
+    }
+
+    /// Search `data` scanning `nprobe` lists plus the unindexed tail
+    pub fn search_nprobe<S: VectorSource + ?Sized>(
+        &self,
+        query: &SearchQuery,
+        data: &S,
+        nprobe: usize,
+    ) -> Result<Vec<SearchResult>> {
+        query.check_dimensions(self.dimensions)?;
+
+        let mut positions: Vec<usize> = self
+            .probe(&query.query_vector, nprobe)
+            .into_iter()
+            .flat_map(|list| self.lists[list].iter().map(|&idx| idx as usize))
+            .collect();
+        positions.extend(self.indexed..data.len());
+
+        let query_norm = query.norm();
+        let candidates = positions
+            .into_par_iter()
+            .filter(|&idx| idx < data.len() && query.accepts(data, idx))
+            .map(|idx| (idx, query.score(data, idx, query_norm)));
+
+        Ok(query.results(data, top_k(candidates, query.top_k)))
+    }
+
+    /// Save the index inside `dir_path`: the centroids as `index.ivf` and every posting
+    /// list as one segment, replacing the segments saved with earlier centroids
+    pub async fn write_binary(&self, dir_path: &str) -> Result<()> {
+        let (quantizer, id) = self.quantizer()?;
+        let path = Path::new(dir_path).join(IVF_FILE_NAME);
+        spawn_blocking(move || {
+            write_atomic(&path, |writer| Ok(writer.write_all(&quantizer)?))
+                .with_context(|| format!("Failed to write index: {:?}", path))
+        })
+        .await??;
+        self.write_lists(dir_path, id, 0).await?;
+
+        let mut read_dir = fs::read_dir(dir_path)
+            .await
+            .with_context(|| format!("Failed to read directory: {:?}", dir_path))?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            if segment_start(&entry.path()).is_some_and(|start| start > 0) {
+                fs::remove_file(entry.path()).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Save the lists of the positions filed since `start` as a new segment next to the
+    /// `index.ivf` written by `write_binary`. Pass the `indexed()` count from before
+    /// `update`, so each batch of ingested vectors only writes its own lists.
+    pub async fn write_segment(&self, dir_path: &str, start: usize) -> Result<()> {
+        let (_, id) = self.quantizer()?;
+        self.write_lists(dir_path, id, start).await
+    }
+
+    /// Load `index.ivf` and its list segments from `dir_path`.
+    ///
+    /// Segments are chained from position 0; one filed against other centroids, or
+    /// overlapping one already read, is ignored, and reading stops at the first gap.
+    /// Positions past the last segment are left for `update`.
+    pub async fn read_binary(dir_path: &str) -> Result<IvfIndex> {
+        let path = Path::new(dir_path).join(IVF_FILE_NAME);
+        let bytes = fs::read(&path)
+            .await
+            .with_context(|| format!("Failed to read index: {:?}", path))?;
+        let quantizer: Quantizer = spawn_blocking(move || bincode::deserialize(&bytes))
+            .await?
+            .with_context(|| format!("Failed to deserialize index: {:?}", path))?;
+
+        let mut segments = Vec::new();
+        let mut read_dir = fs::read_dir(dir_path)
+            .await
+            .with_context(|| format!("Failed to read directory: {:?}", dir_path))?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            if let Some(start) = segment_start(&entry.path()) {
+                segments.push((start, entry.path()));
+            }
+        }
+        segments.sort();
+
+        let list_count = quantizer.centroids.len() / quantizer.dimensions.max(1);
+        let mut index = IvfIndex {
+            config: quantizer.config,
+            metric: quantizer.metric,
+            dimensions: quantizer.dimensions,
+            centroids: quantizer.centroids,
+            lists: vec![Vec::new(); list_count],
+            indexed: 0,
+        };
+        let (_, id) = index.quantizer()?;
+        for (start, path) in segments {
+            if start > index.indexed {
+                break;
+            }
+            let bytes = fs::read(&path)
+                .await
+                .with_context(|| format!("Failed to read index: {:?}", path))?;
+            let segment: ListSegment = spawn_blocking(move || bincode::deserialize(&bytes))
+                .await?
+                .with_context(|| format!("Failed to deserialize index: {:?}", path))?;
+            if segment.quantizer != id || segment.start != index.indexed {
+                continue;
+            }
+            if segment.lists.len() != list_count
+                || segment
+                    .lists
+                    .iter()
+                    .flatten()
+                    .any(|&idx| !(segment.start..segment.end).contains(&(idx as usize)))
+            {
+                anyhow::bail!("Invalid index segment: {:?}", path);
+            }
+            for (list, positions) in index.lists.iter_mut().zip(segment.lists) {
+                list.extend(positions);
+            }
+            index.indexed = segment.end;
+        }
+
+        Ok(index)
+    }
+
+    /// The encoded `index.ivf` contents and their checksum, which segments record to
+    /// tell which centroids their positions were filed against
+    fn quantizer(&self) -> Result<(Vec<u8>, u64)> {
+        let encoded = bincode::serialize(&Quantizer {
+            config: self.config,
+            metric: self.metric,
+            dimensions: self.dimensions,
+            centroids: self.centroids.clone(),
+        })?;
+        let id = checksum(&encoded);
+        Ok((encoded, id))
+    }
+
+    async fn write_lists(&self, dir_path: &str, quantizer: u64, start: usize) -> Result<()> {
+        let segment = ListSegment {
+            quantizer,
+            start,
+            end: self.indexed.max(start),
+            lists: self
+                .lists
+                .iter()
+                .map(|list| {
+                    list.iter()
+                        .copied()
+                        .filter(|&idx| idx as usize >= start)
+                        .collect()
+                })
+                .collect(),
+        };
+        let path = Path::new(dir_path).join(format!("index.{}.{}", start, IVF_EXTENSION));
+        spawn_blocking(move || {
+            let encoded = bincode::serialize(&segment)?;
+            write_atomic(&path, |writer| Ok(writer.write_all(&encoded)?))
+                .with_context(|| format!("Failed to write index: {:?}", path))
+        })
+        .await?
+    }
+}
+
+/// Contents of `index.ivf`: the trained coarse quantizer, without posting lists
+#[derive(Serialize, Deserialize)]
+struct Quantizer {
+    config: IvfConfig,
+    metric: Metrics,
+    dimensions: usize,
+    centroids: Vec<f32>,
+}
+
+/// Posting lists of the positions `start..end`, saved as `index.<start>.ivf`
+#[derive(Serialize, Deserialize)]
+struct ListSegment {
+    /// Checksum of the `index.ivf` the positions were filed against
+    quantizer: u64,
+    start: usize,
+    end: usize,
+    lists: Vec<Vec<u32>>,
+}
+
+/// The first position of the list segment at `path`, `None` for any other file
+fn segment_start(path: &Path) -> Option<usize> {
+    path.file_name()?
+        .to_str()?
+        .strip_prefix("index.")?
+        .strip_suffix(&format!(".{}", IVF_EXTENSION))?
+        .parse()
+        .ok()
+}
+
+impl VectorIndex for IvfIndex {
+    fn search(&self, query: &SearchQuery, data: &VectorData) -> Result<Vec<SearchResult>> {
+        self.search_source(query, data)
+    }
+}
+
+/// The vector the coarse quantizer sees: unit-normalized for cosine, unchanged otherwise.
+/// `norm` is the cached norm of `vector`, if the caller has one.
+fn coarse_vector(metric: Metrics, vector: &[f32], norm: Option<f32>) -> Vec<f32> {
+    match metric {
+        Metrics::Cosine => {
+            let norm = norm.unwrap_or_else(|| simd::norm(vector));
+            if norm < f32::EPSILON {
+                vector.to_vec()
+            } else {
+                vector.iter().map(|x| x / norm).collect()
+            }
+        }
+        Metrics::Euclidean | Metrics::DotProduct => vector.to_vec(),
+    }
+}
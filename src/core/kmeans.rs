@@ -0,0 +1,94 @@
+use crate::utils::SplitMix64;
+use rayon::iter::ParallelIterator;
+use rayon::slice::ParallelSlice;
+
+pub(crate) fn squared_distance(a: &[f32], b: &[f32]) -> f32 {
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| {
+            let diff = x - y;
+            diff * diff
+        })
+        .sum()
+}
+
+/// Index of the centroid in the flat `centroids` matrix closest to `point`
+pub(crate) fn nearest(centroids: &[f32], dimensions: usize, point: &[f32]) -> usize {
+    centroids
+        .chunks_exact(dimensions)
+        .map(|centroid| squared_distance(centroid, point))
+        .enumerate()
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(idx, _)| idx)
+        .unwrap_or(0)
+}
+
+/// Lloyd's k-means over the row-major `points` matrix, returning `k` centroids row-major.
+///
+/// Centroids start from distinct sampled points; a cluster that empties out is reseeded
+/// with a random point. With fewer points than `k` the points are repeated, so callers
+/// always get exactly `k` rows.
+pub(crate) fn kmeans(
+    points: &[f32],
+    dimensions: usize,
+    k: usize,
+    iterations: usize,
+    rng: &mut SplitMix64,
+) -> Vec<f32> {
+    let count = points.len() / dimensions.max(1);
+    if count == 0 || k == 0 {
+        return vec![0.0; k * dimensions];
+    }
+    let row = |idx: usize| &points[idx * dimensions..(idx + 1) * dimensions];
+
+    let mut centroids: Vec<f32> = rng
+        .sample(count, k)
+        .into_iter()
+        .flat_map(|idx| row(idx).to_vec())
+        .collect();
+    let mut next = 0;
+    while centroids.len() < k * dimensions {
+        centroids.extend_from_slice(row(next % count));
+        next += 1;
+    }
+    if count <= k {
+        return centroids;
+    }
+
+    for _ in 0..iterations {
+        let assignments: Vec<usize> = points
+            .par_chunks_exact(dimensions)
+            .map(|point| nearest(&centroids, dimensions, point))
+            .collect();
+
+        let mut sums = vec![0.0f32; k * dimensions];
+        let mut counts = vec![0usize; k];
+        for (idx, &cluster) in assignments.iter().enumerate() {
+            counts[cluster] += 1;
+            sums[cluster * dimensions..(cluster + 1) * dimensions]
+                .iter_mut()
+                .zip(row(idx))
+                .for_each(|(sum, x)| *sum += x);
+        }
+
+        let mut moved = false;
+        for cluster in 0..k {
+            let target = &mut centroids[cluster * dimensions..(cluster + 1) * dimensions];
+            let updated: Vec<f32> = if counts[cluster] == 0 {
+                row(rng.below(count)).to_vec()
+            } else {
+                sums[cluster * dimensions..(cluster + 1) * dimensions]
+                    .iter()
+                    .map(|sum| sum / counts[cluster] as f32)
+                    .collect()
+            };
+            moved |= target != updated.as_slice();
+            target.copy_from_slice(&updated);
+        }
+        if !moved {
+            break;
+        }
+    }
+
+    centroids
+}
@@ -1,3 +1,22 @@
+mod bm25;
+mod filter;
+mod hnsw;
+mod ivf;
+mod kmeans;
+mod pq;
+mod quantize;
 mod search;
+mod simd;
+mod topk;
 
-pub use search::{Metrics, SearchQuery, SearchResult};
+pub use bm25::{Bm25Config, Bm25Index, Fusion, tokenize};
+pub use filter::Filter;
+pub use hnsw::{HnswConfig, HnswIndex};
+pub use ivf::{IvfConfig, IvfIndex};
+pub use pq::{LookupTable, PqCodec, PqConfig, PqIndex};
+pub use quantize::{QuantizationReport, QuantizedIndex, ScalarQuantizer};
+pub use search::{
+    Hybrid, IndexType, Metrics, SearchMode, SearchQuery, SearchResult, VectorIndex, VectorSource,
+    recall_at_k,
+};
+pub use simd::Kernel;
@@ -0,0 +1,363 @@
+use crate::core::kmeans::{kmeans, nearest, squared_distance};
+use crate::core::search::{
+    Metrics, SearchQuery, SearchResult, VectorIndex, VectorSource, sort_candidates,
+};
+use crate::core::topk::top_k;
+use crate::utils::{SplitMix64, VectorData, write_atomic};
+use anyhow::{Context, Result};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+use tokio::fs;
+use tokio::task::spawn_blocking;
+
+/// File name of the persisted PQ index and codebooks, stored next to the `.bin` batches
+const PQ_FILE_NAME: &str = "index.pq";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PqConfig {
+    /// Number of sub-vectors each vector is split into; must divide the dimension
+    pub sub_quantizers: usize,
+    /// Bits per sub-quantizer code, 1 to 8 (`2^bits` centroids per codebook)
+    pub bits: usize,
+    /// Maximum number of live vectors sampled for training
+    pub sample_size: usize,
+    /// Maximum k-means iterations per codebook
+    pub iterations: usize,
+    pub seed: u64,
+}
+
+impl Default for PqConfig {
+    fn default() -> Self {
+        Self {
+            sub_quantizers: 8,
+            bits: 8,
+            sample_size: 20_000,
+            iterations: 20,
+            seed: 0x5EED,
+        }
+    }
+}
+
+/// Product quantizer: one k-means codebook per sub-vector, one byte of code per codebook
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PqCodec {
+    pub config: PqConfig,
+    pub dimensions: usize,
+    /// `codebooks[(m * centroids + c) * sub_dimensions..]` is centroid `c` of sub-quantizer `m`
+    codebooks: Vec<f32>,
+}
+
+impl PqCodec {
+    /// Train codebooks on a sample of the live vectors in `data`
+    pub fn train<S: VectorSource + ?Sized>(data: &S, config: PqConfig) -> Result<Self> {
+        if !(1..=8).contains(&config.bits) {
+            anyhow::bail!("PQ bits must be between 1 and 8, got {}", config.bits);
+        }
+        let live: Vec<usize> = (0..data.len()).filter(|&i| !data.is_deleted(i)).collect();
+        let Some(&first) = live.first() else {
+            anyhow::bail!("Cannot train a PQ codec without vectors");
+        };
+        let dimensions = data.vector(first).len();
+        if config.sub_quantizers == 0 || !dimensions.is_multiple_of(config.sub_quantizers) {
+            anyhow::bail!(
+                "{} sub-quantizers do not divide {} dimensions",
+                config.sub_quantizers,
+                dimensions
+            );
+        }
+
+        let mut rng = SplitMix64::new(config.seed);
+        let sample: Vec<&[f32]> = rng
+            .sample(live.len(), config.sample_size)
+            .into_iter()
+            .map(|i| data.vector(live[i]))
+            .filter(|vector| vector.len() == dimensions)
+            .collect();
+
+        let sub_dimensions = dimensions / config.sub_quantizers;
+        let centroids = 1 << config.bits;
+        let mut codebooks = Vec::with_capacity(config.sub_quantizers * centroids * sub_dimensions);
+        for m in 0..config.sub_quantizers {
+            let points: Vec<f32> = sample
+                .iter()
+                .flat_map(|vector| &vector[m * sub_dimensions..(m + 1) * sub_dimensions])
+                .copied()
+                .collect();
+            codebooks.extend(kmeans(
+                &points,
+                sub_dimensions,
+                centroids,
+                config.iterations,
+                &mut rng,
+            ));
+        }
+
+        Ok(Self {
+            config,
+            dimensions,
+            codebooks,
+        })
+    }
+
+    pub fn sub_dimensions(&self) -> usize {
+        self.dimensions / self.config.sub_quantizers
+    }
+
+    pub fn centroids(&self) -> usize {
+        1 << self.config.bits
+    }
+
+    /// The codebook of sub-quantizer `m`, row-major
+    pub fn codebook(&self, m: usize) -> &[f32] {
+        let len = self.centroids() * self.sub_dimensions();
+        &self.codebooks[m * len..(m + 1) * len]
+    }
+
+    pub fn encode(&self, vector: &[f32]) -> Vec<u8> {
+        let sub = self.sub_dimensions();
+        (0..self.config.sub_quantizers)
+            .map(|m| nearest(self.codebook(m), sub, &vector[m * sub..(m + 1) * sub]) as u8)
+            .collect()
+    }
+
+    pub fn decode(&self, codes: &[u8]) -> Vec<f32> {
+        let sub = self.sub_dimensions();
+        codes
+            .iter()
+            .enumerate()
+            .flat_map(|(m, &code)| {
+                let start = code as usize * sub;
+                self.codebook(m)[start..start + sub].to_vec()
+            })
+            .collect()
+    }
+
+    /// Per-sub-quantizer partial scores of `query` against every centroid, for
+    /// asymmetric distance computation; `query` must have the codec's dimensions
+    pub fn lookup_table(&self, query: &[f32], metric: Metrics) -> Result<LookupTable> {
+        if query.len() != self.dimensions {
+            anyhow::bail!(
+                "Query has {} dimensions, the codebooks have {}",
+                query.len(),
+                self.dimensions
+            );
+        }
+        let sub = self.sub_dimensions();
+        let centroids = self.centroids();
+        let mut table = Vec::with_capacity(self.config.sub_quantizers * centroids);
+        for m in 0..self.config.sub_quantizers {
+            let query_sub = &query[m * sub..(m + 1) * sub];
+            table.extend(
+                self.codebook(m)
+                    .chunks_exact(sub)
+                    .map(|centroid| match metric {
+                        Metrics::Euclidean => squared_distance(query_sub, centroid),
+                        Metrics::Cosine | Metrics::DotProduct => {
+                            query_sub.iter().zip(centroid).map(|(q, c)| q * c).sum()
+                        }
+                    }),
+            );
+        }
+
+        Ok(LookupTable {
+            metric,
+            centroids,
+            table,
+            query_norm: query.iter().map(|x| x * x).sum::<f32>().sqrt(),
+        })
+    }
+}
+
+/// Precomputed partial scores; scoring a code is one table lookup per sub-quantizer
+pub struct LookupTable {
+    metric: Metrics,
+    centroids: usize,
+    table: Vec<f32>,
+    query_norm: f32,
+}
+
+impl LookupTable {
+    /// Score `codes`, whose decoded vector has length `norm`
+    pub fn score(&self, codes: &[u8], norm: f32) -> f32 {
+        let sum: f32 = codes
+            .iter()
+            .enumerate()
+            .map(|(m, &code)| self.table[m * self.centroids + code as usize])
+            .sum();
+        match self.metric {
+            Metrics::DotProduct => sum,
+            Metrics::Cosine => {
+                let denominator = self.query_norm * norm;
+                if denominator < f32::EPSILON {
+                    0.0
+                } else {
+                    sum / denominator
+                }
+            }
+            Metrics::Euclidean => 1.0 / (1.0 + sum.sqrt()),
+        }
+    }
+}
+
+/// PQ-compressed copy of a vector store.
+///
+/// Each vector costs `sub_quantizers` bytes of codes plus one f32 norm, so 1024-dim
+/// vectors with the default 8 sub-quantizers shrink from 4 KiB to 12 bytes. Like
+/// `QuantizedIndex`, `rescore` re-ranks the best `top_k * rescore` candidates with exact
+/// scores from the source being searched.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PqIndex {
+    pub codec: PqCodec,
+    /// Candidate multiplier for exact rescoring; `None` ranks by PQ scores only
+    pub rescore: Option<usize>,
+    codes: Vec<u8>,
+    /// Norm of each decoded vector, used by the cosine kernel
+    norms: Vec<f32>,
+}
+
+impl PqIndex {
+    /// Train a codec on `data` and encode every position so index positions line up with
+    /// the source
+    pub fn build<S: VectorSource + ?Sized>(data: &S, config: PqConfig) -> Result<Self> {
+        let mut index = Self::new(PqCodec::train(data, config)?);
+        let encoded: Vec<(Vec<u8>, f32)> = (0..data.len())
+            .into_par_iter()
+            .map(|idx| index.encode_with_norm(data.vector(idx)))
+            .collect();
+        for (codes, norm) in encoded {
+            index.codes.extend_from_slice(&codes);
+            index.norms.push(norm);
+        }
+        Ok(index)
+    }
+
+    /// An empty index over an already trained codec
+    pub fn new(codec: PqCodec) -> Self {
+        Self {
+            codec,
+            rescore: None,
+            codes: Vec::new(),
+            norms: Vec::new(),
+        }
+    }
+
+    pub fn with_rescore(mut self, rescore: Option<usize>) -> Self {
+        self.rescore = rescore;
+        self
+    }
+
+    /// Append `vector` at the next position
+    pub fn insert(&mut self, vector: &[f32]) -> Result<()> {
+        if vector.len() != self.codec.dimensions {
+            anyhow::bail!(
+                "Vector has {} dimensions, the codec expects {}",
+                vector.len(),
+                self.codec.dimensions
+            );
+        }
+        let (codes, norm) = self.encode_with_norm(vector);
+        self.codes.extend_from_slice(&codes);
+        self.norms.push(norm);
+        Ok(())
+    }
+
+    fn encode_with_norm(&self, vector: &[f32]) -> (Vec<u8>, f32) {
+        if vector.len() != self.codec.dimensions {
+            // Keep positions aligned; a zero norm makes the vector score 0 under cosine
+            return (vec![0; self.codec.config.sub_quantizers], 0.0);
+        }
+        let codes = self.codec.encode(vector);
+        let norm = self
+            .codec
+            .decode(&codes)
+            .iter()
+            .map(|x| x * x)
+            .sum::<f32>()
+            .sqrt();
+        (codes, norm)
+    }
+
+    pub fn len(&self) -> usize {
+        self.norms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.norms.is_empty()
+    }
+
+    pub fn codes(&self, idx: usize) -> &[u8] {
+        let m = self.codec.config.sub_quantizers;
+        &self.codes[idx * m..(idx + 1) * m]
+    }
+
+    /// Memory held by the codes, norms and codebooks, in MB
+    pub fn memory_usage_mb(&self) -> f64 {
+        let bytes = self.codes.len()
+            + self.norms.len() * size_of::<f32>()
+            + self.codec.codebooks.len() * size_of::<f32>();
+        bytes as f64 / (1024.0 * 1024.0)
+    }
+
+    /// Search `data`, which must hold the vectors this index was built from
+    pub fn search_source<S: VectorSource + ?Sized>(
+        &self,
+        query: &SearchQuery,
+        data: &S,
+    ) -> Result<Vec<SearchResult>> {
+        query.check_dimensions(self.codec.dimensions)?;
+        let table = self.codec.lookup_table(&query.query_vector, query.metric)?;
+        let keep = match self.rescore {
+            Some(rescore) => query.top_k.saturating_mul(rescore.max(1)),
+            None => query.top_k,
+        };
+        let candidates = (0..self.len().min(data.len()))
+            .into_par_iter()
+            .filter(|&idx| query.accepts(data, idx))
+            .map(|idx| (idx, table.score(self.codes(idx), self.norms[idx])));
+        let mut ranked = top_k(candidates, keep);
+
+        if self.rescore.is_some() {
+            let query_norm = query.norm();
+            ranked.iter_mut().for_each(|(idx, score)| {
+                *score = query.score(data, *idx, query_norm);
+            });
+            sort_candidates(&mut ranked);
+        }
+
+        Ok(query.results(data, ranked))
+    }
+
+    /// Save the index and its codebooks as `index.pq` inside `dir_path`
+    pub async fn write_binary(&self, dir_path: &str) -> Result<()> {
+        let self_clone = self.clone();
+        let path = Path::new(dir_path).join(PQ_FILE_NAME);
+        spawn_blocking(move || {
+            let encoded = bincode::serialize(&self_clone)?;
+            write_atomic(&path, |writer| Ok(writer.write_all(&encoded)?))
+                .with_context(|| format!("Failed to write index: {:?}", path))
+        })
+        .await?
+    }
+
+    /// Load `index.pq` from `dir_path`
+    pub async fn read_binary(dir_path: &str) -> Result<PqIndex> {
+        let path = Path::new(dir_path).join(PQ_FILE_NAME);
+        let bytes = fs::read(&path)
+            .await
+            .with_context(|| format!("Failed to read index: {:?}", path))?;
+
+        let index: PqIndex = spawn_blocking(move || bincode::deserialize(&bytes))
+            .await?
+            .with_context(|| format!("Failed to deserialize index: {:?}", path))?;
+
+        Ok(index)
+    }
+}
+
+impl VectorIndex for PqIndex {
+    fn search(&self, query: &SearchQuery, data: &VectorData) -> Result<Vec<SearchResult>> {
+        self.search_source(query, data)
+    }
+}
@@ -0,0 +1,344 @@
+use crate::core::search::{
+    Metrics, SearchQuery, SearchResult, VectorIndex, VectorSource, recall_at_k, sort_candidates,
+};
+use crate::core::topk::top_k;
+use crate::utils::{VectorData, write_atomic};
+use anyhow::{Context, Result};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+use tokio::fs;
+use tokio::task::spawn_blocking;
+
+/// File name of the persisted quantized index, stored next to the `.bin` batches
+const SQ8_FILE_NAME: &str = "index.sq8";
+
+/// Largest code value; each dimension is mapped onto `0..=255`
+const LEVELS: f32 = 255.0;
+
+/// Per-dimension int8 scalar quantizer.
+///
+/// Dimension `d` is encoded as `round((x - min[d]) / scale[d])`, clamped to one byte,
+/// and decoded as `min[d] + code * scale[d]`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScalarQuantizer {
+    pub min: Vec<f32>,
+    pub scale: Vec<f32>,
+}
+
+impl ScalarQuantizer {
+    /// Learn per-dimension ranges from the live vectors of `data`
+    pub fn train<S: VectorSource + ?Sized>(data: &S) -> Self {
+        let mut min: Vec<f32> = Vec::new();
+        let mut max: Vec<f32> = Vec::new();
+        for idx in (0..data.len()).filter(|&idx| !data.is_deleted(idx)) {
+            let vector = data.vector(idx);
+            if min.is_empty() {
+                min = vector.to_vec();
+                max = vector.to_vec();
+                continue;
+            }
+            for (d, &x) in vector.iter().enumerate().take(min.len()) {
+                min[d] = min[d].min(x);
+                max[d] = max[d].max(x);
+            }
+        }
+
+        let scale = min
+            .iter()
+            .zip(&max)
+            .map(|(lo, hi)| {
+                let range = hi - lo;
+                if range > f32::EPSILON {
+                    range / LEVELS
+                } else {
+                    1.0
+                }
+            })
+            .collect();
+        Self { min, scale }
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.min.len()
+    }
+
+    pub fn encode(&self, vector: &[f32]) -> Vec<u8> {
+        vector
+            .iter()
+            .zip(self.min.iter().zip(&self.scale))
+            .map(|(&x, (&lo, &scale))| ((x - lo) / scale).round().clamp(0.0, LEVELS) as u8)
+            .collect()
+    }
+
+    pub fn decode(&self, codes: &[u8]) -> Vec<f32> {
+        codes
+            .iter()
+            .zip(self.min.iter().zip(&self.scale))
+            .map(|(&code, (&lo, &scale))| lo + code as f32 * scale)
+            .collect()
+    }
+}
+
+/// Int8-quantized copy of a vector store, searched with asymmetric kernels.
+///
+/// Queries stay in f32 and are compared against the one-byte codes, which take a
+/// quarter of the memory of the original vectors, plus one f32 norm per vector. With
+/// `rescore` set, the best `top_k * rescore` candidates are re-ranked with exact scores
+/// read from the source the query runs against, typically a memory-mapped `Segment` that
+/// keeps the f32 vectors on disk.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QuantizedIndex {
+    pub quantizer: ScalarQuantizer,
+    /// Candidate multiplier for exact rescoring; `None` ranks by quantized scores only
+    pub rescore: Option<usize>,
+    codes: Vec<u8>,
+    /// Norm of each decoded vector, used by the cosine kernel
+    norms: Vec<f32>,
+}
+
+/// How much a quantized index loses against exact search on a set of queries
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuantizationReport {
+    /// Mean recall@k ranking by quantized scores only
+    pub recall: f32,
+    /// Mean recall@k after exact rescoring, if the index rescoring is enabled
+    pub rescored_recall: Option<f32>,
+    /// Size of the f32 vectors divided by the size of the index: codes, the norm of
+    /// each vector and the per-dimension ranges
+    pub compression_ratio: f32,
+}
+
+impl QuantizedIndex {
+    /// Train a quantizer on `data` and encode every position, deleted ones included, so
+    /// index positions line up with the source
+    pub fn build<S: VectorSource + ?Sized>(data: &S) -> Self {
+        let mut index = Self {
+            quantizer: ScalarQuantizer::train(data),
+            rescore: None,
+            codes: Vec::new(),
+            norms: Vec::new(),
+        };
+        for idx in 0..data.len() {
+            index.insert(data.vector(idx));
+        }
+        index
+    }
+
+    pub fn with_rescore(mut self, rescore: Option<usize>) -> Self {
+        self.rescore = rescore;
+        self
+    }
+
+    /// Append `vector` at the next position, using the already trained ranges
+    pub fn insert(&mut self, vector: &[f32]) {
+        let dims = self.quantizer.dimensions();
+        let mut codes = self.quantizer.encode(vector);
+        codes.resize(dims, 0);
+        let norm = self
+            .quantizer
+            .decode(&codes)
+            .iter()
+            .map(|x| x * x)
+            .sum::<f32>()
+            .sqrt();
+        self.codes.extend_from_slice(&codes);
+        self.norms.push(norm);
+    }
+
+    pub fn len(&self) -> usize {
+        self.norms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.norms.is_empty()
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.quantizer.dimensions()
+    }
+
+    pub fn codes(&self, idx: usize) -> &[u8] {
+        let dims = self.dimensions();
+        &self.codes[idx * dims..(idx + 1) * dims]
+    }
+
+    /// Memory held by the codes, norms and quantizer, in MB
+    pub fn memory_usage_mb(&self) -> f64 {
+        self.memory_bytes() as f64 / (1024.0 * 1024.0)
+    }
+
+    fn memory_bytes(&self) -> usize {
+        self.codes.len()
+            + self.norms.len() * size_of::<f32>()
+            + self.dimensions() * 2 * size_of::<f32>()
+    }
+
+    /// Quantized similarity between `query` and the vector at `idx` under `metric`
+    pub fn score(&self, metric: Metrics, query: &[f32], idx: usize) -> f32 {
+        QueryKernel::new(&self.quantizer, metric, query).score(self, idx)
+    }
+
+    /// Search `data`, which must hold the vectors this index was built from
+    pub fn search_source<S: VectorSource + ?Sized>(
+        &self,
+        query: &SearchQuery,
+        data: &S,
+    ) -> Result<Vec<SearchResult>> {
+        query.check_dimensions(self.dimensions())?;
+
+        let kernel = QueryKernel::new(&self.quantizer, query.metric, &query.query_vector);
+        let keep = match self.rescore {
+            Some(rescore) => query.top_k.saturating_mul(rescore.max(1)),
+            None => query.top_k,
+        };
+        let candidates = (0..self.len().min(data.len()))
+            .into_par_iter()
+            .filter(|&idx| query.accepts(data, idx))
+            .map(|idx| (idx, kernel.score(self, idx)));
+        let mut ranked = top_k(candidates, keep);
+
+        if self.rescore.is_some() {
+            let query_norm = query.norm();
+            ranked.iter_mut().for_each(|(idx, score)| {
+                *score = query.score(data, *idx, query_norm);
+            });
+            sort_candidates(&mut ranked);
+        }
+
+        Ok(query.results(data, ranked))
+    }
+
+    /// Compare this index against exact search over `data` for each of `queries`
+    pub fn report<S: VectorSource + ?Sized>(
+        &self,
+        data: &S,
+        queries: &[Vec<f32>],
+        top_k: usize,
+        metric: Metrics,
+    ) -> Result<QuantizationReport> {
+        let mean_recall = |index: &QuantizedIndex| -> Result<f32> {
+            let mut total = 0.0;
+            for vector in queries {
+                let query = SearchQuery::new(top_k, vector.clone(), metric);
+                total += recall_at_k(
+                    &query.search_source(data)?,
+                    &index.search_source(&query, data)?,
+                );
+            }
+            Ok(total / queries.len().max(1) as f32)
+        };
+
+        let unscored = self.clone().with_rescore(None);
+        Ok(QuantizationReport {
+            recall: mean_recall(&unscored)?,
+            rescored_recall: self.rescore.map(|_| mean_recall(self)).transpose()?,
+            compression_ratio: (self.len() * self.dimensions() * size_of::<f32>()) as f32
+                / self.memory_bytes().max(1) as f32,
+        })
+    }
+
+    /// Save the index as `index.sq8` inside `dir_path`
+    pub async fn write_binary(&self, dir_path: &str) -> Result<()> {
+        let self_clone = self.clone();
+        let path = Path::new(dir_path).join(SQ8_FILE_NAME);
+        spawn_blocking(move || {
+            let encoded = bincode::serialize(&self_clone)?;
+            write_atomic(&path, |writer| Ok(writer.write_all(&encoded)?))
+                .with_context(|| format!("Failed to write index: {:?}", path))
+        })
+        .await?
+    }
+
+    /// Load `index.sq8` from `dir_path`
+    pub async fn read_binary(dir_path: &str) -> Result<QuantizedIndex> {
+        let path = Path::new(dir_path).join(SQ8_FILE_NAME);
+        let bytes = fs::read(&path)
+            .await
+            .with_context(|| format!("Failed to read index: {:?}", path))?;
+
+        let index: QuantizedIndex = spawn_blocking(move || bincode::deserialize(&bytes))
+            .await?
+            .with_context(|| format!("Failed to deserialize index: {:?}", path))?;
+
+        Ok(index)
+    }
+}
+
+impl VectorIndex for QuantizedIndex {
+    fn search(&self, query: &SearchQuery, data: &VectorData) -> Result<Vec<SearchResult>> {
+        self.search_source(query, data)
+    }
+}
+
+/// A query folded into the quantizer ranges, so scoring a code is one pass of
+/// multiply-adds without decoding the stored vector
+struct QueryKernel {
+    metric: Metrics,
+    /// Dot and cosine: `query * scale`; Euclidean: `query - min`
+    weights: Vec<f32>,
+    scale: Vec<f32>,
+    /// `query · min`, the constant part of the dot product
+    bias: f32,
+    query_norm: f32,
+}
+
+impl QueryKernel {
+    fn new(quantizer: &ScalarQuantizer, metric: Metrics, query: &[f32]) -> Self {
+        let weights = match metric {
+            Metrics::Euclidean => query
+                .iter()
+                .zip(&quantizer.min)
+                .map(|(q, lo)| q - lo)
+                .collect(),
+            Metrics::Cosine | Metrics::DotProduct => query
+                .iter()
+                .zip(&quantizer.scale)
+                .map(|(q, s)| q * s)
+                .collect(),
+        };
+        Self {
+            metric,
+            weights,
+            scale: quantizer.scale.clone(),
+            bias: query.iter().zip(&quantizer.min).map(|(q, lo)| q * lo).sum(),
+            query_norm: query.iter().map(|x| x * x).sum::<f32>().sqrt(),
+        }
+    }
+
+    fn score(&self, index: &QuantizedIndex, idx: usize) -> f32 {
+        let codes = index.codes(idx);
+        match self.metric {
+            Metrics::DotProduct => self.dot(codes),
+            Metrics::Cosine => {
+                let denominator = self.query_norm * index.norms[idx];
+                if denominator < f32::EPSILON {
+                    0.0
+                } else {
+                    self.dot(codes) / denominator
+                }
+            }
+            Metrics::Euclidean => {
+                let distance_sq: f32 = codes
+                    .iter()
+                    .zip(self.weights.iter().zip(&self.scale))
+                    .map(|(&code, (&w, &s))| {
+                        let diff = w - code as f32 * s;
+                        diff * diff
+                    })
+                    .sum();
+                1.0 / (1.0 + distance_sq.sqrt())
+            }
+        }
+    }
+
+    fn dot(&self, codes: &[u8]) -> f32 {
+        self.bias
+            + codes
+                .iter()
+                .zip(&self.weights)
+                .map(|(&code, &w)| code as f32 * w)
+                .sum::<f32>()
+    }
+}
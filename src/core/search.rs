@@ -1,20 +1,181 @@
-use crate::utils::VectorData;
-use rayon::iter::{IndexedParallelIterator, ParallelIterator};
-use rayon::prelude::IntoParallelRefIterator;
+use crate::core::bm25::{Bm25Index, Fusion};
+use crate::core::filter::Filter;
+use crate::core::hnsw::{HnswConfig, HnswIndex};
+use crate::core::ivf::{IvfConfig, IvfIndex};
+use crate::core::simd::{self, Kernel};
+use crate::core::topk::top_k;
+use crate::utils::{Metadata, VectorData};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SearchQuery {
     pub top_k: usize,
     pub query_vector: Vec<f32>,
     pub metric: Metrics,
+    #[serde(default)]
+    pub mode: SearchMode,
+    /// Metadata predicate applied to every candidate before ranking
+    #[serde(default)]
+    pub filter: Option<Filter>,
+    /// Keyword half of a hybrid query, see `search_hybrid`
+    #[serde(default)]
+    pub hybrid: Option<Hybrid>,
 }
 
-#[derive(Serialize, Deserialize)]
+/// Query text matched against a `Bm25Index` and how its ranking is fused with the
+/// vector ranking
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Hybrid {
+    pub text: String,
+    #[serde(default)]
+    pub fusion: Fusion,
+    /// Candidates taken from each ranking before fusing; defaults to `4 * top_k`, at least 50
+    #[serde(default)]
+    pub candidates: Option<usize>,
+}
+
+/// Whether a query scans every vector or goes through an approximate index
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchMode {
+    #[default]
+    Exact,
+    Approximate,
+}
+
+/// The index a collection searches through; `Flat` scans every vector
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexType {
+    #[default]
+    Flat,
+    Hnsw,
+    Ivf,
+}
+
+impl IndexType {
+    /// Build this kind of index over `data` with default parameters; `None` for `Flat`
+    pub fn build(
+        self,
+        data: &VectorData,
+        metric: Metrics,
+    ) -> anyhow::Result<Option<Box<dyn VectorIndex + Send + Sync>>> {
+        Ok(match self {
+            IndexType::Flat => None,
+            IndexType::Hnsw => Some(Box::new(HnswIndex::build(
+                data,
+                metric,
+                HnswConfig::default(),
+            ))),
+            IndexType::Ivf if data.total_vectors == 0 => None,
+            IndexType::Ivf => Some(Box::new(IvfIndex::build(
+                data,
+                metric,
+                IvfConfig::default(),
+            )?)),
+        })
+    }
+
+    /// The index persisted in `dir_path`, brought up to date with `data`: vectors added
+    /// since it was saved are inserted and the index is saved again. A missing,
+    /// unreadable or outdated index is built over `data` with `metric` and saved.
+    /// `None` for `Flat`, and for `Ivf` while `data` holds no vectors to train on.
+    pub async fn open(
+        self,
+        dir_path: &str,
+        data: &VectorData,
+        metric: Metrics,
+    ) -> anyhow::Result<Option<Box<dyn VectorIndex + Send + Sync>>> {
+        match self {
+            IndexType::Flat => Ok(None),
+            IndexType::Hnsw => {
+                let mut index = match HnswIndex::read_binary(dir_path).await {
+                    Ok(index) if index.len() <= data.embedding.len() => index,
+                    _ => HnswIndex::new(HnswConfig::default(), metric),
+                };
+                let indexed = index.len();
+                for idx in indexed..data.embedding.len() {
+                    index.insert(data, idx);
+                }
+                if index.len() > indexed {
+                    index.write_binary(dir_path).await?;
+                }
+                Ok(Some(Box::new(index)))
+            }
+            IndexType::Ivf if data.total_vectors == 0 => Ok(None),
+            IndexType::Ivf => {
+                let mut index = match IvfIndex::read_binary(dir_path).await {
+                    Ok(index) if index.indexed() <= data.len() => index,
+                    _ => {
+                        let index = IvfIndex::build(data, metric, IvfConfig::default())?;
+                        index.write_binary(dir_path).await?;
+                        return Ok(Some(Box::new(index)));
+                    }
+                };
+                let indexed = index.indexed();
+                if index.update(data) > 0 {
+                    index.prune(data);
+                    index.write_segment(dir_path, indexed).await?;
+                }
+                Ok(Some(Box::new(index)))
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for IndexType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "flat" => Ok(IndexType::Flat),
+            "hnsw" => Ok(IndexType::Hnsw),
+            "ivf" => Ok(IndexType::Ivf),
+            other => anyhow::bail!("Unknown index type: {} (expected flat, hnsw or ivf)", other),
+        }
+    }
+}
+
+/// Read access to stored vectors by position.
+///
+/// Implemented by the in-memory `VectorData` and by memory-mapped `Segment`s, so the
+/// exact search path works on either without copying vectors.
+pub trait VectorSource: Sync {
+    /// Number of positions, including deleted ones
+    fn len(&self) -> usize;
+    fn vector(&self, idx: usize) -> &[f32];
+    fn chunk(&self, idx: usize) -> &str;
+    fn id(&self, idx: usize) -> &str;
+    fn metadata(&self, idx: usize) -> Option<&Metadata>;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn is_deleted(&self, _idx: usize) -> bool {
+        false
+    }
+
+    /// Cached Euclidean norm of the vector at `idx`, if the source keeps one
+    fn norm(&self, _idx: usize) -> Option<f32> {
+        None
+    }
+}
+
+/// An approximate nearest-neighbour index built over a `VectorData`
+pub trait VectorIndex {
+    fn search(&self, query: &SearchQuery, data: &VectorData) -> anyhow::Result<Vec<SearchResult>>;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SearchResult {
+    #[serde(default)]
+    pub id: String,
     pub chunk: String,
     pub score: f32,
+    #[serde(default)]
+    pub metadata: Metadata,
 }
 
 impl SearchQuery {
@@ -23,44 +184,243 @@ impl SearchQuery {
             top_k,
             query_vector,
             metric,
+            mode: SearchMode::Exact,
+            filter: None,
+            hybrid: None,
         }
     }
 
-    pub fn search(&self, data: &VectorData) -> Vec<SearchResult> {
-        let mut results: Vec<SearchResult> = data
-            .embedding
-            .par_iter()
-            .enumerate()
-            .map(|(idx, vector)| {
-                let score = self.metric.calculate(&self.query_vector, vector);
-                SearchResult {
-                    chunk: data.chunk[idx].clone(),
-                    score,
-                }
-            })
-            .collect();
-
-        // Sort results by score in descending order
-        results.sort_by(|a, b| {
-            // Compare scores, treating NaN as less than any number
-            match a.score.is_nan().cmp(&b.score.is_nan()) {
-                Ordering::Equal => b.score.partial_cmp(&a.score).unwrap(),
-                other => other,
-            }
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Whether the vector at `idx` is live and passes the query filter
+    pub fn accepts<S: VectorSource + ?Sized>(&self, data: &S, idx: usize) -> bool {
+        if data.is_deleted(idx) {
+            return false;
+        }
+        match (&self.filter, data.metadata(idx)) {
+            (None, _) => true,
+            (Some(filter), Some(metadata)) => filter.matches(metadata),
+            (Some(filter), None) => filter.matches(&Metadata::new()),
+        }
+    }
+
+    /// Build the result for the vector at `idx`
+    pub(crate) fn result<S: VectorSource + ?Sized>(
+        &self,
+        data: &S,
+        idx: usize,
+        score: f32,
+    ) -> SearchResult {
+        SearchResult {
+            id: data.id(idx).to_string(),
+            chunk: data.chunk(idx).to_string(),
+            score,
+            metadata: data.metadata(idx).cloned().unwrap_or_default(),
+        }
+    }
+
+    pub fn with_mode(mut self, mode: SearchMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Also match `text` against chunk keywords and fuse both rankings with `fusion`
+    pub fn with_hybrid(mut self, text: impl Into<String>, fusion: Fusion) -> Self {
+        self.hybrid = Some(Hybrid {
+            text: text.into(),
+            fusion,
+            candidates: None,
         });
+        self
+    }
+
+    /// Search through `index` when there is one, otherwise scan every vector
+    pub fn search_in(
+        &self,
+        data: &VectorData,
+        index: Option<&(dyn VectorIndex + Send + Sync)>,
+    ) -> anyhow::Result<Vec<SearchResult>> {
+        match index {
+            Some(index) => index.search(self, data),
+            None => self.search(data),
+        }
+    }
+
+    /// Search through `index` when the query is approximate, otherwise fall back to a full scan
+    pub fn search_with_index(
+        &self,
+        data: &VectorData,
+        index: &dyn VectorIndex,
+    ) -> anyhow::Result<Vec<SearchResult>> {
+        match self.mode {
+            SearchMode::Exact => self.search(data),
+            SearchMode::Approximate => index.search(self, data),
+        }
+    }
+
+    /// Exact search over `data`; errors when the query and the stored vectors differ in
+    /// dimensions
+    pub fn search(&self, data: &VectorData) -> anyhow::Result<Vec<SearchResult>> {
+        self.search_source(data)
+    }
+
+    /// Error unless the query has `dimensions` dimensions; 0 stands for an empty store
+    pub fn check_dimensions(&self, dimensions: usize) -> anyhow::Result<()> {
+        if dimensions != 0 && self.query_vector.len() != dimensions {
+            anyhow::bail!(
+                "Query has {} dimensions, store has {}",
+                self.query_vector.len(),
+                dimensions
+            );
+        }
+        Ok(())
+    }
+
+    /// Exact search over any vector source, e.g. a memory-mapped `Segment`.
+    ///
+    /// Each rayon job keeps a bounded heap of its `top_k` best positions; only the merged
+    /// winners are turned into results, so chunk text is copied `top_k` times per query.
+    pub fn search_source<S: VectorSource + ?Sized>(
+        &self,
+        data: &S,
+    ) -> anyhow::Result<Vec<SearchResult>> {
+        Ok(self.results(data, self.ranked(data, self.top_k)?))
+    }
+
+    /// Hybrid search: the exact vector ranking and the BM25 ranking of `lexical` over the
+    /// same positions, fused as configured in `hybrid`. Scores are fusion scores, not
+    /// similarities. Without `hybrid` this is a plain exact search.
+    pub fn search_hybrid<S: VectorSource + ?Sized>(
+        &self,
+        data: &S,
+        lexical: &Bm25Index,
+    ) -> anyhow::Result<Vec<SearchResult>> {
+        let Some(hybrid) = &self.hybrid else {
+            return self.search_source(data);
+        };
+        let candidates = hybrid
+            .candidates
+            .unwrap_or((self.top_k * 4).max(50))
+            .max(self.top_k);
 
-        // Return top_k results
-        results.into_iter().take(self.top_k).collect()
+        let vector = self.ranked(data, candidates)?;
+        let keyword = lexical.ranked(&hybrid.text, self, data, candidates);
+        Ok(self.results(data, hybrid.fusion.fuse(&vector, &keyword, self.top_k)))
+    }
+
+    /// The `k` best `(position, score)` pairs of an exact scan, best first
+    fn ranked<S: VectorSource + ?Sized>(
+        &self,
+        data: &S,
+        k: usize,
+    ) -> anyhow::Result<Vec<(usize, f32)>> {
+        if !data.is_empty() {
+            self.check_dimensions(data.vector(0).len())?;
+        }
+        let query_norm = self.norm();
+        let candidates = (0..data.len())
+            .into_par_iter()
+            .filter(|&idx| self.accepts(data, idx))
+            .map(|idx| (idx, self.score(data, idx, query_norm)));
+
+        Ok(top_k(candidates, k))
+    }
+
+    /// Norm of the query vector, to pass to `score`
+    pub(crate) fn norm(&self) -> f32 {
+        simd::norm(&self.query_vector)
+    }
+
+    /// Exact score of position `idx` under the query metric, using the cached norm of
+    /// the stored vector when `data` has one
+    pub(crate) fn score<S: VectorSource + ?Sized>(
+        &self,
+        data: &S,
+        idx: usize,
+        query_norm: f32,
+    ) -> f32 {
+        self.metric.calculate_with_norms(
+            &self.query_vector,
+            data.vector(idx),
+            Some(query_norm),
+            data.norm(idx),
+        )
+    }
+
+    /// Resolve ranked `(position, score)` pairs into results
+    pub(crate) fn results<S: VectorSource + ?Sized>(
+        &self,
+        data: &S,
+        ranked: Vec<(usize, f32)>,
+    ) -> Vec<SearchResult> {
+        ranked
+            .into_iter()
+            .take(self.top_k)
+            .map(|(idx, score)| self.result(data, idx, score))
+            .collect()
     }
 }
 
-#[derive(Serialize, Deserialize)]
+/// Sort results by score in descending order
+pub(crate) fn sort_by_score(results: &mut [SearchResult]) {
+    results.sort_by(|a, b| {
+        // Compare scores, treating NaN as less than any number
+        match a.score.is_nan().cmp(&b.score.is_nan()) {
+            Ordering::Equal => b.score.partial_cmp(&a.score).unwrap(),
+            other => other,
+        }
+    });
+}
+
+/// Sort `(position, score)` pairs by descending score, NaN last
+pub(crate) fn sort_candidates(candidates: &mut [(usize, f32)]) {
+    candidates.sort_by(|a, b| match a.1.is_nan().cmp(&b.1.is_nan()) {
+        Ordering::Equal => b.1.total_cmp(&a.1),
+        other => other,
+    });
+}
+
+/// Fraction of the `expected` ids that also appear in `actual`; 1.0 when nothing is expected
+pub fn recall_at_k(expected: &[SearchResult], actual: &[SearchResult]) -> f32 {
+    if expected.is_empty() {
+        return 1.0;
+    }
+    let found = expected
+        .iter()
+        .filter(|e| actual.iter().any(|a| a.id == e.id))
+        .count();
+    found as f32 / expected.len() as f32
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Metrics {
+    #[serde(alias = "cosine")]
     Cosine,
+    #[serde(alias = "euclidean")]
     Euclidean,
+    #[serde(alias = "dot", alias = "dot_product")]
     DotProduct,
 }
 
+impl std::str::FromStr for Metrics {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "cosine" => Ok(Metrics::Cosine),
+            "euclidean" => Ok(Metrics::Euclidean),
+            "dot" | "dotproduct" | "dot_product" => Ok(Metrics::DotProduct),
+            other => anyhow::bail!(
+                "Unknown metric: {} (expected cosine, euclidean or dot)",
+                other
+            ),
+        }
+    }
+}
+
 impl Metrics {
     pub fn calculate(&self, a: &[f32], b: &[f32]) -> f32 {
         match self {
@@ -69,21 +429,39 @@ impl Metrics {
             Metrics::DotProduct => dot_product(a, b),
         }
     }
+
+    /// Like `calculate`, reusing norms the caller already has; only cosine needs them
+    pub fn calculate_with_norms(
+        &self,
+        a: &[f32],
+        b: &[f32],
+        norm_a: Option<f32>,
+        norm_b: Option<f32>,
+    ) -> f32 {
+        match self {
+            Metrics::Cosine => {
+                debug_assert_eq!(a.len(), b.len(), "Vector dimensions must match");
+                let norm_a = norm_a.unwrap_or_else(|| simd::norm(a));
+                let norm_b = norm_b.unwrap_or_else(|| simd::norm(b));
+                cosine_from_parts(simd::dot(a, b), norm_a * norm_b)
+            }
+            _ => self.calculate(a, b),
+        }
+    }
 }
 
 /// Cosine similarity: dot(a,b) / (||a|| * ||b||)
-/// Returns value in [-1, 1],
+/// Returns value in [-1, 1]; stores are checked for consistent dimensions on load and
+/// insert, so mismatched lengths only compare their common prefix in release builds
 pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
-    assert_eq!(a.len(), b.len(), "Vector dimensions must match");
+    debug_assert_eq!(a.len(), b.len(), "Vector dimensions must match");
 
-    let (dot, norm_a_sq, norm_b_sq) = a
-        .iter()
-        .zip(b.iter())
-        .fold((0.0f32, 0.0f32, 0.0f32), |(dot, na, nb), (&x, &y)| {
-            (dot + x * y, na + x * x, nb + y * y)
-        });
+    let kernel = Kernel::detect();
+    let denominator = (kernel.norm_squared(a) * kernel.norm_squared(b)).sqrt();
+    cosine_from_parts(kernel.dot(a, b), denominator)
+}
 
-    let denominator = (norm_a_sq * norm_b_sq).sqrt();
+fn cosine_from_parts(dot: f32, denominator: f32) -> f32 {
     if denominator < f32::EPSILON {
         0.0
     } else {
@@ -96,20 +474,12 @@ pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
 pub fn euclidean_similarity(a: &[f32], b: &[f32]) -> f32 {
     debug_assert_eq!(a.len(), b.len(), "Vector dimensions must match");
 
-    let distance_sq: f32 = a
-        .iter()
-        .zip(b.iter())
-        .map(|(&x, &y)| {
-            let diff = x - y;
-            diff * diff
-        })
-        .sum();
-
+    let distance_sq = simd::squared_l2(a, b);
     1.0 / (1.0 + distance_sq.sqrt())
 }
 
 /// Dot product similarity (for normalized vectors)
 pub fn dot_product(a: &[f32], b: &[f32]) -> f32 {
     debug_assert_eq!(a.len(), b.len(), "Vector dimensions must match");
-    a.iter().zip(b.iter()).map(|(&x, &y)| x * y).sum()
+    simd::dot(a, b)
 }
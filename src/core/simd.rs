@@ -0,0 +1,299 @@
+use std::sync::OnceLock;
+
+/// An instruction set the vectorized distance kernels can run on.
+///
+/// Kernels can only be obtained from `detect`, `available` and `scalar`, so a kernel
+/// never runs on a CPU that lacks its features. Every kernel works on the common prefix
+/// of its inputs, like `zip`, so mismatched lengths never read out of bounds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Kernel(Isa);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Isa {
+    Scalar,
+    #[cfg_attr(not(target_arch = "x86_64"), allow(dead_code))]
+    Avx2,
+    #[cfg_attr(not(target_arch = "x86_64"), allow(dead_code))]
+    Avx512,
+    #[cfg_attr(not(target_arch = "aarch64"), allow(dead_code))]
+    Neon,
+}
+
+impl Kernel {
+    /// The portable kernel, available everywhere
+    pub fn scalar() -> Kernel {
+        Kernel(Isa::Scalar)
+    }
+
+    /// The fastest kernel supported by the running CPU, detected once per process
+    pub fn detect() -> Kernel {
+        static DETECTED: OnceLock<Kernel> = OnceLock::new();
+        *DETECTED.get_or_init(|| {
+            Kernel::available()
+                .into_iter()
+                .last()
+                .unwrap_or(Kernel::scalar())
+        })
+    }
+
+    /// Every kernel the running CPU supports, slowest first
+    pub fn available() -> Vec<Kernel> {
+        #[allow(unused_mut)]
+        let mut kernels = vec![Kernel::scalar()];
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma") {
+                kernels.push(Kernel(Isa::Avx2));
+            }
+            if is_x86_feature_detected!("avx512f") {
+                kernels.push(Kernel(Isa::Avx512));
+            }
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            if std::arch::is_aarch64_feature_detected!("neon") {
+                kernels.push(Kernel(Isa::Neon));
+            }
+        }
+        kernels
+    }
+
+    pub fn name(self) -> &'static str {
+        match self.0 {
+            Isa::Scalar => "scalar",
+            Isa::Avx2 => "avx2",
+            Isa::Avx512 => "avx512",
+            Isa::Neon => "neon",
+        }
+    }
+
+    pub fn dot(self, a: &[f32], b: &[f32]) -> f32 {
+        let len = a.len().min(b.len());
+        let (a, b) = (&a[..len], &b[..len]);
+        match self.0 {
+            // SAFETY: kernels other than scalar are only built by `available`, after
+            // the CPU reported the matching features
+            #[cfg(target_arch = "x86_64")]
+            Isa::Avx2 => unsafe { x86::dot_avx2(a, b) },
+            #[cfg(target_arch = "x86_64")]
+            Isa::Avx512 => unsafe { x86::dot_avx512(a, b) },
+            #[cfg(target_arch = "aarch64")]
+            Isa::Neon => unsafe { arm::dot_neon(a, b) },
+            _ => scalar::dot(a, b),
+        }
+    }
+
+    pub fn squared_l2(self, a: &[f32], b: &[f32]) -> f32 {
+        let len = a.len().min(b.len());
+        let (a, b) = (&a[..len], &b[..len]);
+        match self.0 {
+            // SAFETY: see `dot`
+            #[cfg(target_arch = "x86_64")]
+            Isa::Avx2 => unsafe { x86::squared_l2_avx2(a, b) },
+            #[cfg(target_arch = "x86_64")]
+            Isa::Avx512 => unsafe { x86::squared_l2_avx512(a, b) },
+            #[cfg(target_arch = "aarch64")]
+            Isa::Neon => unsafe { arm::squared_l2_neon(a, b) },
+            _ => scalar::squared_l2(a, b),
+        }
+    }
+
+    pub fn norm_squared(self, a: &[f32]) -> f32 {
+        self.dot(a, a)
+    }
+}
+
+/// Dot product on the detected kernel
+pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+    Kernel::detect().dot(a, b)
+}
+
+/// Squared Euclidean distance on the detected kernel
+pub fn squared_l2(a: &[f32], b: &[f32]) -> f32 {
+    Kernel::detect().squared_l2(a, b)
+}
+
+/// Euclidean norm on the detected kernel
+pub fn norm(a: &[f32]) -> f32 {
+    Kernel::detect().norm_squared(a).sqrt()
+}
+
+mod scalar {
+    /// Independent accumulators let the compiler keep several lanes in flight
+    const LANES: usize = 8;
+
+    pub(super) fn dot(a: &[f32], b: &[f32]) -> f32 {
+        let mut acc = [0.0f32; LANES];
+        let chunks = a.len() / LANES * LANES;
+        for (x, y) in a[..chunks]
+            .chunks_exact(LANES)
+            .zip(b[..chunks].chunks_exact(LANES))
+        {
+            for lane in 0..LANES {
+                acc[lane] += x[lane] * y[lane];
+            }
+        }
+        let tail: f32 = a[chunks..]
+            .iter()
+            .zip(&b[chunks..])
+            .map(|(x, y)| x * y)
+            .sum();
+        acc.iter().sum::<f32>() + tail
+    }
+
+    pub(super) fn squared_l2(a: &[f32], b: &[f32]) -> f32 {
+        let mut acc = [0.0f32; LANES];
+        let chunks = a.len() / LANES * LANES;
+        for (x, y) in a[..chunks]
+            .chunks_exact(LANES)
+            .zip(b[..chunks].chunks_exact(LANES))
+        {
+            for lane in 0..LANES {
+                let diff = x[lane] - y[lane];
+                acc[lane] += diff * diff;
+            }
+        }
+        let tail: f32 = a[chunks..]
+            .iter()
+            .zip(&b[chunks..])
+            .map(|(x, y)| (x - y) * (x - y))
+            .sum();
+        acc.iter().sum::<f32>() + tail
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    /// Both slices must have the same length
+    #[target_feature(enable = "avx2,fma")]
+    pub(super) unsafe fn dot_avx2(a: &[f32], b: &[f32]) -> f32 {
+        let chunks = a.len() / 16 * 16;
+        // SAFETY: every load reads 8 floats starting below `chunks <= len`
+        unsafe {
+            let mut acc0 = _mm256_setzero_ps();
+            let mut acc1 = _mm256_setzero_ps();
+            for i in (0..chunks).step_by(16) {
+                let (pa, pb) = (a.as_ptr().add(i), b.as_ptr().add(i));
+                acc0 = _mm256_fmadd_ps(_mm256_loadu_ps(pa), _mm256_loadu_ps(pb), acc0);
+                acc1 =
+                    _mm256_fmadd_ps(_mm256_loadu_ps(pa.add(8)), _mm256_loadu_ps(pb.add(8)), acc1);
+            }
+            horizontal_sum_256(_mm256_add_ps(acc0, acc1))
+                + super::scalar::dot(&a[chunks..], &b[chunks..])
+        }
+    }
+
+    /// Both slices must have the same length
+    #[target_feature(enable = "avx2,fma")]
+    pub(super) unsafe fn squared_l2_avx2(a: &[f32], b: &[f32]) -> f32 {
+        let chunks = a.len() / 16 * 16;
+        // SAFETY: every load reads 8 floats starting below `chunks <= len`
+        unsafe {
+            let mut acc0 = _mm256_setzero_ps();
+            let mut acc1 = _mm256_setzero_ps();
+            for i in (0..chunks).step_by(16) {
+                let (pa, pb) = (a.as_ptr().add(i), b.as_ptr().add(i));
+                let d0 = _mm256_sub_ps(_mm256_loadu_ps(pa), _mm256_loadu_ps(pb));
+                let d1 = _mm256_sub_ps(_mm256_loadu_ps(pa.add(8)), _mm256_loadu_ps(pb.add(8)));
+                acc0 = _mm256_fmadd_ps(d0, d0, acc0);
+                acc1 = _mm256_fmadd_ps(d1, d1, acc1);
+            }
+            horizontal_sum_256(_mm256_add_ps(acc0, acc1))
+                + super::scalar::squared_l2(&a[chunks..], &b[chunks..])
+        }
+    }
+
+    #[target_feature(enable = "avx2")]
+    fn horizontal_sum_256(v: __m256) -> f32 {
+        let sum = _mm_add_ps(_mm256_castps256_ps128(v), _mm256_extractf128_ps(v, 1));
+        let sum = _mm_add_ps(sum, _mm_movehl_ps(sum, sum));
+        let sum = _mm_add_ss(sum, _mm_shuffle_ps(sum, sum, 0b01));
+        _mm_cvtss_f32(sum)
+    }
+
+    /// Both slices must have the same length
+    #[target_feature(enable = "avx512f")]
+    pub(super) unsafe fn dot_avx512(a: &[f32], b: &[f32]) -> f32 {
+        let chunks = a.len() / 32 * 32;
+        // SAFETY: every load reads 16 floats starting below `chunks <= len`
+        unsafe {
+            let mut acc0 = _mm512_setzero_ps();
+            let mut acc1 = _mm512_setzero_ps();
+            for i in (0..chunks).step_by(32) {
+                let (pa, pb) = (a.as_ptr().add(i), b.as_ptr().add(i));
+                acc0 = _mm512_fmadd_ps(_mm512_loadu_ps(pa), _mm512_loadu_ps(pb), acc0);
+                acc1 = _mm512_fmadd_ps(
+                    _mm512_loadu_ps(pa.add(16)),
+                    _mm512_loadu_ps(pb.add(16)),
+                    acc1,
+                );
+            }
+            _mm512_reduce_add_ps(_mm512_add_ps(acc0, acc1))
+                + super::scalar::dot(&a[chunks..], &b[chunks..])
+        }
+    }
+
+    /// Both slices must have the same length
+    #[target_feature(enable = "avx512f")]
+    pub(super) unsafe fn squared_l2_avx512(a: &[f32], b: &[f32]) -> f32 {
+        let chunks = a.len() / 32 * 32;
+        // SAFETY: every load reads 16 floats starting below `chunks <= len`
+        unsafe {
+            let mut acc0 = _mm512_setzero_ps();
+            let mut acc1 = _mm512_setzero_ps();
+            for i in (0..chunks).step_by(32) {
+                let (pa, pb) = (a.as_ptr().add(i), b.as_ptr().add(i));
+                let d0 = _mm512_sub_ps(_mm512_loadu_ps(pa), _mm512_loadu_ps(pb));
+                let d1 = _mm512_sub_ps(_mm512_loadu_ps(pa.add(16)), _mm512_loadu_ps(pb.add(16)));
+                acc0 = _mm512_fmadd_ps(d0, d0, acc0);
+                acc1 = _mm512_fmadd_ps(d1, d1, acc1);
+            }
+            _mm512_reduce_add_ps(_mm512_add_ps(acc0, acc1))
+                + super::scalar::squared_l2(&a[chunks..], &b[chunks..])
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod arm {
+    use std::arch::aarch64::*;
+
+    /// Both slices must have the same length
+    #[target_feature(enable = "neon")]
+    pub(super) unsafe fn dot_neon(a: &[f32], b: &[f32]) -> f32 {
+        let chunks = a.len() / 8 * 8;
+        // SAFETY: every load reads 4 floats starting below `chunks <= len`
+        unsafe {
+            let mut acc0 = vdupq_n_f32(0.0);
+            let mut acc1 = vdupq_n_f32(0.0);
+            for i in (0..chunks).step_by(8) {
+                let (pa, pb) = (a.as_ptr().add(i), b.as_ptr().add(i));
+                acc0 = vfmaq_f32(acc0, vld1q_f32(pa), vld1q_f32(pb));
+                acc1 = vfmaq_f32(acc1, vld1q_f32(pa.add(4)), vld1q_f32(pb.add(4)));
+            }
+            vaddvq_f32(vaddq_f32(acc0, acc1)) + super::scalar::dot(&a[chunks..], &b[chunks..])
+        }
+    }
+
+    /// Both slices must have the same length
+    #[target_feature(enable = "neon")]
+    pub(super) unsafe fn squared_l2_neon(a: &[f32], b: &[f32]) -> f32 {
+        let chunks = a.len() / 8 * 8;
+        // SAFETY: every load reads 4 floats starting below `chunks <= len`
+        unsafe {
+            let mut acc0 = vdupq_n_f32(0.0);
+            let mut acc1 = vdupq_n_f32(0.0);
+            for i in (0..chunks).step_by(8) {
+                let (pa, pb) = (a.as_ptr().add(i), b.as_ptr().add(i));
+                let d0 = vsubq_f32(vld1q_f32(pa), vld1q_f32(pb));
+                let d1 = vsubq_f32(vld1q_f32(pa.add(4)), vld1q_f32(pb.add(4)));
+                acc0 = vfmaq_f32(acc0, d0, d0);
+                acc1 = vfmaq_f32(acc1, d1, d1);
+            }
+            vaddvq_f32(vaddq_f32(acc0, acc1))
+                + super::scalar::squared_l2(&a[chunks..], &b[chunks..])
+        }
+    }
+}
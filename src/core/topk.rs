@@ -0,0 +1,105 @@
+use rayon::iter::ParallelIterator;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+/// A scored position; greater means a better match
+#[derive(Clone, Copy, Debug)]
+struct Ranked {
+    idx: usize,
+    score: f32,
+}
+
+impl PartialEq for Ranked {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ranked {}
+
+impl PartialOrd for Ranked {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ranked {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // NaN ranks below every number; equal scores prefer the earlier position, which
+        // matches a stable descending sort
+        other
+            .score
+            .is_nan()
+            .cmp(&self.score.is_nan())
+            .then_with(|| self.score.total_cmp(&other.score))
+            .then_with(|| other.idx.cmp(&self.idx))
+    }
+}
+
+/// The `k` best `(position, score)` pairs seen so far, kept in a min-heap so each push
+/// costs `O(log k)` and memory stays at `k` entries
+#[derive(Debug)]
+pub(crate) struct TopK {
+    k: usize,
+    heap: BinaryHeap<Reverse<Ranked>>,
+}
+
+impl TopK {
+    pub(crate) fn new(k: usize) -> Self {
+        Self {
+            k,
+            heap: BinaryHeap::with_capacity(k.min(4096) + 1),
+        }
+    }
+
+    pub(crate) fn push(&mut self, idx: usize, score: f32) {
+        if self.k == 0 {
+            return;
+        }
+        let candidate = Ranked { idx, score };
+        if self.heap.len() < self.k {
+            self.heap.push(Reverse(candidate));
+        } else if let Some(mut worst) = self.heap.peek_mut()
+            && candidate > worst.0
+        {
+            *worst = Reverse(candidate);
+        }
+    }
+
+    pub(crate) fn merge(mut self, other: TopK) -> TopK {
+        if self.heap.len() < other.heap.len() {
+            return other.merge(self);
+        }
+        for Reverse(ranked) in other.heap {
+            self.push(ranked.idx, ranked.score);
+        }
+        self
+    }
+
+    /// The kept pairs, best first
+    pub(crate) fn into_sorted(self) -> Vec<(usize, f32)> {
+        self.heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(ranked)| (ranked.idx, ranked.score))
+            .collect()
+    }
+}
+
+/// Select the `k` best pairs from a parallel iterator with one bounded heap per rayon
+/// job, merged at the end; returned best first
+pub(crate) fn top_k<I>(candidates: I, k: usize) -> Vec<(usize, f32)>
+where
+    I: ParallelIterator<Item = (usize, f32)>,
+{
+    candidates
+        .fold(
+            || TopK::new(k),
+            |mut top, (idx, score)| {
+                top.push(idx, score);
+                top
+            },
+        )
+        .reduce(|| TopK::new(k), TopK::merge)
+        .into_sorted()
+}
@@ -1,8 +1,19 @@
-mod cli;
+pub mod cli;
+pub mod config;
 mod core;
+pub mod server;
 pub mod utils;
 
 pub mod prelude {
-    pub use crate::core::{Metrics, SearchQuery, SearchResult};
-    pub use crate::utils::{EmbeddingStore, Ingestor, Provider, VectorData};
+    pub use crate::core::{
+        Bm25Config, Bm25Index, Filter, Fusion, HnswConfig, HnswIndex, Hybrid, IndexType, IvfConfig,
+        IvfIndex, Kernel, LookupTable, Metrics, PqCodec, PqConfig, PqIndex, QuantizationReport,
+        QuantizedIndex, ScalarQuantizer, SearchMode, SearchQuery, SearchResult, VectorIndex,
+        VectorSource, recall_at_k, tokenize,
+    };
+    pub use crate::utils::{
+        CollectionSchema, Collections, ConsistencyError, EmbeddingProvider, EmbeddingStore,
+        HashingProvider, Ingestor, MappedStore, Metadata, MetadataValue, Provider, ProviderKind,
+        Segment, VectorData,
+    };
 }
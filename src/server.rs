@@ -0,0 +1,329 @@
+use crate::config::{Config, SearchConfig};
+use crate::core::{Filter, Metrics, SearchQuery, SearchResult, VectorIndex};
+use crate::utils::{
+    CollectionSchema, ConsistencyError, EmbeddingData, EmbeddingProvider, EmbeddingStore, Metadata,
+    VectorData,
+};
+use anyhow::Result;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::RwLock;
+
+/// Shared state of a running server: one store loaded once and kept in memory
+pub struct AppState {
+    pub store_path: String,
+    pub provider: Arc<dyn EmbeddingProvider>,
+    pub data: RwLock<VectorData>,
+    /// Defaults for search requests that omit `top_k` or `metric`
+    pub search: SearchConfig,
+    /// Schema of the collection being served, if one was selected
+    pub collection: Option<CollectionSchema>,
+    /// The collection's declared index over `data`, kept up to date with inserts
+    index: RwLock<Option<Box<dyn VectorIndex + Send + Sync>>>,
+}
+
+impl AppState {
+    /// Load every batch in `store_path`, starting empty when the directory has none
+    pub async fn load(
+        store_path: impl Into<String>,
+        provider: impl EmbeddingProvider + 'static,
+    ) -> Result<Self> {
+        let store_path = store_path.into();
+        let data = EmbeddingStore::open(&store_path).await?;
+
+        Ok(Self {
+            store_path,
+            provider: Arc::new(provider),
+            data: RwLock::new(data),
+            search: SearchConfig::default(),
+            collection: None,
+            index: RwLock::new(None),
+        })
+    }
+
+    /// Load the store and provider named in `config`. A selected collection is served
+    /// with its schema's model and metric and searched through its declared index, as
+    /// the CLI does.
+    pub async fn from_config(config: &Config) -> Result<Self> {
+        let mut config = config.clone();
+        let (store, collection) = config.select_collection().await?;
+        let mut state = Self::load(store, config.provider.embedder()).await?;
+        state.search = config.search;
+        if let Some(schema) = &collection {
+            let index = schema
+                .index
+                .open(&state.store_path, state.data.get_mut(), schema.metric)
+                .await?;
+            state.index = RwLock::new(index);
+        }
+        state.collection = collection;
+        Ok(state)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SearchRequest {
+    /// Raw query vector; takes precedence over `text`
+    pub vector: Option<Vec<f32>>,
+    /// Text to embed with the server's provider
+    pub text: Option<String>,
+    pub top_k: Option<usize>,
+    pub metric: Option<Metrics>,
+    pub filter: Option<Filter>,
+}
+
+#[derive(Serialize)]
+pub struct SearchResponse {
+    pub results: Vec<SearchResult>,
+}
+
+#[derive(Deserialize)]
+pub struct InsertItem {
+    #[serde(default)]
+    pub id: String,
+    pub chunk: String,
+    /// Precomputed embedding; the chunk is embedded with the provider when absent
+    pub vector: Option<Vec<f32>>,
+    #[serde(default)]
+    pub metadata: Metadata,
+}
+
+#[derive(Deserialize)]
+pub struct InsertRequest {
+    pub items: Vec<InsertItem>,
+}
+
+#[derive(Serialize)]
+pub struct InsertResponse {
+    pub inserted: usize,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteRequest {
+    pub ids: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct DeleteResponse {
+    pub deleted: usize,
+}
+
+#[derive(Serialize)]
+pub struct StatsResponse {
+    pub total_vectors: usize,
+    pub dimensions: usize,
+    pub memory_usage_mb: f64,
+}
+
+/// Error returned to HTTP clients as `{"error": "..."}`
+pub struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            message: message.into(),
+        }
+    }
+
+    fn bad_gateway(error: anyhow::Error) -> Self {
+        Self {
+            status: StatusCode::BAD_GATEWAY,
+            message: format!("Embedding provider failed: {}", error),
+        }
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(error: anyhow::Error) -> Self {
+        if error.downcast_ref::<ConsistencyError>().is_some() {
+            return Self::bad_request(error.to_string());
+        }
+        Self {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: error.to_string(),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = Json(serde_json::json!({ "error": self.message }));
+        (self.status, body).into_response()
+    }
+}
+
+/// Build the API routes over `state`
+pub fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/stats", get(stats))
+        .route("/search", post(search))
+        .route("/insert", post(insert))
+        .route("/delete", post(delete))
+        .with_state(state)
+}
+
+/// Serve the API on an already bound listener until the process exits
+pub async fn serve(listener: TcpListener, state: Arc<AppState>) -> Result<()> {
+    axum::serve(listener, router(state)).await?;
+    Ok(())
+}
+
+async fn health() -> &'static str {
+    "ok"
+}
+
+async fn stats(State(state): State<Arc<AppState>>) -> Json<StatsResponse> {
+    let data = state.data.read().await;
+    Json(StatsResponse {
+        total_vectors: data.total_vectors,
+        dimensions: data.dimensions,
+        memory_usage_mb: data.memory_usage_mb(),
+    })
+}
+
+async fn search(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<SearchRequest>,
+) -> Result<Json<SearchResponse>, ApiError> {
+    let query_vector = match (request.vector, request.text) {
+        (Some(vector), _) => vector,
+        (None, Some(text)) => {
+            let embeddings = state
+                .provider
+                .fetch_query(&text)
+                .await
+                .map_err(ApiError::bad_gateway)?;
+            embeddings
+                .data
+                .into_iter()
+                .next()
+                .map(|e| e.embedding)
+                .ok_or_else(|| ApiError::bad_gateway(anyhow::anyhow!("No embedding returned")))?
+        }
+        (None, None) => return Err(ApiError::bad_request("Either vector or text is required")),
+    };
+
+    let data = state.data.read().await;
+    let mut query = SearchQuery::new(
+        request.top_k.unwrap_or(state.search.top_k),
+        query_vector,
+        request.metric.unwrap_or(state.search.metric),
+    );
+    if let Some(filter) = request.filter {
+        query = query.with_filter(filter);
+    }
+
+    // A metric other than the collection's is ranked exactly
+    let index = state.index.read().await;
+    let index = match &state.collection {
+        Some(schema) if schema.metric == query.metric => index.as_deref(),
+        _ => None,
+    };
+    let results = query
+        .search_in(&data, index)
+        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+    Ok(Json(SearchResponse { results }))
+}
+
+async fn insert(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<InsertRequest>,
+) -> Result<Json<InsertResponse>, ApiError> {
+    // Embed every item that did not come with its own vector in a single request
+    let missing: Vec<String> = request
+        .items
+        .iter()
+        .filter(|item| item.vector.is_none())
+        .map(|item| item.chunk.clone())
+        .collect();
+    let fetched = if missing.is_empty() {
+        Vec::new()
+    } else {
+        state
+            .provider
+            .fetch_embeddings(&missing)
+            .await
+            .map_err(ApiError::bad_gateway)?
+            .data
+    };
+    // The provider may answer in any order; `index` is the position of the chunk in
+    // `missing`
+    let mut embedded: Vec<Option<Vec<f32>>> = vec![None; missing.len()];
+    for item in fetched {
+        match embedded.get_mut(item.index) {
+            Some(slot @ None) => *slot = Some(item.embedding),
+            _ => {
+                return Err(ApiError::bad_gateway(anyhow::anyhow!(
+                    "Provider returned an unexpected embedding index {}",
+                    item.index
+                )));
+            }
+        }
+    }
+    let mut embedded = embedded.into_iter();
+
+    let mut items = Vec::with_capacity(request.items.len());
+    for (index, item) in request.items.into_iter().enumerate() {
+        let embedding = match item.vector {
+            Some(vector) => vector,
+            None => embedded.next().flatten().ok_or_else(|| {
+                ApiError::bad_gateway(anyhow::anyhow!(
+                    "Provider returned no embedding for item {}",
+                    index
+                ))
+            })?,
+        };
+        items.push(EmbeddingData {
+            id: item.id,
+            index,
+            chunk: item.chunk,
+            dimensions: embedding.len(),
+            embedding,
+            metadata: item.metadata,
+        });
+    }
+
+    // Only batches embedded here are known to come from the provider's model; a
+    // collection's batches always carry its schema's model
+    let model = match &state.collection {
+        Some(schema) => {
+            schema.check(&items).map_err(anyhow::Error::from)?;
+            schema.model.as_str()
+        }
+        None if missing.is_empty() => "",
+        None => state.provider.model(),
+    };
+    let mut data = state.data.write().await;
+    let inserted = items.len();
+    EmbeddingStore::upsert_with_model(&state.store_path, &mut data, model, items).await?;
+    if let Some(schema) = &state.collection {
+        let index = schema
+            .index
+            .open(&state.store_path, &data, schema.metric)
+            .await?;
+        *state.index.write().await = index;
+    }
+
+    Ok(Json(InsertResponse { inserted }))
+}
+
+async fn delete(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<DeleteRequest>,
+) -> Result<Json<DeleteResponse>, ApiError> {
+    let mut data = state.data.write().await;
+    let deleted = EmbeddingStore::delete(&state.store_path, &mut data, request.ids).await?;
+
+    Ok(Json(DeleteResponse { deleted }))
+}
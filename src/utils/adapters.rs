@@ -0,0 +1,146 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+/// Cohere input type of ingested text unless `input_type` sets another
+pub const DEFAULT_INPUT_TYPE: &str = "search_document";
+/// Cohere input type of search queries
+pub const QUERY_INPUT_TYPE: &str = "search_query";
+
+/// Request and response format of an embeddings API.
+///
+/// Every kind is served by the same `Provider`, so retries, timeouts and rate limits
+/// behave alike; only the JSON sent and parsed differs.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderKind {
+    /// OpenAI `/v1/embeddings` and compatible servers (LM Studio, vLLM, llama.cpp):
+    /// `{"model","input","dimensions"}` → `{"data":[{"index","embedding"}]}`
+    #[default]
+    #[serde(rename = "openai")]
+    OpenAi,
+    /// Ollama `/api/embed`: `{"model","input"}` → `{"embeddings":[[..]]}`
+    Ollama,
+    /// Hugging Face Text Embeddings Inference `/embed`: `{"inputs"}` → `[[..]]`
+    Tei,
+    /// Cohere `/v1/embed` or `/v2/embed`: `{"model","texts","input_type"}` →
+    /// `{"embeddings":[[..]]}` or `{"embeddings":{"float":[[..]]}}`
+    Cohere,
+}
+
+impl std::str::FromStr for ProviderKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "openai" => Ok(ProviderKind::OpenAi),
+            "ollama" => Ok(ProviderKind::Ollama),
+            "tei" => Ok(ProviderKind::Tei),
+            "cohere" => Ok(ProviderKind::Cohere),
+            _ => anyhow::bail!(
+                "Unknown provider kind: {} (expected openai, ollama, tei or cohere)",
+                s
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for ProviderKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ProviderKind::OpenAi => "openai",
+            ProviderKind::Ollama => "ollama",
+            ProviderKind::Tei => "tei",
+            ProviderKind::Cohere => "cohere",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Per-request options an adapter may send
+pub(crate) struct RequestOptions<'a> {
+    pub model: &'a str,
+    pub dimensions: Option<usize>,
+    pub input_type: &'a str,
+}
+
+#[derive(Deserialize)]
+struct OpenAiResponse {
+    data: Vec<OpenAiEmbedding>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiEmbedding {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingsResponse {
+    embeddings: EmbeddingList,
+}
+
+/// Ollama and Cohere v1 return a plain list; Cohere v2 groups it by embedding type
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum EmbeddingList {
+    Plain(Vec<Vec<f32>>),
+    Typed { float: Vec<Vec<f32>> },
+}
+
+impl ProviderKind {
+    /// JSON body asking for embeddings of `chunks`
+    pub(crate) fn request(&self, chunks: &[String], options: &RequestOptions) -> Value {
+        match self {
+            ProviderKind::OpenAi => {
+                let mut body = json!({
+                    "model": options.model,
+                    "input": chunks,
+                });
+                if let Some(dimensions) = options.dimensions {
+                    body["dimensions"] = json!(dimensions);
+                }
+                body
+            }
+            ProviderKind::Ollama => json!({
+                "model": options.model,
+                "input": chunks,
+            }),
+            // The model is fixed when the server starts
+            ProviderKind::Tei => json!({ "inputs": chunks }),
+            ProviderKind::Cohere => json!({
+                "model": options.model,
+                "texts": chunks,
+                "input_type": options.input_type,
+                "embedding_types": ["float"],
+            }),
+        }
+    }
+
+    /// Parse a response body into `(index, embedding)` pairs, where `index` is the
+    /// position of the chunk in the request
+    pub(crate) fn parse(&self, body: &[u8]) -> Result<Vec<(usize, Vec<f32>)>> {
+        let context = || format!("Unexpected {} embeddings response", self);
+        let embeddings = match self {
+            ProviderKind::OpenAi => serde_json::from_slice::<OpenAiResponse>(body)
+                .with_context(context)?
+                .data
+                .into_iter()
+                .map(|item| (item.index, item.embedding))
+                .collect(),
+            ProviderKind::Ollama | ProviderKind::Cohere => {
+                let response: EmbeddingsResponse =
+                    serde_json::from_slice(body).with_context(context)?;
+                let (EmbeddingList::Plain(embeddings) | EmbeddingList::Typed { float: embeddings }) =
+                    response.embeddings;
+                embeddings.into_iter().enumerate().collect()
+            }
+            ProviderKind::Tei => serde_json::from_slice::<Vec<Vec<f32>>>(body)
+                .with_context(context)?
+                .into_iter()
+                .enumerate()
+                .collect(),
+        };
+        Ok(embeddings)
+    }
+}
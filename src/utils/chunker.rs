@@ -0,0 +1,446 @@
+use crate::utils::Metadata;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Window and section size, in characters or tokens, unless a chunker sets another
+pub const DEFAULT_CHUNK_SIZE: usize = 1000;
+/// Units shared by consecutive windows unless a chunker sets another
+pub const DEFAULT_CHUNK_OVERLAP: usize = 100;
+
+/// A piece of source text and where it came from
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Chunk {
+    pub text: String,
+    /// Byte range in the document text, so `&text[start..end] == chunk.text`
+    pub start: usize,
+    pub end: usize,
+    /// Markdown heading trail, e.g. `Install > Linux`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub section: Option<String>,
+    /// Metadata of the document the chunk was cut from
+    #[serde(default, skip_serializing_if = "Metadata::is_empty")]
+    pub metadata: Metadata,
+}
+
+/// What the size of a fixed window counts
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowUnit {
+    #[default]
+    Chars,
+    /// Whitespace-separated words
+    Tokens,
+}
+
+/// How source text is cut into chunks before embedding.
+///
+/// Every chunker trims whitespace around its chunks, drops blank ones and keeps the
+/// byte offsets of each chunk in the source. Sizes count characters, not bytes.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Chunker {
+    /// Every non-empty line
+    #[default]
+    Line,
+    /// Text between blank lines
+    Paragraph,
+    /// Sentences ending in `.`, `!` or `?` (and their CJK forms), never crossing a
+    /// paragraph break; common abbreviations such as "Mr." and punctuation followed by a
+    /// lowercase word do not end a sentence
+    Sentence,
+    /// Fixed windows of `size` units, each starting `size - overlap` units after the last
+    Window {
+        #[serde(default = "default_size")]
+        size: usize,
+        #[serde(default = "default_overlap")]
+        overlap: usize,
+        #[serde(default)]
+        unit: WindowUnit,
+    },
+    /// Split at the first separator that occurs, merge neighbouring pieces up to `size`
+    /// characters with `overlap` characters repeated between chunks, and split pieces
+    /// that are still too large at the next separator, ending with character windows
+    Recursive {
+        #[serde(default = "default_size")]
+        size: usize,
+        #[serde(default = "default_overlap")]
+        overlap: usize,
+        #[serde(default = "default_separators")]
+        separators: Vec<String>,
+    },
+    /// One chunk per section under an ATX heading (`#` to `######`, outside code fences),
+    /// labelled with its heading trail; sections over `size` characters are split
+    /// recursively
+    Markdown {
+        #[serde(default = "default_size")]
+        size: usize,
+    },
+}
+
+fn default_size() -> usize {
+    DEFAULT_CHUNK_SIZE
+}
+
+fn default_overlap() -> usize {
+    DEFAULT_CHUNK_OVERLAP
+}
+
+/// Paragraphs, then lines, then sentences, then words
+pub fn default_separators() -> Vec<String> {
+    ["\n\n", "\n", ". ", " "].map(String::from).to_vec()
+}
+
+impl std::str::FromStr for Chunker {
+    type Err = anyhow::Error;
+
+    /// A chunker by name, with default sizes
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "line" => Ok(Chunker::Line),
+            "paragraph" => Ok(Chunker::Paragraph),
+            "sentence" => Ok(Chunker::Sentence),
+            "window" => Ok(Chunker::Window {
+                size: DEFAULT_CHUNK_SIZE,
+                overlap: DEFAULT_CHUNK_OVERLAP,
+                unit: WindowUnit::Chars,
+            }),
+            "recursive" => Ok(Chunker::Recursive {
+                size: DEFAULT_CHUNK_SIZE,
+                overlap: DEFAULT_CHUNK_OVERLAP,
+                separators: default_separators(),
+            }),
+            "markdown" => Ok(Chunker::Markdown {
+                size: DEFAULT_CHUNK_SIZE,
+            }),
+            other => anyhow::bail!(
+                "Unknown chunker: {} (expected line, paragraph, sentence, window, recursive or markdown)",
+                other
+            ),
+        }
+    }
+}
+
+impl Chunker {
+    /// Cut `text` into chunks in source order
+    pub fn chunk(&self, text: &str) -> Vec<Chunk> {
+        let spans = match self {
+            Chunker::Line => lines(text)
+                .map(|(offset, line)| (offset, offset + line.len()))
+                .collect(),
+            Chunker::Paragraph => paragraphs(text),
+            Chunker::Sentence => paragraphs(text)
+                .into_iter()
+                .flat_map(|(start, end)| sentences(text, start, end))
+                .collect(),
+            Chunker::Window {
+                size,
+                overlap,
+                unit: WindowUnit::Chars,
+            } => char_windows(text, 0, text.len(), *size, *overlap),
+            Chunker::Window {
+                size,
+                overlap,
+                unit: WindowUnit::Tokens,
+            } => token_windows(text, *size, *overlap),
+            Chunker::Recursive {
+                size,
+                overlap,
+                separators,
+            } => recursive(text, 0, text.len(), *size, *overlap, separators),
+            Chunker::Markdown { size } => {
+                let separators = default_separators();
+                return sections(text)
+                    .into_iter()
+                    .flat_map(|(start, end, section)| {
+                        recursive(text, start, end, *size, 0, &separators)
+                            .into_iter()
+                            .filter_map(move |(start, end)| {
+                                trimmed(text, start, end, section.clone())
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .collect();
+            }
+        };
+        spans
+            .into_iter()
+            .filter_map(|(start, end)| trimmed(text, start, end, None))
+            .collect()
+    }
+}
+
+/// Chunk of `source[start..end]` without surrounding whitespace, or `None` when blank
+fn trimmed(source: &str, start: usize, end: usize, section: Option<String>) -> Option<Chunk> {
+    let slice = &source[start..end];
+    let text = slice.trim_start();
+    let start = start + (slice.len() - text.len());
+    let text = text.trim_end();
+    if text.is_empty() {
+        return None;
+    }
+    Some(Chunk {
+        text: text.to_string(),
+        start,
+        end: start + text.len(),
+        section,
+        metadata: Metadata::new(),
+    })
+}
+
+/// Lines including their newline, with the byte offset of each
+fn lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.split_inclusive('\n').scan(0, |offset, line| {
+        let start = *offset;
+        *offset += line.len();
+        Some((start, line))
+    })
+}
+
+fn paragraphs(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (offset, line) in lines(text) {
+        if line.trim().is_empty() {
+            if let Some(start) = start.take() {
+                spans.push((start, offset));
+            }
+        } else if start.is_none() {
+            start = Some(offset);
+        }
+    }
+    if let Some(start) = start {
+        spans.push((start, text.len()));
+    }
+    spans
+}
+
+/// Words that end in a period without ending the sentence
+const ABBREVIATIONS: &[&str] = &[
+    "Mr", "Mrs", "Ms", "Dr", "Prof", "St", "Jr", "Sr", "vs", "e.g", "i.e",
+];
+
+fn sentences(text: &str, start: usize, end: usize) -> Vec<(usize, usize)> {
+    let slice = &text[start..end];
+    let mut spans = Vec::new();
+    let mut begin = 0;
+    let mut chars = slice.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if !matches!(c, '.' | '!' | '?' | '。' | '！' | '？') {
+            continue;
+        }
+        // Keep trailing punctuation and closing quotes with the sentence
+        let mut stop = i + c.len_utf8();
+        while let Some(&(j, next)) = chars.peek() {
+            if !matches!(
+                next,
+                '.' | '!' | '?' | '"' | '\'' | '”' | '’' | ')' | ']' | '»'
+            ) {
+                break;
+            }
+            stop = j + next.len_utf8();
+            chars.next();
+        }
+
+        // CJK text has no space after a full stop
+        let at_boundary = chars
+            .peek()
+            .is_none_or(|&(_, next)| next.is_whitespace() || !c.is_ascii());
+        // A lowercase word after "?" or a closing quote continues the sentence
+        let continues = slice[stop..]
+            .trim_start()
+            .chars()
+            .next()
+            .is_some_and(char::is_lowercase);
+        if at_boundary && !continues && !(c == '.' && is_abbreviation(&slice[begin..i])) {
+            spans.push((start + begin, start + stop));
+            begin = stop;
+        }
+    }
+    if begin < slice.len() {
+        spans.push((start + begin, end));
+    }
+    spans
+}
+
+fn is_abbreviation(before: &str) -> bool {
+    let word = before
+        .rsplit(char::is_whitespace)
+        .next()
+        .unwrap_or("")
+        .trim_start_matches(|c: char| !c.is_alphanumeric());
+    // Initials like "J. R. R. Tolkien"
+    let initial = word.chars().count() == 1 && word.chars().all(char::is_uppercase);
+    initial || ABBREVIATIONS.contains(&word)
+}
+
+fn char_windows(
+    text: &str,
+    start: usize,
+    end: usize,
+    size: usize,
+    overlap: usize,
+) -> Vec<(usize, usize)> {
+    let mut bounds: Vec<usize> = text[start..end]
+        .char_indices()
+        .map(|(i, _)| start + i)
+        .collect();
+    bounds.push(end);
+    windows(bounds.len() - 1, size, overlap)
+        .into_iter()
+        .map(|(first, last)| (bounds[first], bounds[last]))
+        .collect()
+}
+
+fn token_windows(text: &str, size: usize, overlap: usize) -> Vec<(usize, usize)> {
+    let mut words = Vec::new();
+    let mut begin = None;
+    for (i, c) in text.char_indices() {
+        if c.is_whitespace() {
+            if let Some(begin) = begin.take() {
+                words.push((begin, i));
+            }
+        } else if begin.is_none() {
+            begin = Some(i);
+        }
+    }
+    if let Some(begin) = begin {
+        words.push((begin, text.len()));
+    }
+    if words.is_empty() {
+        return Vec::new();
+    }
+    windows(words.len(), size, overlap)
+        .into_iter()
+        .map(|(first, last)| (words[first].0, words[last - 1].1))
+        .collect()
+}
+
+/// `[first, last)` unit ranges of windows over `units` units; the last one ends at `units`
+fn windows(units: usize, size: usize, overlap: usize) -> Vec<(usize, usize)> {
+    let size = size.max(1);
+    let step = size.saturating_sub(overlap).max(1);
+    let mut spans = Vec::new();
+    let mut first = 0;
+    loop {
+        let last = (first + size).min(units);
+        spans.push((first, last));
+        if last >= units {
+            return spans;
+        }
+        first += step;
+    }
+}
+
+fn recursive(
+    text: &str,
+    start: usize,
+    end: usize,
+    size: usize,
+    overlap: usize,
+    separators: &[String],
+) -> Vec<(usize, usize)> {
+    let size = size.max(1);
+    let slice = &text[start..end];
+    if slice.chars().count() <= size {
+        return vec![(start, end)];
+    }
+    let Some(index) = separators
+        .iter()
+        .position(|separator| !separator.is_empty() && slice.contains(separator.as_str()))
+    else {
+        return char_windows(text, start, end, size, overlap);
+    };
+    let rest = &separators[index + 1..];
+
+    let mut spans = Vec::new();
+    // Pieces in the chunk being built, as (start, end, chars)
+    let mut window: VecDeque<(usize, usize, usize)> = VecDeque::new();
+    let mut window_chars = 0;
+    let mut offset = start;
+    // Pieces keep their separator, so together they cover the whole range
+    for piece in slice.split_inclusive(separators[index].as_str()) {
+        let piece_start = offset;
+        offset += piece.len();
+        let chars = piece.chars().count();
+
+        if chars > size {
+            if let (Some(first), Some(last)) = (window.front(), window.back()) {
+                spans.push((first.0, last.1));
+            }
+            window.clear();
+            window_chars = 0;
+            spans.extend(recursive(text, piece_start, offset, size, overlap, rest));
+            continue;
+        }
+        if window_chars + chars > size
+            && let (Some(first), Some(last)) = (window.front(), window.back())
+        {
+            spans.push((first.0, last.1));
+            // Carry at most `overlap` characters of trailing pieces into the next chunk
+            while window_chars > overlap || (!window.is_empty() && window_chars + chars > size) {
+                let Some((_, _, dropped)) = window.pop_front() else {
+                    break;
+                };
+                window_chars -= dropped;
+            }
+        }
+        window.push_back((piece_start, offset, chars));
+        window_chars += chars;
+    }
+    if let (Some(first), Some(last)) = (window.front(), window.back()) {
+        spans.push((first.0, last.1));
+    }
+    spans
+}
+
+/// Markdown sections as (start, end, heading trail); text before the first heading has
+/// no trail
+fn sections(text: &str) -> Vec<(usize, usize, Option<String>)> {
+    let mut sections = Vec::new();
+    let mut trail: Vec<(usize, String)> = Vec::new();
+    let mut start = 0;
+    let mut section = None;
+    let mut fence: Option<&str> = None;
+    for (offset, line) in lines(text) {
+        let content = line.trim_start();
+        if let Some(marker) = ["```", "~~~"].into_iter().find(|m| content.starts_with(m)) {
+            match fence {
+                Some(open) if open == marker => fence = None,
+                None => fence = Some(marker),
+                _ => {}
+            }
+            continue;
+        }
+        if fence.is_some() {
+            continue;
+        }
+        let Some((level, title)) = heading(line) else {
+            continue;
+        };
+
+        if offset > start {
+            sections.push((start, offset, section.clone()));
+        }
+        trail.retain(|(parent, _)| *parent < level);
+        trail.push((level, title.to_string()));
+        let titles: Vec<&str> = trail.iter().map(|(_, title)| title.as_str()).collect();
+        section = Some(titles.join(" > "));
+        start = offset;
+    }
+    sections.push((start, text.len(), section));
+    sections
+}
+
+/// Level and title of an ATX heading line
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let content = line.trim_start_matches(' ');
+    if line.len() - content.len() > 3 {
+        return None;
+    }
+    let level = content.len() - content.trim_start_matches('#').len();
+    let title = &content[level..];
+    if !(1..=6).contains(&level) || !(title.is_empty() || title.starts_with(char::is_whitespace)) {
+        return None;
+    }
+    Some((level, title.trim().trim_end_matches('#').trim_end()))
+}
@@ -0,0 +1,300 @@
+use crate::core::{IndexType, Metrics};
+use crate::utils::{ConsistencyError, EmbeddingData, EmbeddingStore, VectorData, write_atomic};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// File name of the collection manifest, stored in the root of the store
+pub const MANIFEST_FILE_NAME: &str = "collections.json";
+
+/// Declared shape of a named collection.
+///
+/// Every vector in the collection must have `dimensions` values and come from `model`;
+/// `metric` and `index` are the defaults for searching it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CollectionSchema {
+    pub name: String,
+    pub dimensions: usize,
+    pub metric: Metrics,
+    pub model: String,
+    #[serde(default)]
+    pub index: IndexType,
+}
+
+impl CollectionSchema {
+    pub fn new(
+        name: impl Into<String>,
+        dimensions: usize,
+        metric: Metrics,
+        model: impl Into<String>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            dimensions,
+            metric,
+            model: model.into(),
+            index: IndexType::Flat,
+        }
+    }
+
+    pub fn with_index(mut self, index: IndexType) -> Self {
+        self.index = index;
+        self
+    }
+
+    /// Fail on the first item whose vector does not have the declared dimensions
+    pub fn check(&self, items: &[EmbeddingData]) -> Result<(), ConsistencyError> {
+        match items
+            .iter()
+            .find(|item| item.embedding.len() != self.dimensions)
+        {
+            Some(item) => Err(ConsistencyError::Dimensions {
+                id: item.id.clone(),
+                expected: self.dimensions,
+                found: item.embedding.len(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Extensions of the files a store directory holds next to its collection directories
+const STORE_EXTENSIONS: &[&str] = &[
+    "bin",
+    "log",
+    "json",
+    "seg",
+    "tmp",
+    "manifest",
+    "checkpoint",
+    "counter",
+    "hnsw",
+    "ivf",
+    "sq8",
+    "pq",
+    "bm25",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+struct Manifest {
+    collections: Vec<CollectionSchema>,
+    /// Directory moves `(from, to)` of renames already recorded in `collections`;
+    /// a move interrupted by a crash is finished when the manifest is next opened
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    moves: Vec<(String, String)>,
+}
+
+/// Named collections under one root directory.
+///
+/// Each collection keeps its batches and write-ahead log in `<root>/<name>`; their
+/// schemas live in `<root>/collections.json`, rewritten atomically on every change.
+#[derive(Debug, Clone)]
+pub struct Collections {
+    root: PathBuf,
+    manifest: Manifest,
+}
+
+impl Collections {
+    /// Read the manifest under `root`; a root without one has no collections
+    pub async fn open(root: impl AsRef<Path>) -> Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        let path = root.join(MANIFEST_FILE_NAME);
+        let manifest = match fs::read(&path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .with_context(|| format!("Failed to parse manifest: {:?}", path))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Manifest::default(),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read manifest: {:?}", path));
+            }
+        };
+        let mut collections = Self { root, manifest };
+        if !collections.manifest.moves.is_empty() {
+            collections.finish_moves().await?;
+        }
+        Ok(collections)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Schemas of every collection, sorted by name
+    pub fn list(&self) -> Vec<&CollectionSchema> {
+        let mut schemas: Vec<&CollectionSchema> = self.manifest.collections.iter().collect();
+        schemas.sort_by(|a, b| a.name.cmp(&b.name));
+        schemas
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CollectionSchema> {
+        self.manifest.collections.iter().find(|c| c.name == name)
+    }
+
+    /// Schema of `name`, or an error listing the known collections
+    pub fn schema(&self, name: &str) -> Result<&CollectionSchema> {
+        self.get(name).with_context(|| {
+            let names: Vec<&str> = self.list().iter().map(|c| c.name.as_str()).collect();
+            format!(
+                "Unknown collection: {} (have: {})",
+                name,
+                if names.is_empty() {
+                    "none".to_string()
+                } else {
+                    names.join(", ")
+                }
+            )
+        })
+    }
+
+    /// Directory holding the batches of `name`
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.root.join(name)
+    }
+
+    /// Register a new collection and create its directory
+    pub async fn create(&mut self, schema: CollectionSchema) -> Result<()> {
+        validate_name(&schema.name)?;
+        if schema.dimensions == 0 {
+            anyhow::bail!(
+                "Collection {} must have at least one dimension",
+                schema.name
+            );
+        }
+        if self.get(&schema.name).is_some() {
+            anyhow::bail!("Collection already exists: {}", schema.name);
+        }
+
+        let path = self.path(&schema.name);
+        fs::create_dir_all(&path)
+            .await
+            .with_context(|| format!("Failed to create directory: {:?}", path))?;
+        self.manifest.collections.push(schema);
+        self.save().await
+    }
+
+    /// Remove `name` from the manifest and delete its data
+    pub async fn drop(&mut self, name: &str) -> Result<CollectionSchema> {
+        self.schema(name)?;
+        let position = self
+            .manifest
+            .collections
+            .iter()
+            .position(|c| c.name == name)
+            .expect("collection exists");
+        let schema = self.manifest.collections.remove(position);
+        // The manifest goes first so a crash never leaves an entry without a directory
+        self.save().await?;
+
+        let path = self.path(name);
+        match fs::remove_dir_all(&path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("Failed to remove directory: {:?}", path))
+            }
+            _ => Ok(schema),
+        }
+    }
+
+    /// Rename a collection and move its directory.
+    ///
+    /// The manifest is saved with the new name and the pending move before the
+    /// directory moves, so a crash in between leaves a move that `open` finishes.
+    pub async fn rename(&mut self, from: &str, to: &str) -> Result<()> {
+        validate_name(to)?;
+        self.schema(from)?;
+        if self.get(to).is_some() {
+            anyhow::bail!("Collection already exists: {}", to);
+        }
+        let target = self.path(to);
+        if fs::try_exists(&target).await? {
+            anyhow::bail!("Directory already exists: {:?}", target);
+        }
+
+        if let Some(schema) = self
+            .manifest
+            .collections
+            .iter_mut()
+            .find(|c| c.name == from)
+        {
+            schema.name = to.to_string();
+        }
+        self.manifest.moves.push((from.to_string(), to.to_string()));
+        self.save().await?;
+        self.finish_moves().await
+    }
+
+    /// Move the directories of recorded renames, then drop the moves from the manifest
+    async fn finish_moves(&mut self) -> Result<()> {
+        for (from, to) in &self.manifest.moves {
+            let (source, target) = (self.path(from), self.path(to));
+            // Already moved when the crash came after the directory rename
+            if fs::try_exists(&source).await? && !fs::try_exists(&target).await? {
+                fs::rename(&source, &target)
+                    .await
+                    .with_context(|| format!("Failed to move {:?} to {:?}", source, target))?;
+            }
+        }
+        self.manifest.moves.clear();
+        self.save().await
+    }
+
+    /// Load the vectors of `name`
+    pub async fn load(&self, name: &str) -> Result<VectorData> {
+        self.schema(name)?;
+        EmbeddingStore::open(&self.path(name).to_string_lossy()).await
+    }
+
+    /// Check `items` against the schema of `name`, then upsert them into its store under
+    /// the schema's model
+    pub async fn upsert(
+        &self,
+        name: &str,
+        data: &mut VectorData,
+        items: Vec<EmbeddingData>,
+    ) -> Result<()> {
+        let schema = self.schema(name)?;
+        schema.check(&items)?;
+        let path = self.path(name);
+        EmbeddingStore::upsert_with_model(&path.to_string_lossy(), data, &schema.model, items).await
+    }
+
+    async fn save(&self) -> Result<()> {
+        fs::create_dir_all(&self.root)
+            .await
+            .with_context(|| format!("Failed to create directory: {:?}", self.root))?;
+        let path = self.root.join(MANIFEST_FILE_NAME);
+        let encoded = serde_json::to_vec_pretty(&self.manifest)?;
+        tokio::task::spawn_blocking(move || {
+            write_atomic(&path, |writer| Ok(writer.write_all(&encoded)?))
+                .with_context(|| format!("Failed to write manifest: {:?}", path))
+        })
+        .await?
+    }
+}
+
+/// Collection names become directory names, so keep them to a portable subset that
+/// cannot be mistaken for a file of the store around them
+fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        anyhow::bail!(
+            "Invalid collection name: {:?} (use up to 64 letters, digits, '-', '_' or '.')",
+            name
+        );
+    }
+    if let Some(extension) = Path::new(name).extension().and_then(|ext| ext.to_str())
+        && STORE_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+    {
+        anyhow::bail!(
+            "Invalid collection name: {:?} (names ending in .{} are used by store files)",
+            name,
+            extension
+        );
+    }
+    Ok(())
+}
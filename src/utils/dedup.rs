@@ -0,0 +1,455 @@
+use crate::core::tokenize;
+use crate::utils::hashing::mix;
+use crate::utils::wal::checksum;
+use crate::utils::{Chunk, HashingProvider, Metadata, MetadataValue};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// Estimated Jaccard similarity of word shingles above which MinHash calls two chunks
+/// duplicates unless `threshold` says otherwise
+pub const DEFAULT_MINHASH_THRESHOLD: f32 = 0.8;
+/// Differing SimHash bits up to which two chunks are duplicates unless `max_distance`
+/// says otherwise
+pub const DEFAULT_SIMHASH_DISTANCE: u32 = 3;
+/// Cosine similarity of hashing embeddings above which two chunks are duplicates unless
+/// `threshold` says otherwise
+pub const DEFAULT_COSINE_THRESHOLD: f32 = 0.95;
+
+/// MinHash signature length, split into `MINHASH_BANDS` bands for candidate lookup
+const MINHASH_PERMUTATIONS: usize = 64;
+const MINHASH_BANDS: usize = 16;
+/// Words per MinHash shingle
+const SHINGLE_WORDS: usize = 3;
+
+/// How duplicate chunks are recognised
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum DedupMethod {
+    /// Every chunk is kept
+    #[default]
+    None,
+    /// Identical text, by content hash
+    Exact,
+    /// Shared three-word shingles, estimated from 64 MinHash values; catches copies
+    /// with a few words changed
+    #[serde(rename = "minhash")]
+    MinHash {
+        #[serde(default = "default_minhash_threshold")]
+        threshold: f32,
+    },
+    /// 64-bit SimHash of the words within `max_distance` bits; catches copies with small
+    /// edits
+    #[serde(rename = "simhash")]
+    SimHash {
+        #[serde(default = "default_simhash_distance")]
+        max_distance: u32,
+    },
+    /// Cosine similarity of offline `HashingProvider` embeddings, so the embedding API is
+    /// not called for chunks that are dropped. Compares each chunk with every kept one,
+    /// which is slower than the hashing methods on large inputs, so streamed files
+    /// cannot use it.
+    Cosine {
+        #[serde(default = "default_cosine_threshold")]
+        threshold: f32,
+    },
+}
+
+fn default_minhash_threshold() -> f32 {
+    DEFAULT_MINHASH_THRESHOLD
+}
+
+fn default_simhash_distance() -> u32 {
+    DEFAULT_SIMHASH_DISTANCE
+}
+
+fn default_cosine_threshold() -> f32 {
+    DEFAULT_COSINE_THRESHOLD
+}
+
+/// What happens to a chunk and its duplicates
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupPolicy {
+    /// Embed the first copy, drop the others
+    #[default]
+    KeepFirst,
+    /// Embed the first copy with the metadata of every copy: differing values become
+    /// lists, and `duplicates` counts the copies dropped
+    MergeMetadata,
+    /// Drop every copy, for boilerplate such as repeated headers and footers
+    Skip,
+}
+
+/// Duplicate detection applied to chunks before they are embedded, e.g.
+/// `dedup = { method = "minhash", threshold = 0.9, policy = "merge_metadata" }`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Dedup {
+    #[serde(flatten)]
+    pub method: DedupMethod,
+    #[serde(default)]
+    pub policy: DedupPolicy,
+}
+
+impl Dedup {
+    pub fn new(method: DedupMethod, policy: DedupPolicy) -> Self {
+        Self { method, policy }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.method != DedupMethod::None
+    }
+}
+
+impl std::str::FromStr for DedupMethod {
+    type Err = anyhow::Error;
+
+    /// A method by name, with default thresholds
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" | "off" => Ok(DedupMethod::None),
+            "exact" => Ok(DedupMethod::Exact),
+            "minhash" => Ok(DedupMethod::MinHash {
+                threshold: DEFAULT_MINHASH_THRESHOLD,
+            }),
+            "simhash" => Ok(DedupMethod::SimHash {
+                max_distance: DEFAULT_SIMHASH_DISTANCE,
+            }),
+            "cosine" => Ok(DedupMethod::Cosine {
+                threshold: DEFAULT_COSINE_THRESHOLD,
+            }),
+            other => anyhow::bail!(
+                "Unknown dedup method: {} (expected none, exact, minhash, simhash or cosine)",
+                other
+            ),
+        }
+    }
+}
+
+impl std::str::FromStr for DedupPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().replace('-', "_").as_str() {
+            "keep_first" | "first" => Ok(DedupPolicy::KeepFirst),
+            "merge_metadata" | "merge" => Ok(DedupPolicy::MergeMetadata),
+            "skip" => Ok(DedupPolicy::Skip),
+            other => anyhow::bail!(
+                "Unknown dedup policy: {} (expected keep_first, merge_metadata or skip)",
+                other
+            ),
+        }
+    }
+}
+
+/// What a chunk is compared by
+#[derive(Debug)]
+enum Fingerprint {
+    Hash(u64),
+    MinHash(Vec<u64>),
+    Vector(Vec<f32>),
+}
+
+/// Drops duplicate chunks according to a `Dedup`, remembering every chunk it kept, or
+/// the last `window` of them.
+///
+/// Each call to `dedup` compares its chunks with each other and with the chunks of
+/// earlier calls. Chunks of earlier calls are already gone, so a later copy of one is
+/// simply dropped: metadata is only merged, and `Skip` only drops the first copy too,
+/// within one call.
+///
+/// Memory grows with every chunk kept: about 100 bytes for `Exact`, a few hundred for
+/// `SimHash` and 1.5 KiB for a `MinHash` signature or `Cosine` embedding, which is also
+/// compared with every remembered chunk instead of a few lookup candidates.
+#[derive(Debug)]
+pub struct Deduplicator {
+    config: Dedup,
+    hasher: HashingProvider,
+    /// Fingerprints of the kept chunks still remembered, oldest first
+    fingerprints: VecDeque<Fingerprint>,
+    /// Fingerprints forgotten to stay within `window`; `fingerprints[0]` has this number
+    evicted: usize,
+    window: Option<usize>,
+    /// Fingerprint numbers by lookup key: the content hash, a MinHash band or a SimHash
+    /// block
+    buckets: HashMap<u64, VecDeque<usize>>,
+    /// Chunks dropped as duplicates so far
+    dropped: usize,
+}
+
+impl Deduplicator {
+    pub fn new(config: Dedup) -> Self {
+        Self {
+            config,
+            hasher: HashingProvider::default(),
+            fingerprints: VecDeque::new(),
+            evicted: 0,
+            window: None,
+            buckets: HashMap::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Remember only the last `window` chunks kept, so memory stays bounded on inputs
+    /// of any size; copies of older chunks are kept again
+    pub fn with_window(mut self, window: usize) -> Self {
+        self.window = Some(window.max(1));
+        self
+    }
+
+    /// Chunks dropped as duplicates so far
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Remember `texts` as kept without returning them, e.g. chunks already in the store,
+    /// so later copies of them are dropped
+    pub fn remember(&mut self, texts: &[&str]) {
+        if !self.config.is_enabled() {
+            return;
+        }
+        let fingerprints: Vec<Fingerprint> = texts
+            .par_iter()
+            .map(|text| self.fingerprint(text))
+            .collect();
+        for fingerprint in fingerprints {
+            if self.find(&fingerprint).is_none() {
+                self.insert(fingerprint);
+            }
+        }
+    }
+
+    /// Position to `rollback` to, before chunks that may be thrown away
+    pub fn mark(&self) -> usize {
+        self.evicted + self.fingerprints.len()
+    }
+
+    /// Forget the chunks kept since `mark`, e.g. those of a file that failed to embed
+    pub fn rollback(&mut self, mark: usize) {
+        while self.mark() > mark {
+            let Some(latest) = self.fingerprints.pop_back() else {
+                break;
+            };
+            // The latest number is at the back of each of its buckets
+            for key in self.keys(&latest) {
+                if let Some(bucket) = self.buckets.get_mut(&key) {
+                    bucket.pop_back();
+                    if bucket.is_empty() {
+                        self.buckets.remove(&key);
+                    }
+                }
+            }
+        }
+    }
+
+    /// `chunks` without their duplicates, in order
+    pub fn dedup(&mut self, chunks: Vec<Chunk>) -> Vec<Chunk> {
+        if !self.config.is_enabled() {
+            return chunks;
+        }
+        let fingerprints: Vec<Fingerprint> = chunks
+            .par_iter()
+            .map(|chunk| self.fingerprint(&chunk.text))
+            .collect();
+
+        // Fingerprints from `first` on belong to `kept`, in the same order
+        let first = self.evicted + self.fingerprints.len();
+        let mut kept: Vec<Chunk> = Vec::new();
+        let mut copies: Vec<usize> = Vec::new();
+        let total = chunks.len();
+        for (chunk, fingerprint) in chunks.into_iter().zip(fingerprints) {
+            match self.find(&fingerprint) {
+                Some(original) if original >= first => {
+                    let original = original - first;
+                    copies[original] += 1;
+                    if self.config.policy == DedupPolicy::MergeMetadata {
+                        merge_metadata(&mut kept[original].metadata, &chunk.metadata);
+                        kept[original].metadata.insert(
+                            "duplicates".to_string(),
+                            MetadataValue::from(copies[original] as i64 - 1),
+                        );
+                    }
+                }
+                Some(_) => {}
+                None => {
+                    self.insert(fingerprint);
+                    kept.push(chunk);
+                    copies.push(1);
+                }
+            }
+        }
+
+        if self.config.policy == DedupPolicy::Skip {
+            let mut copies = copies.into_iter();
+            kept.retain(|_| copies.next() == Some(1));
+        }
+        self.dropped += total - kept.len();
+        kept
+    }
+
+    fn fingerprint(&self, text: &str) -> Fingerprint {
+        match self.config.method {
+            DedupMethod::None | DedupMethod::Exact => Fingerprint::Hash(checksum(text.as_bytes())),
+            DedupMethod::MinHash { .. } => Fingerprint::MinHash(minhash(text)),
+            DedupMethod::SimHash { .. } => Fingerprint::Hash(simhash(text)),
+            DedupMethod::Cosine { .. } => Fingerprint::Vector(self.hasher.embed(text)),
+        }
+    }
+
+    /// Keys under which a fingerprint is filed; a duplicate shares at least one
+    fn keys(&self, fingerprint: &Fingerprint) -> Vec<u64> {
+        match (fingerprint, self.config.method) {
+            (Fingerprint::Hash(hash), DedupMethod::SimHash { max_distance }) => {
+                // Pigeonhole: `max_distance + 1` blocks cannot all hold a differing bit
+                let blocks = (max_distance as usize + 1).min(64);
+                (0..blocks)
+                    .map(|block| {
+                        let (from, to) = (block * 64 / blocks, (block + 1) * 64 / blocks);
+                        let mask = if to - from == 64 {
+                            u64::MAX
+                        } else {
+                            ((1u64 << (to - from)) - 1) << from
+                        };
+                        mix(mix(block as u64) ^ (hash & mask))
+                    })
+                    .collect()
+            }
+            (Fingerprint::Hash(hash), _) => vec![*hash],
+            (Fingerprint::MinHash(signature), _) => signature
+                .chunks(MINHASH_PERMUTATIONS / MINHASH_BANDS)
+                .enumerate()
+                .map(|(band, rows)| {
+                    let mut bytes = (band as u64).to_le_bytes().to_vec();
+                    rows.iter().for_each(|row| bytes.extend(row.to_le_bytes()));
+                    checksum(&bytes)
+                })
+                .collect(),
+            // Vectors are compared with every kept one
+            (Fingerprint::Vector(_), _) => Vec::new(),
+        }
+    }
+
+    fn similar(&self, a: &Fingerprint, b: &Fingerprint) -> bool {
+        match (a, b, self.config.method) {
+            (Fingerprint::Hash(a), Fingerprint::Hash(b), DedupMethod::SimHash { max_distance }) => {
+                (a ^ b).count_ones() <= max_distance
+            }
+            (Fingerprint::Hash(a), Fingerprint::Hash(b), _) => a == b,
+            (
+                Fingerprint::MinHash(a),
+                Fingerprint::MinHash(b),
+                DedupMethod::MinHash { threshold },
+            ) => {
+                let equal = a.iter().zip(b).filter(|(a, b)| a == b).count();
+                equal as f32 / MINHASH_PERMUTATIONS as f32 >= threshold
+            }
+            (Fingerprint::Vector(a), Fingerprint::Vector(b), DedupMethod::Cosine { threshold }) => {
+                // Hashing embeddings are normalized
+                a.iter().zip(b).map(|(a, b)| a * b).sum::<f32>() >= threshold
+            }
+            _ => false,
+        }
+    }
+
+    /// Number of the earliest remembered fingerprint `fingerprint` duplicates
+    fn find(&self, fingerprint: &Fingerprint) -> Option<usize> {
+        if let Fingerprint::Vector(_) = fingerprint {
+            return self
+                .fingerprints
+                .par_iter()
+                .position_first(|kept| self.similar(fingerprint, kept))
+                .map(|index| self.evicted + index);
+        }
+        self.keys(fingerprint)
+            .iter()
+            .filter_map(|key| self.buckets.get(key))
+            .flatten()
+            .copied()
+            .filter(|&number| self.similar(fingerprint, &self.fingerprints[number - self.evicted]))
+            .min()
+    }
+
+    fn insert(&mut self, fingerprint: Fingerprint) {
+        let number = self.evicted + self.fingerprints.len();
+        for key in self.keys(&fingerprint) {
+            self.buckets.entry(key).or_default().push_back(number);
+        }
+        self.fingerprints.push_back(fingerprint);
+
+        if self
+            .window
+            .is_some_and(|window| self.fingerprints.len() > window)
+        {
+            let Some(oldest) = self.fingerprints.pop_front() else {
+                return;
+            };
+            // The oldest number is at the front of each of its buckets
+            for key in self.keys(&oldest) {
+                if let Some(bucket) = self.buckets.get_mut(&key) {
+                    bucket.pop_front();
+                    if bucket.is_empty() {
+                        self.buckets.remove(&key);
+                    }
+                }
+            }
+            self.evicted += 1;
+        }
+    }
+}
+
+/// Minimum hash of the word shingles under each of the `MINHASH_PERMUTATIONS` hash
+/// functions; texts shorter than a shingle are one shingle
+fn minhash(text: &str) -> Vec<u64> {
+    let tokens = tokenize(text);
+    let shingles: Vec<u64> = tokens
+        .windows(SHINGLE_WORDS.min(tokens.len()).max(1))
+        .map(|words| checksum(words.join(" ").as_bytes()))
+        .collect();
+    (0..MINHASH_PERMUTATIONS as u64)
+        .map(|permutation| {
+            let seed = mix(permutation + 1);
+            shingles
+                .iter()
+                .map(|&shingle| mix(shingle ^ seed))
+                .min()
+                .unwrap_or(u64::MAX)
+        })
+        .collect()
+}
+
+/// Bits set where most words of `text` hash to a set bit
+fn simhash(text: &str) -> u64 {
+    let mut weights = [0i32; 64];
+    for token in tokenize(text) {
+        let hash = mix(checksum(token.as_bytes()));
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            *weight += if hash >> bit & 1 == 1 { 1 } else { -1 };
+        }
+    }
+    weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0, |hash, (bit, _)| hash | 1 << bit)
+}
+
+/// Add the metadata of a dropped copy to the one kept; values that differ are
+/// collected in a list
+fn merge_metadata(into: &mut Metadata, from: &Metadata) {
+    for (key, value) in from {
+        match into.get_mut(key) {
+            None => {
+                into.insert(key.clone(), value.clone());
+            }
+            Some(existing) if existing == value => {}
+            Some(MetadataValue::List(values)) => {
+                if !values.contains(value) {
+                    values.push(value.clone());
+                }
+            }
+            Some(existing) => {
+                *existing = MetadataValue::List(vec![existing.clone(), value.clone()]);
+            }
+        }
+    }
+}
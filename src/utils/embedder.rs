@@ -1,42 +1,197 @@
+use crate::utils::Metadata;
+use crate::utils::adapters::{DEFAULT_INPUT_TYPE, ProviderKind, QUERY_INPUT_TYPE, RequestOptions};
+use crate::utils::retry::{RateLimiter, RetryPolicy, estimate_tokens};
 use anyhow::Result;
 use rayon::prelude::{IntoParallelIterator, ParallelIterator};
 use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Embeddings {
     pub data: Vec<EmbeddingData>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct EmbeddingData {
+    /// Persistent identifier, generated from the batch and position when left empty
+    #[serde(default)]
+    pub id: String,
     pub index: usize,
     #[serde(default)]
     pub chunk: String,
     pub embedding: Vec<f32>,
     #[serde(default)]
     pub dimensions: usize,
+    #[serde(default)]
+    pub metadata: Metadata,
+}
+
+/// Future returned by `EmbeddingProvider`, boxed so providers can be trait objects
+pub type EmbeddingFuture<'a> = Pin<Box<dyn Future<Output = Result<Embeddings>> + Send + 'a>>;
+
+/// Anything that turns text into vectors.
+///
+/// Ingestion, the CLI and the server hold an `Arc<dyn EmbeddingProvider>`, so the
+/// backend is picked from the config at runtime.
+pub trait EmbeddingProvider: std::fmt::Debug + Send + Sync {
+    /// Model recorded with every batch embedded by this provider
+    fn model(&self) -> &str;
+
+    /// Embed documents; `index` of each result is the position of its chunk in `chunks`
+    fn fetch_embeddings<'a>(&'a self, chunks: &'a [String]) -> EmbeddingFuture<'a>;
+
+    /// Embed a search query; providers that embed queries and documents differently
+    /// override this
+    fn fetch_query<'a>(&'a self, text: &'a str) -> EmbeddingFuture<'a> {
+        Box::pin(async move {
+            let chunks = [text.to_string()];
+            self.fetch_embeddings(&chunks).await
+        })
+    }
+}
+
+impl<P: EmbeddingProvider + ?Sized> EmbeddingProvider for Arc<P> {
+    fn model(&self) -> &str {
+        (**self).model()
+    }
+
+    fn fetch_embeddings<'a>(&'a self, chunks: &'a [String]) -> EmbeddingFuture<'a> {
+        (**self).fetch_embeddings(chunks)
+    }
+
+    fn fetch_query<'a>(&'a self, text: &'a str) -> EmbeddingFuture<'a> {
+        (**self).fetch_query(text)
+    }
 }
 
-#[derive(Debug, Clone)]
+/// Request timeout used unless `with_timeouts` sets another
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+/// Connect timeout used unless `with_timeouts` sets another
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// HTTP client for an embeddings endpoint speaking one of the `ProviderKind` formats,
+/// OpenAI-compatible by default.
+///
+/// Clones share one connection pool and one rate limiter.
+#[derive(Clone)]
 pub struct Provider {
     pub url: String,
     pub model: String,
+    pub kind: ProviderKind,
+    /// Requested output size, for OpenAI models that can shorten their embeddings
+    pub dimensions: Option<usize>,
+    /// Cohere input type of documents; queries are always sent as `search_query`
+    pub input_type: String,
+    /// Sent as a bearer token when set
+    pub api_key: Option<String>,
+    /// Retries for throttled (429) and failing (5xx) requests and timeouts
+    pub retry: RetryPolicy,
+    client: reqwest::Client,
+    timeout: Duration,
+    connect_timeout: Duration,
+    limiter: Option<Arc<RateLimiter>>,
+}
+
+impl std::fmt::Debug for Provider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Never leak the API key into logs
+        f.debug_struct("Provider")
+            .field("url", &self.url)
+            .field("model", &self.model)
+            .field("kind", &self.kind)
+            .field("dimensions", &self.dimensions)
+            .field("input_type", &self.input_type)
+            .field("api_key", &self.api_key.as_ref().map(|_| "<redacted>"))
+            .field("retry", &self.retry)
+            .field("timeout", &self.timeout)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("limiter", &self.limiter)
+            .finish()
+    }
 }
 
 impl Provider {
     pub fn new(url: impl Into<String>, model: impl Into<String>) -> Self {
         let url = url.into();
-        let model = model.into();
+        let mut model = model.into();
         if model.is_empty() {
             // Default model if none provided
             let default_model = "text-embedding-nomic-embed-text-v1.5";
             println!("Model not provided. Using default model: {}", default_model);
-            return Self {
-                url,
-                model: default_model.to_string(),
-            };
+            model = default_model.to_string();
+        }
+        Self {
+            url,
+            model,
+            kind: ProviderKind::default(),
+            dimensions: None,
+            input_type: DEFAULT_INPUT_TYPE.to_string(),
+            api_key: None,
+            retry: RetryPolicy::default(),
+            client: build_client(DEFAULT_CONNECT_TIMEOUT, DEFAULT_TIMEOUT),
+            timeout: DEFAULT_TIMEOUT,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            limiter: None,
         }
-        Self { url, model }
+    }
+
+    pub fn with_kind(mut self, kind: ProviderKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub fn with_dimensions(mut self, dimensions: usize) -> Self {
+        self.dimensions = Some(dimensions);
+        self
+    }
+
+    pub fn with_input_type(mut self, input_type: impl Into<String>) -> Self {
+        self.input_type = input_type.into();
+        self
+    }
+
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Rebuild the HTTP client with new connect and whole-request timeouts
+    pub fn with_timeouts(mut self, connect_timeout: Duration, timeout: Duration) -> Self {
+        self.client = build_client(connect_timeout, timeout);
+        self.connect_timeout = connect_timeout;
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Limit requests and estimated tokens per minute; `None` leaves either unlimited
+    pub fn with_rate_limit(
+        mut self,
+        requests_per_minute: Option<u32>,
+        tokens_per_minute: Option<u32>,
+    ) -> Self {
+        self.limiter = (requests_per_minute.is_some() || tokens_per_minute.is_some())
+            .then(|| Arc::new(RateLimiter::new(requests_per_minute, tokens_per_minute)));
+        self
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn connect_timeout(&self) -> Duration {
+        self.connect_timeout
+    }
+
+    pub fn rate_limiter(&self) -> Option<&RateLimiter> {
+        self.limiter.as_deref()
     }
 
     /// Fetch embedding for a single piece of text
@@ -46,41 +201,102 @@ impl Provider {
 
     /// Fetch embeddings for the given chunks of text
     pub async fn fetch_embeddings(&self, chunks: &[String]) -> Result<Embeddings> {
-        let body = serde_json::json!({
-            "model": &self.model,
-            "input": chunks,
-        });
-
-        let response = reqwest::Client::new()
-            .post(&self.url)
-            .json(&body)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            anyhow::bail!("Failed to fetch embeddings: HTTP {}", response.status());
-        }
+        self.embed(chunks, &self.input_type).await
+    }
 
-        let mut embeddings_response: Embeddings = response.json().await?;
+    /// Fetch the embedding of a search query
+    pub async fn fetch_query(&self, text: &str) -> Result<Embeddings> {
+        self.embed(&[text.to_string()], QUERY_INPUT_TYPE).await
+    }
+
+    async fn embed(&self, chunks: &[String], input_type: &str) -> Result<Embeddings> {
+        let body = self.kind.request(
+            chunks,
+            &RequestOptions {
+                model: &self.model,
+                dimensions: self.dimensions,
+                input_type,
+            },
+        );
 
-        // Validate & filter embeddings
-        embeddings_response.data = embeddings_response
-            .data
+        let response = self.send(&body, estimate_tokens(chunks)).await?;
+        let embeddings = self.kind.parse(&response.bytes().await?)?;
+
+        // Validate & filter embeddings, then fill in the chunk & dimensions of each
+        let data = embeddings
             .into_par_iter()
-            .filter(|embedding| !embedding.embedding.is_empty())
+            .filter(|(_, embedding)| !embedding.is_empty())
+            .map(|(index, embedding)| EmbeddingData {
+                index,
+                chunk: chunks.get(index).cloned().unwrap_or_default(),
+                dimensions: embedding.len(),
+                embedding,
+                ..Default::default()
+            })
             .collect();
 
-        // Fill in the chunk & dimensions for each embedding
-        embeddings_response.data.iter_mut().for_each(|embedding| {
-            if let Some(chunk) = chunks.get(embedding.index) {
-                embedding.chunk = chunk.to_string();
-            } else {
-                embedding.chunk = String::from("");
+        Ok(Embeddings { data })
+    }
+
+    /// POST `body`, waiting for the rate limiter before every attempt and retrying
+    /// throttled, failing, unreachable and timed out requests as configured by `retry`
+    async fn send(&self, body: &serde_json::Value, tokens: usize) -> Result<reqwest::Response> {
+        let mut attempt = 0;
+        loop {
+            if let Some(limiter) = &self.limiter {
+                limiter.acquire(tokens).await;
             }
 
-            embedding.dimensions = embedding.embedding.len();
-        });
+            let mut request = self.client.post(&self.url).json(body);
+            if let Some(api_key) = &self.api_key {
+                request = request.bearer_auth(api_key);
+            }
+            let (error, headers) = match request.send().await {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) if RetryPolicy::is_retryable(response.status()) => (
+                    anyhow::anyhow!("Failed to fetch embeddings: HTTP {}", response.status()),
+                    Some(response.headers().clone()),
+                ),
+                Ok(response) => {
+                    anyhow::bail!("Failed to fetch embeddings: HTTP {}", response.status())
+                }
+                Err(e) if e.is_timeout() || e.is_connect() => (anyhow::Error::from(e), None),
+                Err(e) => return Err(e.into()),
+            };
 
-        Ok(embeddings_response)
+            if attempt >= self.retry.max_retries {
+                return Err(error.context(format!("Giving up after {} attempts", attempt + 1)));
+            }
+            let Some(delay) = self.retry.delay(attempt, headers.as_ref()) else {
+                return Err(error.context(format!(
+                    "Server asked to retry after more than {}s",
+                    self.retry.max_retry_after.as_secs()
+                )));
+            };
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
     }
 }
+
+impl EmbeddingProvider for Provider {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn fetch_embeddings<'a>(&'a self, chunks: &'a [String]) -> EmbeddingFuture<'a> {
+        Box::pin(Provider::fetch_embeddings(self, chunks))
+    }
+
+    fn fetch_query<'a>(&'a self, text: &'a str) -> EmbeddingFuture<'a> {
+        Box::pin(Provider::fetch_query(self, text))
+    }
+}
+
+fn build_client(connect_timeout: Duration, timeout: Duration) -> reqwest::Client {
+    reqwest::Client::builder()
+        .connect_timeout(connect_timeout)
+        .timeout(timeout)
+        .build()
+        .expect("Failed to build HTTP client")
+}
@@ -0,0 +1,154 @@
+use crate::core::tokenize;
+use crate::utils::embedder::{EmbeddingFuture, EmbeddingProvider};
+use crate::utils::{EmbeddingData, Embeddings};
+use rayon::prelude::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+
+/// Dimensions of a `HashingProvider` unless `provider.dimensions` sets others
+pub const DEFAULT_HASHING_DIMENSIONS: usize = 384;
+
+/// Offline provider hashing words and character trigrams into a fixed number of
+/// dimensions ("feature hashing").
+///
+/// The same text always maps to the same L2-normalized vector on every machine, and
+/// texts sharing words or word fragments land close together, which is enough to run
+/// ingestion and search end to end without an embedding server. It knows nothing about
+/// meaning, so synonyms are as far apart as unrelated words.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HashingProvider {
+    dimensions: usize,
+    model: String,
+}
+
+impl HashingProvider {
+    pub fn new(dimensions: usize) -> Self {
+        let dimensions = dimensions.max(1);
+        Self {
+            dimensions,
+            model: format!("hashing-{}", dimensions),
+        }
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    /// Embedding of `text`; text without any word maps to a fixed unit vector so cosine
+    /// similarity stays defined
+    pub fn embed(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0f32; self.dimensions];
+        for token in tokenize(text) {
+            self.add(&mut vector, b'w', token.as_bytes(), 1.0);
+
+            // Trigrams of the padded word match inflections and typos: "<wa", "war", "ar>"
+            let chars: Vec<char> = format!("<{}>", token).chars().collect();
+            for trigram in chars.windows(3) {
+                let trigram: String = trigram.iter().collect();
+                self.add(&mut vector, b'c', trigram.as_bytes(), 0.5);
+            }
+        }
+
+        let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm == 0.0 {
+            vector[0] = 1.0;
+        } else {
+            vector.iter_mut().for_each(|x| *x /= norm);
+        }
+        vector
+    }
+
+    /// Add `weight` to the bucket of `feature`, with a sign taken from the hash so
+    /// collisions cancel out on average instead of piling up
+    fn add(&self, vector: &mut [f32], kind: u8, feature: &[u8], weight: f32) {
+        let hash = mix(feature_hash(kind, feature));
+
+        let bucket = (hash % self.dimensions as u64) as usize;
+        vector[bucket] += if hash >> 63 == 0 { weight } else { -weight };
+    }
+}
+
+impl Default for HashingProvider {
+    fn default() -> Self {
+        Self::new(DEFAULT_HASHING_DIMENSIONS)
+    }
+}
+
+impl EmbeddingProvider for HashingProvider {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn fetch_embeddings<'a>(&'a self, chunks: &'a [String]) -> EmbeddingFuture<'a> {
+        let data = chunks
+            .into_par_iter()
+            .enumerate()
+            .map(|(index, chunk)| EmbeddingData {
+                index,
+                chunk: chunk.clone(),
+                dimensions: self.dimensions,
+                embedding: self.embed(chunk),
+                ..Default::default()
+            })
+            .collect();
+        Box::pin(std::future::ready(Ok(Embeddings { data })))
+    }
+}
+
+/// 64-bit FNV-1a of `kind` followed by `feature`. Stored hashing embeddings depend on
+/// it, so it must not change with the checksums used elsewhere.
+fn feature_hash(kind: u8, feature: &[u8]) -> u64 {
+    std::iter::once(&kind)
+        .chain(feature)
+        .fold(0xcbf29ce484222325, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
+/// Increment of the SplitMix64 state, the golden ratio in 64-bit fixed point
+pub(crate) const SPLITMIX_GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// Deterministic SplitMix64 generator, so trained codebooks, centroids and HNSW levels
+/// are reproducible without an RNG crate
+pub(crate) struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// Current state, to resume the sequence with `new`
+    pub(crate) fn state(&self) -> u64 {
+        self.0
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(SPLITMIX_GAMMA);
+        mix(self.0)
+    }
+
+    /// Uniform value in `0..bound`
+    pub(crate) fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    /// `count` distinct values from `0..len`, or all of them when `count >= len`
+    pub(crate) fn sample(&mut self, len: usize, count: usize) -> Vec<usize> {
+        let mut indices: Vec<usize> = (0..len).collect();
+        let count = count.min(len);
+        // Partial Fisher-Yates shuffle
+        for i in 0..count {
+            let j = i + self.below(len - i);
+            indices.swap(i, j);
+        }
+        indices.truncate(count);
+        indices
+    }
+}
+
+/// Final avalanche of splitmix64, spreading FNV's weak low bits over the whole word
+pub(crate) fn mix(mut hash: u64) -> u64 {
+    hash ^= hash >> 30;
+    hash = hash.wrapping_mul(0xbf58476d1ce4e5b9);
+    hash ^= hash >> 27;
+    hash = hash.wrapping_mul(0x94d049bb133111eb);
+    hash ^ (hash >> 31)
+}
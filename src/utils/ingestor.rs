@@ -1,14 +1,33 @@
+use crate::utils::{
+    BatchStream, Chunk, ChunkBatches, Chunker, Dedup, DedupMethod, Deduplicator, Reader,
+};
+use anyhow::Context;
 use memmap2::Mmap;
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::io::Result;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Ingestor {
     pub source: PathBuf,
     pub batch_size: usize,
+    /// How `read_chunks` cuts the source
+    #[serde(default)]
+    pub chunker: Chunker,
+    /// How `read_chunks` parses the source
+    #[serde(default)]
+    pub reader: Reader,
+    /// Duplicate chunks dropped before embedding
+    #[serde(default)]
+    pub dedup: Dedup,
+    /// Globs a file of a source directory must match, every file when empty
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Globs of files and directories to leave out of a source directory
+    #[serde(default)]
+    pub exclude: Vec<String>,
 }
 
 impl Ingestor {
@@ -16,13 +35,99 @@ impl Ingestor {
         let source = source.into();
         assert_eq!(batch_size % 8, 0, "Batch size must be a multiple of 8");
         assert!(
-            source.exists() && source.is_file(),
-            "Source file must exist and be a file"
+            source.is_file() || source.is_dir(),
+            "Source file must exist or be a directory"
         );
-        Self { source, batch_size }
+        Self {
+            source,
+            batch_size,
+            chunker: Chunker::default(),
+            reader: Reader::default(),
+            dedup: Dedup::default(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
     }
 
-    /// Read lines from the source file and batch them
+    pub fn with_chunker(mut self, chunker: Chunker) -> Self {
+        self.chunker = chunker;
+        self
+    }
+
+    pub fn with_reader(mut self, reader: Reader) -> Self {
+        self.reader = reader;
+        self
+    }
+
+    pub fn with_dedup(mut self, dedup: Dedup) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    pub fn with_include(mut self, globs: Vec<String>) -> Self {
+        self.include = globs;
+        self
+    }
+
+    pub fn with_exclude(mut self, globs: Vec<String>) -> Self {
+        self.exclude = globs;
+        self
+    }
+
+    /// Files to ingest: the source itself, or every file under the source directory
+    /// matching `include` and none of `exclude`, sorted.
+    ///
+    /// Globs are matched against paths relative to the source with `/` separators: `*`
+    /// and `?` stay within a path segment, `**` spans segments and `{md,html}` lists
+    /// alternatives. A glob without `/` matches file and directory names at any depth.
+    /// Hidden files and directories, such as `.git`, are skipped.
+    pub fn files(&self) -> anyhow::Result<Vec<PathBuf>> {
+        if self.source.is_file() {
+            return Ok(vec![self.source.clone()]);
+        }
+
+        let mut files = Vec::new();
+        let mut pending = vec![self.source.clone()];
+        while let Some(dir) = pending.pop() {
+            let entries = std::fs::read_dir(&dir)
+                .with_context(|| format!("Failed to read directory: {:?}", dir))?;
+            for entry in entries {
+                let entry = entry?;
+                if entry.file_name().to_string_lossy().starts_with('.') {
+                    continue;
+                }
+                let path = entry.path();
+                let relative = self.relative(&path);
+                // Symlinked directories are not followed, so a link cycle cannot loop
+                if entry.file_type()?.is_dir() {
+                    if !self.exclude.iter().any(|glob| glob_match(glob, &relative)) {
+                        pending.push(path);
+                    }
+                } else if path.is_file() && self.selects(&relative) {
+                    files.push(path);
+                }
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// Whether a file at `relative` inside the source directory is ingested
+    pub fn selects(&self, relative: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|glob| glob_match(glob, relative)))
+            && !self.exclude.iter().any(|glob| glob_match(glob, relative))
+    }
+
+    fn relative(&self, path: &Path) -> String {
+        let relative = path.strip_prefix(&self.source).unwrap_or(path);
+        relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Read lines from the source file, drop duplicates with `dedup` and batch them
     pub fn read_line(&self) -> Result<Vec<Vec<String>>> {
         let file = File::open(&self.source)?;
         let mmap = unsafe { Mmap::map(&file)? };
@@ -38,7 +143,154 @@ impl Ingestor {
                 if s.is_empty() { None } else { Some(s) }
             })
             .collect();
+        let lines = if self.dedup.is_enabled() {
+            let chunks = lines
+                .into_iter()
+                .map(|text| Chunk {
+                    text,
+                    ..Default::default()
+                })
+                .collect();
+            Deduplicator::new(self.dedup)
+                .dedup(chunks)
+                .into_iter()
+                .map(|chunk| chunk.text)
+                .collect()
+        } else {
+            lines
+        };
 
         Ok(lines.into_par_iter().chunks(self.batch_size).collect())
     }
+
+    /// Parse the source file with `reader`, cut every document with `chunker`, drop
+    /// duplicates with `dedup` and batch the chunks. Each chunk carries the metadata of
+    /// its document.
+    pub fn read_chunks(&self) -> anyhow::Result<Vec<Vec<Chunk>>> {
+        self.read_file(&self.source)
+    }
+
+    /// `read_chunks` one block at a time, for source files larger than memory; see
+    /// `ChunkBatches`. `Cosine` dedup compares every chunk with every one kept, so it is
+    /// refused here.
+    pub fn batches(&self) -> anyhow::Result<ChunkBatches> {
+        if matches!(self.dedup.method, DedupMethod::Cosine { .. }) {
+            anyhow::bail!(
+                "Cosine dedup cannot stream {:?}, use exact, minhash or simhash",
+                self.source
+            );
+        }
+        Ok(ChunkBatches::open(
+            &self.source,
+            self.batch_size,
+            self.chunker.clone(),
+            self.reader.clone(),
+        )?
+        .with_dedup(self.dedup))
+    }
+
+    /// `batches` read on a blocking thread at most `buffer` batches ahead of the consumer
+    pub fn stream(&self, buffer: usize) -> anyhow::Result<BatchStream> {
+        Ok(BatchStream::spawn(self.batches()?, buffer))
+    }
+
+    /// `read_chunks` for one file of the source, e.g. from `files`
+    pub fn read_file(&self, path: &Path) -> anyhow::Result<Vec<Vec<Chunk>>> {
+        self.read_file_with(path, &mut Deduplicator::new(self.dedup))
+    }
+
+    /// `read_file` dropping chunks that duplicate ones `deduplicator` kept before, so
+    /// several files can be deduplicated against each other
+    pub fn read_file_with(
+        &self,
+        path: &Path,
+        deduplicator: &mut Deduplicator,
+    ) -> anyhow::Result<Vec<Vec<Chunk>>> {
+        let documents = self.reader.read(path)?;
+        let chunks: Vec<Chunk> = documents
+            .into_par_iter()
+            .flat_map_iter(|document| {
+                let mut chunks = self.chunker.chunk(&document.text);
+                for chunk in &mut chunks {
+                    chunk.metadata = document.metadata.clone();
+                }
+                chunks
+            })
+            .collect();
+        let chunks = deduplicator.dedup(chunks);
+
+        Ok(chunks.into_par_iter().chunks(self.batch_size).collect())
+    }
+}
+
+/// Whether `path`, relative and `/`-separated, matches `glob`
+fn glob_match(glob: &str, path: &str) -> bool {
+    expand_braces(glob).iter().any(|glob| {
+        let glob = glob.trim_start_matches("./").trim_start_matches('/');
+        if glob.contains('/') {
+            let glob: Vec<&str> = glob.split('/').collect();
+            let path: Vec<&str> = path.split('/').collect();
+            segments_match(&glob, &path)
+        } else {
+            path.rsplit('/')
+                .next()
+                .is_some_and(|name| wildcard_match(glob, name))
+        }
+    })
+}
+
+/// `a/{b,c}/*.{md,txt}` as the four globs it stands for
+fn expand_braces(glob: &str) -> Vec<String> {
+    let Some(open) = glob.find('{') else {
+        return vec![glob.to_string()];
+    };
+    let Some(close) = glob[open..].find('}').map(|close| open + close) else {
+        return vec![glob.to_string()];
+    };
+    glob[open + 1..close]
+        .split(',')
+        .flat_map(|alternative| {
+            expand_braces(&format!(
+                "{}{}{}",
+                &glob[..open],
+                alternative,
+                &glob[close + 1..]
+            ))
+        })
+        .collect()
+}
+
+fn segments_match(glob: &[&str], path: &[&str]) -> bool {
+    match glob.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| segments_match(rest, &path[skip..])),
+        Some((segment, rest)) => path.split_first().is_some_and(|(name, path)| {
+            wildcard_match(segment, name) && segments_match(rest, path)
+        }),
+    }
+}
+
+/// Match one path segment against `*` and `?`, backtracking to the last `*`
+fn wildcard_match(glob: &str, name: &str) -> bool {
+    let glob: Vec<char> = glob.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut g, mut n) = (0, 0);
+    let mut star = None;
+    while n < name.len() {
+        if g < glob.len() && glob[g] == '*' {
+            star = Some((g, n));
+            g += 1;
+        } else if g < glob.len() && (glob[g] == '?' || glob[g] == name[n]) {
+            g += 1;
+            n += 1;
+        } else if let Some((star_g, star_n)) = star {
+            // Let the last `*` swallow one more character
+            star = Some((star_g, star_n + 1));
+            g = star_g + 1;
+            n = star_n + 1;
+        } else {
+            return false;
+        }
+    }
+    glob[g..].iter().all(|&c| c == '*')
 }
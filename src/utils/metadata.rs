@@ -0,0 +1,178 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+/// Arbitrary key/value payload stored alongside a vector
+pub type Metadata = BTreeMap<String, MetadataValue>;
+
+/// A JSON-like metadata value.
+///
+/// Human-readable formats (JSON, TOML) see plain values such as `"acme"` or `42`, while
+/// binary formats like bincode, which cannot deserialize untagged data, store a tagged enum.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum MetadataValue {
+    #[default]
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    List(Vec<MetadataValue>),
+    Map(BTreeMap<String, MetadataValue>),
+}
+
+/// Tagged mirror of `MetadataValue` used for binary serialization
+#[derive(Serialize, Deserialize)]
+enum Tagged {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    List(Vec<MetadataValue>),
+    Map(BTreeMap<String, MetadataValue>),
+}
+
+impl MetadataValue {
+    /// Compare two values of compatible types; integers and floats compare numerically
+    pub fn compare(&self, other: &MetadataValue) -> Option<Ordering> {
+        match (self, other) {
+            (MetadataValue::Int(a), MetadataValue::Int(b)) => Some(a.cmp(b)),
+            (MetadataValue::Int(a), MetadataValue::Float(b)) => (*a as f64).partial_cmp(b),
+            (MetadataValue::Float(a), MetadataValue::Int(b)) => a.partial_cmp(&(*b as f64)),
+            (MetadataValue::Float(a), MetadataValue::Float(b)) => a.partial_cmp(b),
+            (MetadataValue::String(a), MetadataValue::String(b)) => Some(a.cmp(b)),
+            (MetadataValue::Bool(a), MetadataValue::Bool(b)) => Some(a.cmp(b)),
+            (MetadataValue::Null, MetadataValue::Null) => Some(Ordering::Equal),
+            _ => None,
+        }
+    }
+
+    /// Equality that treats `1` and `1.0` as the same value
+    pub fn loosely_equals(&self, other: &MetadataValue) -> bool {
+        match (self, other) {
+            (MetadataValue::List(a), MetadataValue::List(b)) => a == b,
+            (MetadataValue::Map(a), MetadataValue::Map(b)) => a == b,
+            _ => self.compare(other) == Some(Ordering::Equal),
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            MetadataValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            MetadataValue::Null => serde_json::Value::Null,
+            MetadataValue::Bool(b) => serde_json::Value::Bool(*b),
+            MetadataValue::Int(i) => serde_json::Value::from(*i),
+            MetadataValue::Float(f) => serde_json::Value::from(*f),
+            MetadataValue::String(s) => serde_json::Value::String(s.clone()),
+            MetadataValue::List(values) => {
+                serde_json::Value::Array(values.iter().map(|v| v.to_json()).collect())
+            }
+            MetadataValue::Map(map) => serde_json::Value::Object(
+                map.iter().map(|(k, v)| (k.clone(), v.to_json())).collect(),
+            ),
+        }
+    }
+}
+
+impl From<serde_json::Value> for MetadataValue {
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => MetadataValue::Null,
+            serde_json::Value::Bool(b) => MetadataValue::Bool(b),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => MetadataValue::Int(i),
+                None => MetadataValue::Float(n.as_f64().unwrap_or(f64::NAN)),
+            },
+            serde_json::Value::String(s) => MetadataValue::String(s),
+            serde_json::Value::Array(values) => {
+                MetadataValue::List(values.into_iter().map(MetadataValue::from).collect())
+            }
+            serde_json::Value::Object(map) => MetadataValue::Map(
+                map.into_iter()
+                    .map(|(k, v)| (k, MetadataValue::from(v)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<&str> for MetadataValue {
+    fn from(value: &str) -> Self {
+        MetadataValue::String(value.to_string())
+    }
+}
+
+impl From<String> for MetadataValue {
+    fn from(value: String) -> Self {
+        MetadataValue::String(value)
+    }
+}
+
+impl From<i64> for MetadataValue {
+    fn from(value: i64) -> Self {
+        MetadataValue::Int(value)
+    }
+}
+
+impl From<f64> for MetadataValue {
+    fn from(value: f64) -> Self {
+        MetadataValue::Float(value)
+    }
+}
+
+impl From<bool> for MetadataValue {
+    fn from(value: bool) -> Self {
+        MetadataValue::Bool(value)
+    }
+}
+
+impl<T: Into<MetadataValue>> From<Vec<T>> for MetadataValue {
+    fn from(values: Vec<T>) -> Self {
+        MetadataValue::List(values.into_iter().map(Into::into).collect())
+    }
+}
+
+impl Serialize for MetadataValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            return self.to_json().serialize(serializer);
+        }
+
+        let tagged = match self.clone() {
+            MetadataValue::Null => Tagged::Null,
+            MetadataValue::Bool(b) => Tagged::Bool(b),
+            MetadataValue::Int(i) => Tagged::Int(i),
+            MetadataValue::Float(f) => Tagged::Float(f),
+            MetadataValue::String(s) => Tagged::String(s),
+            MetadataValue::List(values) => Tagged::List(values),
+            MetadataValue::Map(map) => Tagged::Map(map),
+        };
+        tagged.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for MetadataValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let value = serde_json::Value::deserialize(deserializer)?;
+            return Ok(MetadataValue::from(value));
+        }
+
+        Ok(match Tagged::deserialize(deserializer)? {
+            Tagged::Null => MetadataValue::Null,
+            Tagged::Bool(b) => MetadataValue::Bool(b),
+            Tagged::Int(i) => MetadataValue::Int(i),
+            Tagged::Float(f) => MetadataValue::Float(f),
+            Tagged::String(s) => MetadataValue::String(s),
+            Tagged::List(values) => MetadataValue::List(values),
+            Tagged::Map(map) => MetadataValue::Map(map),
+        })
+    }
+}
@@ -1,8 +1,48 @@
+mod adapters;
+mod chunker;
+mod collection;
+mod dedup;
 mod embedder;
+mod hashing;
 mod ingestor;
+mod metadata;
+mod pipeline;
+mod reader;
+mod retry;
+mod segment;
 mod storage;
+mod stream;
+mod sync;
+mod wal;
 
-pub use embedder::Provider;
+pub use adapters::{DEFAULT_INPUT_TYPE, ProviderKind, QUERY_INPUT_TYPE};
+pub use chunker::{
+    Chunk, Chunker, DEFAULT_CHUNK_OVERLAP, DEFAULT_CHUNK_SIZE, WindowUnit, default_separators,
+};
+pub use collection::{CollectionSchema, Collections, MANIFEST_FILE_NAME};
+pub use dedup::{
+    DEFAULT_COSINE_THRESHOLD, DEFAULT_MINHASH_THRESHOLD, DEFAULT_SIMHASH_DISTANCE, Dedup,
+    DedupMethod, DedupPolicy, Deduplicator,
+};
+pub use embedder::{
+    DEFAULT_CONNECT_TIMEOUT, DEFAULT_TIMEOUT, EmbeddingFuture, EmbeddingProvider, Provider,
+};
 pub use embedder::{EmbeddingData, Embeddings};
+pub use hashing::{DEFAULT_HASHING_DIMENSIONS, HashingProvider};
+pub(crate) use hashing::{SPLITMIX_GAMMA, SplitMix64};
 pub use ingestor::Ingestor;
-pub use storage::{EmbeddingStore, VectorData};
+pub use metadata::{Metadata, MetadataValue};
+pub use pipeline::{BatchOutcome, Checkpoint, DEFAULT_CONCURRENCY, IngestReport, Pipeline};
+pub use reader::{Document, Format, Reader, html_to_text};
+pub use retry::{RateLimiter, RetryPolicy, estimate_tokens, retry_after};
+pub use segment::{
+    MappedStore, SEGMENT_EXTENSION, SEGMENT_MAGIC, SEGMENT_VERSION, STORE_SEGMENT_FILE_NAME,
+    Segment, SegmentHeader,
+};
+pub use storage::{
+    BATCH_MAGIC, BATCH_VERSION, ConsistencyError, EmbeddingStore, InconsistentFile, VectorData,
+};
+pub use stream::{BatchStream, ChunkBatches, STREAM_BLOCK_BYTES, STREAM_DEDUP_WINDOW};
+pub use sync::{FileChanges, FileEntry, FileOutcome, SourceFile, SyncManifest, SyncReport};
+pub use wal::{BATCH_COUNTER_FILE_NAME, WAL_CHECKPOINT_BYTES, WAL_FILE_NAME, Wal, WalReplay};
+pub(crate) use wal::{checksum, write_atomic};
@@ -0,0 +1,526 @@
+use crate::utils::wal::{self, Wal, write_atomic};
+use crate::utils::{
+    BatchStream, Chunk, CollectionSchema, Deduplicator, EmbeddingProvider, EmbeddingStore,
+    Embeddings, FileOutcome, Ingestor, Metadata, MetadataValue, SourceFile, SyncManifest,
+    SyncReport, VectorData,
+};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::fs;
+use tokio::task::JoinSet;
+
+/// Provider requests in flight unless `with_concurrency` says otherwise
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+/// Batch count recorded in the checkpoint of a streamed job, whose length is not known
+const STREAMED_BATCHES: usize = 0;
+
+/// Batches of one ingestion job already embedded and stored.
+///
+/// Saved as `ingest_<hash>.checkpoint` in the store after every batch, keyed by the job
+/// name, so a restarted job skips them. A job whose batch count changed starts over.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Checkpoint {
+    pub job: String,
+    pub batches: usize,
+    pub completed: BTreeSet<usize>,
+}
+
+impl Checkpoint {
+    /// Checkpoint file of `job` inside `dir_path`
+    pub fn path(dir_path: &str, job: &str) -> PathBuf {
+        Path::new(dir_path).join(format!(
+            "ingest_{:016x}.checkpoint",
+            wal::checksum(job.as_bytes())
+        ))
+    }
+
+    /// Load the checkpoint of `job`, or an empty one when there is none for `batches` batches
+    pub async fn load(dir_path: &str, job: &str, batches: usize) -> Result<Checkpoint> {
+        let path = Self::path(dir_path, job);
+        let empty = Checkpoint {
+            job: job.to_string(),
+            batches,
+            completed: BTreeSet::new(),
+        };
+        let bytes = match fs::read(&path).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(empty),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read checkpoint: {:?}", path));
+            }
+        };
+        let checkpoint: Checkpoint = serde_json::from_slice(&bytes)
+            .with_context(|| format!("Failed to parse checkpoint: {:?}", path))?;
+        if checkpoint.job != job || checkpoint.batches != batches {
+            return Ok(empty);
+        }
+        Ok(checkpoint)
+    }
+
+    pub async fn save(&self, dir_path: &str) -> Result<()> {
+        let path = Self::path(dir_path, &self.job);
+        let encoded = serde_json::to_vec(self)?;
+        tokio::task::spawn_blocking(move || {
+            write_atomic(&path, |writer| Ok(writer.write_all(&encoded)?))
+                .with_context(|| format!("Failed to write checkpoint: {:?}", path))
+        })
+        .await?
+    }
+
+    /// Remove the checkpoint of `job`, if any
+    pub async fn clear(dir_path: &str, job: &str) -> Result<()> {
+        match fs::remove_file(Self::path(dir_path, job)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.completed.len() >= self.batches
+    }
+}
+
+/// What happened to one batch of a pipeline run
+#[derive(Debug, Clone, PartialEq)]
+pub enum BatchOutcome {
+    /// Already stored by an earlier run
+    Skipped {
+        batch: usize,
+    },
+    Stored {
+        batch: usize,
+        items: usize,
+    },
+    /// The provider failed after its retries; the batch is left for the next run
+    Failed {
+        batch: usize,
+        error: String,
+    },
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IngestReport {
+    pub batches: usize,
+    pub skipped: usize,
+    pub stored: usize,
+    /// Batch indices still missing, with the last error of each
+    pub failed: Vec<(usize, String)>,
+}
+
+impl IngestReport {
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+/// Embeds batches of text with bounded concurrency and stores them in one directory.
+///
+/// Up to `concurrency` provider requests run at once while results are upserted one at a
+/// time in completion order. Vector ids are derived from the batch and line number, so
+/// re-embedding a batch after a crash replaces its vectors instead of duplicating them.
+#[derive(Debug, Clone)]
+pub struct Pipeline {
+    provider: Arc<dyn EmbeddingProvider>,
+    store: String,
+    concurrency: usize,
+    id_prefix: String,
+    metadata: Metadata,
+    schema: Option<CollectionSchema>,
+}
+
+impl Pipeline {
+    pub fn new(provider: impl EmbeddingProvider + 'static, store: impl Into<String>) -> Self {
+        Self {
+            provider: Arc::new(provider),
+            store: store.into(),
+            concurrency: DEFAULT_CONCURRENCY,
+            id_prefix: String::new(),
+            metadata: Metadata::new(),
+            schema: None,
+        }
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Prefix for generated ids, `<prefix><batch>-<line>`; keeps several sources apart
+    pub fn with_id_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.id_prefix = prefix.into();
+        self
+    }
+
+    /// Metadata added to every stored vector, e.g. its source file
+    pub fn with_metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Check every batch against a collection schema before storing it
+    pub fn with_schema(mut self, schema: CollectionSchema) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    /// Embed and store every batch of `job` not completed by an earlier run.
+    ///
+    /// Provider failures are reported per batch and left for the next run; storage errors
+    /// abort the run. The checkpoint is removed once every batch is stored, and the
+    /// write-ahead log is folded into batch files at the end.
+    pub async fn run(
+        &self,
+        job: &str,
+        batches: &[Vec<String>],
+        data: &mut VectorData,
+        on_batch: impl FnMut(&BatchOutcome),
+    ) -> Result<IngestReport> {
+        self.execute(job, batches, None, data, on_batch).await
+    }
+
+    /// `run` for chunks, storing each chunk's metadata along with its `start` and `end`
+    /// offsets and markdown `section`
+    pub async fn run_chunks(
+        &self,
+        job: &str,
+        batches: &[Vec<Chunk>],
+        data: &mut VectorData,
+        on_batch: impl FnMut(&BatchOutcome),
+    ) -> Result<IngestReport> {
+        let texts: Vec<Vec<String>> = batches
+            .iter()
+            .map(|batch| batch.iter().map(|chunk| chunk.text.clone()).collect())
+            .collect();
+        let metadata: Vec<Vec<Metadata>> = batches
+            .iter()
+            .map(|batch| batch.iter().map(chunk_metadata).collect())
+            .collect();
+        self.execute(job, &texts, Some(&metadata), data, on_batch)
+            .await
+    }
+
+    /// Bring the store up to date with every file of `ingestor`'s source.
+    ///
+    /// New files and files whose content or embedding settings changed since the last
+    /// sync are embedded again, and the vectors of their chunks that no longer exist are
+    /// deleted; so are the vectors of files gone from the source. Each file is stored
+    /// with its path relative to the source as `source` metadata and its canonical path
+    /// as id prefix, so `docs` and `./docs` are the same source. Unchanged files are not
+    /// read again, and a file that fails is left for the next sync.
+    ///
+    /// Chunks are deduplicated across files: a chunk already stored for an unchanged file
+    /// or kept from an earlier changed one is not embedded again. Its vector stays with
+    /// the file it was first stored for, so removing that file removes it too until the
+    /// other files are embedded again.
+    pub async fn sync(
+        &self,
+        ingestor: &Ingestor,
+        data: &mut VectorData,
+        mut on_file: impl FnMut(&FileOutcome),
+    ) -> Result<SyncReport> {
+        let root = std::fs::canonicalize(&ingestor.source)
+            .with_context(|| format!("Failed to resolve {:?}", ingestor.source))?;
+        // Keys are relative to the directory, or to the directory of a single file
+        let base = match root.parent() {
+            Some(parent) if root.is_file() => parent.to_path_buf(),
+            _ => root.clone(),
+        };
+        let source = root.to_string_lossy().to_string();
+        let settings = serde_json::to_string(&(
+            self.provider.model(),
+            ingestor.batch_size,
+            &ingestor.chunker,
+            &ingestor.reader,
+            &ingestor.dedup,
+        ))?;
+        let settings = wal::checksum(settings.as_bytes());
+
+        let mut manifest = SyncManifest::load(&self.store, &source).await?;
+        let changes = manifest.changes(&ingestor.source, &ingestor.files()?, settings)?;
+        let mut report = SyncReport::default();
+
+        for path in changes.removed {
+            let ids = live_ids(data, &file_prefix(&base, &path)).collect();
+            let vectors = EmbeddingStore::delete(&self.store, data, ids).await?;
+            manifest.files.remove(&path);
+            manifest.save(&self.store).await?;
+            report.removed += 1;
+            on_file(&FileOutcome::Removed { path, vectors });
+        }
+
+        let mut deduplicator = Deduplicator::new(ingestor.dedup);
+        if ingestor.dedup.is_enabled() {
+            let unchanged: HashSet<String> = changes
+                .unchanged
+                .iter()
+                .map(|file| file_prefix(&base, &file.key))
+                .collect();
+            let stored: Vec<&str> = (0..data.ids.len())
+                .filter(|&index| {
+                    !data.is_deleted(index)
+                        && data.ids[index]
+                            .rfind(':')
+                            .is_some_and(|end| unchanged.contains(&data.ids[index][..=end]))
+                })
+                .map(|index| data.chunk[index].as_str())
+                .collect();
+            deduplicator.remember(&stored);
+        }
+
+        for file in changes.changed {
+            let path = file.key.clone();
+            let mark = deduplicator.mark();
+            let outcome = match self
+                .sync_file(ingestor, &base, &file, settings, data, &mut deduplicator)
+                .await?
+            {
+                Ok(chunks) => {
+                    manifest.files.insert(path.clone(), file.entry);
+                    manifest.save(&self.store).await?;
+                    if file.known {
+                        report.updated += 1;
+                        FileOutcome::Updated { path, chunks }
+                    } else {
+                        report.added += 1;
+                        FileOutcome::Added { path, chunks }
+                    }
+                }
+                Err(error) => {
+                    // Its chunks were not stored, so copies in later files are kept
+                    deduplicator.rollback(mark);
+                    report.failed.push((path.clone(), error.clone()));
+                    FileOutcome::Failed { path, error }
+                }
+            };
+            on_file(&outcome);
+        }
+
+        // Touched files only need their new modification time
+        report.unchanged = changes.unchanged.len();
+        for file in changes.unchanged {
+            manifest.files.insert(file.key.clone(), file.entry);
+            on_file(&FileOutcome::Unchanged { path: file.key });
+        }
+        manifest.save(&self.store).await?;
+        Ok(report)
+    }
+
+    /// Embed one file of a sync, returning its chunk count, or the reason it has to wait
+    /// for the next sync. Storage errors abort the sync.
+    async fn sync_file(
+        &self,
+        ingestor: &Ingestor,
+        base: &Path,
+        file: &SourceFile,
+        settings: u64,
+        data: &mut VectorData,
+        deduplicator: &mut Deduplicator,
+    ) -> Result<std::result::Result<usize, String>> {
+        let batches = match ingestor.read_file_with(&file.path, deduplicator) {
+            Ok(batches) => batches,
+            Err(e) => return Ok(Err(format!("{:#}", e))),
+        };
+        let prefix = file_prefix(base, &file.key);
+        let mut metadata = self.metadata.clone();
+        metadata.insert("source".to_string(), MetadataValue::from(file.key.clone()));
+        let pipeline = self
+            .clone()
+            .with_id_prefix(prefix.clone())
+            .with_metadata(metadata);
+
+        // The content hash keeps a checkpoint of an older version from being resumed
+        let job = format!("{}{:016x}:{:016x}", prefix, file.entry.hash, settings);
+        let report = pipeline.run_chunks(&job, &batches, data, |_| {}).await?;
+        if let Some((batch, error)) = report.failed.first() {
+            return Ok(Err(format!("batch {}: {}", batch, error)));
+        }
+
+        let current: HashSet<String> = batches
+            .iter()
+            .enumerate()
+            .flat_map(|(batch, chunks)| {
+                let prefix = &prefix;
+                (0..chunks.len()).map(move |index| format!("{}{}-{}", prefix, batch, index))
+            })
+            .collect();
+        let stale = live_ids(data, &prefix)
+            .filter(|id| !current.contains(id))
+            .collect::<Vec<_>>();
+        if !stale.is_empty() {
+            EmbeddingStore::delete(&self.store, data, stale).await?;
+        }
+        Ok(Ok(current.len()))
+    }
+
+    /// `run_chunks` for batches streamed from a file larger than memory.
+    ///
+    /// A batch is only taken from `batches` when a provider request slot is free, so
+    /// reading waits for the provider and at most `concurrency` batches are in flight
+    /// on top of the stream's buffer. The checkpoint of a streamed job records batch
+    /// numbers without a total, so `job` should change whenever the file does.
+    pub async fn run_stream(
+        &self,
+        job: &str,
+        mut batches: BatchStream,
+        data: &mut VectorData,
+        on_batch: impl FnMut(&BatchOutcome),
+    ) -> Result<IngestReport> {
+        let next = async || {
+            let chunks = batches.next().await?;
+            Some(chunks.map(|chunks| {
+                let lines = chunks.iter().map(|chunk| chunk.text.clone()).collect();
+                (lines, Some(chunks.iter().map(chunk_metadata).collect()))
+            }))
+        };
+        self.drive(job, STREAMED_BATCHES, next, data, on_batch)
+            .await
+    }
+
+    /// Give a batch's embeddings their ids and metadata and upsert them, returning how
+    /// many were stored
+    async fn store(
+        &self,
+        batch: usize,
+        embeddings: Embeddings,
+        item_metadata: Option<&[Metadata]>,
+        data: &mut VectorData,
+    ) -> Result<usize> {
+        let mut items = embeddings.data;
+        for item in &mut items {
+            item.id = format!("{}{}-{}", self.id_prefix, batch, item.index);
+            item.metadata.extend(self.metadata.clone());
+            if let Some(metadata) = item_metadata.and_then(|items| items.get(item.index)) {
+                item.metadata.extend(metadata.clone());
+            }
+        }
+        if let Some(schema) = &self.schema {
+            schema.check(&items)?;
+        }
+        let stored = items.len();
+        EmbeddingStore::upsert_with_model(&self.store, data, self.provider.model(), items).await?;
+        Ok(stored)
+    }
+
+    async fn execute(
+        &self,
+        job: &str,
+        batches: &[Vec<String>],
+        item_metadata: Option<&[Vec<Metadata>]>,
+        data: &mut VectorData,
+        on_batch: impl FnMut(&BatchOutcome),
+    ) -> Result<IngestReport> {
+        let mut lines = batches.iter().enumerate();
+        let next = async || {
+            let (batch, lines) = lines.next()?;
+            let metadata = item_metadata.and_then(|batches| batches.get(batch));
+            Some(Ok((lines.clone(), metadata.cloned())))
+        };
+        self.drive(job, batches.len(), next, data, on_batch).await
+    }
+
+    /// Embed and store the batches `next` yields, numbered in order, skipping those in
+    /// the checkpoint of `job`.
+    ///
+    /// A batch is only taken from `next` when a provider request slot is free, so at
+    /// most `concurrency` batches are in flight. Errors of `next` and of storage abort the
+    /// run.
+    async fn drive(
+        &self,
+        job: &str,
+        total: usize,
+        mut next: impl AsyncFnMut() -> Option<Result<(Vec<String>, Option<Vec<Metadata>>)>>,
+        data: &mut VectorData,
+        mut on_batch: impl FnMut(&BatchOutcome),
+    ) -> Result<IngestReport> {
+        let mut checkpoint = Checkpoint::load(&self.store, job, total).await?;
+        let mut report = IngestReport::default();
+        let mut exhausted = false;
+        let mut tasks = JoinSet::new();
+        loop {
+            while !exhausted && tasks.len() < self.concurrency {
+                let Some(next) = next().await else {
+                    exhausted = true;
+                    break;
+                };
+                let (lines, metadata) = next?;
+                let batch = report.batches;
+                report.batches += 1;
+                if checkpoint.completed.contains(&batch) {
+                    report.skipped += 1;
+                    on_batch(&BatchOutcome::Skipped { batch });
+                    continue;
+                }
+                let provider = self.provider.clone();
+                tasks.spawn(async move {
+                    let result = provider.fetch_embeddings(&lines).await;
+                    (batch, metadata, result)
+                });
+            }
+            let Some(joined) = tasks.join_next().await else {
+                break;
+            };
+
+            let (batch, metadata, result) = joined?;
+            let outcome = match result {
+                Ok(embeddings) => {
+                    let stored = self
+                        .store(batch, embeddings, metadata.as_deref(), data)
+                        .await?;
+                    checkpoint.completed.insert(batch);
+                    checkpoint.save(&self.store).await?;
+                    report.stored += 1;
+                    BatchOutcome::Stored {
+                        batch,
+                        items: stored,
+                    }
+                }
+                Err(e) => {
+                    let error = format!("{:#}", e);
+                    report.failed.push((batch, error.clone()));
+                    BatchOutcome::Failed { batch, error }
+                }
+            };
+            on_batch(&outcome);
+        }
+
+        report.failed.sort_by_key(|(batch, _)| *batch);
+        if report.is_complete() {
+            Checkpoint::clear(&self.store, job).await?;
+        }
+        Wal::checkpoint(&self.store).await?;
+        Ok(report)
+    }
+}
+
+/// Id prefix of the vectors of the file at `key` below `base`: its canonical path
+fn file_prefix(base: &Path, key: &str) -> String {
+    format!("{}:", base.join(key).to_string_lossy())
+}
+
+/// Ids of live vectors starting with `prefix`
+fn live_ids<'a>(data: &'a VectorData, prefix: &'a str) -> impl Iterator<Item = String> + 'a {
+    data.ids
+        .iter()
+        .enumerate()
+        .filter(move |(index, id)| id.starts_with(prefix) && !data.is_deleted(*index))
+        .map(|(_, id)| id.clone())
+}
+
+fn chunk_metadata(chunk: &Chunk) -> Metadata {
+    let mut metadata = chunk.metadata.clone();
+    metadata.insert("start".to_string(), MetadataValue::from(chunk.start as i64));
+    metadata.insert("end".to_string(), MetadataValue::from(chunk.end as i64));
+    if let Some(section) = &chunk.section {
+        metadata.insert("section".to_string(), MetadataValue::from(section.clone()));
+    }
+    metadata
+}
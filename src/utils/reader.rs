@@ -0,0 +1,564 @@
+use crate::utils::{Metadata, MetadataValue};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Text of one source record and the metadata that goes with it
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Document {
+    pub text: String,
+    #[serde(default)]
+    pub metadata: Metadata,
+}
+
+/// Format of a source file
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    /// The whole file is one document
+    #[default]
+    Text,
+    /// One document; YAML-style `key: value` front matter becomes metadata
+    Markdown,
+    /// One document of the visible text; scripts, styles and comments are dropped and
+    /// the `<title>` becomes metadata
+    Html,
+    /// One document per JSON object line
+    Jsonl,
+    /// One document per row after a header row
+    Csv,
+}
+
+impl Format {
+    /// Format implied by the extension of `path`, plain text when unknown
+    pub fn from_path(path: &Path) -> Format {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        match extension.as_str() {
+            "md" | "markdown" | "mdx" => Format::Markdown,
+            "html" | "htm" | "xhtml" => Format::Html,
+            "jsonl" | "ndjson" => Format::Jsonl,
+            "csv" | "tsv" => Format::Csv,
+            _ => Format::Text,
+        }
+    }
+}
+
+impl std::str::FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" | "txt" => Ok(Format::Text),
+            "markdown" | "md" => Ok(Format::Markdown),
+            "html" => Ok(Format::Html),
+            "jsonl" | "ndjson" => Ok(Format::Jsonl),
+            "csv" => Ok(Format::Csv),
+            other => anyhow::bail!(
+                "Unknown format: {} (expected text, markdown, html, jsonl or csv)",
+                other
+            ),
+        }
+    }
+}
+
+/// Turns source files into documents.
+///
+/// JSONL and CSV records take their text from `text_fields`, joined by blank lines, and
+/// carry `metadata_fields` (every other field when empty) plus their `record` number as
+/// metadata. Records without text are skipped.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Reader {
+    /// Picked from the file extension when unset
+    pub format: Option<Format>,
+    /// JSONL keys (`a.b` reaches into nested objects) or CSV columns holding the text
+    pub text_fields: Vec<String>,
+    pub metadata_fields: Vec<String>,
+    /// CSV field separator; a tab for `.tsv` files and a comma otherwise when unset
+    pub delimiter: Option<char>,
+}
+
+impl Default for Reader {
+    fn default() -> Self {
+        Self {
+            format: None,
+            text_fields: vec!["text".to_string()],
+            metadata_fields: Vec::new(),
+            delimiter: None,
+        }
+    }
+}
+
+impl Reader {
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    pub fn with_text_fields(mut self, fields: Vec<String>) -> Self {
+        self.text_fields = fields;
+        self
+    }
+
+    pub fn with_metadata_fields(mut self, fields: Vec<String>) -> Self {
+        self.metadata_fields = fields;
+        self
+    }
+
+    /// Format used for `path`
+    pub fn format_of(&self, path: &Path) -> Format {
+        self.format.unwrap_or_else(|| Format::from_path(path))
+    }
+
+    /// Read `path` as UTF-8, replacing invalid sequences, and parse its documents
+    pub fn read(&self, path: &Path) -> Result<Vec<Document>> {
+        let bytes = std::fs::read(path).with_context(|| format!("Failed to read {:?}", path))?;
+        let text = String::from_utf8_lossy(&bytes);
+        let delimiter = self.delimiter.unwrap_or(
+            if path.extension().is_some_and(|extension| extension == "tsv") {
+                '\t'
+            } else {
+                ','
+            },
+        );
+        self.parse_with(&text, self.format_of(path), delimiter)
+            .with_context(|| format!("Failed to parse {:?}", path))
+    }
+
+    /// Parse `text` in `format`
+    pub fn parse(&self, text: &str, format: Format) -> Result<Vec<Document>> {
+        self.parse_with(text, format, self.delimiter.unwrap_or(','))
+    }
+
+    fn parse_with(&self, text: &str, format: Format, delimiter: char) -> Result<Vec<Document>> {
+        match format {
+            Format::Text => Ok(vec![Document {
+                text: text.to_string(),
+                metadata: Metadata::new(),
+            }]),
+            Format::Markdown => Ok(vec![front_matter(text)]),
+            Format::Html => Ok(vec![html_document(text)]),
+            Format::Jsonl => self.jsonl(text, 0),
+            Format::Csv => self.csv(text, delimiter),
+        }
+    }
+
+    /// JSONL records of `text`, whose first line is line `first_line` of the file
+    pub(crate) fn jsonl(&self, text: &str, first_line: usize) -> Result<Vec<Document>> {
+        let mut documents = Vec::new();
+        for (line_number, line) in text.lines().enumerate() {
+            let line_number = first_line + line_number;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: serde_json::Value = serde_json::from_str(line)
+                .with_context(|| format!("Invalid JSON on line {}", line_number + 1))?;
+            let serde_json::Value::Object(object) = record else {
+                anyhow::bail!("Line {} is not a JSON object", line_number + 1);
+            };
+
+            let text = join_text(self.text_fields.iter().map(|field| {
+                lookup(&object, field).map(|value| match value {
+                    serde_json::Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                })
+            }));
+            if text.is_empty() {
+                continue;
+            }
+            let mut metadata = Metadata::new();
+            for (key, value) in &object {
+                if self.is_metadata(key) {
+                    metadata.insert(key.clone(), MetadataValue::from(value.clone()));
+                }
+            }
+            for field in self.metadata_fields.iter().filter(|f| f.contains('.')) {
+                if let Some(value) = lookup(&object, field) {
+                    metadata.insert(field.clone(), MetadataValue::from(value.clone()));
+                }
+            }
+            metadata.insert(
+                "record".to_string(),
+                MetadataValue::from(line_number as i64),
+            );
+            documents.push(Document { text, metadata });
+        }
+        Ok(documents)
+    }
+
+    fn csv(&self, text: &str, delimiter: char) -> Result<Vec<Document>> {
+        let mut rows = parse_csv(text, delimiter).into_iter();
+        let Some(header) = rows.next() else {
+            return Ok(Vec::new());
+        };
+        let columns = |fields: &[String]| -> Result<Vec<usize>> {
+            fields
+                .iter()
+                .map(|field| {
+                    header
+                        .iter()
+                        .position(|column| column == field)
+                        .with_context(|| {
+                            format!(
+                                "CSV has no column {} (columns: {})",
+                                field,
+                                header.join(", ")
+                            )
+                        })
+                })
+                .collect()
+        };
+        let text_columns = columns(&self.text_fields)?;
+        let metadata_columns = if self.metadata_fields.is_empty() {
+            (0..header.len())
+                .filter(|column| !text_columns.contains(column))
+                .collect()
+        } else {
+            columns(&self.metadata_fields)?
+        };
+
+        let mut documents = Vec::new();
+        for (record, row) in rows.enumerate() {
+            let text = join_text(text_columns.iter().map(|&column| row.get(column).cloned()));
+            if text.is_empty() {
+                continue;
+            }
+            let mut metadata = Metadata::new();
+            for &column in &metadata_columns {
+                if let Some(value) = row.get(column).filter(|value| !value.is_empty()) {
+                    metadata.insert(header[column].clone(), csv_value(value));
+                }
+            }
+            metadata.insert("record".to_string(), MetadataValue::from(record as i64));
+            documents.push(Document { text, metadata });
+        }
+        Ok(documents)
+    }
+
+    /// Whether the top-level JSONL key `key` is carried as metadata
+    fn is_metadata(&self, key: &str) -> bool {
+        if self.metadata_fields.is_empty() {
+            // Objects holding a nested text field would only repeat the text
+            !self
+                .text_fields
+                .iter()
+                .any(|field| field == key || field.split('.').next() == Some(key))
+        } else {
+            self.metadata_fields.iter().any(|field| field == key)
+        }
+    }
+}
+
+/// Non-empty values joined by blank lines
+fn join_text(values: impl Iterator<Item = Option<String>>) -> String {
+    values
+        .flatten()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Value at a dotted path such as `fields.description`
+fn lookup<'a>(
+    object: &'a serde_json::Map<String, serde_json::Value>,
+    path: &str,
+) -> Option<&'a serde_json::Value> {
+    if let Some(value) = object.get(path) {
+        return Some(value);
+    }
+    let (first, rest) = path.split_once('.')?;
+    match object.get(first)? {
+        serde_json::Value::Object(inner) => lookup(inner, rest),
+        _ => None,
+    }
+}
+
+/// Integers and floats keep their type so filters can compare them
+fn csv_value(value: &str) -> MetadataValue {
+    if let Ok(int) = value.parse::<i64>() {
+        MetadataValue::Int(int)
+    } else if let Ok(float) = value.parse::<f64>()
+        && float.is_finite()
+    {
+        MetadataValue::Float(float)
+    } else {
+        MetadataValue::from(value)
+    }
+}
+
+/// Rows of RFC 4180 CSV: quoted fields may contain the delimiter, newlines and doubled
+/// quotes. Blank lines are skipped.
+fn parse_csv(text: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.trim_start_matches('\u{feff}').chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                '"' => quoted = false,
+                _ => field.push(c),
+            }
+        } else if c == '"' && field.is_empty() {
+            quoted = true;
+        } else if c == delimiter {
+            row.push(std::mem::take(&mut field));
+        } else if c == '\n' || c == '\r' {
+            if c == '\r' && chars.peek() == Some(&'\n') {
+                chars.next();
+            }
+            row.push(std::mem::take(&mut field));
+            if row.len() > 1 || !row[0].is_empty() {
+                rows.push(std::mem::take(&mut row));
+            } else {
+                row.clear();
+            }
+        } else {
+            field.push(c);
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+/// Split `---` delimited front matter off a markdown file
+fn front_matter(text: &str) -> Document {
+    let mut metadata = Metadata::new();
+    let body = text.trim_start_matches('\u{feff}');
+    let Some(rest) = body
+        .strip_prefix("---\n")
+        .or_else(|| body.strip_prefix("---\r\n"))
+    else {
+        return Document {
+            text: text.to_string(),
+            metadata,
+        };
+    };
+    let Some(close) = rest
+        .match_indices("\n---")
+        .map(|(index, _)| index)
+        .find(|&index| matches!(rest[index + 4..].chars().next(), None | Some('\n' | '\r')))
+    else {
+        return Document {
+            text: text.to_string(),
+            metadata,
+        };
+    };
+
+    for line in rest[..close].lines() {
+        if let Some((key, value)) = line.split_once(':')
+            && !key.starts_with([' ', '\t', '-', '#'])
+        {
+            let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+            if !value.is_empty() {
+                metadata.insert(key.trim().to_string(), MetadataValue::from(value));
+            }
+        }
+    }
+    let body = rest[close + 4..].trim_start_matches(['\r', '\n']);
+    Document {
+        text: body.to_string(),
+        metadata,
+    }
+}
+
+/// Elements whose content is never visible text
+const HIDDEN_ELEMENTS: &[&str] = &[
+    "script", "style", "noscript", "template", "svg", "iframe", "object",
+];
+
+/// Elements that start a new paragraph
+const BLOCK_ELEMENTS: &[&str] = &[
+    "p",
+    "div",
+    "section",
+    "article",
+    "main",
+    "header",
+    "footer",
+    "nav",
+    "aside",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "ul",
+    "ol",
+    "table",
+    "blockquote",
+    "pre",
+    "figure",
+    "form",
+    "hr",
+    "dl",
+    "body",
+];
+
+/// Elements that start a new line
+const LINE_ELEMENTS: &[&str] = &["br", "li", "tr", "dt", "dd"];
+
+fn html_document(html: &str) -> Document {
+    let (text, title) = html_to_text(html);
+    let mut metadata = Metadata::new();
+    if let Some(title) = title {
+        metadata.insert("title".to_string(), MetadataValue::from(title));
+    }
+    Document { text, metadata }
+}
+
+/// Visible text of an HTML page, with blank lines between blocks, and its title.
+///
+/// A small tag scanner rather than a full HTML parser: good enough for wiki and ticket
+/// exports, not for pages assembled by scripts.
+pub fn html_to_text(html: &str) -> (String, Option<String>) {
+    // ASCII lowercasing keeps byte offsets, so tags can be searched case-insensitively
+    let lower = html.to_ascii_lowercase();
+    let mut raw = String::with_capacity(html.len() / 2);
+    let mut title = None;
+    let mut position = 0;
+
+    while let Some(found) = html[position..].find('<') {
+        let open = position + found;
+        raw.push_str(&decode_entities(&html[position..open]));
+
+        if lower[open..].starts_with("<!--") {
+            position = lower[open..]
+                .find("-->")
+                .map_or(html.len(), |end| open + end + 3);
+            continue;
+        }
+        let Some(close) = tag_end(html, open) else {
+            // A stray '<' in text
+            raw.push('<');
+            position = open + 1;
+            continue;
+        };
+        let tag = &lower[open + 1..close];
+        let closing = tag.starts_with('/');
+        let name: String = tag
+            .trim_start_matches('/')
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect();
+        position = close + 1;
+
+        if !closing && (name == "title" || HIDDEN_ELEMENTS.contains(&name.as_str())) {
+            // Skip to the matching end tag; the title is kept aside
+            let end_tag = format!("</{}", name);
+            let end = lower[position..]
+                .find(&end_tag)
+                .map_or(html.len(), |end| position + end);
+            if name == "title" {
+                let text = collapse(&decode_entities(&html[position..end]));
+                title = Some(text).filter(|text| !text.is_empty());
+            }
+            position = tag_end(html, end).map_or(html.len(), |close| close + 1);
+            continue;
+        }
+        if BLOCK_ELEMENTS.contains(&name.as_str()) {
+            raw.push_str("\n\n");
+        } else if !closing && LINE_ELEMENTS.contains(&name.as_str()) {
+            // Only the start tag, or `</li><li>` would leave a blank line between items
+            raw.push('\n');
+        } else if name == "td" || name == "th" {
+            raw.push(' ');
+        }
+    }
+    raw.push_str(&decode_entities(&html[position..]));
+
+    // Collapse whitespace inside lines and runs of blank lines
+    let mut text = String::with_capacity(raw.len());
+    let mut blank = false;
+    for line in raw.lines().map(collapse) {
+        if line.is_empty() {
+            blank = !text.is_empty();
+            continue;
+        }
+        if !text.is_empty() {
+            text.push_str(if blank { "\n\n" } else { "\n" });
+        }
+        text.push_str(&line);
+        blank = false;
+    }
+    (text, title)
+}
+
+/// Index of the `>` closing the tag opened at `open`, skipping quoted attribute values
+fn tag_end(html: &str, open: usize) -> Option<usize> {
+    let mut quote = None;
+    // `open` is the end of the input when a hidden element is never closed
+    for (offset, c) in html.get(open + 1..)?.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => return Some(open + 1 + offset),
+            (None, '<') => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+fn collapse(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .map(|end| &rest[1..end + 1]);
+        let character = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => {
+                let code = entity.strip_prefix('#')?;
+                let code = match code.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => code.parse().ok()?,
+                };
+                char::from_u32(code)
+            }
+        });
+        match (character, entity) {
+            (Some(character), Some(entity)) => {
+                decoded.push(character);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
@@ -0,0 +1,196 @@
+use reqwest::StatusCode;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// How failed provider requests are retried.
+///
+/// Attempt `n` waits `initial_backoff * multiplier^n`, capped at `max_backoff`, with
+/// "equal jitter": half of the delay is fixed and half random, so clients throttled
+/// together do not retry in lockstep. A `Retry-After` header replaces the computed delay
+/// in full; one longer than `max_retry_after` fails the request instead.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; 0 disables retrying
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub multiplier: f64,
+    /// Longest `Retry-After` waited for
+    pub max_retry_after: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            multiplier: 2.0,
+            max_retry_after: Duration::from_secs(300),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Fail on the first error
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    /// Whether a response with `status` is worth retrying: throttling and server errors
+    pub fn is_retryable(status: StatusCode) -> bool {
+        status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+    }
+
+    /// Upper bound of the delay before retry `attempt` (0-based), without jitter
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = self.multiplier.max(1.0).powi(attempt.min(64) as i32);
+        let delay = self.initial_backoff.as_secs_f64() * factor;
+        Duration::from_secs_f64(delay.min(self.max_backoff.as_secs_f64()))
+    }
+
+    /// Delay before retry `attempt`: `Retry-After` when the server sent one, otherwise
+    /// the jittered backoff. `None` when the server asks for more than `max_retry_after`.
+    pub fn delay(&self, attempt: u32, headers: Option<&HeaderMap>) -> Option<Duration> {
+        if let Some(retry_after) = headers.and_then(retry_after) {
+            return (retry_after <= self.max_retry_after).then_some(retry_after);
+        }
+        let backoff = self.backoff(attempt);
+        Some(backoff / 2 + backoff.mul_f64(random_fraction() / 2.0))
+    }
+}
+
+/// Parse a `Retry-After` header given in seconds; HTTP dates are ignored
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+        .map(Duration::from_secs_f64)
+}
+
+/// A uniform value in [0, 1) from the randomly seeded std hasher, which is enough for
+/// jitter without pulling in an RNG crate
+fn random_fraction() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+    hasher.write_u64(nanos);
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Client-side requests-per-minute and tokens-per-minute limits.
+///
+/// Each limit is a token bucket holding one minute of budget that refills continuously,
+/// so short bursts up to the limit go through and sustained load is spread evenly.
+/// Clones of a `Provider` share one limiter.
+#[derive(Debug)]
+pub struct RateLimiter {
+    requests_per_minute: Option<u32>,
+    tokens_per_minute: Option<u32>,
+    buckets: Mutex<Buckets>,
+}
+
+#[derive(Debug)]
+struct Buckets {
+    requests: f64,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl RateLimiter {
+    /// `None` leaves that dimension unlimited
+    pub fn new(requests_per_minute: Option<u32>, tokens_per_minute: Option<u32>) -> Self {
+        Self {
+            requests_per_minute,
+            tokens_per_minute,
+            buckets: Mutex::new(Buckets {
+                requests: requests_per_minute.unwrap_or(0) as f64,
+                tokens: tokens_per_minute.unwrap_or(0) as f64,
+                refilled: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn requests_per_minute(&self) -> Option<u32> {
+        self.requests_per_minute
+    }
+
+    pub fn tokens_per_minute(&self) -> Option<u32> {
+        self.tokens_per_minute
+    }
+
+    /// Wait until one request costing `tokens` fits in both budgets, then spend them.
+    ///
+    /// A request larger than a whole minute of tokens waits for a full bucket instead of
+    /// blocking forever.
+    pub async fn acquire(&self, tokens: usize) {
+        loop {
+            let wait = {
+                let mut buckets = self.buckets.lock().await;
+                let now = Instant::now();
+                let minutes = now.duration_since(buckets.refilled).as_secs_f64() / 60.0;
+                buckets.refilled = now;
+
+                let request_wait = refill_and_wait(
+                    &mut buckets.requests,
+                    self.requests_per_minute,
+                    1.0,
+                    minutes,
+                );
+                let token_wait = refill_and_wait(
+                    &mut buckets.tokens,
+                    self.tokens_per_minute,
+                    tokens as f64,
+                    minutes,
+                );
+                let wait = request_wait.max(token_wait);
+                if wait.is_zero() {
+                    if self.requests_per_minute.is_some() {
+                        buckets.requests -= 1.0;
+                    }
+                    if let Some(limit) = self.tokens_per_minute {
+                        buckets.tokens -= (tokens as f64).min(limit as f64);
+                    }
+                    return;
+                }
+                wait
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Refill `bucket` for `minutes` of elapsed time and return how long until it holds
+/// `cost`; zero for an unlimited bucket
+fn refill_and_wait(bucket: &mut f64, limit: Option<u32>, cost: f64, minutes: f64) -> Duration {
+    let Some(limit) = limit.filter(|limit| *limit > 0) else {
+        return Duration::ZERO;
+    };
+    let limit = limit as f64;
+    *bucket = (*bucket + minutes * limit).min(limit);
+    let missing = cost.min(limit) - *bucket;
+    if missing <= 0.0 {
+        Duration::ZERO
+    } else {
+        Duration::from_secs_f64(missing / limit * 60.0)
+    }
+}
+
+/// Rough token count of `chunks` for the tokens-per-minute budget: about four bytes of
+/// text per token, which is close for English with BPE tokenizers
+pub fn estimate_tokens(chunks: &[String]) -> usize {
+    chunks
+        .iter()
+        .map(|chunk| chunk.len().div_ceil(4).max(1))
+        .sum()
+}
@@ -0,0 +1,545 @@
+use crate::core::{Kernel, Metrics, VectorSource};
+use crate::utils::wal;
+use crate::utils::{EmbeddingStore, Metadata, VectorData};
+use anyhow::{Context, Result};
+use memmap2::Mmap;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// File signature of a columnar segment
+pub const SEGMENT_MAGIC: &[u8; 8] = b"BLAZESEG";
+pub const SEGMENT_VERSION: u32 = 1;
+/// File extension used for segments inside a store directory
+pub const SEGMENT_EXTENSION: &str = "seg";
+/// Segment `EmbeddingStore::seal` writes inside a store directory
+pub const STORE_SEGMENT_FILE_NAME: &str = "store.seg";
+
+/// Size of the fixed header; also the alignment of the vector matrix
+const HEADER_LEN: usize = 64;
+
+/// Fixed-size header at the start of every segment.
+///
+/// All integers are little-endian. The sections follow in order: the f32 matrix
+/// (`count * dimensions`, starting at byte 64), the chunk offset table and bytes, the id
+/// offset table and bytes, then a bincode-encoded `Vec<Metadata>`. Offset tables hold
+/// `count + 1` u64 positions relative to the start of their string bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SegmentHeader {
+    pub version: u32,
+    pub dimensions: u32,
+    pub count: u64,
+    /// Metric the vectors were prepared for, if any
+    pub metric_hint: Option<Metrics>,
+    /// Every vector has unit length, so cosine and dot product rank identically
+    pub normalized: bool,
+    /// Batches with a lower index were written into the segment; later ones are not
+    pub batches: u32,
+    pub chunks_offset: u64,
+    pub ids_offset: u64,
+    pub metadata_offset: u64,
+    pub metadata_len: u64,
+}
+
+impl SegmentHeader {
+    fn encode(&self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0u8; HEADER_LEN];
+        bytes[0..8].copy_from_slice(SEGMENT_MAGIC);
+        bytes[8..12].copy_from_slice(&self.version.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.dimensions.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.count.to_le_bytes());
+        bytes[24] = metric_to_byte(self.metric_hint);
+        bytes[25] = self.normalized as u8;
+        bytes[28..32].copy_from_slice(&self.batches.to_le_bytes());
+        bytes[32..40].copy_from_slice(&self.chunks_offset.to_le_bytes());
+        bytes[40..48].copy_from_slice(&self.ids_offset.to_le_bytes());
+        bytes[48..56].copy_from_slice(&self.metadata_offset.to_le_bytes());
+        bytes[56..64].copy_from_slice(&self.metadata_len.to_le_bytes());
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < HEADER_LEN {
+            anyhow::bail!("Segment is too short for a header");
+        }
+        if &bytes[0..8] != SEGMENT_MAGIC {
+            anyhow::bail!("Not a segment file (bad magic)");
+        }
+
+        let header = Self {
+            version: read_u32(bytes, 8),
+            dimensions: read_u32(bytes, 12),
+            count: read_u64(bytes, 16),
+            metric_hint: metric_from_byte(bytes[24])?,
+            normalized: bytes[25] != 0,
+            batches: read_u32(bytes, 28),
+            chunks_offset: read_u64(bytes, 32),
+            ids_offset: read_u64(bytes, 40),
+            metadata_offset: read_u64(bytes, 48),
+            metadata_len: read_u64(bytes, 56),
+        };
+        if header.version != SEGMENT_VERSION {
+            anyhow::bail!("Unsupported segment version {}", header.version);
+        }
+        Ok(header)
+    }
+}
+
+/// An immutable, memory-mapped columnar segment.
+///
+/// Vectors are served straight out of the mapping, so opening a segment costs one
+/// `mmap` plus decoding the (usually small) metadata section. Vector norms are worked out
+/// on the first cosine score and cached for the lifetime of the segment.
+pub struct Segment {
+    path: PathBuf,
+    header: SegmentHeader,
+    mmap: Mmap,
+    metadata: Vec<Metadata>,
+    norms: OnceLock<Vec<f32>>,
+}
+
+impl Segment {
+    /// Write the live vectors of `data` as a segment at `path`, atomically replacing any
+    /// existing file. The header records `data.next_batch_index`, the batches it covers.
+    pub fn write(
+        path: impl AsRef<Path>,
+        data: &VectorData,
+        metric_hint: Option<Metrics>,
+    ) -> Result<()> {
+        let path = path.as_ref();
+        let live: Vec<usize> = (0..data.embedding.len())
+            .filter(|idx| !data.is_deleted(*idx))
+            .collect();
+        let dimensions = live
+            .first()
+            .map(|&idx| data.embedding[idx].len())
+            .unwrap_or(0);
+        if let Some(&idx) = live
+            .iter()
+            .find(|&&idx| data.embedding[idx].len() != dimensions)
+        {
+            anyhow::bail!(
+                "Vector {} has {} dimensions, expected {}",
+                idx,
+                data.embedding[idx].len(),
+                dimensions
+            );
+        }
+
+        let normalized = !live.is_empty()
+            && live.iter().all(|&idx| {
+                let norm: f32 = data.embedding[idx].iter().map(|x| x * x).sum();
+                (norm.sqrt() - 1.0).abs() < 1e-3
+            });
+
+        let count = live.len();
+        let vectors_len = count * dimensions * size_of::<f32>();
+        let chunks: Vec<&str> = live.iter().map(|&idx| data.chunk[idx].as_str()).collect();
+        let ids: Vec<&str> = live
+            .iter()
+            .map(|&idx| data.get_id(idx).unwrap_or_default())
+            .collect();
+        let metadata: Vec<Metadata> = live
+            .iter()
+            .map(|&idx| data.get_metadata(idx).cloned().unwrap_or_default())
+            .collect();
+        let metadata_bytes = bincode::serialize(&metadata)?;
+
+        let chunks_offset = (HEADER_LEN + vectors_len) as u64;
+        let ids_offset = chunks_offset + string_section_len(&chunks);
+        let metadata_offset = ids_offset + string_section_len(&ids);
+        let header = SegmentHeader {
+            version: SEGMENT_VERSION,
+            dimensions: dimensions as u32,
+            count: count as u64,
+            metric_hint,
+            normalized,
+            batches: u32::try_from(data.next_batch_index).context("Too many batches")?,
+            chunks_offset,
+            ids_offset,
+            metadata_offset,
+            metadata_len: metadata_bytes.len() as u64,
+        };
+
+        wal::write_atomic(path, |writer| {
+            writer.write_all(&header.encode())?;
+            for &idx in &live {
+                for value in &data.embedding[idx] {
+                    writer.write_all(&value.to_le_bytes())?;
+                }
+            }
+            write_string_section(writer, &chunks)?;
+            write_string_section(writer, &ids)?;
+            writer.write_all(&metadata_bytes)?;
+            Ok(())
+        })
+        .with_context(|| format!("Failed to write segment: {:?}", path))?;
+
+        Ok(())
+    }
+
+    /// Memory-map the segment at `path` and validate its layout
+    pub fn open(path: impl AsRef<Path>) -> Result<Segment> {
+        if cfg!(target_endian = "big") {
+            anyhow::bail!("Segments can only be mapped on little-endian targets");
+        }
+
+        let path = path.as_ref().to_path_buf();
+        let file =
+            File::open(&path).with_context(|| format!("Failed to open segment: {:?}", path))?;
+        // SAFETY: segments are immutable once written; callers must not modify the file
+        // while it is mapped.
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        let header =
+            SegmentHeader::decode(&mmap).with_context(|| format!("Invalid segment: {:?}", path))?;
+        validate_layout(&header, &mmap).with_context(|| format!("Invalid segment: {:?}", path))?;
+
+        let metadata_start = header.metadata_offset as usize;
+        let metadata_bytes = &mmap[metadata_start..metadata_start + header.metadata_len as usize];
+        let metadata: Vec<Metadata> = bincode::deserialize(metadata_bytes)
+            .with_context(|| format!("Failed to decode segment metadata: {:?}", path))?;
+
+        Ok(Segment {
+            path,
+            header,
+            mmap,
+            metadata,
+            norms: OnceLock::new(),
+        })
+    }
+
+    /// Open every `.seg` file in `dir_path`, ordered by file name
+    pub fn open_dir(dir_path: impl AsRef<Path>) -> Result<Vec<Segment>> {
+        let dir_path = dir_path.as_ref();
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(dir_path)
+            .with_context(|| format!("Failed to read directory: {:?}", dir_path))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().and_then(|e| e.to_str()) == Some(SEGMENT_EXTENSION))
+            .collect();
+        paths.sort();
+
+        paths.into_iter().map(Segment::open).collect()
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn header(&self) -> &SegmentHeader {
+        &self.header
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.header.dimensions as usize
+    }
+
+    /// The whole vector matrix, row-major, borrowed from the mapping
+    pub fn vectors(&self) -> &[f32] {
+        let len = self.header.count as usize * self.dimensions();
+        let bytes = &self.mmap[HEADER_LEN..HEADER_LEN + len * size_of::<f32>()];
+        // SAFETY: the mapping is page aligned and the matrix starts at byte 64, so the
+        // pointer is aligned for f32; `validate_layout` checked the length, and every bit
+        // pattern is a valid f32 on the little-endian targets `open` accepts.
+        unsafe { std::slice::from_raw_parts(bytes.as_ptr() as *const f32, len) }
+    }
+
+    /// Copy the segment into an in-memory `VectorData`
+    pub fn to_vector_data(&self) -> VectorData {
+        let count = VectorSource::len(self);
+        VectorData {
+            chunk: (0..count).map(|idx| self.chunk(idx).to_string()).collect(),
+            embedding: (0..count).map(|idx| self.vector(idx).to_vec()).collect(),
+            dimensions: self.dimensions(),
+            total_vectors: count,
+            ids: (0..count).map(|idx| self.id(idx).to_string()).collect(),
+            metadata: self.metadata.clone(),
+            ..Default::default()
+        }
+    }
+
+    fn string_at(&self, section: u64, idx: usize) -> &str {
+        let count = self.header.count as usize;
+        let table = section as usize;
+        let bytes_start = table + (count + 1) * size_of::<u64>();
+        let start = read_u64(&self.mmap, table + idx * size_of::<u64>()) as usize;
+        let end = read_u64(&self.mmap, table + (idx + 1) * size_of::<u64>()) as usize;
+        std::str::from_utf8(&self.mmap[bytes_start + start..bytes_start + end]).unwrap_or_default()
+    }
+}
+
+impl VectorSource for Segment {
+    fn len(&self) -> usize {
+        self.header.count as usize
+    }
+
+    fn vector(&self, idx: usize) -> &[f32] {
+        let dimensions = self.dimensions();
+        &self.vectors()[idx * dimensions..(idx + 1) * dimensions]
+    }
+
+    fn chunk(&self, idx: usize) -> &str {
+        self.string_at(self.header.chunks_offset, idx)
+    }
+
+    fn id(&self, idx: usize) -> &str {
+        self.string_at(self.header.ids_offset, idx)
+    }
+
+    fn metadata(&self, idx: usize) -> Option<&Metadata> {
+        self.metadata.get(idx)
+    }
+
+    fn norm(&self, idx: usize) -> Option<f32> {
+        let norms = self.norms.get_or_init(|| {
+            let kernel = Kernel::detect();
+            self.vectors()
+                .chunks(self.dimensions().max(1))
+                .map(|vector| kernel.norm_squared(vector).sqrt())
+                .collect()
+        });
+        norms.get(idx).copied()
+    }
+}
+
+/// A store opened through the segment `EmbeddingStore::seal` wrote: sealed vectors are
+/// served from the memory map, and only batches written since are read into memory.
+///
+/// Vectors of the segment that later batches replaced or deleted are skipped like
+/// tombstones. Positions run through the segment first, then the later batches.
+pub struct MappedStore {
+    segment: Option<Segment>,
+    /// Batches written after the segment was sealed
+    delta: VectorData,
+    /// Segment positions whose id a later batch upserted or deleted
+    replaced: HashSet<usize>,
+}
+
+impl MappedStore {
+    /// Map `store.seg` in `dir_path` and read the batches it does not cover; a store
+    /// that was never sealed is read whole
+    pub async fn open(dir_path: &str) -> Result<MappedStore> {
+        let path = Path::new(dir_path).join(STORE_SEGMENT_FILE_NAME);
+        let segment = if path.exists() {
+            Some(Segment::open(&path)?)
+        } else {
+            None
+        };
+        let from = segment.as_ref().map_or(0, |s| s.header().batches as usize);
+        let (stores, discarded) = EmbeddingStore::read_batches(dir_path, from).await?;
+
+        let touched: HashSet<&str> = stores
+            .iter()
+            .flat_map(|(_, store)| {
+                let upserted = store.items.iter().map(|item| item.id.as_str());
+                upserted.chain(store.deleted.iter().map(String::as_str))
+            })
+            .collect();
+        let replaced = match &segment {
+            Some(segment) if !touched.is_empty() => (0..VectorSource::len(segment))
+                .filter(|&idx| touched.contains(segment.id(idx)))
+                .collect(),
+            _ => HashSet::new(),
+        };
+
+        let mut delta = VectorData::default();
+        for (_, store) in stores {
+            delta.apply(store);
+        }
+        delta.discarded_log_bytes = discarded;
+        if let Some(segment) = &segment
+            && delta.dimensions != 0
+            && segment.dimensions() != 0
+            && delta.dimensions != segment.dimensions()
+        {
+            anyhow::bail!(
+                "Batches written after {:?} have {} dimensions, the segment has {}",
+                path,
+                delta.dimensions,
+                segment.dimensions()
+            );
+        }
+
+        Ok(MappedStore {
+            segment,
+            delta,
+            replaced,
+        })
+    }
+
+    pub fn segment(&self) -> Option<&Segment> {
+        self.segment.as_ref()
+    }
+
+    /// The batches written after the segment, loaded into memory
+    pub fn delta(&self) -> &VectorData {
+        &self.delta
+    }
+
+    /// Number of live vectors
+    pub fn total_vectors(&self) -> usize {
+        let sealed = self.sealed_len() - self.replaced.len();
+        sealed + self.delta.total_vectors
+    }
+
+    pub fn dimensions(&self) -> usize {
+        match &self.segment {
+            Some(segment) if segment.dimensions() != 0 => segment.dimensions(),
+            _ => self.delta.dimensions,
+        }
+    }
+
+    fn sealed_len(&self) -> usize {
+        self.segment.as_ref().map_or(0, VectorSource::len)
+    }
+}
+
+impl VectorSource for MappedStore {
+    fn len(&self) -> usize {
+        self.sealed_len() + self.delta.len()
+    }
+
+    fn vector(&self, idx: usize) -> &[f32] {
+        match &self.segment {
+            Some(segment) if idx < self.sealed_len() => segment.vector(idx),
+            _ => self.delta.vector(idx - self.sealed_len()),
+        }
+    }
+
+    fn chunk(&self, idx: usize) -> &str {
+        match &self.segment {
+            Some(segment) if idx < self.sealed_len() => segment.chunk(idx),
+            _ => self.delta.chunk(idx - self.sealed_len()),
+        }
+    }
+
+    fn id(&self, idx: usize) -> &str {
+        match &self.segment {
+            Some(segment) if idx < self.sealed_len() => segment.id(idx),
+            _ => self.delta.id(idx - self.sealed_len()),
+        }
+    }
+
+    fn metadata(&self, idx: usize) -> Option<&Metadata> {
+        match &self.segment {
+            Some(segment) if idx < self.sealed_len() => segment.metadata(idx),
+            _ => self.delta.metadata(idx - self.sealed_len()),
+        }
+    }
+
+    fn is_deleted(&self, idx: usize) -> bool {
+        if idx < self.sealed_len() {
+            self.replaced.contains(&idx)
+        } else {
+            self.delta.is_deleted(idx - self.sealed_len())
+        }
+    }
+
+    fn norm(&self, idx: usize) -> Option<f32> {
+        match &self.segment {
+            Some(segment) if idx < self.sealed_len() => segment.norm(idx),
+            _ => self.delta.norm(idx - self.sealed_len()),
+        }
+    }
+}
+
+/// Check that every section and string offset lies inside the file
+fn validate_layout(header: &SegmentHeader, bytes: &[u8]) -> Result<()> {
+    let count = header.count as usize;
+    let vectors_end = count
+        .checked_mul(header.dimensions as usize)
+        .and_then(|n| n.checked_mul(size_of::<f32>()))
+        .and_then(|n| n.checked_add(HEADER_LEN))
+        .context("Vector section overflows")?;
+    if vectors_end != header.chunks_offset as usize {
+        anyhow::bail!("Vector section does not end where chunks begin");
+    }
+
+    let ids_start = validate_strings(bytes, header.chunks_offset as usize, count, "chunk")?;
+    if ids_start != header.ids_offset as usize {
+        anyhow::bail!("Chunk section does not end where ids begin");
+    }
+    let metadata_start = validate_strings(bytes, ids_start, count, "id")?;
+    if metadata_start != header.metadata_offset as usize {
+        anyhow::bail!("Id section does not end where metadata begins");
+    }
+    let metadata_end = metadata_start
+        .checked_add(header.metadata_len as usize)
+        .context("Metadata section overflows")?;
+    if metadata_end > bytes.len() {
+        anyhow::bail!("Metadata section is truncated");
+    }
+    Ok(())
+}
+
+/// Validate one offset table and return the end of its string bytes
+fn validate_strings(bytes: &[u8], table: usize, count: usize, name: &str) -> Result<usize> {
+    let bytes_start = count
+        .checked_add(1)
+        .and_then(|n| n.checked_mul(size_of::<u64>()))
+        .and_then(|n| n.checked_add(table))
+        .with_context(|| format!("{} offset table overflows", name))?;
+    if bytes_start > bytes.len() {
+        anyhow::bail!("{} offset table is truncated", name);
+    }
+
+    let mut previous = 0;
+    for idx in 0..=count {
+        let offset = read_u64(bytes, table + idx * size_of::<u64>()) as usize;
+        let in_bounds = bytes_start
+            .checked_add(offset)
+            .is_some_and(|end| end <= bytes.len());
+        if offset < previous || !in_bounds {
+            anyhow::bail!("{} offset {} is out of bounds", name, idx);
+        }
+        previous = offset;
+    }
+    Ok(bytes_start + previous)
+}
+
+fn string_section_len(strings: &[&str]) -> u64 {
+    let bytes: usize = strings.iter().map(|s| s.len()).sum();
+    ((strings.len() + 1) * size_of::<u64>() + bytes) as u64
+}
+
+fn write_string_section(writer: &mut impl Write, strings: &[&str]) -> Result<()> {
+    let mut offset = 0u64;
+    writer.write_all(&offset.to_le_bytes())?;
+    for s in strings {
+        offset += s.len() as u64;
+        writer.write_all(&offset.to_le_bytes())?;
+    }
+    for s in strings {
+        writer.write_all(s.as_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+fn metric_to_byte(metric: Option<Metrics>) -> u8 {
+    match metric {
+        None => 0,
+        Some(Metrics::Cosine) => 1,
+        Some(Metrics::Euclidean) => 2,
+        Some(Metrics::DotProduct) => 3,
+    }
+}
+
+fn metric_from_byte(byte: u8) -> Result<Option<Metrics>> {
+    Ok(match byte {
+        0 => None,
+        1 => Some(Metrics::Cosine),
+        2 => Some(Metrics::Euclidean),
+        3 => Some(Metrics::DotProduct),
+        other => anyhow::bail!("Unknown metric hint {}", other),
+    })
+}
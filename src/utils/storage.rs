@@ -1,24 +1,276 @@
 use anyhow::{Context, Result};
-use rayon::iter::ParallelIterator;
-use rayon::prelude::{IntoParallelIterator, IntoParallelRefIterator};
+use rayon::iter::{IndexedParallelIterator, ParallelIterator};
+use rayon::prelude::IntoParallelRefIterator;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
 use std::path::Path;
 use tokio::fs;
-use tokio::fs::File;
-use tokio::io::{AsyncWriteExt, BufWriter};
 use tokio::task::spawn_blocking;
 
-use crate::utils::EmbeddingData;
+use crate::core::{Kernel, VectorSource};
+use crate::utils::wal::{self, WAL_CHECKPOINT_BYTES, Wal};
+use crate::utils::{EmbeddingData, Metadata, STORE_SEGMENT_FILE_NAME, Segment};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct VectorData {
     pub chunk: Vec<String>,
     pub embedding: Vec<Vec<f32>>,
     pub dimensions: usize,
+    /// Number of live (non-deleted) vectors
     pub total_vectors: usize,
+    #[serde(default)]
+    pub ids: Vec<String>,
+    #[serde(default)]
+    pub metadata: Vec<Metadata>,
+    /// Positions of replaced or deleted vectors, skipped by search until compaction
+    #[serde(default)]
+    pub deleted: HashSet<usize>,
+    /// Cached Euclidean norm of each vector, used by cosine search
+    #[serde(default)]
+    pub norms: Vec<f32>,
+    /// Batch index the next upsert or delete is persisted under
+    #[serde(default)]
+    pub next_batch_index: usize,
+    /// Embedding model of the stored vectors; empty until a batch names one
+    #[serde(default)]
+    pub model: String,
+    /// Bytes at the end of the write-ahead log that were not a complete record when the
+    /// store was loaded, see `WalReplay::discarded`
+    #[serde(skip)]
+    pub discarded_log_bytes: u64,
 }
 
+/// A batch that does not fit the store it is inserted into or loaded with
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConsistencyError {
+    /// A vector's length differs from the dimension of its batch or store
+    Dimensions {
+        id: String,
+        expected: usize,
+        found: usize,
+    },
+    /// A batch was embedded with a different model than the store
+    Model { expected: String, found: String },
+    /// Batches on disk disagree with the first batch of the store
+    Files {
+        expected_dimensions: usize,
+        expected_model: String,
+        files: Vec<InconsistentFile>,
+    },
+}
+
+/// A persisted batch whose header or vectors disagree with the rest of the store
+#[derive(Debug, Clone, PartialEq)]
+pub struct InconsistentFile {
+    /// `.bin` path, or the write-ahead log for batches not yet checkpointed
+    pub source: String,
+    pub batch_index: usize,
+    pub dimensions: usize,
+    pub model: String,
+}
+
+impl std::fmt::Display for ConsistencyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConsistencyError::Dimensions {
+                id,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Vector {:?} has {} dimensions, expected {}",
+                id, found, expected
+            ),
+            ConsistencyError::Model { expected, found } => write!(
+                f,
+                "Batch was embedded with {}, the store uses {}",
+                found, expected
+            ),
+            ConsistencyError::Files {
+                expected_dimensions,
+                expected_model,
+                files,
+            } => {
+                write!(
+                    f,
+                    "{} batch files do not match the store ({} dimensions, model {:?}):",
+                    files.len(),
+                    expected_dimensions,
+                    expected_model
+                )?;
+                for file in files {
+                    write!(
+                        f,
+                        "\n  {} (batch {}): {} dimensions, model {:?}",
+                        file.source, file.batch_index, file.dimensions, file.model
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConsistencyError {}
+
 impl VectorData {
+    /// Get the persistent id of the vector at index
+    pub fn get_id(&self, index: usize) -> Option<&str> {
+        self.ids.get(index).map(|s| s.as_str())
+    }
+
+    /// Get the metadata of the vector at index
+    pub fn get_metadata(&self, index: usize) -> Option<&Metadata> {
+        self.metadata.get(index)
+    }
+
+    /// Find the position of a live vector by id
+    pub fn position(&self, id: &str) -> Option<usize> {
+        self.ids
+            .par_iter()
+            .enumerate()
+            .find_any(|(idx, v)| v.as_str() == id && !self.deleted.contains(idx))
+            .map(|(idx, _)| idx)
+    }
+
+    /// Check that `store` can be applied: its vectors match its header, the store's
+    /// dimension and, when both are known, the store's model
+    pub fn check(&self, store: &EmbeddingStore) -> Result<(), ConsistencyError> {
+        store.check()?;
+        if store.items.is_empty() {
+            return Ok(());
+        }
+        if self.dimensions != 0 && store.dimensions != self.dimensions {
+            return Err(ConsistencyError::Dimensions {
+                id: store.items[0].id.clone(),
+                expected: self.dimensions,
+                found: store.dimensions,
+            });
+        }
+        if !self.model.is_empty() && !store.model.is_empty() && store.model != self.model {
+            return Err(ConsistencyError::Model {
+                expected: self.model.clone(),
+                found: store.model.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Whether the vector at index has been replaced or deleted
+    pub fn is_deleted(&self, index: usize) -> bool {
+        self.deleted.contains(&index)
+    }
+
+    /// Apply a batch on top of the current data: its deletions first, then its items.
+    ///
+    /// Replaced and deleted vectors are tombstoned rather than removed, so positions
+    /// (and any index built over them) stay valid.
+    pub fn apply(&mut self, store: EmbeddingStore) {
+        let mut positions = self.live_positions();
+        self.apply_with(store, &mut positions);
+    }
+
+    /// Drop tombstoned vectors, renumbering the remaining positions.
+    ///
+    /// Only affects this copy: loads replay every batch and keep tombstones. Indexes
+    /// built over the uncompacted positions must be rebuilt on the compacted data.
+    pub fn compact(&mut self) {
+        if self.deleted.is_empty() {
+            return;
+        }
+
+        let deleted = std::mem::take(&mut self.deleted);
+        let keep = |idx: &usize| !deleted.contains(idx);
+
+        self.chunk = std::mem::take(&mut self.chunk)
+            .into_iter()
+            .enumerate()
+            .filter(|(idx, _)| keep(idx))
+            .map(|(_, v)| v)
+            .collect();
+        self.embedding = std::mem::take(&mut self.embedding)
+            .into_iter()
+            .enumerate()
+            .filter(|(idx, _)| keep(idx))
+            .map(|(_, v)| v)
+            .collect();
+        self.ids = std::mem::take(&mut self.ids)
+            .into_iter()
+            .enumerate()
+            .filter(|(idx, _)| keep(idx))
+            .map(|(_, v)| v)
+            .collect();
+        self.metadata = std::mem::take(&mut self.metadata)
+            .into_iter()
+            .enumerate()
+            .filter(|(idx, _)| keep(idx))
+            .map(|(_, v)| v)
+            .collect();
+        self.norms = std::mem::take(&mut self.norms)
+            .into_iter()
+            .enumerate()
+            .filter(|(idx, _)| keep(idx))
+            .map(|(_, v)| v)
+            .collect();
+        self.total_vectors = self.embedding.len();
+    }
+
+    fn live_positions(&self) -> HashMap<String, usize> {
+        self.ids
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| !self.deleted.contains(idx))
+            .map(|(idx, id)| (id.clone(), idx))
+            .collect()
+    }
+
+    fn apply_with(&mut self, store: EmbeddingStore, positions: &mut HashMap<String, usize>) {
+        self.cache_norms();
+        for id in &store.deleted {
+            if let Some(idx) = positions.remove(id) {
+                self.deleted.insert(idx);
+                self.total_vectors -= 1;
+            }
+        }
+
+        for item in store.items {
+            if let Some(idx) = positions.remove(&item.id) {
+                self.deleted.insert(idx);
+                self.total_vectors -= 1;
+            }
+            if self.dimensions == 0 {
+                self.dimensions = item.embedding.len();
+            }
+
+            positions.insert(item.id.clone(), self.embedding.len());
+            self.ids.push(item.id);
+            self.metadata.push(item.metadata);
+            self.chunk.push(item.chunk);
+            self.norms
+                .push(Kernel::detect().norm_squared(&item.embedding).sqrt());
+            self.embedding.push(item.embedding);
+            self.total_vectors += 1;
+        }
+
+        if self.model.is_empty() {
+            self.model = store.model;
+        }
+        self.next_batch_index = self.next_batch_index.max(store.batch_index + 1);
+    }
+
+    /// Compute norms for vectors added without one, e.g. when `embedding` was filled by hand
+    pub fn cache_norms(&mut self) {
+        if self.norms.len() > self.embedding.len() {
+            self.norms.truncate(self.embedding.len());
+        }
+        let missing: Vec<f32> = self.embedding[self.norms.len()..]
+            .par_iter()
+            .map(|vector| Kernel::detect().norm_squared(vector).sqrt())
+            .collect();
+        self.norms.extend(missing);
+    }
+
     /// Get a specific vector by index
     pub fn get_vector(&self, index: usize) -> Option<&[f32]> {
         self.embedding.get(index).map(|v| v.as_slice())
@@ -45,22 +297,206 @@ impl VectorData {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+impl VectorSource for VectorData {
+    fn len(&self) -> usize {
+        self.embedding.len()
+    }
+
+    fn vector(&self, idx: usize) -> &[f32] {
+        &self.embedding[idx]
+    }
+
+    fn chunk(&self, idx: usize) -> &str {
+        &self.chunk[idx]
+    }
+
+    fn id(&self, idx: usize) -> &str {
+        self.get_id(idx).unwrap_or_default()
+    }
+
+    fn metadata(&self, idx: usize) -> Option<&Metadata> {
+        self.get_metadata(idx)
+    }
+
+    fn is_deleted(&self, idx: usize) -> bool {
+        self.deleted.contains(&idx)
+    }
+
+    fn norm(&self, idx: usize) -> Option<f32> {
+        self.norms.get(idx).copied()
+    }
+}
+
+/// Bytes every `.bin` batch starts with, followed by `BATCH_VERSION` as a little-endian u32
+pub const BATCH_MAGIC: [u8; 4] = *b"BLZB";
+
+/// Layout version of `.bin` batches written by this build
+pub const BATCH_VERSION: u32 = 1;
+
+/// Item of a batch written before batch files had a header
+#[derive(Deserialize)]
+struct LegacyItem {
+    index: usize,
+    chunk: String,
+    embedding: Vec<f32>,
+    dimensions: usize,
+}
+
+/// Batch written before batch files had a header: no ids, metadata, deletions or model
+#[derive(Deserialize)]
+struct LegacyStore {
+    batch_index: usize,
+    items: Vec<LegacyItem>,
+}
+
+impl From<LegacyStore> for EmbeddingStore {
+    fn from(legacy: LegacyStore) -> Self {
+        let items = legacy
+            .items
+            .into_iter()
+            .map(|item| EmbeddingData {
+                index: item.index,
+                chunk: item.chunk,
+                embedding: item.embedding,
+                dimensions: item.dimensions,
+                ..Default::default()
+            })
+            .collect();
+        Self::new(legacy.batch_index, items)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct EmbeddingStore {
     pub batch_index: usize,
     pub items: Vec<EmbeddingData>,
+    /// Ids deleted by this batch; later batches win over earlier ones
+    #[serde(default)]
+    pub deleted: Vec<String>,
+    /// Length of every vector in the batch, 0 for a batch that only deletes
+    #[serde(default)]
+    pub dimensions: usize,
+    /// Embedding model the vectors came from; empty when unknown
+    #[serde(default)]
+    pub model: String,
 }
 
 impl EmbeddingStore {
-    pub fn new(batch_index: usize, items: Vec<EmbeddingData>) -> Self {
-        Self { batch_index, items }
+    pub fn new(batch_index: usize, mut items: Vec<EmbeddingData>) -> Self {
+        // Items without an explicit id get one derived from their batch and position
+        items
+            .iter_mut()
+            .filter(|item| item.id.is_empty())
+            .for_each(|item| item.id = format!("{}-{}", batch_index, item.index));
+
+        Self {
+            batch_index,
+            dimensions: items.first().map_or(0, |item| item.embedding.len()),
+            items,
+            deleted: Vec::new(),
+            model: String::new(),
+        }
+    }
+
+    /// Record the embedding model of this batch in its header
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    /// Check that every vector matches the dimension in the header
+    pub fn check(&self) -> Result<(), ConsistencyError> {
+        match self.mismatched_item() {
+            Some(item) => Err(ConsistencyError::Dimensions {
+                id: item.id.clone(),
+                expected: self.dimensions,
+                found: item.embedding.len(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn mismatched_item(&self) -> Option<&EmbeddingData> {
+        self.items
+            .iter()
+            .find(|item| item.embedding.len() != self.dimensions)
+    }
+
+    /// A batch that only deletes the given ids
+    pub fn deletion(batch_index: usize, ids: Vec<String>) -> Self {
+        Self {
+            batch_index,
+            items: Vec::new(),
+            deleted: ids,
+            dimensions: 0,
+            model: String::new(),
+        }
+    }
+
+    /// Persist `items` as a new batch in `dir_path`, replacing vectors with the same ids.
+    ///
+    /// The batch is durable once this returns: it is fsynced to the write-ahead log and
+    /// folded into a `.bin` file when the log reaches `WAL_CHECKPOINT_BYTES`. Items whose
+    /// dimension differs from the store fail with a `ConsistencyError`.
+    pub async fn upsert(
+        dir_path: &str,
+        data: &mut VectorData,
+        items: Vec<EmbeddingData>,
+    ) -> Result<()> {
+        Self::upsert_with_model(dir_path, data, "", items).await
+    }
+
+    /// Like `upsert`, recording `model` in the batch header; fails with a
+    /// `ConsistencyError` when the store was embedded with another model
+    pub async fn upsert_with_model(
+        dir_path: &str,
+        data: &mut VectorData,
+        model: &str,
+        items: Vec<EmbeddingData>,
+    ) -> Result<()> {
+        let mut store = Self::new(data.next_batch_index, items).with_model(model);
+        data.check(&store)?;
+        Self::log(dir_path, &mut store).await?;
+        data.apply(store);
+        Ok(())
+    }
+
+    /// Persist a deletion of `ids` in `dir_path`, returning how many live vectors were removed
+    pub async fn delete(dir_path: &str, data: &mut VectorData, ids: Vec<String>) -> Result<usize> {
+        let before = data.total_vectors;
+        let mut store = Self::deletion(data.next_batch_index, ids);
+        Self::log(dir_path, &mut store).await?;
+        data.apply(store);
+        Ok(before - data.total_vectors)
+    }
+
+    /// Append `store` to the write-ahead log under a freshly allocated batch index,
+    /// checkpointing when the log grows large
+    async fn log(dir_path: &str, store: &mut EmbeddingStore) -> Result<()> {
+        fs::create_dir_all(dir_path)
+            .await
+            .with_context(|| format!("Failed to create directory: {:?}", dir_path))?;
+        if Wal::append(dir_path, store).await? >= WAL_CHECKPOINT_BYTES {
+            Wal::checkpoint(dir_path).await?;
+        }
+        Ok(())
+    }
+
+    /// Write this batch as `embeddings_batch_<batch_index>.bin` inside `dir_path`
+    pub async fn write_batch(&self, dir_path: &str) -> Result<()> {
+        fs::create_dir_all(dir_path)
+            .await
+            .with_context(|| format!("Failed to create directory: {:?}", dir_path))?;
+        let file_path = Path::new(dir_path).join(format!("embeddings_batch_{}", self.batch_index));
+        self.write_binary(&file_path.to_string_lossy()).await
     }
 
     pub fn debug_print(&self) {
         println!("Batch Index: {}", self.batch_index);
         self.items.iter().take(3).for_each(|item| {
             println!(
-                "Index: {:?}\n Chunk: {:?}\n Embeddings (first 3): {:?}\n Embedding Length : {:?}\n",
+                "Id: {:?}\n Index: {:?}\n Chunk: {:?}\n Embeddings (first 3): {:?}\n Embedding Length : {:?}\n",
+                &item.id,
                 &item.index,
                 &item.chunk,
                 &item.embedding[..3],
@@ -69,74 +505,152 @@ impl EmbeddingStore {
         });
     }
 
-    /// Load multiple binary files from a directory
+    /// Open a store directory for writing, creating it and starting empty when it holds
+    /// no batches.
+    ///
+    /// Temporary files from interrupted writes are removed, a torn tail of the
+    /// write-ahead log is truncated and the log is replayed on top of the `.bin`
+    /// batches. `discarded_log_bytes` holds how much of the log was truncated.
+    pub async fn open(dir_path: &str) -> Result<VectorData> {
+        fs::create_dir_all(dir_path)
+            .await
+            .with_context(|| format!("Failed to create directory: {:?}", dir_path))?;
+        let dir = Path::new(dir_path).to_path_buf();
+        spawn_blocking(move || wal::remove_temp_files(&dir)).await??;
+        let discarded = Wal::recover(dir_path).await?;
+
+        let mut stored = fs::metadata(Wal::path(dir_path))
+            .await
+            .map(|meta| meta.len() > 0)
+            .unwrap_or(false);
+        let mut read_dir = fs::read_dir(dir_path).await?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            if entry.path().extension().and_then(|ext| ext.to_str()) == Some("bin") {
+                stored = true;
+                break;
+            }
+        }
+
+        let mut data = if stored {
+            Self::read_binary(dir_path).await?
+        } else {
+            VectorData::default()
+        };
+        data.discarded_log_bytes = discarded;
+        Ok(data)
+    }
+
+    /// Write the live vectors of `dir_path` into its `store.seg`, which
+    /// `MappedStore::open` maps instead of reading the batches it covers; returns how
+    /// many vectors were sealed. The batches stay in place, so other readers and writers
+    /// are unaffected, and batches written later are read on top of the segment.
+    pub async fn seal(dir_path: &str) -> Result<usize> {
+        let data = Self::read_binary(dir_path).await?;
+        let sealed = data.total_vectors;
+        let path = Path::new(dir_path).join(STORE_SEGMENT_FILE_NAME);
+        spawn_blocking(move || Segment::write(&path, &data, None)).await??;
+        Ok(sealed)
+    }
+
+    /// Load multiple binary files from a directory, followed by the write-ahead log.
+    ///
+    /// Fails if any batch cannot be read: batches are written atomically, so a broken
+    /// `.bin` file means real corruption rather than an interrupted write. The store is
+    /// only read, so this is safe while another process writes to it; an incomplete
+    /// record at the end of the log is skipped and counted in `discarded_log_bytes`.
+    /// Writers open the store with `open` instead.
     pub async fn read_binary(dir_path: &str) -> Result<VectorData> {
-        // Read directory to get all .bin files
+        let (stores, discarded) = Self::read_batches(dir_path, 0).await?;
+
+        // Replaced and deleted vectors stay as tombstones: compacting here would renumber
+        // positions under indexes saved by an earlier load
+        let mut vector_data = VectorData::default();
+        let mut positions = HashMap::new();
+        for (_, store) in stores {
+            vector_data.apply_with(store, &mut positions);
+        }
+        vector_data.discarded_log_bytes = discarded;
+
+        Ok(vector_data)
+    }
+
+    /// Every batch of `dir_path` with a batch index of at least `from`, from `.bin` files
+    /// and the write-ahead log, in replay order and paired with the file it came from.
+    /// Also returns the bytes skipped at the end of the log. `.bin` files of earlier
+    /// batches are not read.
+    pub(crate) async fn read_batches(
+        dir_path: &str,
+        from: usize,
+    ) -> Result<(Vec<(String, EmbeddingStore)>, u64)> {
         let mut read_dir = fs::read_dir(dir_path)
             .await
             .with_context(|| format!("Failed to read directory: {:?}", dir_path))?;
 
+        let mut found = false;
         let mut bin_files = Vec::new();
         while let Some(entry) = read_dir.next_entry().await? {
             let path = entry.path();
-            if path
-                .extension()
-                .and_then(|ext| ext.to_str())
-                .map(|ext| ext == "bin")
-                .unwrap_or(false)
-            {
+            if path.extension().and_then(|ext| ext.to_str()) != Some("bin") {
+                continue;
+            }
+            found = true;
+            let index = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(wal::batch_file_index);
+            if index.is_none_or(|index| index >= from) {
                 bin_files.push(path);
             }
         }
 
-        if bin_files.is_empty() {
+        let logged = Wal::replay(dir_path).await?;
+        if !found && logged.stores.is_empty() {
             anyhow::bail!("No .bin files found in {:?}", dir_path);
         }
 
-        println!("Found {} binary files to load...", bin_files.len());
-
         // Load all files concurrently using tokio tasks
         let mut tasks = Vec::new();
         for path in bin_files {
-            let task = tokio::spawn(async move {
-                match Self::read_binary_file(&path).await {
-                    Ok(store) => Some(store),
-                    Err(e) => {
-                        eprintln!("Failed to load {:?}: {}", path, e);
-                        None
-                    }
-                }
-            });
-            tasks.push(task);
+            tasks.push(tokio::spawn(async move {
+                let store = Self::read_binary_file(&path).await?;
+                Ok::<_, anyhow::Error>((path.to_string_lossy().to_string(), store))
+            }));
         }
 
         // Await all tasks and collect results
         let mut stores = Vec::new();
         for task in tasks {
-            if let Ok(Some(store)) = task.await {
-                stores.push(store);
-            }
+            stores.push(task.await??);
         }
 
-        // Flatten all items from all stores in parallel
-        let (all_chunks, all_embeddings): (Vec<String>, Vec<Vec<f32>>) = stores
-            .into_par_iter()
-            .flat_map(|store| store.items)
-            .map(|item| (item.chunk, item.embedding))
-            .unzip();
+        // A logged batch is skipped only when its file holds the very same batch: a
+        // checkpoint wrote it but stopped before emptying the log
+        let on_disk: HashMap<usize, &EmbeddingStore> = stores
+            .iter()
+            .map(|(_, store)| (store.batch_index, store))
+            .collect();
+        let log_path = Wal::path(dir_path).to_string_lossy().to_string();
+        let pending: Vec<(String, EmbeddingStore)> = logged
+            .stores
+            .into_iter()
+            .filter(|store| on_disk.get(&store.batch_index) != Some(&store))
+            .map(|store| (log_path.clone(), store))
+            .collect();
+        stores.extend(pending);
+        stores.retain(|(_, store)| store.batch_index >= from);
 
-        let dimensions = all_embeddings.first().map(|v| v.len()).unwrap_or(0);
-        let total_vectors = all_embeddings.len();
+        // Replay batches in order so later upserts and deletes win, and so vector
+        // positions stay stable across loads for persisted indexes
+        stores.sort_by_key(|(_, store)| store.batch_index);
+        check_batches(&stores)?;
 
-        Ok(VectorData {
-            chunk: all_chunks,
-            embedding: all_embeddings,
-            dimensions,
-            total_vectors,
-        })
+        Ok((stores, logged.discarded))
     }
 
-    /// Load from a single binary file
+    /// Load from a single binary file.
+    ///
+    /// Files without the `BATCH_MAGIC` header were written before batches had ids and
+    /// are read in that layout, with ids derived from their batch and position.
     pub async fn read_binary_file(path: &Path) -> Result<EmbeddingStore> {
         let path_clone = path.to_path_buf();
         let bytes = fs::read(&path_clone)
@@ -144,26 +658,80 @@ impl EmbeddingStore {
             .with_context(|| format!("Failed to read file: {:?}", path_clone))?;
 
         let path_for_error = path_clone.clone();
-        let store: EmbeddingStore = spawn_blocking(move || bincode::deserialize(&bytes))
+        let store: EmbeddingStore = spawn_blocking(move || Self::decode(&bytes))
             .await?
             .with_context(|| format!("Failed to deserialize: {:?}", path_for_error))?;
 
         Ok(store)
     }
 
-    /// Write the embedding store to a binary file
-    pub async fn write_binary(&self, file_path: &str) -> Result<()> {
-        let encoded = {
-            let self_clone = self.clone();
-            spawn_blocking(move || bincode::serialize(&self_clone)).await??
+    /// Decode the bytes of a `.bin` batch, headed or legacy
+    fn decode(bytes: &[u8]) -> Result<EmbeddingStore> {
+        let Some(body) = bytes.strip_prefix(&BATCH_MAGIC) else {
+            let legacy: LegacyStore = bincode::deserialize(bytes)?;
+            return Ok(legacy.into());
         };
+        let (version, body) = body
+            .split_first_chunk::<4>()
+            .context("Batch header is truncated")?;
+        match u32::from_le_bytes(*version) {
+            BATCH_VERSION => Ok(bincode::deserialize(body)?),
+            version => anyhow::bail!(
+                "Batch format version {} is newer than this build supports ({})",
+                version,
+                BATCH_VERSION
+            ),
+        }
+    }
 
+    /// Write the embedding store to a binary file, behind the `BATCH_MAGIC` header.
+    ///
+    /// The bytes go to a temporary file that is fsynced and renamed into place, so a
+    /// crash never leaves a truncated `.bin` behind.
+    pub async fn write_binary(&self, file_path: &str) -> Result<()> {
+        let self_clone = self.clone();
         let formatted_path = format!("{}.bin", file_path);
-        let file = File::create(formatted_path).await?;
-        let mut writer = BufWriter::with_capacity(1024 * 1024, file);
-        writer.write_all(&encoded).await?;
-        writer.flush().await?;
+        spawn_blocking(move || {
+            let encoded = bincode::serialize(&self_clone)?;
+            wal::write_atomic(Path::new(&formatted_path), |writer| {
+                writer.write_all(&BATCH_MAGIC)?;
+                writer.write_all(&BATCH_VERSION.to_le_bytes())?;
+                Ok(writer.write_all(&encoded)?)
+            })
+        })
+        .await?
+    }
+}
 
-        Ok(())
+/// Compare every batch with the first one that holds vectors, collecting each file whose
+/// dimension or model differs, or whose vectors disagree with its own header
+fn check_batches(stores: &[(String, EmbeddingStore)]) -> Result<(), ConsistencyError> {
+    let mut expected: Option<(usize, String)> = None;
+    let mut files = Vec::new();
+    for (source, store) in stores.iter().filter(|(_, store)| !store.items.is_empty()) {
+        let (dimensions, model) = expected.get_or_insert_with(|| (store.dimensions, String::new()));
+        if model.is_empty() {
+            model.clone_from(&store.model);
+        }
+        let model_differs = !store.model.is_empty() && store.model != *model;
+        if store.dimensions != *dimensions || model_differs || store.mismatched_item().is_some() {
+            files.push(InconsistentFile {
+                source: source.clone(),
+                batch_index: store.batch_index,
+                dimensions: store
+                    .mismatched_item()
+                    .map_or(store.dimensions, |item| item.embedding.len()),
+                model: store.model.clone(),
+            });
+        }
+    }
+
+    match (files.is_empty(), expected) {
+        (false, Some((expected_dimensions, expected_model))) => Err(ConsistencyError::Files {
+            expected_dimensions,
+            expected_model,
+            files,
+        }),
+        _ => Ok(()),
     }
 }
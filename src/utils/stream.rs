@@ -0,0 +1,177 @@
+use crate::utils::{Chunk, Chunker, Dedup, Deduplicator, Format, Reader};
+use anyhow::{Context, Result};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use tokio::sync::mpsc;
+
+/// Bytes `ChunkBatches` reads before looking for the end of a block
+pub const STREAM_BLOCK_BYTES: usize = 1 << 20;
+/// Kept chunks a streamed file is deduplicated against, so memory does not grow with
+/// the file: a few MiB of hashes, or up to 100 MiB of MinHash signatures
+pub const STREAM_DEDUP_WINDOW: usize = 1 << 16;
+
+/// Batches of chunks read from a file one block at a time, for files too large to read
+/// whole.
+///
+/// Text is read in blocks of about `STREAM_BLOCK_BYTES` ending at a blank line, so
+/// paragraphs stay whole unless a block grows to twice the size, and every block is cut
+/// with the chunker; chunk offsets are byte offsets in the file. JSONL is read a block of
+/// lines at a time. Only one block and the chunks not yet batched are held in memory.
+/// The chunker sees one block at a time, so a markdown heading trail does not carry
+/// over into the next block; duplicates are found among the last `STREAM_DEDUP_WINDOW`
+/// chunks kept, see `Deduplicator` for how policies apply across blocks.
+#[derive(Debug)]
+pub struct ChunkBatches {
+    source: BufReader<File>,
+    format: Format,
+    chunker: Chunker,
+    reader: Reader,
+    deduplicator: Deduplicator,
+    batch_size: usize,
+    block_bytes: usize,
+    /// Byte offset and line number where the next block starts
+    offset: usize,
+    line: usize,
+    pending: VecDeque<Chunk>,
+    done: bool,
+}
+
+impl ChunkBatches {
+    /// Stream `path`, which must be a text or JSONL file
+    pub fn open(path: &Path, batch_size: usize, chunker: Chunker, reader: Reader) -> Result<Self> {
+        let format = reader.format_of(path);
+        if !matches!(format, Format::Text | Format::Jsonl) {
+            anyhow::bail!(
+                "Only text and JSONL files can be streamed, {:?} is read as {:?}",
+                path,
+                format
+            );
+        }
+        let file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+        Ok(Self {
+            source: BufReader::new(file),
+            format,
+            chunker,
+            reader,
+            deduplicator: Deduplicator::new(Dedup::default()),
+            batch_size: batch_size.max(1),
+            block_bytes: STREAM_BLOCK_BYTES,
+            offset: 0,
+            line: 0,
+            pending: VecDeque::new(),
+            done: false,
+        })
+    }
+
+    pub fn with_dedup(mut self, dedup: Dedup) -> Self {
+        self.deduplicator = Deduplicator::new(dedup).with_window(STREAM_DEDUP_WINDOW);
+        self
+    }
+
+    pub fn with_block_bytes(mut self, bytes: usize) -> Self {
+        self.block_bytes = bytes.max(1);
+        self
+    }
+
+    /// Read the next block and queue its chunks; false at the end of the file
+    fn read_block(&mut self) -> Result<bool> {
+        let mut block = Vec::new();
+        let mut lines = 0;
+        loop {
+            let read = self.source.read_until(b'\n', &mut block)?;
+            if read == 0 {
+                break;
+            }
+            lines += 1;
+            let blank = block[block.len() - read..]
+                .iter()
+                .all(u8::is_ascii_whitespace);
+            let boundary = blank || self.format == Format::Jsonl;
+            if block.len() >= 2 * self.block_bytes || (block.len() >= self.block_bytes && boundary)
+            {
+                break;
+            }
+        }
+        if block.is_empty() {
+            return Ok(false);
+        }
+
+        let text = String::from_utf8_lossy(&block);
+        let mut chunks = Vec::new();
+        if self.format == Format::Jsonl {
+            for document in self.reader.jsonl(&text, self.line)? {
+                for mut chunk in self.chunker.chunk(&document.text) {
+                    chunk.metadata = document.metadata.clone();
+                    chunks.push(chunk);
+                }
+            }
+        } else {
+            for mut chunk in self.chunker.chunk(&text) {
+                chunk.start += self.offset;
+                chunk.end += self.offset;
+                chunks.push(chunk);
+            }
+        }
+        self.pending.extend(self.deduplicator.dedup(chunks));
+        self.offset += block.len();
+        self.line += lines;
+        Ok(true)
+    }
+}
+
+impl Iterator for ChunkBatches {
+    type Item = Result<Vec<Chunk>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done && self.pending.len() < self.batch_size {
+            match self.read_block() {
+                Ok(more) => self.done = !more,
+                Err(e) => {
+                    // Nothing follows an error, so a consumer cannot skip past it
+                    self.done = true;
+                    self.pending.clear();
+                    return Some(Err(e));
+                }
+            }
+        }
+        if self.pending.is_empty() {
+            return None;
+        }
+        let size = self.batch_size.min(self.pending.len());
+        Some(Ok(self.pending.drain(..size).collect()))
+    }
+}
+
+/// `ChunkBatches` read on a blocking thread, ahead of the consumer.
+///
+/// At most `buffer` batches wait in the channel. When the consumer falls behind, e.g. a
+/// pipeline waiting on its provider, the reading thread blocks instead of reading
+/// further, so a slow provider holds back the file rather than filling memory.
+#[derive(Debug)]
+pub struct BatchStream {
+    receiver: mpsc::Receiver<Result<Vec<Chunk>>>,
+}
+
+impl BatchStream {
+    /// Start reading `batches`; must be called within a Tokio runtime
+    pub fn spawn(batches: ChunkBatches, buffer: usize) -> Self {
+        let (sender, receiver) = mpsc::channel(buffer.max(1));
+        tokio::task::spawn_blocking(move || {
+            for batch in batches {
+                let failed = batch.is_err();
+                // A dropped stream stops the reader
+                if sender.blocking_send(batch).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        Self { receiver }
+    }
+
+    /// The next batch, or `None` once the file is read
+    pub async fn next(&mut self) -> Option<Result<Vec<Chunk>>> {
+        self.receiver.recv().await
+    }
+}
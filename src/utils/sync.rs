@@ -0,0 +1,217 @@
+use crate::utils::wal::{self, write_atomic};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tokio::fs;
+
+/// State of one source file when it was last embedded
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileEntry {
+    /// FNV-1a hash of the file content
+    pub hash: u64,
+    pub size: u64,
+    /// Modification time in nanoseconds since the Unix epoch
+    pub modified: u64,
+    /// Hash of the model, batch size, chunker and reader the file was embedded with
+    pub settings: u64,
+}
+
+impl FileEntry {
+    /// Size and modification time of `path`, without reading it
+    pub fn stat(path: &Path) -> Result<(u64, u64)> {
+        let metadata =
+            std::fs::metadata(path).with_context(|| format!("Failed to stat {:?}", path))?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |since| since.as_nanos() as u64);
+        Ok((metadata.len(), modified))
+    }
+}
+
+/// A file of the source tree and its current entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceFile {
+    /// Path relative to the source directory, `/`-separated, also the `source` metadata
+    /// of its vectors
+    pub key: String,
+    pub path: PathBuf,
+    pub entry: FileEntry,
+    /// Whether the manifest already lists the file
+    pub known: bool,
+}
+
+/// Difference between a source tree and its manifest
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileChanges {
+    /// New files, files whose content changed and files embedded with other settings
+    pub changed: Vec<SourceFile>,
+    /// Files with the content and settings of the manifest; a touched file keeps its
+    /// vectors and only gets its new modification time recorded
+    pub unchanged: Vec<SourceFile>,
+    /// Keys of files in the manifest that are gone
+    pub removed: Vec<String>,
+}
+
+/// Files of one source directory embedded into a store, used by `Pipeline::sync` to
+/// re-embed only what changed since the last run.
+///
+/// Saved as `sync_<hash>.manifest` in the store, keyed by the canonical source path,
+/// after every file so an interrupted sync picks up where it stopped.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct SyncManifest {
+    pub source: String,
+    pub files: BTreeMap<String, FileEntry>,
+}
+
+impl SyncManifest {
+    /// Manifest file of `source` inside `dir_path`
+    pub fn path(dir_path: &str, source: &str) -> PathBuf {
+        Path::new(dir_path).join(format!(
+            "sync_{:016x}.manifest",
+            wal::checksum(source.as_bytes())
+        ))
+    }
+
+    /// Load the manifest of `source`, or an empty one when it was never synced
+    pub async fn load(dir_path: &str, source: &str) -> Result<SyncManifest> {
+        let path = Self::path(dir_path, source);
+        let bytes = match fs::read(&path).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(SyncManifest {
+                    source: source.to_string(),
+                    files: BTreeMap::new(),
+                });
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read manifest: {:?}", path));
+            }
+        };
+        serde_json::from_slice(&bytes)
+            .with_context(|| format!("Failed to parse manifest: {:?}", path))
+    }
+
+    pub async fn save(&self, dir_path: &str) -> Result<()> {
+        fs::create_dir_all(dir_path)
+            .await
+            .with_context(|| format!("Failed to create directory: {:?}", dir_path))?;
+        let path = Self::path(dir_path, &self.source);
+        let encoded = serde_json::to_vec_pretty(self)?;
+        tokio::task::spawn_blocking(move || {
+            write_atomic(&path, |writer| Ok(writer.write_all(&encoded)?))
+                .with_context(|| format!("Failed to write manifest: {:?}", path))
+        })
+        .await?
+    }
+
+    /// Compare `files` of the directory or file `source` on disk with the manifest.
+    ///
+    /// A file whose size and modification time match its entry is not read; any other
+    /// file is hashed, so a touched but identical file is not embedded again.
+    pub fn changes(&self, source: &Path, files: &[PathBuf], settings: u64) -> Result<FileChanges> {
+        let base = match source.parent() {
+            Some(parent) if source.is_file() => parent,
+            _ => source,
+        };
+        let mut changes = FileChanges::default();
+        for path in files {
+            let key = path
+                .strip_prefix(base)
+                .unwrap_or(path)
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let (size, modified) = FileEntry::stat(path)?;
+            let known = self.files.get(&key).copied();
+
+            let hash = match known {
+                Some(entry) if entry.size == size && entry.modified == modified => entry.hash,
+                _ => {
+                    let bytes = std::fs::read(path)
+                        .with_context(|| format!("Failed to read {:?}", path))?;
+                    wal::checksum(&bytes)
+                }
+            };
+            let file = SourceFile {
+                key,
+                path: path.clone(),
+                entry: FileEntry {
+                    hash,
+                    size,
+                    modified,
+                    settings,
+                },
+                known: known.is_some(),
+            };
+            if known.is_some_and(|entry| entry.hash == hash && entry.settings == settings) {
+                changes.unchanged.push(file);
+            } else {
+                changes.changed.push(file);
+            }
+        }
+
+        let present: HashSet<&str> = changes
+            .changed
+            .iter()
+            .chain(&changes.unchanged)
+            .map(|file| file.key.as_str())
+            .collect();
+        changes.removed = self
+            .files
+            .keys()
+            .filter(|key| !present.contains(key.as_str()))
+            .cloned()
+            .collect();
+        Ok(changes)
+    }
+}
+
+/// What happened to one file of a sync
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileOutcome {
+    Unchanged {
+        path: String,
+    },
+    /// Embedded for the first time
+    Added {
+        path: String,
+        chunks: usize,
+    },
+    /// Re-embedded; vectors of chunks that no longer exist were deleted
+    Updated {
+        path: String,
+        chunks: usize,
+    },
+    /// Gone from the source, its vectors were deleted
+    Removed {
+        path: String,
+        vectors: usize,
+    },
+    /// Unreadable or not completely embedded; retried by the next sync
+    Failed {
+        path: String,
+        error: String,
+    },
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SyncReport {
+    pub added: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+    /// Files still to embed, with their error
+    pub failed: Vec<(String, String)>,
+}
+
+impl SyncReport {
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
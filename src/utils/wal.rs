@@ -0,0 +1,325 @@
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use tokio::task::spawn_blocking;
+
+use crate::utils::EmbeddingStore;
+
+/// Name of the write-ahead log inside a store directory
+pub const WAL_FILE_NAME: &str = "wal.log";
+/// Name of the file holding the next free batch index, next to the log
+pub const BATCH_COUNTER_FILE_NAME: &str = "batch.counter";
+/// Size at which `EmbeddingStore::upsert` and `delete` fold the log into batch files
+pub const WAL_CHECKPOINT_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Bytes before each record: payload length (u32) and FNV-1a checksum (u64)
+const RECORD_HEADER_LEN: usize = 12;
+
+/// Write-ahead log of batches that are not yet stored as `.bin` files.
+///
+/// Each record is a bincode `EmbeddingStore` behind a length and checksum, and is fsynced
+/// before `append` returns. Writers hold an exclusive lock on the log while appending,
+/// checkpointing or recovering, and batch indexes are allocated under that lock, so
+/// writers in separate processes never log two batches under the same index. `replay` takes no lock and never modifies the log, so
+/// readers can load a store while a server appends to it; a record cut short by a crash
+/// was never acknowledged and is dropped by `recover` when a writer opens the store.
+pub struct Wal;
+
+/// Records read from the log by `Wal::replay`
+#[derive(Debug, Clone, Default)]
+pub struct WalReplay {
+    pub stores: Vec<EmbeddingStore>,
+    /// Bytes after the last complete record: a write torn by a crash, or a record
+    /// still being appended by another writer
+    pub discarded: u64,
+}
+
+impl Wal {
+    pub fn path(dir_path: &str) -> PathBuf {
+        Path::new(dir_path).join(WAL_FILE_NAME)
+    }
+
+    /// Durably append `store` to the log, returning the log size afterwards.
+    ///
+    /// `store.batch_index` is raised to the next index no batch file, logged record or
+    /// earlier allocation in `dir_path` uses; the allocation is recorded in
+    /// `batch.counter` before the record is written. The log must have been recovered
+    /// since the last crash, which `EmbeddingStore::open` does: a record behind a torn
+    /// tail is never replayed.
+    pub async fn append(dir_path: &str, store: &mut EmbeddingStore) -> Result<u64> {
+        let path = Self::path(dir_path);
+        let dir = PathBuf::from(dir_path);
+        let mut record_store = store.clone();
+        let (size, batch_index) = spawn_blocking(move || -> Result<(u64, usize)> {
+            let created = !path.exists();
+            let mut file = lock(&path)?;
+            record_store.batch_index = allocate(&dir, &mut file, record_store.batch_index)?;
+
+            let payload = bincode::serialize(&record_store)?;
+            let len = u32::try_from(payload.len()).context("WAL record is larger than 4 GiB")?;
+            let mut record = Vec::with_capacity(RECORD_HEADER_LEN + payload.len());
+            record.extend_from_slice(&len.to_le_bytes());
+            record.extend_from_slice(&checksum(&payload).to_le_bytes());
+            record.extend_from_slice(&payload);
+            file.write_all(&record)?;
+            file.sync_data()
+                .with_context(|| format!("Failed to sync WAL: {:?}", path))?;
+            if created {
+                sync_parent(&path)?;
+            }
+
+            Ok((file.metadata()?.len(), record_store.batch_index))
+        })
+        .await??;
+        store.batch_index = batch_index;
+        Ok(size)
+    }
+
+    /// Read every complete record without modifying the log
+    pub async fn replay(dir_path: &str) -> Result<WalReplay> {
+        let path = Self::path(dir_path);
+        spawn_blocking(move || {
+            let mut bytes = Vec::new();
+            match File::open(&path) {
+                Ok(mut file) => file.read_to_end(&mut bytes)?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    return Ok(WalReplay::default());
+                }
+                Err(e) => return Err(e).with_context(|| format!("Failed to read WAL: {:?}", path)),
+            };
+            Ok(read_records(&bytes))
+        })
+        .await?
+    }
+
+    /// Truncate a torn tail left by a crash, returning how many bytes were dropped.
+    ///
+    /// Holds the log lock, so a record another writer is appending is never cut off.
+    pub async fn recover(dir_path: &str) -> Result<u64> {
+        let path = Self::path(dir_path);
+        spawn_blocking(move || {
+            if !path.exists() {
+                return Ok(0);
+            }
+            let mut file = lock(&path)?;
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes)?;
+            let discarded = read_records(&bytes).discarded;
+            if discarded > 0 {
+                file.set_len(bytes.len() as u64 - discarded)?;
+                file.sync_all()
+                    .with_context(|| format!("Failed to truncate WAL: {:?}", path))?;
+            }
+            Ok(discarded)
+        })
+        .await?
+    }
+
+    /// Write every logged batch as a `.bin` file, then empty the log.
+    ///
+    /// The log stays locked throughout, so no record appended meanwhile is emptied away.
+    /// A batch whose file already holds it, written by a checkpoint that stopped before
+    /// emptying the log, is not written again. A file holding a different batch under
+    /// the same index is kept, and the logged batch is written under a new index.
+    pub async fn checkpoint(dir_path: &str) -> Result<usize> {
+        let path = Self::path(dir_path);
+        if !path.exists() {
+            return Ok(0);
+        }
+        let (mut file, replay) = spawn_blocking(move || -> Result<(File, WalReplay)> {
+            let mut file = lock(&path)?;
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes)?;
+            Ok((file, read_records(&bytes)))
+        })
+        .await??;
+
+        let mut written = 0;
+        for mut store in replay.stores {
+            let file_path = batch_file(Path::new(dir_path), store.batch_index);
+            if file_path.exists() {
+                if EmbeddingStore::read_binary_file(&file_path).await? == store {
+                    continue;
+                }
+                let dir = PathBuf::from(dir_path);
+                let (returned, batch_index) = spawn_blocking(move || -> Result<(File, usize)> {
+                    let batch_index = allocate(&dir, &mut file, 0)?;
+                    Ok((file, batch_index))
+                })
+                .await??;
+                file = returned;
+                store.batch_index = batch_index;
+            }
+            store.write_batch(dir_path).await?;
+            written += 1;
+        }
+
+        let path = Self::path(dir_path);
+        spawn_blocking(move || -> Result<()> {
+            file.set_len(0)?;
+            file.sync_all()
+                .with_context(|| format!("Failed to truncate WAL: {:?}", path))
+        })
+        .await??;
+
+        Ok(written)
+    }
+}
+
+/// Open the log for appending under an exclusive lock, released when the file is dropped
+fn lock(path: &Path) -> Result<File> {
+    let file = OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open WAL: {:?}", path))?;
+    file.lock()
+        .with_context(|| format!("Failed to lock WAL: {:?}", path))?;
+    Ok(file)
+}
+
+/// Allocate the next free batch index of the store in `dir`, at least `at_least`. Must
+/// be called with the store's `log` locked.
+///
+/// The index is recorded in `batch.counter` before it is returned, so a crash never
+/// hands it out twice. Stores logged before the counter existed are scanned instead.
+fn allocate(dir: &Path, log: &mut File, at_least: usize) -> Result<usize> {
+    let counter_path = dir.join(BATCH_COUNTER_FILE_NAME);
+    let counted = match std::fs::read_to_string(&counter_path) {
+        Ok(text) => text
+            .trim()
+            .parse::<usize>()
+            .with_context(|| format!("Invalid batch counter: {:?}", counter_path))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let mut bytes = Vec::new();
+            log.seek(SeekFrom::Start(0))?;
+            log.read_to_end(&mut bytes)?;
+            read_records(&bytes)
+                .stores
+                .iter()
+                .map(|store| store.batch_index + 1)
+                .max()
+                .unwrap_or(0)
+        }
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read {:?}", counter_path));
+        }
+    };
+
+    let mut next = counted.max(at_least);
+    for entry in std::fs::read_dir(dir)? {
+        let name = entry?.file_name();
+        if let Some(index) = name.to_str().and_then(batch_file_index) {
+            next = next.max(index + 1);
+        }
+    }
+
+    write_atomic(&counter_path, |writer| {
+        Ok(writer.write_all((next + 1).to_string().as_bytes())?)
+    })?;
+    Ok(next)
+}
+
+/// Path of the `.bin` file of batch `batch_index`, see `EmbeddingStore::write_batch`
+fn batch_file(dir_path: &Path, batch_index: usize) -> PathBuf {
+    dir_path.join(format!("embeddings_batch_{}.bin", batch_index))
+}
+
+/// The batch index in the name of a `.bin` file written by `EmbeddingStore::write_batch`
+pub(crate) fn batch_file_index(name: &str) -> Option<usize> {
+    name.strip_prefix("embeddings_batch_")?
+        .strip_suffix(".bin")?
+        .parse()
+        .ok()
+}
+
+/// Every complete record of `bytes`, stopping at the first torn or garbled one
+fn read_records(bytes: &[u8]) -> WalReplay {
+    let mut stores = Vec::new();
+    let mut offset = 0;
+    while let Some((store, next)) = read_record(bytes, offset) {
+        stores.push(store);
+        offset = next;
+    }
+    WalReplay {
+        stores,
+        discarded: (bytes.len() - offset) as u64,
+    }
+}
+
+fn read_record(bytes: &[u8], offset: usize) -> Option<(EmbeddingStore, usize)> {
+    let header = bytes.get(offset..offset + RECORD_HEADER_LEN)?;
+    let len = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+    let expected = u64::from_le_bytes(header[4..12].try_into().unwrap());
+
+    let start = offset + RECORD_HEADER_LEN;
+    let payload = bytes.get(start..start + len)?;
+    if checksum(payload) != expected {
+        return None;
+    }
+    let store = bincode::deserialize(payload).ok()?;
+    Some((store, start + len))
+}
+
+/// 64-bit FNV-1a, enough to detect torn or garbled records
+pub(crate) fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Write `path` crash-safely: into a temporary sibling, fsync, rename over the target and
+/// fsync the directory, so readers only ever see the old file or the complete new one
+pub(crate) fn write_atomic(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> Result<()>,
+) -> Result<()> {
+    let mut tmp_name = path
+        .file_name()
+        .context("Path has no file name")?
+        .to_owned();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let file = File::create(&tmp_path)
+        .with_context(|| format!("Failed to create file: {:?}", tmp_path))?;
+    let mut writer = BufWriter::with_capacity(1024 * 1024, file);
+    write(&mut writer)?;
+    let file = writer.into_inner().map_err(|e| e.into_error())?;
+    file.sync_all()
+        .with_context(|| format!("Failed to sync file: {:?}", tmp_path))?;
+
+    std::fs::rename(&tmp_path, path)
+        .with_context(|| format!("Failed to rename {:?} to {:?}", tmp_path, path))?;
+    sync_parent(path)
+}
+
+/// Remove temporary files left behind by writes interrupted before their rename
+pub(crate) fn remove_temp_files(dir_path: &Path) -> Result<()> {
+    for entry in std::fs::read_dir(dir_path)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) == Some("tmp") {
+            std::fs::remove_file(&path).with_context(|| format!("Failed to remove {:?}", path))?;
+        }
+    }
+    Ok(())
+}
+
+/// Persist a rename or file creation by syncing the containing directory
+fn sync_parent(path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        File::open(parent)?
+            .sync_all()
+            .with_context(|| format!("Failed to sync directory: {:?}", parent))?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
@@ -0,0 +1,188 @@
+mod common;
+use blaze_db::prelude::{
+    Bm25Config, Bm25Index, EmbeddingStore, Filter, Fusion, Metadata, Metrics, SearchQuery,
+    VectorData, tokenize,
+};
+use blaze_db::utils::EmbeddingData;
+use common::data_of;
+use tempfile::tempdir;
+
+const CHUNKS: [&str; 6] = [
+    "The quick brown fox jumps over the lazy dog",
+    "Replacement filter for model XJ-9000 vacuum cleaners",
+    "Hello 世界! This is unicode text.",
+    "Café, naïve, résumé - accented characters",
+    "😭 Emoji support test 🤧",
+    "A slow green turtle walks under the busy bridge",
+];
+
+/// Chunk `i` gets a vector pointing mostly along axis `i`, so vector and keyword rankings
+/// can be steered independently
+fn store() -> VectorData {
+    let items = CHUNKS
+        .iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut embedding = vec![0.1; CHUNKS.len()];
+            embedding[i] = 1.0;
+            let mut metadata = Metadata::new();
+            metadata.insert("even".to_string(), (i % 2 == 0).into());
+            EmbeddingData {
+                id: format!("doc-{}", i),
+                index: i,
+                chunk: chunk.to_string(),
+                embedding,
+                dimensions: CHUNKS.len(),
+                metadata,
+            }
+        })
+        .collect();
+    data_of(items)
+}
+
+fn axis(i: usize) -> Vec<f32> {
+    let mut vector = vec![0.0; CHUNKS.len()];
+    vector[i] = 1.0;
+    vector
+}
+
+#[test]
+fn test_tokenize_unicode() {
+    assert_eq!(
+        tokenize("Hello 世界! This is unicode text."),
+        vec!["hello", "世", "界", "this", "is", "unicode", "text"]
+    );
+    assert_eq!(
+        tokenize("Café, naïve, résumé - accented characters"),
+        vec!["café", "naïve", "résumé", "accented", "characters"]
+    );
+    assert_eq!(
+        tokenize("😭 Emoji support test 🤧"),
+        vec!["emoji", "support", "test"]
+    );
+    assert_eq!(tokenize("XJ-9000"), vec!["xj", "9000"]);
+    assert!(tokenize("!!! 🤧 ...").is_empty());
+}
+
+#[test]
+fn test_bm25_ranks_keyword_matches() {
+    let data = store();
+    let index = Bm25Index::build(&data, Bm25Config::default());
+    assert_eq!(index.len(), CHUNKS.len());
+    assert_eq!(index.document_frequency("The"), 2);
+
+    let query = SearchQuery::new(3, axis(0), Metrics::Cosine);
+    let ranked = index.ranked("xj-9000 filter", &query, &data, 3);
+    assert_eq!(ranked[0].0, 1);
+    assert_eq!(ranked.len(), 1);
+
+    // Each accented, CJK and emoji chunk is reachable by its own words
+    assert_eq!(index.ranked("世界", &query, &data, 1)[0].0, 2);
+    assert_eq!(index.ranked("RÉSUMÉ", &query, &data, 1)[0].0, 3);
+    assert_eq!(index.ranked("emoji 🤧", &query, &data, 1)[0].0, 4);
+    assert!(index.ranked("nothing matches", &query, &data, 3).is_empty());
+
+    // Rarer terms weigh more: "turtle" appears once, "the" in two chunks
+    let scores = index.scores("the turtle");
+    assert!(scores[&5] > scores[&0]);
+}
+
+#[test]
+fn test_bm25_respects_filters_deletes_and_updates() {
+    let mut data = store();
+    let mut index = Bm25Index::build(&data, Bm25Config::default());
+
+    let query = SearchQuery::new(3, axis(0), Metrics::Cosine).with_filter(Filter::eq("even", true));
+    assert!(index.ranked("model filter", &query, &data, 3).is_empty());
+
+    data.apply(EmbeddingStore::deletion(1, vec!["doc-0".to_string()]));
+    let plain = SearchQuery::new(3, axis(0), Metrics::Cosine);
+    assert!(index.ranked("fox", &plain, &data, 3).is_empty());
+
+    data.apply(EmbeddingStore::new(
+        2,
+        vec![EmbeddingData {
+            id: "doc-0".to_string(),
+            chunk: "A red fox rests".to_string(),
+            embedding: axis(0),
+            dimensions: CHUNKS.len(),
+            ..Default::default()
+        }],
+    ));
+    assert_eq!(index.update(&data), 1);
+    let ranked = index.ranked("fox", &plain, &data, 3);
+    assert_eq!(ranked.len(), 1);
+    assert_eq!(ranked[0].0, CHUNKS.len());
+}
+
+#[test]
+fn test_hybrid_rrf_combines_both_rankings() {
+    let data = store();
+    let index = Bm25Index::build(&data, Bm25Config::default());
+
+    // The vector points at the turtle chunk, the text at the vacuum filter
+    let query = SearchQuery::new(2, axis(5), Metrics::Cosine)
+        .with_hybrid("XJ-9000 vacuum", Fusion::default());
+    let results = query.search_hybrid(&data, &index).unwrap();
+    let ids: Vec<&str> = results.iter().map(|r| r.id.as_str()).collect();
+    assert_eq!(results.len(), 2);
+    assert!(ids.contains(&"doc-5"));
+    assert!(ids.contains(&"doc-1"));
+
+    // A chunk first in both rankings wins outright
+    let query = SearchQuery::new(1, axis(1), Metrics::Cosine)
+        .with_hybrid("vacuum", Fusion::Rrf { k: 60.0 });
+    let results = query.search_hybrid(&data, &index).unwrap();
+    assert_eq!(results[0].id, "doc-1");
+    assert!((results[0].score - 2.0 / 61.0).abs() < 1e-6);
+}
+
+#[test]
+fn test_hybrid_linear_weights() {
+    let data = store();
+    let index = Bm25Index::build(&data, Bm25Config::default());
+    let query = SearchQuery::new(1, axis(5), Metrics::Cosine);
+
+    let vector_only = query
+        .clone()
+        .with_hybrid("unicode", Fusion::Linear { alpha: 1.0 })
+        .search_hybrid(&data, &index)
+        .unwrap();
+    assert_eq!(vector_only[0].id, "doc-5");
+
+    let keyword_only = query
+        .clone()
+        .with_hybrid("unicode", Fusion::Linear { alpha: 0.0 })
+        .search_hybrid(&data, &index)
+        .unwrap();
+    assert_eq!(keyword_only[0].id, "doc-2");
+    assert!((keyword_only[0].score - 1.0).abs() < 1e-6);
+
+    // Without hybrid settings it is a plain vector search
+    let plain = query.search_hybrid(&data, &index).unwrap();
+    assert_eq!(plain[0].id, "doc-5");
+    assert_eq!(plain[0].score, query.search(&data).unwrap()[0].score);
+
+    // Filters apply to both halves
+    let filtered = query
+        .clone()
+        .with_filter(Filter::eq("even", true))
+        .with_hybrid("vacuum filter", Fusion::Linear { alpha: 0.0 })
+        .search_hybrid(&data, &index)
+        .unwrap();
+    assert!(filtered.iter().all(|r| r.id != "doc-1"));
+}
+
+#[tokio::test]
+async fn test_bm25_round_trip() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let data = store();
+    let index = Bm25Index::build(&data, Bm25Config { k1: 1.5, b: 0.5 });
+    index.write_binary(path).await.unwrap();
+
+    let loaded = Bm25Index::read_binary(path).await.unwrap();
+    assert_eq!(loaded.config, index.config);
+    assert_eq!(loaded.len(), index.len());
+    assert_eq!(loaded.scores("quick fox"), index.scores("quick fox"));
+}
@@ -0,0 +1,232 @@
+use blaze_db::config::Config;
+use blaze_db::prelude::{HashingProvider, Ingestor, MetadataValue, VectorData};
+use blaze_db::utils::{Chunk, Chunker, Pipeline, WindowUnit};
+use tempfile::tempdir;
+
+const TEXT: &str = "Well, Prince, so Genoa and Lucca are now just family estates.\n\
+But I warn you, Mr. Bonaparte is no friend of ours! \"Is he not?\" asked the prince.\n\
+\n\
+   It was in July, 1805, and the speaker was the well-known Anna Pávlovna Schérer.\n\
+\n\n\
+戦争と平和。トルストイの小説です。\n";
+
+fn texts(chunks: &[Chunk]) -> Vec<&str> {
+    chunks.iter().map(|chunk| chunk.text.as_str()).collect()
+}
+
+fn all_chunkers() -> Vec<Chunker> {
+    [
+        "line",
+        "paragraph",
+        "sentence",
+        "window",
+        "recursive",
+        "markdown",
+    ]
+    .iter()
+    .map(|name| name.parse().unwrap())
+    .chain([
+        Chunker::Window {
+            size: 7,
+            overlap: 3,
+            unit: WindowUnit::Chars,
+        },
+        Chunker::Window {
+            size: 5,
+            overlap: 1,
+            unit: WindowUnit::Tokens,
+        },
+        Chunker::Recursive {
+            size: 40,
+            overlap: 10,
+            separators: vec!["\n\n".into(), "\n".into(), " ".into()],
+        },
+        Chunker::Markdown { size: 30 },
+    ])
+    .collect()
+}
+
+#[test]
+fn test_chunks_point_back_into_the_source() {
+    for chunker in all_chunkers() {
+        let chunks = chunker.chunk(TEXT);
+        assert!(!chunks.is_empty(), "{:?}", chunker);
+        let mut previous = 0;
+        for chunk in &chunks {
+            assert_eq!(&TEXT[chunk.start..chunk.end], chunk.text, "{:?}", chunker);
+            assert_eq!(chunk.text, chunk.text.trim());
+            assert!(!chunk.text.is_empty());
+            assert!(chunk.start >= previous || chunker != Chunker::Line);
+            previous = chunk.start;
+        }
+    }
+    assert!(Chunker::Paragraph.chunk("\n \n\t\n").is_empty());
+}
+
+#[test]
+fn test_paragraph_and_sentence_chunkers() {
+    let paragraphs = Chunker::Paragraph.chunk(TEXT);
+    assert_eq!(paragraphs.len(), 3);
+    assert!(paragraphs[0].text.ends_with("asked the prince."));
+    assert!(paragraphs[1].text.starts_with("It was in July"));
+
+    let sentences = Chunker::Sentence.chunk(TEXT);
+    assert_eq!(
+        texts(&sentences),
+        vec![
+            "Well, Prince, so Genoa and Lucca are now just family estates.",
+            "But I warn you, Mr. Bonaparte is no friend of ours!",
+            "\"Is he not?\" asked the prince.",
+            "It was in July, 1805, and the speaker was the well-known Anna Pávlovna Schérer.",
+            "戦争と平和。",
+            "トルストイの小説です。",
+        ]
+    );
+    // Initials do not end a sentence, a closing quote stays with its sentence
+    let sentences = Chunker::Sentence.chunk("Written by J. R. R. Tolkien. \"Run!\" He ran");
+    assert_eq!(
+        texts(&sentences),
+        vec!["Written by J. R. R. Tolkien.", "\"Run!\"", "He ran"]
+    );
+}
+
+#[test]
+fn test_window_chunkers_overlap() {
+    let chars = Chunker::Window {
+        size: 4,
+        overlap: 2,
+        unit: WindowUnit::Chars,
+    };
+    assert_eq!(
+        texts(&chars.chunk("abcdéfgh")),
+        vec!["abcd", "cdéf", "éfgh"]
+    );
+
+    let tokens = Chunker::Window {
+        size: 3,
+        overlap: 1,
+        unit: WindowUnit::Tokens,
+    };
+    assert_eq!(
+        texts(&tokens.chunk("one two  three\nfour five six seven")),
+        vec!["one two  three", "three\nfour five", "five six seven"]
+    );
+    // An overlap as large as the window still moves forward
+    let stuck = Chunker::Window {
+        size: 2,
+        overlap: 5,
+        unit: WindowUnit::Chars,
+    };
+    assert_eq!(texts(&stuck.chunk("abc")), vec!["ab", "bc"]);
+}
+
+#[test]
+fn test_recursive_chunker_respects_size() {
+    let text = "First paragraph is short.\n\nSecond paragraph has a great many more words \
+        in it and no full stop anywhere\n\nThird.";
+    let chunker = Chunker::Recursive {
+        size: 50,
+        overlap: 20,
+        separators: blaze_db::utils::default_separators(),
+    };
+    let chunks = chunker.chunk(text);
+    assert_eq!(chunks[0].text, "First paragraph is short.");
+    for chunk in &chunks {
+        assert!(chunk.text.chars().count() <= 50, "{:?}", chunk.text);
+    }
+    // The long paragraph is split at words, and consecutive pieces share some
+    let second = chunks
+        .iter()
+        .position(|c| c.text.starts_with("Second"))
+        .unwrap();
+    assert!(chunks[second + 1].start < chunks[second].end);
+    assert_eq!(chunks.last().unwrap().text, "Third.");
+
+    // Text without any separator falls back to character windows
+    let chunks = Chunker::Recursive {
+        size: 4,
+        overlap: 0,
+        separators: vec![" ".into()],
+    }
+    .chunk("abcdefghij");
+    assert_eq!(texts(&chunks), vec!["abcd", "efgh", "ij"]);
+}
+
+#[test]
+fn test_markdown_chunker_tracks_headings() {
+    let text = "Intro text.\n\n# Guide\nOverview.\n## Install\nRun it.\n```sh\n# not a heading\n```\n\
+        ### Linux\nUse apt.\n## Usage\nCall it.\n";
+    let chunks = Chunker::Markdown { size: 1000 }.chunk(text);
+    let sections: Vec<Option<&str>> = chunks.iter().map(|c| c.section.as_deref()).collect();
+    assert_eq!(
+        sections,
+        vec![
+            None,
+            Some("Guide"),
+            Some("Guide > Install"),
+            Some("Guide > Install > Linux"),
+            Some("Guide > Usage"),
+        ]
+    );
+    assert!(chunks[2].text.starts_with("## Install"));
+    assert!(chunks[2].text.contains("# not a heading"));
+
+    // Long sections are split and every piece keeps its heading trail
+    let long = format!("# Long\n{}", "word ".repeat(40));
+    let chunks = Chunker::Markdown { size: 50 }.chunk(&long);
+    assert!(chunks.len() > 3);
+    assert!(chunks.iter().all(|c| c.section.as_deref() == Some("Long")));
+}
+
+#[test]
+fn test_chunker_from_config() {
+    let config =
+        Config::parse("[ingest]\nchunker = { kind = \"recursive\", size = 800 }\n").unwrap();
+    match config.ingest.chunker {
+        Chunker::Recursive {
+            size,
+            overlap,
+            separators,
+        } => {
+            assert_eq!(size, 800);
+            assert_eq!(overlap, 100);
+            assert_eq!(separators, blaze_db::utils::default_separators());
+        }
+        other => panic!("Expected recursive chunker, got {:?}", other),
+    }
+    assert_eq!(Config::default().ingest.chunker, Chunker::Line);
+    assert!("pages".parse::<Chunker>().is_err());
+
+    let mut config = Config::default();
+    config
+        .apply_overrides(|key| (key == "BLAZE_INGEST_CHUNKER").then(|| "Sentence".into()))
+        .unwrap();
+    assert_eq!(config.ingest.chunker, Chunker::Sentence);
+}
+
+#[tokio::test]
+async fn test_pipeline_stores_chunk_offsets() {
+    let dir = tempdir().unwrap();
+    let source = dir.path().join("book.md");
+    std::fs::write(&source, "# One\nFirst part.\n\n# Two\nSecond part.\n").unwrap();
+    let store = dir.path().join("store");
+    let store = store.to_str().unwrap();
+
+    let batches = Ingestor::new(&source, 8)
+        .with_chunker(Chunker::Markdown { size: 100 })
+        .read_chunks()
+        .unwrap();
+    assert_eq!(batches.len(), 1);
+    let mut data = VectorData::default();
+    Pipeline::new(HashingProvider::new(16), store)
+        .run_chunks("book", &batches, &mut data, |_| {})
+        .await
+        .unwrap();
+
+    let position = data.position("0-1").unwrap();
+    let metadata = &data.metadata[position];
+    assert_eq!(data.chunk[position], "# Two\nSecond part.");
+    assert_eq!(metadata["start"], MetadataValue::Int(19));
+    assert_eq!(metadata["end"], MetadataValue::Int(37));
+    assert_eq!(metadata["section"], MetadataValue::from("Two"));
+}
@@ -0,0 +1,251 @@
+use blaze_db::cli::{Cli, Command, run_with};
+use blaze_db::prelude::{EmbeddingStore, HashingProvider, Metrics, SearchQuery};
+use blaze_db::utils::{EmbeddingData, MetadataValue, STORE_SEGMENT_FILE_NAME};
+use clap::Parser;
+use std::path::Path;
+use tempfile::tempdir;
+
+async fn write_store(dir: &Path) {
+    let items = (0..3)
+        .map(|i| EmbeddingData {
+            id: format!("doc-{}", i),
+            chunk: format!("chunk {}", i),
+            embedding: vec![i as f32, 1.0],
+            dimensions: 2,
+            ..Default::default()
+        })
+        .collect();
+    EmbeddingStore::new(0, items)
+        .write_batch(dir.to_str().unwrap())
+        .await
+        .unwrap();
+}
+
+#[test]
+fn test_parse_search_command() {
+    let cli = Cli::try_parse_from([
+        "blaze",
+        "search",
+        "war and peace",
+        "--top-k",
+        "3",
+        "--metric",
+        "dot",
+        "--store",
+        "./other",
+    ])
+    .unwrap();
+
+    assert_eq!(cli.store.as_deref(), Some("./other"));
+    match cli.command {
+        Command::Search {
+            text,
+            top_k,
+            metric,
+            filter,
+        } => {
+            assert_eq!(text, "war and peace");
+            assert_eq!(top_k, Some(3));
+            assert_eq!(metric, Some(Metrics::DotProduct));
+            assert!(filter.is_none());
+        }
+        _ => panic!("Expected search command"),
+    }
+}
+
+#[test]
+fn test_parse_rejects_unknown_metric() {
+    let result = Cli::try_parse_from(["blaze", "search", "text", "--metric", "manhattan"]);
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_parse_delete_requires_ids() {
+    assert!(Cli::try_parse_from(["blaze", "delete"]).is_err());
+
+    let cli = Cli::try_parse_from(["blaze", "delete", "a", "b"]).unwrap();
+    match cli.command {
+        Command::Delete { ids } => assert_eq!(ids, vec!["a", "b"]),
+        _ => panic!("Expected delete command"),
+    }
+}
+
+#[tokio::test]
+async fn test_export_writes_json_lines() {
+    let dir = tempdir().unwrap();
+    let store = dir.path().join("store");
+    write_store(&store).await;
+    let output = dir.path().join("export.jsonl");
+
+    let cli = Cli::try_parse_from([
+        "blaze",
+        "--store",
+        store.to_str().unwrap(),
+        "export",
+        "--output",
+        output.to_str().unwrap(),
+    ])
+    .unwrap();
+    run_with(cli).await.unwrap();
+
+    let contents = std::fs::read_to_string(&output).unwrap();
+    let lines: Vec<serde_json::Value> = contents
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0]["id"], "doc-0");
+    assert_eq!(lines[2]["chunk"], "chunk 2");
+    assert_eq!(lines[1]["embedding"], serde_json::json!([1.0, 1.0]));
+}
+
+#[tokio::test]
+async fn test_export_to_stdout_is_json_lines() {
+    let dir = tempdir().unwrap();
+    let store = dir.path().join("store");
+    write_store(&store).await;
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_blaze"))
+        .args(["--store", store.to_str().unwrap(), "export"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0]["id"], "doc-0");
+}
+
+#[tokio::test]
+async fn test_delete_uses_store_from_config() {
+    let dir = tempdir().unwrap();
+    let store = dir.path().join("store");
+    write_store(&store).await;
+    let config = dir.path().join("blaze.toml");
+    std::fs::write(
+        &config,
+        format!("[storage]\npath = {:?}\n", store.to_str().unwrap()),
+    )
+    .unwrap();
+
+    let cli = Cli::try_parse_from([
+        "blaze",
+        "--config",
+        config.to_str().unwrap(),
+        "delete",
+        "doc-1",
+    ])
+    .unwrap();
+    run_with(cli).await.unwrap();
+
+    let data = EmbeddingStore::read_binary(store.to_str().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(data.total_vectors, 2);
+    assert_eq!(data.position("doc-1"), None);
+}
+
+#[tokio::test]
+async fn test_ingest_rejects_missing_file() {
+    let dir = tempdir().unwrap();
+    let cli = Cli::try_parse_from([
+        "blaze",
+        "--store",
+        dir.path().to_str().unwrap(),
+        "ingest",
+        "/nonexistent/file.txt",
+    ])
+    .unwrap();
+
+    let error = run_with(cli).await.unwrap_err();
+    assert!(error.to_string().contains("Source file must exist"));
+}
+
+#[tokio::test]
+async fn test_offline_ingest_and_search() {
+    let dir = tempdir().unwrap();
+    let store = dir.path().join("store");
+    let config = dir.path().join("blaze.toml");
+    std::fs::write(
+        &config,
+        format!(
+            "[storage]\npath = {:?}\n\n[provider]\noffline = true\ndimensions = 64\n",
+            store.to_str().unwrap()
+        ),
+    )
+    .unwrap();
+    let source = dir.path().join("lines.txt");
+    std::fs::write(
+        &source,
+        "Prince Andrew rode to the battle\nNatasha danced at the ball\nPierre inherited a fortune\n",
+    )
+    .unwrap();
+    let run = |args: &[&str]| {
+        let mut argv = vec!["blaze", "--config", config.to_str().unwrap()];
+        argv.extend_from_slice(args);
+        run_with(Cli::try_parse_from(argv).unwrap())
+    };
+
+    run(&["ingest", source.to_str().unwrap(), "--batch-size", "8"])
+        .await
+        .unwrap();
+    run(&["search", "who danced at the ball"]).await.unwrap();
+    run(&["seal"]).await.unwrap();
+    assert!(store.join(STORE_SEGMENT_FILE_NAME).exists());
+    run(&["search", "who danced at the ball"]).await.unwrap();
+
+    let data = EmbeddingStore::read_binary(store.to_str().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(data.total_vectors, 3);
+    assert_eq!(data.dimensions, 64);
+    assert_eq!(data.model, "hashing-64");
+
+    let query = HashingProvider::new(64).embed("who danced at the ball");
+    let results = SearchQuery::new(1, query, Metrics::Cosine)
+        .search(&data)
+        .unwrap();
+    assert_eq!(results[0].chunk, "Natasha danced at the ball");
+}
+
+#[tokio::test]
+async fn test_ingest_keys_a_file_by_its_canonical_path() {
+    let dir = tempdir().unwrap();
+    let store = dir.path().join("store");
+    let config = dir.path().join("blaze.toml");
+    std::fs::write(
+        &config,
+        format!(
+            "[storage]\npath = {:?}\n\n[provider]\noffline = true\ndimensions = 16\n",
+            store.to_str().unwrap()
+        ),
+    )
+    .unwrap();
+    let source = dir.path().join("lines.txt");
+    std::fs::write(&source, "Alpha\nBeta\n").unwrap();
+    let run = |args: &[&str]| {
+        let mut argv = vec!["blaze", "--config", config.to_str().unwrap()];
+        argv.extend_from_slice(args);
+        run_with(Cli::try_parse_from(argv).unwrap())
+    };
+
+    let typed = dir.path().join(".").join("lines.txt");
+    for path in [&source, &typed] {
+        run(&["ingest", path.to_str().unwrap(), "--batch-size", "8"])
+            .await
+            .unwrap();
+    }
+
+    let data = EmbeddingStore::read_binary(store.to_str().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(data.total_vectors, 2);
+    let canonical = std::fs::canonicalize(&source).unwrap();
+    let canonical = canonical.to_str().unwrap();
+    assert!(data.ids[0].starts_with(&format!("{}:", canonical)));
+    assert_eq!(data.metadata[0]["source"], MetadataValue::from(canonical));
+}
@@ -0,0 +1,264 @@
+mod common;
+use blaze_db::cli::{Cli, run_with};
+use blaze_db::prelude::{CollectionSchema, Collections, IndexType, Metrics};
+use blaze_db::utils::{EmbeddingData, MANIFEST_FILE_NAME};
+use clap::Parser;
+use common::items;
+use tempfile::tempdir;
+
+/// `count` items of `dimensions` dimensions
+fn filled(count: usize, dimensions: usize) -> Vec<EmbeddingData> {
+    items((0..count).map(|i| vec![i as f32; dimensions]).collect())
+}
+
+#[tokio::test]
+async fn test_create_list_and_reopen() {
+    let dir = tempdir().unwrap();
+    let mut collections = Collections::open(dir.path()).await.unwrap();
+    assert!(collections.list().is_empty());
+
+    collections
+        .create(CollectionSchema::new(
+            "qwen",
+            4,
+            Metrics::Cosine,
+            "qwen3-0.6b",
+        ))
+        .await
+        .unwrap();
+    collections
+        .create(
+            CollectionSchema::new("minilm", 3, Metrics::DotProduct, "all-minilm")
+                .with_index(IndexType::Hnsw),
+        )
+        .await
+        .unwrap();
+    assert!(dir.path().join(MANIFEST_FILE_NAME).exists());
+    assert!(dir.path().join("qwen").is_dir());
+
+    let reopened = Collections::open(dir.path()).await.unwrap();
+    let names: Vec<&str> = reopened.list().iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, vec!["minilm", "qwen"]);
+    let minilm = reopened.schema("minilm").unwrap();
+    assert_eq!(minilm.dimensions, 3);
+    assert_eq!(minilm.metric, Metrics::DotProduct);
+    assert_eq!(minilm.model, "all-minilm");
+    assert_eq!(minilm.index, IndexType::Hnsw);
+}
+
+#[tokio::test]
+async fn test_create_rejects_bad_schemas() {
+    let dir = tempdir().unwrap();
+    let mut collections = Collections::open(dir.path()).await.unwrap();
+    collections
+        .create(CollectionSchema::new("docs", 4, Metrics::Cosine, "m"))
+        .await
+        .unwrap();
+
+    for name in [
+        "",
+        "../escape",
+        ".hidden",
+        "a/b",
+        "with space",
+        "wal.log",
+        "x.bin",
+        "collections.json",
+        "index.HNSW",
+    ] {
+        let schema = CollectionSchema::new(name, 4, Metrics::Cosine, "m");
+        assert!(collections.create(schema).await.is_err(), "{:?}", name);
+    }
+    let dotted = CollectionSchema::new("minilm.v2", 4, Metrics::Cosine, "m");
+    collections.create(dotted).await.unwrap();
+    let duplicate = CollectionSchema::new("docs", 8, Metrics::Cosine, "m");
+    assert!(collections.create(duplicate).await.is_err());
+    let empty = CollectionSchema::new("empty", 0, Metrics::Cosine, "m");
+    assert!(collections.create(empty).await.is_err());
+    assert_eq!(collections.list().len(), 2);
+}
+
+#[tokio::test]
+async fn test_upsert_checks_dimensions_per_collection() {
+    let dir = tempdir().unwrap();
+    let mut collections = Collections::open(dir.path()).await.unwrap();
+    collections
+        .create(CollectionSchema::new("small", 2, Metrics::Cosine, "a"))
+        .await
+        .unwrap();
+    collections
+        .create(CollectionSchema::new("large", 5, Metrics::Euclidean, "b"))
+        .await
+        .unwrap();
+
+    let mut small = collections.load("small").await.unwrap();
+    collections
+        .upsert("small", &mut small, filled(3, 2))
+        .await
+        .unwrap();
+    let error = collections
+        .upsert("small", &mut small, filled(1, 5))
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("expected 2"));
+
+    let mut large = collections.load("large").await.unwrap();
+    collections
+        .upsert("large", &mut large, filled(4, 5))
+        .await
+        .unwrap();
+
+    assert_eq!(collections.load("small").await.unwrap().total_vectors, 3);
+    assert_eq!(collections.load("large").await.unwrap().dimensions, 5);
+    assert!(collections.load("missing").await.is_err());
+}
+
+#[tokio::test]
+async fn test_drop_and_rename() {
+    let dir = tempdir().unwrap();
+    let mut collections = Collections::open(dir.path()).await.unwrap();
+    for name in ["a", "b"] {
+        collections
+            .create(CollectionSchema::new(name, 2, Metrics::Cosine, "m"))
+            .await
+            .unwrap();
+    }
+    let mut data = collections.load("a").await.unwrap();
+    collections
+        .upsert("a", &mut data, filled(2, 2))
+        .await
+        .unwrap();
+
+    assert!(collections.rename("a", "b").await.is_err());
+    collections.rename("a", "renamed").await.unwrap();
+    assert!(collections.get("a").is_none());
+    assert!(!dir.path().join("a").exists());
+    assert_eq!(collections.load("renamed").await.unwrap().total_vectors, 2);
+
+    let dropped = collections.drop("b").await.unwrap();
+    assert_eq!(dropped.name, "b");
+    assert!(!dir.path().join("b").exists());
+    assert!(collections.drop("b").await.is_err());
+
+    let reopened = Collections::open(dir.path()).await.unwrap();
+    let names: Vec<&str> = reopened.list().iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, vec!["renamed"]);
+}
+
+#[tokio::test]
+async fn test_interrupted_rename_is_finished_on_open() {
+    let dir = tempdir().unwrap();
+    let mut collections = Collections::open(dir.path()).await.unwrap();
+    collections
+        .create(CollectionSchema::new("old", 2, Metrics::Cosine, "m"))
+        .await
+        .unwrap();
+    let mut data = collections.load("old").await.unwrap();
+    collections
+        .upsert("old", &mut data, filled(3, 2))
+        .await
+        .unwrap();
+
+    // A crash after the manifest was saved but before the directory moved
+    let manifest = dir.path().join(MANIFEST_FILE_NAME);
+    let text = std::fs::read_to_string(&manifest).unwrap();
+    let mut json: serde_json::Value = serde_json::from_str(&text).unwrap();
+    json["collections"][0]["name"] = "new".into();
+    json["moves"] = serde_json::json!([["old", "new"]]);
+    std::fs::write(&manifest, json.to_string()).unwrap();
+
+    let collections = Collections::open(dir.path()).await.unwrap();
+    assert!(!dir.path().join("old").exists());
+    assert_eq!(collections.load("new").await.unwrap().total_vectors, 3);
+    let saved = std::fs::read_to_string(&manifest).unwrap();
+    assert!(!saved.contains("moves"), "{}", saved);
+}
+
+#[tokio::test]
+async fn test_cli_manages_collections() {
+    let dir = tempdir().unwrap();
+    let store = dir.path().to_str().unwrap();
+    let run = |args: &[&str]| {
+        let mut argv = vec!["blaze", "--store", store];
+        argv.extend_from_slice(args);
+        run_with(Cli::try_parse_from(argv).unwrap())
+    };
+
+    run(&[
+        "collection",
+        "create",
+        "docs",
+        "--dimensions",
+        "2",
+        "--index",
+        "ivf",
+    ])
+    .await
+    .unwrap();
+    run(&["collection", "rename", "docs", "notes"])
+        .await
+        .unwrap();
+    run(&["collection", "list"]).await.unwrap();
+
+    let collections = Collections::open(dir.path()).await.unwrap();
+    let notes = collections.schema("notes").unwrap();
+    assert_eq!(notes.index, IndexType::Ivf);
+    assert_eq!(notes.dimensions, 2);
+
+    // Selecting an unknown collection fails before touching the store
+    assert!(run(&["--collection", "missing", "stats"]).await.is_err());
+    // The schema pins the model
+    assert!(
+        run(&["--collection", "notes", "--model", "other", "stats"])
+            .await
+            .is_err()
+    );
+
+    run(&["collection", "drop", "notes"]).await.unwrap();
+    let collections = Collections::open(dir.path()).await.unwrap();
+    assert!(collections.list().is_empty());
+}
+
+#[tokio::test]
+async fn test_cli_searches_collections_through_their_index() {
+    let dir = tempdir().unwrap();
+    let store = dir.path().join("store");
+    let config = dir.path().join("blaze.toml");
+    std::fs::write(
+        &config,
+        format!(
+            "[storage]\npath = {:?}\n\n[provider]\noffline = true\ndimensions = 32\n",
+            store.to_str().unwrap()
+        ),
+    )
+    .unwrap();
+    let source = dir.path().join("notes.txt");
+    let lines: Vec<String> = (0..40).map(|i| format!("note number {}", i)).collect();
+    std::fs::write(&source, lines.join("\n")).unwrap();
+    let run = |args: &[&str]| {
+        let mut argv = vec!["blaze", "--config", config.to_str().unwrap()];
+        argv.extend_from_slice(args);
+        run_with(Cli::try_parse_from(argv).unwrap())
+    };
+
+    for (name, index) in [("graph", "hnsw"), ("lists", "ivf")] {
+        run(&[
+            "collection",
+            "create",
+            name,
+            "--dimensions",
+            "32",
+            "--index",
+            index,
+        ])
+        .await
+        .unwrap();
+        run(&["--collection", name, "ingest", source.to_str().unwrap()])
+            .await
+            .unwrap();
+        run(&["--collection", name, "search", "note number 7"])
+            .await
+            .unwrap();
+        assert!(store.join(name).join(format!("index.{}", index)).exists());
+    }
+}
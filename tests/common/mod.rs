@@ -0,0 +1,94 @@
+//! Fixtures shared by the integration tests; each test crate uses some of them
+#![allow(dead_code)]
+
+use blaze_db::prelude::{EmbeddingStore, VectorData};
+use blaze_db::utils::EmbeddingData;
+
+/// Deterministic pseudo-random values in [-1, 1] so the tests do not depend on an RNG crate
+pub fn random_values(count: usize, seed: u64) -> Vec<f32> {
+    let mut state = seed;
+    (0..count)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((state >> 33) as f32 / (1u64 << 31) as f32) * 2.0 - 1.0
+        })
+        .collect()
+}
+
+/// `count` vectors of consecutive `random_values`
+pub fn random_vectors(count: usize, dimensions: usize, seed: u64) -> Vec<Vec<f32>> {
+    random_values(count * dimensions, seed)
+        .chunks(dimensions.max(1))
+        .map(<[f32]>::to_vec)
+        .collect()
+}
+
+/// Vectors scattered around `clusters` centres with noise of up to `spread`, like real
+/// embeddings
+pub fn clustered_vectors(
+    count: usize,
+    dimensions: usize,
+    clusters: usize,
+    spread: f32,
+    seed: u64,
+) -> Vec<Vec<f32>> {
+    let centres = random_vectors(clusters, dimensions, seed);
+    let noise = random_vectors(count, dimensions, seed + 1);
+    noise
+        .iter()
+        .enumerate()
+        .map(|(i, noise)| {
+            centres[i % clusters]
+                .iter()
+                .zip(noise)
+                .map(|(c, n)| c + spread * n)
+                .collect()
+        })
+        .collect()
+}
+
+/// An item with the given id and chunk text
+pub fn item(id: &str, chunk: &str, embedding: Vec<f32>) -> EmbeddingData {
+    EmbeddingData {
+        id: id.to_string(),
+        chunk: chunk.to_string(),
+        dimensions: embedding.len(),
+        embedding,
+        ..Default::default()
+    }
+}
+
+/// One item per vector, with id `doc-{n}` and chunk `chunk {n}` counting from `offset`
+pub fn items_from(offset: usize, vectors: Vec<Vec<f32>>) -> Vec<EmbeddingData> {
+    vectors
+        .into_iter()
+        .enumerate()
+        .map(|(i, embedding)| EmbeddingData {
+            id: format!("doc-{}", offset + i),
+            index: i,
+            chunk: format!("chunk {}", offset + i),
+            dimensions: embedding.len(),
+            embedding,
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// `items_from` counting from 0
+pub fn items(vectors: Vec<Vec<f32>>) -> Vec<EmbeddingData> {
+    items_from(0, vectors)
+}
+
+/// A store holding `items` as its first batch
+pub fn data_of(items: Vec<EmbeddingData>) -> VectorData {
+    let mut data = VectorData::default();
+    data.apply(EmbeddingStore::new(0, items));
+    data
+}
+
+/// A store holding `items(vectors)`
+pub fn vector_data(vectors: Vec<Vec<f32>>) -> VectorData {
+    data_of(items(vectors))
+}
@@ -0,0 +1,256 @@
+use blaze_db::config::Config;
+use blaze_db::prelude::{EmbeddingProvider, Metrics, ProviderKind};
+use blaze_db::utils::{Chunker, DedupMethod};
+use std::collections::HashMap;
+use tempfile::tempdir;
+
+#[test]
+fn test_config_defaults() {
+    let config = Config::default();
+
+    assert_eq!(config.storage.path, "./embeddings");
+    assert_eq!(config.provider.url, "http://localhost:1234/v1/embeddings");
+    assert_eq!(config.ingest.batch_size, 512);
+    assert_eq!(config.search.metric, Metrics::Cosine);
+    assert_eq!(config.search.top_k, 5);
+    assert_eq!(config.server.bind, "127.0.0.1:8080");
+    assert!(config.provider.api_key.is_none());
+}
+
+#[test]
+fn test_config_parse_full() {
+    let config = Config::parse(
+        r#"
+        [storage]
+        path = "/data/blaze"
+
+        [provider]
+        url = "https://api.openai.com/v1/embeddings"
+        model = "text-embedding-3-small"
+        api_key = "sk-test"
+
+        [ingest]
+        batch_size = 64
+
+        [search]
+        metric = "dot"
+        top_k = 20
+
+        [server]
+        bind = "0.0.0.0:9000"
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(config.storage.path, "/data/blaze");
+    assert_eq!(config.provider.model, "text-embedding-3-small");
+    assert_eq!(config.provider.api_key.as_deref(), Some("sk-test"));
+    assert_eq!(config.ingest.batch_size, 64);
+    assert_eq!(config.search.metric, Metrics::DotProduct);
+    assert_eq!(config.search.top_k, 20);
+    assert_eq!(config.server.bind, "0.0.0.0:9000");
+}
+
+#[test]
+fn test_config_partial_sections_keep_defaults() {
+    let config = Config::parse("[provider]\nmodel = \"nomic\"\n").unwrap();
+
+    assert_eq!(config.provider.model, "nomic");
+    assert_eq!(config.provider.url, "http://localhost:1234/v1/embeddings");
+    assert_eq!(config.storage.path, "./embeddings");
+}
+
+#[test]
+fn test_config_rejects_invalid_toml() {
+    assert!(Config::parse("[search]\ntop_k = \"many\"\n").is_err());
+    assert!(Config::parse("[search]\nmetric = \"manhattan\"\n").is_err());
+}
+
+#[test]
+fn test_config_overrides() {
+    let vars: HashMap<&str, &str> = HashMap::from([
+        ("BLAZE_STORAGE_PATH", "/tmp/store"),
+        ("BLAZE_PROVIDER_URL", "http://embed:8080"),
+        ("BLAZE_PROVIDER_API_KEY", "secret"),
+        ("BLAZE_INGEST_BATCH_SIZE", "128"),
+        ("BLAZE_SEARCH_METRIC", "euclidean"),
+        ("BLAZE_SEARCH_TOP_K", "3"),
+        ("BLAZE_SERVER_BIND", "0.0.0.0:1"),
+    ]);
+    let mut config = Config::default();
+
+    config
+        .apply_overrides(|key| vars.get(key).map(|v| v.to_string()))
+        .unwrap();
+
+    assert_eq!(config.storage.path, "/tmp/store");
+    assert_eq!(config.provider.url, "http://embed:8080");
+    assert_eq!(config.provider.api_key.as_deref(), Some("secret"));
+    assert_eq!(config.ingest.batch_size, 128);
+    assert_eq!(config.search.metric, Metrics::Euclidean);
+    assert_eq!(config.search.top_k, 3);
+    assert_eq!(config.server.bind, "0.0.0.0:1");
+    // Untouched values keep their previous setting
+    assert_eq!(config.provider.model, "text-embedding-qwen3-embedding-0.6b");
+}
+
+#[test]
+fn test_config_overrides_nested_ingest_settings() {
+    let vars: HashMap<&str, &str> = HashMap::from([
+        ("BLAZE_PROVIDER_CONNECT_TIMEOUT_SECS", "7"),
+        ("BLAZE_INGEST_DELIMITER", ";"),
+        ("BLAZE_INGEST_CHUNKER", "recursive"),
+        ("BLAZE_INGEST_CHUNKER_SIZE", "300"),
+        ("BLAZE_INGEST_CHUNKER_OVERLAP", "30"),
+        ("BLAZE_INGEST_DEDUP", "minhash"),
+        ("BLAZE_INGEST_DEDUP_THRESHOLD", "0.75"),
+    ]);
+    let mut config = Config::default();
+    config
+        .apply_overrides(|key| vars.get(key).map(|v| v.to_string()))
+        .unwrap();
+
+    assert_eq!(config.provider.connect_timeout_secs, 7);
+    assert_eq!(config.ingest.reader.delimiter, Some(';'));
+    assert!(matches!(
+        config.ingest.chunker,
+        Chunker::Recursive {
+            size: 300,
+            overlap: 30,
+            ..
+        }
+    ));
+    assert_eq!(
+        config.ingest.dedup.method,
+        DedupMethod::MinHash { threshold: 0.75 }
+    );
+
+    // Settings the selected chunker or method does not have are rejected
+    let mut config = Config::default();
+    let result =
+        config.apply_overrides(|key| (key == "BLAZE_INGEST_CHUNKER_SIZE").then(|| "10".into()));
+    assert!(result.unwrap_err().to_string().contains("no size"));
+    let result = config
+        .apply_overrides(|key| (key == "BLAZE_INGEST_DEDUP_MAX_DISTANCE").then(|| "3".into()));
+    assert!(result.unwrap_err().to_string().contains("no max_distance"));
+}
+
+#[test]
+fn test_config_invalid_override() {
+    let mut config = Config::default();
+    let result =
+        config.apply_overrides(|key| (key == "BLAZE_SEARCH_TOP_K").then(|| "lots".to_string()));
+
+    assert!(result.is_err());
+    assert!(
+        result
+            .unwrap_err()
+            .to_string()
+            .contains("BLAZE_SEARCH_TOP_K")
+    );
+}
+
+#[test]
+fn test_config_load_file_and_missing_file() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("blaze.toml");
+    std::fs::write(&path, "[storage]\npath = \"./custom\"\n").unwrap();
+
+    assert_eq!(Config::load(&path).unwrap().storage.path, "./custom");
+    assert!(Config::load(dir.path().join("missing.toml")).is_err());
+    assert_eq!(
+        Config::load_or_default(dir.path().join("missing.toml"))
+            .unwrap()
+            .storage
+            .path,
+        "./embeddings"
+    );
+}
+
+#[test]
+fn test_repository_config_parses() {
+    let config = Config::load(concat!(env!("CARGO_MANIFEST_DIR"), "/config.toml")).unwrap();
+    assert_eq!(config.storage.path, "./embeddings");
+}
+
+#[test]
+fn test_provider_from_config_redacts_api_key() {
+    let config = Config::parse("[provider]\napi_key = \"sk-very-secret\"\n").unwrap();
+    let provider = config.provider.build();
+
+    assert_eq!(provider.api_key.as_deref(), Some("sk-very-secret"));
+    assert!(!format!("{:?}", provider).contains("sk-very-secret"));
+}
+
+#[test]
+fn test_config_provider_limits() {
+    let config =
+        Config::parse("[provider]\ntimeout_secs = 5\nmax_retries = 2\nrequests_per_minute = 300\n")
+            .unwrap();
+    assert_eq!(config.provider.timeout_secs, 5);
+    assert_eq!(config.provider.connect_timeout_secs, 10);
+    assert_eq!(config.provider.tokens_per_minute, None);
+
+    let provider = config.provider.build();
+    assert_eq!(provider.timeout(), std::time::Duration::from_secs(5));
+    assert_eq!(provider.retry.max_retries, 2);
+    let limiter = provider.rate_limiter().unwrap();
+    assert_eq!(limiter.requests_per_minute(), Some(300));
+    assert!(Config::default().provider.build().rate_limiter().is_none());
+
+    let mut config = Config::default();
+    config
+        .apply_overrides(|key| (key == "BLAZE_PROVIDER_TOKENS_PER_MINUTE").then(|| "1000".into()))
+        .unwrap();
+    assert_eq!(config.provider.tokens_per_minute, Some(1000));
+    let result =
+        config.apply_overrides(|key| (key == "BLAZE_PROVIDER_MAX_RETRIES").then(|| "-1".into()));
+    assert!(
+        result
+            .unwrap_err()
+            .to_string()
+            .contains("BLAZE_PROVIDER_MAX_RETRIES")
+    );
+}
+
+#[test]
+fn test_config_provider_kind() {
+    let config = Config::parse(
+        "[provider]\nkind = \"cohere\"\nurl = \"https://api.cohere.com/v2/embed\"\ninput_type = \"classification\"\n",
+    )
+    .unwrap();
+    assert_eq!(config.provider.kind, ProviderKind::Cohere);
+    let provider = config.provider.build();
+    assert_eq!(provider.kind, ProviderKind::Cohere);
+    assert_eq!(provider.input_type, "classification");
+    assert_eq!(
+        Config::default().provider.build().kind,
+        ProviderKind::OpenAi
+    );
+    assert!(Config::parse("[provider]\nkind = \"bedrock\"\n").is_err());
+    assert!(Config::parse("[provider]\nkind = \"hashing\"\n").is_err());
+    let offline = Config::parse("[provider]\nkind = \"ollama\"\noffline = true\n").unwrap();
+    assert_eq!(offline.provider.embedder().model(), "hashing-384");
+
+    let mut config = Config::default();
+    config
+        .apply_overrides(|key| match key {
+            "BLAZE_PROVIDER_KIND" => Some("Ollama".into()),
+            "BLAZE_PROVIDER_DIMENSIONS" => Some("512".into()),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(config.provider.kind, ProviderKind::Ollama);
+    assert_eq!(config.provider.build().dimensions, Some(512));
+    config
+        .apply_overrides(|key| (key == "BLAZE_PROVIDER_OFFLINE").then(|| "true".into()))
+        .unwrap();
+    assert_eq!(config.provider.embedder().model(), "hashing-512");
+    let result = config.apply_overrides(|key| (key == "BLAZE_PROVIDER_KIND").then(|| "x".into()));
+    assert!(
+        result
+            .unwrap_err()
+            .to_string()
+            .contains("Unknown provider kind")
+    );
+}
@@ -0,0 +1,264 @@
+use blaze_db::config::Config;
+use blaze_db::prelude::{Ingestor, Metadata, MetadataValue};
+use blaze_db::utils::{
+    Chunk, DEFAULT_COSINE_THRESHOLD, DEFAULT_MINHASH_THRESHOLD, DEFAULT_SIMHASH_DISTANCE, Dedup,
+    DedupMethod, DedupPolicy, Deduplicator,
+};
+use tempfile::tempdir;
+
+const FOX: &str = "The quick brown fox jumps over the lazy dog while the farmer watches \
+                   from the porch of his old wooden house.";
+const FOX_EDITED: &str = "The quick brown fox jumps over the lazy dog while the farmer \
+                          watches from the porch of his old stone house.";
+
+fn chunk(text: &str, source: &str) -> Chunk {
+    let mut metadata = Metadata::new();
+    metadata.insert("source".to_string(), MetadataValue::from(source));
+    Chunk {
+        text: text.to_string(),
+        metadata,
+        ..Default::default()
+    }
+}
+
+fn texts(chunks: &[Chunk]) -> Vec<&str> {
+    chunks.iter().map(|chunk| chunk.text.as_str()).collect()
+}
+
+fn dedup(method: DedupMethod, policy: DedupPolicy, chunks: Vec<Chunk>) -> Vec<Chunk> {
+    Deduplicator::new(Dedup::new(method, policy)).dedup(chunks)
+}
+
+#[test]
+fn test_exact_dedup_policies() {
+    let chunks = vec![
+        chunk("header", "a.txt"),
+        chunk("body a", "a.txt"),
+        chunk("header", "b.txt"),
+        chunk("body b", "b.txt"),
+        chunk("header", "c.txt"),
+    ];
+
+    let mut deduplicator =
+        Deduplicator::new(Dedup::new(DedupMethod::Exact, DedupPolicy::KeepFirst));
+    let kept = deduplicator.dedup(chunks.clone());
+    assert_eq!(texts(&kept), ["header", "body a", "body b"]);
+    assert_eq!(kept[0].metadata["source"], MetadataValue::from("a.txt"));
+    assert_eq!(deduplicator.dropped(), 2);
+    // Later calls are compared with the chunks already kept
+    let kept = deduplicator.dedup(vec![chunk("body a", "d.txt"), chunk("new", "d.txt")]);
+    assert_eq!(texts(&kept), ["new"]);
+    assert_eq!(deduplicator.dropped(), 3);
+
+    let merged = dedup(
+        DedupMethod::Exact,
+        DedupPolicy::MergeMetadata,
+        chunks.clone(),
+    );
+    assert_eq!(texts(&merged), ["header", "body a", "body b"]);
+    assert_eq!(
+        merged[0].metadata["source"],
+        MetadataValue::List(vec!["a.txt".into(), "b.txt".into(), "c.txt".into()])
+    );
+    assert_eq!(merged[0].metadata["duplicates"], MetadataValue::Int(2));
+    assert!(!merged[1].metadata.contains_key("duplicates"));
+
+    let skipped = dedup(DedupMethod::Exact, DedupPolicy::Skip, chunks.clone());
+    assert_eq!(texts(&skipped), ["body a", "body b"]);
+
+    assert_eq!(
+        dedup(DedupMethod::None, DedupPolicy::Skip, chunks.clone()),
+        chunks
+    );
+}
+
+#[test]
+fn test_near_duplicates() {
+    let unrelated = "Prince Andrew rode out to the battle at dawn.";
+    let chunks = || {
+        vec![
+            chunk(FOX, "a.txt"),
+            chunk(unrelated, "a.txt"),
+            chunk(FOX_EDITED, "b.txt"),
+        ]
+    };
+    let methods = [
+        DedupMethod::MinHash {
+            threshold: DEFAULT_MINHASH_THRESHOLD,
+        },
+        DedupMethod::SimHash {
+            max_distance: DEFAULT_SIMHASH_DISTANCE,
+        },
+        DedupMethod::Cosine {
+            threshold: DEFAULT_COSINE_THRESHOLD,
+        },
+    ];
+    for method in methods {
+        let kept = dedup(method, DedupPolicy::KeepFirst, chunks());
+        assert_eq!(texts(&kept), [FOX, unrelated], "{:?}", method);
+    }
+
+    // Exact hashing tells the edited copy apart
+    let exact = dedup(DedupMethod::Exact, DedupPolicy::KeepFirst, chunks());
+    assert_eq!(exact.len(), 3);
+
+    // A stricter threshold keeps both
+    let strict = dedup(
+        DedupMethod::MinHash { threshold: 0.99 },
+        DedupPolicy::KeepFirst,
+        chunks(),
+    );
+    assert_eq!(strict.len(), 3);
+    let strict = dedup(
+        DedupMethod::SimHash { max_distance: 0 },
+        DedupPolicy::KeepFirst,
+        chunks(),
+    );
+    assert_eq!(strict.len(), 3);
+}
+
+#[test]
+fn test_dedup_config_and_parsing() {
+    let config = Config::parse(
+        "[ingest]\ndedup = { method = \"minhash\", threshold = 0.9, policy = \"skip\" }\n",
+    )
+    .unwrap();
+    assert_eq!(
+        config.ingest.dedup,
+        Dedup::new(DedupMethod::MinHash { threshold: 0.9 }, DedupPolicy::Skip)
+    );
+    assert!(!Config::default().ingest.dedup.is_enabled());
+
+    let mut config = Config::default();
+    config
+        .apply_overrides(|key| match key {
+            "BLAZE_INGEST_DEDUP" => Some("simhash".into()),
+            "BLAZE_INGEST_DEDUP_POLICY" => Some("merge".into()),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(
+        config.ingest.dedup,
+        Dedup::new(
+            DedupMethod::SimHash {
+                max_distance: DEFAULT_SIMHASH_DISTANCE
+            },
+            DedupPolicy::MergeMetadata
+        )
+    );
+    assert!("fuzzy".parse::<DedupMethod>().is_err());
+    assert!("drop".parse::<DedupPolicy>().is_err());
+}
+
+#[test]
+fn test_ingestor_dedups_before_batching() {
+    let dir = tempdir().unwrap();
+    let source = dir.path().join("log.txt");
+    let mut lines = Vec::new();
+    for i in 0..30 {
+        lines.push(format!("request {} served", i));
+        lines.push("heartbeat ok".to_string());
+    }
+    std::fs::write(&source, lines.join("\n")).unwrap();
+
+    let exact = Dedup::new(DedupMethod::Exact, DedupPolicy::KeepFirst);
+    let ingestor = Ingestor::new(&source, 8).with_dedup(exact);
+    let batches = ingestor.read_chunks().unwrap();
+    let chunks: Vec<Chunk> = batches.concat();
+    assert_eq!(chunks.len(), 31);
+    assert_eq!(batches.len(), 4);
+    assert_eq!(
+        chunks
+            .iter()
+            .filter(|chunk| chunk.text == "heartbeat ok")
+            .count(),
+        1
+    );
+    assert_eq!(ingestor.read_line().unwrap().concat().len(), 31);
+
+    // Streaming dedups across blocks
+    let streamed: Vec<Chunk> = ingestor
+        .batches()
+        .unwrap()
+        .with_block_bytes(40)
+        .flat_map(Result::unwrap)
+        .collect();
+    assert_eq!(streamed, chunks);
+}
+
+#[test]
+fn test_dedup_window_forgets_oldest_chunks() {
+    let methods = [
+        DedupMethod::Exact,
+        DedupMethod::MinHash {
+            threshold: DEFAULT_MINHASH_THRESHOLD,
+        },
+        DedupMethod::SimHash {
+            max_distance: DEFAULT_SIMHASH_DISTANCE,
+        },
+        DedupMethod::Cosine {
+            threshold: DEFAULT_COSINE_THRESHOLD,
+        },
+    ];
+    for method in methods {
+        let mut deduplicator =
+            Deduplicator::new(Dedup::new(method, DedupPolicy::KeepFirst)).with_window(2);
+        let first = deduplicator.dedup(vec![
+            chunk(FOX, "a.txt"),
+            chunk("Prince Andrew rode out to the battle at dawn.", "a.txt"),
+            chunk(FOX, "a.txt"),
+        ]);
+        assert_eq!(first.len(), 2, "{:?}", method);
+        // Still remembered
+        assert!(deduplicator.dedup(vec![chunk(FOX, "b.txt")]).is_empty());
+        let pushed = deduplicator.dedup(vec![chunk("Lorem ipsum dolor sit amet.", "b.txt")]);
+        assert_eq!(pushed.len(), 1);
+        // The fox fell out of the window, so a copy is kept again
+        assert_eq!(
+            texts(&deduplicator.dedup(vec![chunk(FOX, "c.txt")])),
+            [FOX],
+            "{:?}",
+            method
+        );
+        assert_eq!(deduplicator.dropped(), 2);
+    }
+}
+
+#[test]
+fn test_streaming_refuses_cosine_dedup() {
+    let dir = tempdir().unwrap();
+    let source = dir.path().join("notes.txt");
+    std::fs::write(&source, "one\n\ntwo\n").unwrap();
+    let cosine = Dedup::new(
+        DedupMethod::Cosine {
+            threshold: DEFAULT_COSINE_THRESHOLD,
+        },
+        DedupPolicy::KeepFirst,
+    );
+    let ingestor = Ingestor::new(&source, 8).with_dedup(cosine);
+    assert!(ingestor.batches().is_err());
+    assert_eq!(ingestor.read_chunks().unwrap().concat().len(), 2);
+}
+
+#[test]
+fn test_remember_and_rollback() {
+    let mut deduplicator = Deduplicator::new(Dedup::new(
+        DedupMethod::MinHash {
+            threshold: DEFAULT_MINHASH_THRESHOLD,
+        },
+        DedupPolicy::KeepFirst,
+    ));
+    deduplicator.remember(&["stored footer text here"]);
+    assert!(
+        deduplicator
+            .dedup(vec![chunk("stored footer text here", "a.txt")])
+            .is_empty()
+    );
+
+    // Chunks of a file that failed are forgotten, so a later file keeps its copy
+    let mark = deduplicator.mark();
+    assert_eq!(deduplicator.dedup(vec![chunk(FOX, "b.txt")]).len(), 1);
+    deduplicator.rollback(mark);
+    assert_eq!(deduplicator.dedup(vec![chunk(FOX, "c.txt")]).len(), 1);
+    assert!(deduplicator.dedup(vec![chunk(FOX, "d.txt")]).is_empty());
+}
@@ -0,0 +1,172 @@
+mod common;
+use blaze_db::prelude::{
+    EmbeddingStore, Filter, HnswConfig, HnswIndex, Metadata, MetadataValue, Metrics, SearchMode,
+    SearchQuery, VectorData,
+};
+use blaze_db::utils::EmbeddingData;
+use common::data_of;
+use tempfile::tempdir;
+
+fn metadata(pairs: Vec<(&str, MetadataValue)>) -> Metadata {
+    pairs.into_iter().map(|(k, v)| (k.to_string(), v)).collect()
+}
+
+fn tenant_data() -> VectorData {
+    let items = (0..20)
+        .map(|i| EmbeddingData {
+            id: format!("doc-{}", i),
+            chunk: format!("chunk {}", i),
+            embedding: vec![1.0, i as f32 * 0.1],
+            dimensions: 2,
+            metadata: metadata(vec![
+                ("tenant", if i % 2 == 0 { "acme" } else { "globex" }.into()),
+                ("line", (i as i64).into()),
+                (
+                    "tags",
+                    vec!["rust", if i < 5 { "intro" } else { "body" }].into(),
+                ),
+            ]),
+            ..Default::default()
+        })
+        .collect();
+    data_of(items)
+}
+
+#[test]
+fn test_filter_equality_and_ranges() {
+    let meta = metadata(vec![
+        ("tenant", "acme".into()),
+        ("line", 42i64.into()),
+        ("score", 0.5f64.into()),
+    ]);
+
+    assert!(Filter::eq("tenant", "acme").matches(&meta));
+    assert!(!Filter::eq("tenant", "globex").matches(&meta));
+    assert!(Filter::ne("tenant", "globex").matches(&meta));
+    assert!(Filter::gt("line", 41i64).matches(&meta));
+    assert!(!Filter::gt("line", 42i64).matches(&meta));
+    assert!(Filter::gte("line", 42i64).matches(&meta));
+    assert!(Filter::lt("score", 1i64).matches(&meta));
+    assert!(Filter::lte("line", 42.0f64).matches(&meta));
+    assert!(Filter::between("line", 40i64, 50i64).matches(&meta));
+    assert!(!Filter::gt("missing", 0i64).matches(&meta));
+    // Mismatched types never compare
+    assert!(!Filter::gt("tenant", 0i64).matches(&meta));
+}
+
+#[test]
+fn test_filter_in_exists_and_combinators() {
+    let meta = metadata(vec![
+        ("author", "tolstoy".into()),
+        ("tags", vec!["war", "peace"].into()),
+    ]);
+
+    assert!(Filter::is_in("author", vec!["tolstoy", "dostoevsky"]).matches(&meta));
+    assert!(!Filter::is_in("author", vec!["chekhov"]).matches(&meta));
+    assert!(Filter::eq("tags", "peace").matches(&meta));
+    assert!(Filter::exists("tags").matches(&meta));
+    assert!(!Filter::exists("date").matches(&meta));
+
+    let filter = Filter::eq("author", "tolstoy").and(Filter::eq("tags", "war"));
+    assert!(filter.matches(&meta));
+    let filter = Filter::eq("author", "chekhov").or(Filter::eq("tags", "war"));
+    assert!(filter.matches(&meta));
+    assert!(!Filter::eq("tags", "war").negate().matches(&meta));
+}
+
+#[test]
+fn test_filter_nested_fields() {
+    let source: serde_json::Value = serde_json::json!({"file": "a.txt", "line": 3});
+    let meta = metadata(vec![("source", MetadataValue::from(source))]);
+
+    assert!(Filter::eq("source.file", "a.txt").matches(&meta));
+    assert!(Filter::lt("source.line", 10i64).matches(&meta));
+    assert!(!Filter::exists("source.author").matches(&meta));
+}
+
+#[test]
+fn test_search_applies_filter_before_top_k() {
+    let data = tenant_data();
+    // The query favours high line numbers, which alternate between tenants
+    let query = SearchQuery::new(3, vec![0.0, 1.0], Metrics::DotProduct)
+        .with_filter(Filter::eq("tenant", "acme"));
+
+    let results = query.search(&data).unwrap();
+
+    assert_eq!(results.len(), 3);
+    let ids: Vec<&str> = results.iter().map(|r| r.id.as_str()).collect();
+    assert_eq!(ids, vec!["doc-18", "doc-16", "doc-14"]);
+    assert!(
+        results
+            .iter()
+            .all(|r| r.metadata.get("tenant") == Some(&"acme".into()))
+    );
+}
+
+#[test]
+fn test_hnsw_search_applies_filter() {
+    let data = tenant_data();
+    let index = HnswIndex::build(&data, Metrics::DotProduct, HnswConfig::default());
+    let query = SearchQuery::new(5, vec![0.0, 1.0], Metrics::DotProduct)
+        .with_mode(SearchMode::Approximate)
+        .with_filter(Filter::eq("tags", "intro").and(Filter::eq("tenant", "globex")));
+
+    let results = query.search_with_index(&data, &index).unwrap();
+
+    let ids: Vec<&str> = results.iter().map(|r| r.id.as_str()).collect();
+    assert_eq!(ids, vec!["doc-3", "doc-1"]);
+}
+
+#[tokio::test]
+async fn test_metadata_persists_in_batch_files() {
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path().to_str().unwrap();
+    let meta = metadata(vec![
+        ("source", "war_and_peace.txt".into()),
+        ("line", 12i64.into()),
+        ("weight", 0.25f64.into()),
+        ("published", true.into()),
+        ("tags", vec!["novel", "russian"].into()),
+    ]);
+    let item = EmbeddingData {
+        id: "wp-12".to_string(),
+        chunk: "Well, Prince".to_string(),
+        embedding: vec![0.1, 0.2],
+        dimensions: 2,
+        metadata: meta.clone(),
+        ..Default::default()
+    };
+
+    EmbeddingStore::new(0, vec![item])
+        .write_batch(dir_path)
+        .await
+        .unwrap();
+    let data = EmbeddingStore::read_binary(dir_path).await.unwrap();
+
+    assert_eq!(data.get_metadata(0), Some(&meta));
+}
+
+#[test]
+fn test_metadata_and_filter_json() {
+    let meta: Metadata =
+        serde_json::from_str(r#"{"tenant": "acme", "line": 7, "tags": ["a", "b"]}"#).unwrap();
+    assert_eq!(
+        meta.get("tenant"),
+        Some(&MetadataValue::String("acme".into()))
+    );
+    assert_eq!(meta.get("line"), Some(&MetadataValue::Int(7)));
+    assert_eq!(
+        serde_json::to_value(&meta).unwrap(),
+        serde_json::json!({"tenant": "acme", "line": 7, "tags": ["a", "b"]})
+    );
+
+    let filter: Filter = serde_json::from_str(
+        r#"{"and": [{"eq": {"field": "tenant", "value": "acme"}}, {"gte": {"field": "line", "value": 5}}]}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        filter,
+        Filter::eq("tenant", "acme").and(Filter::gte("line", 5i64))
+    );
+    assert!(filter.matches(&meta));
+}
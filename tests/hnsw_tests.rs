@@ -0,0 +1,173 @@
+mod common;
+
+use blaze_db::prelude::{HnswConfig, HnswIndex, Metrics, SearchMode, SearchQuery, VectorIndex};
+use common::{random_vectors, vector_data};
+use tempfile::tempdir;
+
+#[test]
+fn test_hnsw_build_indexes_every_vector() {
+    let data = vector_data(random_vectors(200, 16, 42));
+    let index = HnswIndex::build(&data, Metrics::Cosine, HnswConfig::default());
+
+    assert_eq!(index.len(), 200);
+    assert!(!index.is_empty());
+}
+
+#[test]
+fn test_hnsw_finds_exact_match() {
+    let data = vector_data(random_vectors(500, 16, 42));
+    let index = HnswIndex::build(&data, Metrics::Cosine, HnswConfig::default());
+
+    let query = SearchQuery::new(1, data.embedding[123].clone(), Metrics::Cosine)
+        .with_mode(SearchMode::Approximate);
+    let results = query.search_with_index(&data, &index).unwrap();
+
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].chunk, "chunk 123");
+    assert!((results[0].score - 1.0).abs() < 1e-5);
+}
+
+#[test]
+fn test_hnsw_recall_against_exact_search() {
+    let data = vector_data(random_vectors(1000, 32, 42));
+    let index = HnswIndex::build(&data, Metrics::Cosine, HnswConfig::default());
+    let queries = random_vectors(20, 32, 7);
+
+    let mut hits = 0;
+    for query_vector in queries {
+        let query = SearchQuery::new(10, query_vector, Metrics::Cosine);
+        let exact = query.search(&data).unwrap();
+        let approximate = index.search(&query, &data).unwrap();
+
+        hits += approximate
+            .iter()
+            .filter(|a| exact.iter().any(|e| e.chunk == a.chunk))
+            .count();
+    }
+
+    let recall = hits as f32 / 200.0;
+    assert!(recall >= 0.9, "recall too low: {}", recall);
+}
+
+#[test]
+fn test_search_mode_switches_per_query() {
+    let data = vector_data(random_vectors(300, 8, 42));
+    let index = HnswIndex::build(&data, Metrics::Euclidean, HnswConfig::default());
+    let query_vector = random_vectors(1, 8, 99).remove(0);
+
+    let exact = SearchQuery::new(5, query_vector.clone(), Metrics::Euclidean)
+        .search_with_index(&data, &index)
+        .unwrap();
+    let expected = SearchQuery::new(5, query_vector.clone(), Metrics::Euclidean)
+        .search(&data)
+        .unwrap();
+    let approximate = SearchQuery::new(5, query_vector, Metrics::Euclidean)
+        .with_mode(SearchMode::Approximate)
+        .search_with_index(&data, &index)
+        .unwrap();
+
+    assert_eq!(exact.len(), 5);
+    assert_eq!(approximate.len(), 5);
+    for (a, b) in exact.iter().zip(expected.iter()) {
+        assert_eq!(a.chunk, b.chunk);
+        assert_eq!(a.score, b.score);
+    }
+    // Approximate results must still be ranked best first
+    assert!(approximate.windows(2).all(|w| w[0].score >= w[1].score));
+}
+
+#[test]
+fn test_hnsw_empty_data() {
+    let data = vector_data(random_vectors(0, 4, 42));
+    let index = HnswIndex::build(&data, Metrics::DotProduct, HnswConfig::default());
+
+    let query = SearchQuery::new(3, vec![1.0, 0.0, 0.0, 0.0], Metrics::DotProduct);
+    assert!(index.is_empty());
+    assert!(index.search(&query, &data).unwrap().is_empty());
+}
+
+#[test]
+fn test_hnsw_top_k_larger_than_data() {
+    let data = vector_data(random_vectors(10, 4, 42));
+    let index = HnswIndex::build(&data, Metrics::Cosine, HnswConfig::default());
+
+    let query = SearchQuery::new(50, vec![0.5, 0.5, 0.5, 0.5], Metrics::Cosine);
+    assert_eq!(index.search(&query, &data).unwrap().len(), 10);
+}
+
+#[tokio::test]
+async fn test_hnsw_write_read_binary() {
+    let dir = tempdir().unwrap();
+    let data = vector_data(random_vectors(100, 8, 42));
+    let config = HnswConfig {
+        m: 8,
+        ef_construction: 64,
+        ef_search: 32,
+    };
+    let index = HnswIndex::build(&data, Metrics::Cosine, config);
+
+    index
+        .write_binary(dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+    assert!(dir.path().join("index.hnsw").exists());
+
+    let loaded = HnswIndex::read_binary(dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(loaded.len(), index.len());
+    assert_eq!(loaded.config.m, 8);
+    assert_eq!(loaded.config.ef_search, 32);
+    assert_eq!(loaded.metric, Metrics::Cosine);
+
+    let query = SearchQuery::new(5, data.embedding[0].clone(), Metrics::Cosine);
+    let before: Vec<String> = index
+        .search(&query, &data)
+        .unwrap()
+        .into_iter()
+        .map(|r| r.chunk)
+        .collect();
+    let after: Vec<String> = loaded
+        .search(&query, &data)
+        .unwrap()
+        .into_iter()
+        .map(|r| r.chunk)
+        .collect();
+    assert_eq!(before, after);
+}
+
+#[tokio::test]
+async fn test_hnsw_read_binary_missing_index() {
+    let dir = tempdir().unwrap();
+    let result = HnswIndex::read_binary(dir.path().to_str().unwrap()).await;
+
+    assert!(result.is_err());
+    assert!(
+        result
+            .unwrap_err()
+            .to_string()
+            .contains("Failed to read index")
+    );
+}
+
+#[test]
+fn test_search_skips_deleted_vectors() {
+    let mut data = vector_data(random_vectors(50, 8, 42));
+    data.ids = (0..50).map(|i| format!("id-{}", i)).collect();
+    let index = HnswIndex::build(&data, Metrics::Cosine, HnswConfig::default());
+    data.deleted.insert(10);
+
+    let query_vector = data.embedding[10].clone();
+    let exact = SearchQuery::new(3, query_vector.clone(), Metrics::Cosine)
+        .search(&data)
+        .unwrap();
+    let approximate = SearchQuery::new(3, query_vector, Metrics::Cosine)
+        .with_mode(SearchMode::Approximate)
+        .search_with_index(&data, &index)
+        .unwrap();
+
+    assert!(exact.iter().all(|r| r.id != "id-10"));
+    assert!(approximate.iter().all(|r| r.id != "id-10"));
+    assert!(exact.iter().all(|r| r.id.starts_with("id-")));
+}
@@ -27,17 +27,59 @@ fn test_ingestor_invalid_batch_size() {
 }
 
 #[test]
-#[should_panic(expected = "Source file must exist and be a file")]
+#[should_panic(expected = "Source file must exist or be a directory")]
 fn test_ingestor_nonexistent_file() {
     Ingestor::new("/nonexistent/file.txt", 8);
 }
 
 #[test]
-#[should_panic(expected = "Source file must exist and be a file")]
-fn test_ingestor_directory_instead_of_file() {
+fn test_ingestor_lists_directory_files() {
     let dir = tempdir().unwrap();
-    // Try to create ingestor with directory path instead of file
-    Ingestor::new(dir.path(), 8);
+    for path in [
+        "guide.md",
+        "notes.txt",
+        "api/index.html",
+        "api/v1/old.md",
+        "drafts/wip.md",
+        ".git/HEAD",
+        "build/out.md",
+    ] {
+        let path = dir.path().join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "content").unwrap();
+    }
+    let relative = |ingestor: &Ingestor| -> Vec<String> {
+        ingestor
+            .files()
+            .unwrap()
+            .iter()
+            .map(|path| {
+                let path = path.strip_prefix(dir.path()).unwrap();
+                path.to_string_lossy().replace('\\', "/")
+            })
+            .collect()
+    };
+
+    // Hidden directories are skipped
+    let all = Ingestor::new(dir.path(), 8);
+    assert_eq!(relative(&all).len(), 6);
+
+    let docs = Ingestor::new(dir.path(), 8)
+        .with_include(vec!["*.{md,html}".into()])
+        .with_exclude(vec!["drafts".into(), "api/v1/**".into()]);
+    assert_eq!(
+        relative(&docs),
+        vec!["api/index.html", "build/out.md", "guide.md"]
+    );
+    assert!(docs.selects("deep/er/page.md"));
+    assert!(!docs.selects("api/v1/x/y.md"));
+
+    let rooted = Ingestor::new(dir.path(), 8).with_include(vec!["api/**/*.?d".into()]);
+    assert_eq!(relative(&rooted), vec!["api/v1/old.md"]);
+
+    // A file source is its only file
+    let file = dir.path().join("notes.txt");
+    assert_eq!(Ingestor::new(&file, 8).files().unwrap(), vec![file]);
 }
 
 #[test]
@@ -26,12 +26,14 @@ async fn test_ingest_to_storage_pipeline() {
             chunk: "This is line 1".to_string(),
             embedding: vec![1.0, 2.0, 3.0],
             dimensions: 3,
+            ..Default::default()
         },
         EmbeddingData {
             index: 1,
             chunk: "This is line 2".to_string(),
             embedding: vec![4.0, 5.0, 6.0],
             dimensions: 3,
+            ..Default::default()
         },
     ];
 
@@ -83,6 +85,7 @@ async fn test_multiple_batch_processing() {
             chunk: format!("Line number {}", i + 1),
             embedding: vec![i as f32, (i + 1) as f32],
             dimensions: 2,
+            ..Default::default()
         })
         .collect();
 
@@ -95,6 +98,7 @@ async fn test_multiple_batch_processing() {
             chunk: format!("Line number {}", i + 9),
             embedding: vec![(i + 8) as f32, (i + 9) as f32],
             dimensions: 2,
+            ..Default::default()
         })
         .collect();
 
@@ -152,18 +156,21 @@ async fn test_unicode_text_processing() {
             chunk: "Hello 世界! This is unicode text.".to_string(),
             embedding: vec![1.0, 2.0],
             dimensions: 2,
+            ..Default::default()
         },
         EmbeddingData {
             index: 1,
             chunk: "Café, naïve, résumé - accented characters".to_string(),
             embedding: vec![3.0, 4.0],
             dimensions: 2,
+            ..Default::default()
         },
         EmbeddingData {
             index: 2,
             chunk: "😭 Emoji support test 🤧".to_string(),
             embedding: vec![5.0, 6.0],
             dimensions: 2,
+            ..Default::default()
         },
     ];
 
@@ -219,6 +226,7 @@ async fn test_large_embedding_dimensions() {
         chunk: "Test with large embedding dimensions".to_string(),
         embedding: embedding_vector.clone(),
         dimensions: 1536,
+        ..Default::default()
     }];
 
     let store = EmbeddingStore::new(0, embeddings);
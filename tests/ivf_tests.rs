@@ -0,0 +1,289 @@
+mod common;
+
+use blaze_db::prelude::{
+    EmbeddingStore, Filter, IndexType, IvfConfig, IvfIndex, Metrics, SearchMode, SearchQuery,
+    VectorData, VectorIndex, recall_at_k,
+};
+use blaze_db::utils::{EmbeddingData, MetadataValue};
+use common::{clustered_vectors, data_of, items, items_from};
+use tempfile::tempdir;
+
+/// Tag each item with one of four `group`s
+fn grouped(items: Vec<EmbeddingData>) -> Vec<EmbeddingData> {
+    items
+        .into_iter()
+        .map(|mut item| {
+            item.metadata = [(
+                "group".to_string(),
+                MetadataValue::Int((item.index % 4) as i64),
+            )]
+            .into();
+            item
+        })
+        .collect()
+}
+
+/// Clustered vectors in four groups
+fn clustered_data(count: usize, dimensions: usize) -> VectorData {
+    let vectors = clustered_vectors(count, dimensions, 32, 0.3, 1);
+    data_of(grouped(items(vectors)))
+}
+
+fn config(lists: usize, nprobe: usize) -> IvfConfig {
+    IvfConfig {
+        lists,
+        nprobe,
+        ..Default::default()
+    }
+}
+
+fn mean_recall(index: &IvfIndex, data: &VectorData, metric: Metrics, nprobe: usize) -> f32 {
+    let queries = clustered_vectors(20, 16, 32, 0.3, 9);
+    let total: f32 = queries
+        .into_iter()
+        .map(|vector| {
+            let query = SearchQuery::new(10, vector, metric);
+            recall_at_k(
+                &query.search(data).unwrap(),
+                &index.search_nprobe(&query, data, nprobe).unwrap(),
+            )
+        })
+        .sum();
+    total / 20.0
+}
+
+#[test]
+fn test_ivf_build_files_every_vector() {
+    let data = clustered_data(1000, 16);
+    let index = IvfIndex::build(&data, Metrics::Euclidean, config(32, 4)).unwrap();
+
+    assert_eq!(index.list_count(), 32);
+    assert_eq!(index.len(), 1000);
+    assert_eq!(index.indexed(), 1000);
+    let mut positions: Vec<u32> = (0..32).flat_map(|l| index.list(l).to_vec()).collect();
+    positions.sort();
+    assert_eq!(positions, (0..1000).collect::<Vec<u32>>());
+}
+
+#[test]
+fn test_ivf_recall_improves_with_nprobe() {
+    let data = clustered_data(2000, 16);
+
+    for metric in [Metrics::Cosine, Metrics::Euclidean, Metrics::DotProduct] {
+        let index = IvfIndex::build(&data, metric, config(32, 4)).unwrap();
+        let narrow = mean_recall(&index, &data, metric, 1);
+        let wide = mean_recall(&index, &data, metric, 8);
+        let all = mean_recall(&index, &data, metric, 32);
+
+        assert!(wide >= narrow, "{:?}: {} < {}", metric, wide, narrow);
+        assert!(wide >= 0.9, "{:?} recall {}", metric, wide);
+        assert_eq!(all, 1.0);
+    }
+}
+
+#[test]
+fn test_ivf_composes_with_search_query() {
+    let data = clustered_data(500, 16);
+    let index = IvfIndex::build(&data, Metrics::Cosine, config(16, 16)).unwrap();
+    let query = SearchQuery::new(
+        5,
+        clustered_vectors(1, 16, 32, 0.3, 3).remove(0),
+        Metrics::Cosine,
+    )
+    .with_filter(Filter::eq("group", 2))
+    .with_mode(SearchMode::Approximate);
+
+    let approximate = query.search_with_index(&data, &index).unwrap();
+    let exact = query.search(&data).unwrap();
+    assert_eq!(approximate.len(), 5);
+    assert!(
+        approximate
+            .iter()
+            .all(|r| r.metadata.get("group") == Some(&MetadataValue::Int(2)))
+    );
+    assert_eq!(
+        approximate.iter().map(|r| &r.id).collect::<Vec<_>>(),
+        exact.iter().map(|r| &r.id).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_ivf_incremental_update() {
+    let mut data = clustered_data(500, 16);
+    let mut index = IvfIndex::build(&data, Metrics::Euclidean, config(16, 2)).unwrap();
+
+    // A nightly batch lands; new vectors are searchable before the lists are updated
+    let fresh = clustered_vectors(50, 16, 32, 0.3, 21);
+    let target = fresh[7].clone();
+    data.apply(EmbeddingStore::new(1, grouped(items_from(500, fresh))));
+    let query = SearchQuery::new(1, target, Metrics::Euclidean);
+    assert_eq!(index.search(&query, &data).unwrap()[0].id, "doc-507");
+
+    assert_eq!(index.update(&data), 50);
+    assert_eq!(index.len(), 550);
+    assert_eq!(index.update(&data), 0);
+    assert_eq!(index.search(&query, &data).unwrap()[0].id, "doc-507");
+}
+
+#[test]
+fn test_ivf_skips_deleted_vectors_and_prunes() {
+    let mut data = clustered_data(300, 16);
+    let mut index = IvfIndex::build(&data, Metrics::DotProduct, config(8, 8)).unwrap();
+    let target = data.embedding[42].clone();
+
+    data.apply(EmbeddingStore::deletion(1, vec!["doc-42".to_string()]));
+    let query = SearchQuery::new(3, target, Metrics::Euclidean);
+    assert!(
+        index
+            .search(&query, &data)
+            .unwrap()
+            .iter()
+            .all(|r| r.id != "doc-42")
+    );
+
+    index.prune(&data);
+    assert_eq!(index.len(), 299);
+}
+
+#[test]
+fn test_ivf_rejects_empty_data_and_bad_dimensions() {
+    assert!(IvfIndex::build(&VectorData::default(), Metrics::Cosine, config(4, 1)).is_err());
+    assert!(IvfIndex::build(&clustered_data(10, 4), Metrics::Cosine, config(0, 1)).is_err());
+
+    // Fewer vectors than lists shrinks the list count
+    let data = clustered_data(10, 4);
+    let index = IvfIndex::build(&data, Metrics::Cosine, config(64, 4)).unwrap();
+    assert_eq!(index.list_count(), 10);
+    let wrong = SearchQuery::new(3, vec![1.0; 8], Metrics::Cosine);
+    assert!(index.search(&wrong, &data).is_err());
+}
+
+#[test]
+fn test_index_type_selects_flat_or_ivf() {
+    let data = clustered_data(400, 16);
+    let query = SearchQuery::new(
+        5,
+        clustered_vectors(1, 16, 32, 0.3, 5).remove(0),
+        Metrics::Cosine,
+    );
+
+    assert!("IVF".parse::<IndexType>().unwrap() == IndexType::Ivf);
+    assert!("annoy".parse::<IndexType>().is_err());
+    assert!(
+        IndexType::Flat
+            .build(&data, Metrics::Cosine)
+            .unwrap()
+            .is_none()
+    );
+
+    for kind in [IndexType::Flat, IndexType::Hnsw, IndexType::Ivf] {
+        let index = kind.build(&data, Metrics::Cosine).unwrap();
+        let results = query.search_in(&data, index.as_deref()).unwrap();
+        assert_eq!(results.len(), 5, "{:?}", kind);
+    }
+}
+
+#[tokio::test]
+async fn test_index_type_open_persists_and_updates() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let mut data = clustered_data(300, 16);
+    let query = SearchQuery::new(
+        5,
+        clustered_vectors(1, 16, 32, 0.3, 5).remove(0),
+        Metrics::Cosine,
+    );
+
+    assert!(
+        IndexType::Ivf
+            .open(path, &VectorData::default(), Metrics::Cosine)
+            .await
+            .unwrap()
+            .is_none()
+    );
+    for (kind, file) in [
+        (IndexType::Hnsw, "index.hnsw"),
+        (IndexType::Ivf, "index.ivf"),
+    ] {
+        let index = kind.open(path, &data, Metrics::Cosine).await.unwrap();
+        assert!(dir.path().join(file).exists(), "{:?}", kind);
+        assert_eq!(query.search_in(&data, index.as_deref()).unwrap().len(), 5);
+    }
+
+    // Vectors added later are filed into the saved indexes on the next open
+    data.apply(EmbeddingStore::new(
+        1,
+        grouped(items_from(300, clustered_vectors(20, 16, 32, 0.3, 9))),
+    ));
+    let target = data.position("doc-310").unwrap();
+    let exact = SearchQuery::new(1, data.embedding[target].clone(), Metrics::Cosine);
+    for kind in [IndexType::Hnsw, IndexType::Ivf] {
+        let index = kind.open(path, &data, Metrics::Cosine).await.unwrap();
+        let results = exact.search_in(&data, index.as_deref()).unwrap();
+        assert_eq!(results[0].id, "doc-310", "{:?}", kind);
+    }
+    assert_eq!(
+        IvfIndex::read_binary(path).await.unwrap().indexed(),
+        data.embedding.len()
+    );
+    // The new vectors were saved as their own list segment
+    assert!(dir.path().join("index.0.ivf").exists());
+    assert!(dir.path().join("index.300.ivf").exists());
+}
+
+#[tokio::test]
+async fn test_ivf_write_read_binary() {
+    let dir = tempdir().unwrap();
+    let data = clustered_data(200, 16);
+    let index = IvfIndex::build(&data, Metrics::Euclidean, config(8, 3)).unwrap();
+    index
+        .write_binary(dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+
+    let loaded = IvfIndex::read_binary(dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(loaded.config, index.config);
+    assert_eq!(loaded.list(3), index.list(3));
+    assert_eq!(loaded.centroid(5), index.centroid(5));
+}
+
+#[tokio::test]
+async fn test_ivf_lists_are_saved_per_segment() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let mut data = clustered_data(200, 16);
+    let mut index = IvfIndex::build(&data, Metrics::Euclidean, config(8, 3)).unwrap();
+    index.write_binary(path).await.unwrap();
+
+    data.apply(EmbeddingStore::new(
+        1,
+        grouped(items_from(200, clustered_vectors(50, 16, 32, 0.3, 7))),
+    ));
+    let indexed = index.indexed();
+    assert_eq!(index.update(&data), 50);
+    index.write_segment(path, indexed).await.unwrap();
+
+    let loaded = IvfIndex::read_binary(path).await.unwrap();
+    assert_eq!(loaded.indexed(), 250);
+    for list in 0..index.list_count() {
+        assert_eq!(loaded.list(list), index.list(list));
+    }
+
+    // Segments after a gap are not read; `update` files those positions again
+    std::fs::remove_file(dir.path().join("index.0.ivf")).unwrap();
+    let mut loaded = IvfIndex::read_binary(path).await.unwrap();
+    assert_eq!(loaded.indexed(), 0);
+    assert!(loaded.is_empty());
+    assert_eq!(loaded.update(&data), 250);
+    assert_eq!(loaded.len(), index.len());
+
+    // Retraining replaces the segments filed against the old centroids
+    let retrained = IvfIndex::build(&data, Metrics::Euclidean, config(4, 2)).unwrap();
+    retrained.write_binary(path).await.unwrap();
+    assert!(!dir.path().join("index.200.ivf").exists());
+    let loaded = IvfIndex::read_binary(path).await.unwrap();
+    assert_eq!(loaded.list_count(), 4);
+    assert_eq!(loaded.indexed(), 250);
+}
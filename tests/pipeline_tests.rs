@@ -0,0 +1,182 @@
+use axum::routing::post;
+use axum::{Json, Router};
+use blaze_db::prelude::{EmbeddingStore, Provider, VectorData};
+use blaze_db::utils::{BatchOutcome, Checkpoint, Pipeline, RetryPolicy};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+use tempfile::tempdir;
+use tokio::net::TcpListener;
+
+/// Mock embeddings endpoint that tracks concurrent requests and rejects any batch whose
+/// first line starts with "bad" while `failing` is set
+#[derive(Default)]
+struct MockProvider {
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+    requests: AtomicUsize,
+    failing: AtomicBool,
+}
+
+async fn spawn_provider(mock: Arc<MockProvider>) -> Provider {
+    let app = Router::new().route(
+        "/v1/embeddings",
+        post(move |Json(body): Json<Value>| {
+            let mock = mock.clone();
+            async move {
+                mock.requests.fetch_add(1, Ordering::SeqCst);
+                let now = mock.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                mock.max_in_flight.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                mock.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+                let input: Vec<String> = serde_json::from_value(body["input"].clone()).unwrap();
+                if mock.failing.load(Ordering::SeqCst) && input[0].starts_with("bad") {
+                    return Err(axum::http::StatusCode::BAD_REQUEST);
+                }
+                let data: Vec<Value> = input
+                    .iter()
+                    .enumerate()
+                    .map(|(i, line)| json!({"index": i, "embedding": [line.len() as f32, 1.0]}))
+                    .collect();
+                Ok(Json(json!({ "data": data })))
+            }
+        }),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/v1/embeddings", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    Provider::new(url, "mock").with_retry(RetryPolicy::none())
+}
+
+fn batches(count: usize, bad: &[usize]) -> Vec<Vec<String>> {
+    (0..count)
+        .map(|b| {
+            let prefix = if bad.contains(&b) { "bad" } else { "line" };
+            (0..4).map(|l| format!("{} {} {}", prefix, b, l)).collect()
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_pipeline_bounds_concurrency() {
+    let dir = tempdir().unwrap();
+    let store = dir.path().to_str().unwrap();
+    let mock = Arc::new(MockProvider::default());
+    let provider = spawn_provider(mock.clone()).await;
+
+    let mut data = VectorData::default();
+    let mut stored = Vec::new();
+    let report = Pipeline::new(provider, store)
+        .with_concurrency(3)
+        .run("job", &batches(12, &[]), &mut data, |outcome| {
+            if let BatchOutcome::Stored { batch, items } = outcome {
+                assert_eq!(*items, 4);
+                stored.push(*batch);
+            }
+        })
+        .await
+        .unwrap();
+
+    assert!(report.is_complete());
+    assert_eq!(report.stored, 12);
+    let max = mock.max_in_flight.load(Ordering::SeqCst);
+    assert!((2..=3).contains(&max), "max in flight {}", max);
+    stored.sort();
+    assert_eq!(stored, (0..12).collect::<Vec<_>>());
+
+    assert_eq!(data.total_vectors, 48);
+    assert!(data.position("5-3").is_some());
+    assert_eq!(data.model, "mock");
+    // Finished jobs leave no checkpoint and everything is folded into batch files
+    assert!(!Checkpoint::path(store, "job").exists());
+    assert_eq!(
+        EmbeddingStore::read_binary(store)
+            .await
+            .unwrap()
+            .total_vectors,
+        48
+    );
+}
+
+#[tokio::test]
+async fn test_pipeline_resumes_failed_batches() {
+    let dir = tempdir().unwrap();
+    let store = dir.path().to_str().unwrap();
+    let mock = Arc::new(MockProvider::default());
+    mock.failing.store(true, Ordering::SeqCst);
+    let pipeline = Pipeline::new(spawn_provider(mock.clone()).await, store).with_id_prefix("war:");
+    let input = batches(10, &[3, 7]);
+
+    let mut data = EmbeddingStore::open(store).await.unwrap();
+    let report = pipeline
+        .run("war", &input, &mut data, |_| {})
+        .await
+        .unwrap();
+    assert_eq!(report.stored, 8);
+    let failed: Vec<usize> = report.failed.iter().map(|(batch, _)| *batch).collect();
+    assert_eq!(failed, vec![3, 7]);
+    assert!(report.failed[0].1.contains("400"));
+
+    let checkpoint = Checkpoint::load(store, "war", 10).await.unwrap();
+    assert_eq!(checkpoint.completed.len(), 8);
+    assert!(!checkpoint.completed.contains(&3));
+
+    // A restart only asks the provider for the missing batches
+    mock.failing.store(false, Ordering::SeqCst);
+    let before = mock.requests.load(Ordering::SeqCst);
+    let mut data = EmbeddingStore::open(store).await.unwrap();
+    let mut skipped = 0;
+    let report = pipeline
+        .run("war", &input, &mut data, |outcome| {
+            if matches!(outcome, BatchOutcome::Skipped { .. }) {
+                skipped += 1;
+            }
+        })
+        .await
+        .unwrap();
+    assert_eq!(mock.requests.load(Ordering::SeqCst) - before, 2);
+    assert_eq!((report.skipped, report.stored, skipped), (8, 2, 8));
+    assert!(report.is_complete());
+    assert_eq!(data.total_vectors, 40);
+    assert!(data.position("war:7-0").is_some());
+    assert!(!Checkpoint::path(store, "war").exists());
+}
+
+#[tokio::test]
+async fn test_checkpoint_resets_when_job_changes() {
+    let dir = tempdir().unwrap();
+    let store = dir.path().to_str().unwrap();
+    let mut checkpoint = Checkpoint::load(store, "job", 5).await.unwrap();
+    checkpoint.completed.extend([0, 1, 2]);
+    checkpoint.save(store).await.unwrap();
+
+    assert_eq!(Checkpoint::load(store, "job", 5).await.unwrap(), checkpoint);
+    assert!(!checkpoint.is_complete());
+    // The source now splits into a different number of batches
+    assert!(
+        Checkpoint::load(store, "job", 6)
+            .await
+            .unwrap()
+            .completed
+            .is_empty()
+    );
+    assert!(
+        Checkpoint::load(store, "other", 5)
+            .await
+            .unwrap()
+            .completed
+            .is_empty()
+    );
+
+    Checkpoint::clear(store, "job").await.unwrap();
+    Checkpoint::clear(store, "job").await.unwrap();
+    assert!(
+        Checkpoint::load(store, "job", 5)
+            .await
+            .unwrap()
+            .completed
+            .is_empty()
+    );
+}
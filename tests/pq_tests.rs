@@ -0,0 +1,139 @@
+mod common;
+
+use blaze_db::prelude::{
+    Metrics, PqCodec, PqConfig, PqIndex, SearchMode, SearchQuery, VectorData, VectorSource,
+    recall_at_k,
+};
+use common::{clustered_vectors, random_values, vector_data};
+use tempfile::tempdir;
+
+fn config(sub_quantizers: usize, bits: usize) -> PqConfig {
+    PqConfig {
+        sub_quantizers,
+        bits,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_pq_codec_rejects_invalid_configs() {
+    let data = vector_data(clustered_vectors(50, 12, 16, 0.2, 1));
+
+    assert!(PqCodec::train(&data, config(5, 8)).is_err());
+    assert!(PqCodec::train(&data, config(4, 0)).is_err());
+    assert!(PqCodec::train(&data, config(4, 9)).is_err());
+    assert!(PqCodec::train(&VectorData::default(), config(4, 8)).is_err());
+    assert!(PqCodec::train(&data, config(4, 8)).is_ok());
+}
+
+#[test]
+fn test_pq_codec_encode_decode() {
+    let data = vector_data(clustered_vectors(500, 16, 16, 0.2, 2));
+    let codec = PqCodec::train(&data, config(4, 6)).unwrap();
+
+    assert_eq!(codec.sub_dimensions(), 4);
+    assert_eq!(codec.centroids(), 64);
+    assert_eq!(codec.codebook(3).len(), 64 * 4);
+
+    let mut total_error = 0.0;
+    for idx in 0..data.len() {
+        let codes = codec.encode(data.vector(idx));
+        assert_eq!(codes.len(), 4);
+        assert!(codes.iter().all(|&c| (c as usize) < 64));
+        let decoded = codec.decode(&codes);
+        total_error += data
+            .vector(idx)
+            .iter()
+            .zip(&decoded)
+            .map(|(a, b)| (a - b) * (a - b))
+            .sum::<f32>();
+    }
+    // Noise around the cluster centres has a variance of about 0.013 per dimension
+    assert!(total_error / (data.len() * 16) as f32 <= 0.02);
+}
+
+#[test]
+fn test_pq_training_is_deterministic() {
+    let data = vector_data(clustered_vectors(300, 8, 16, 0.2, 3));
+    let a = PqCodec::train(&data, config(2, 4)).unwrap();
+    let b = PqCodec::train(&data, config(2, 4)).unwrap();
+    assert_eq!(a, b);
+}
+
+#[test]
+fn test_pq_lookup_table_matches_decoded_vectors() {
+    let data = vector_data(clustered_vectors(200, 8, 16, 0.2, 4));
+    let index = PqIndex::build(&data, config(4, 4)).unwrap();
+    let query = random_values(8, 77);
+
+    for metric in [Metrics::Cosine, Metrics::Euclidean, Metrics::DotProduct] {
+        let table = index.codec.lookup_table(&query, metric).unwrap();
+        for idx in [0, 17, 199] {
+            let decoded = index.codec.decode(index.codes(idx));
+            let norm = decoded.iter().map(|x| x * x).sum::<f32>().sqrt();
+            let expected = metric.calculate(&query, &decoded);
+            assert!((table.score(index.codes(idx), norm) - expected).abs() < 1e-4);
+        }
+    }
+    // A short query is an error, not an out-of-bounds slice
+    assert!(
+        index
+            .codec
+            .lookup_table(&query[..6], Metrics::Cosine)
+            .is_err()
+    );
+}
+
+#[test]
+fn test_pq_search_recall_with_rescoring() {
+    let data = vector_data(clustered_vectors(2000, 32, 16, 0.2, 5));
+    let index = PqIndex::build(&data, config(8, 6))
+        .unwrap()
+        .with_rescore(Some(10));
+    let queries = clustered_vectors(10, 32, 16, 0.2, 5);
+
+    for metric in [Metrics::Cosine, Metrics::Euclidean, Metrics::DotProduct] {
+        let mut recall = 0.0;
+        for vector in &queries {
+            let query =
+                SearchQuery::new(10, vector.clone(), metric).with_mode(SearchMode::Approximate);
+            recall += recall_at_k(
+                &query.search(&data).unwrap(),
+                &query.search_with_index(&data, &index).unwrap(),
+            );
+        }
+        let recall = recall / queries.len() as f32;
+        assert!(recall >= 0.9, "{:?} recall {}", metric, recall);
+    }
+    assert!(index.memory_usage_mb() * 4.0 < data.memory_usage_mb());
+}
+
+#[test]
+fn test_pq_insert_checks_dimensions() {
+    let data = vector_data(clustered_vectors(100, 8, 16, 0.2, 6));
+    let mut index = PqIndex::build(&data, config(2, 4)).unwrap();
+
+    index.insert(&[0.5; 8]).unwrap();
+    assert_eq!(index.len(), 101);
+    assert!(index.insert(&[0.5; 4]).is_err());
+    let wrong = SearchQuery::new(3, vec![0.1; 4], Metrics::Cosine);
+    assert!(index.search_source(&wrong, &data).is_err());
+}
+
+#[tokio::test]
+async fn test_pq_index_write_read_binary() {
+    let dir = tempdir().unwrap();
+    let data = vector_data(clustered_vectors(200, 8, 16, 0.2, 7));
+    let index = PqIndex::build(&data, config(2, 5)).unwrap();
+    index
+        .write_binary(dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+
+    let loaded = PqIndex::read_binary(dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(loaded.codec, index.codec);
+    assert_eq!(loaded.len(), 200);
+    assert_eq!(loaded.codes(10), index.codes(10));
+}
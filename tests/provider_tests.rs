@@ -1,4 +1,15 @@
-use blaze_db::prelude::Provider;
+use axum::Json;
+use axum::Router;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use blaze_db::prelude::{EmbeddingProvider, HashingProvider, Provider, ProviderKind};
+use blaze_db::utils::{RateLimiter, RetryPolicy, estimate_tokens, retry_after};
+use serde_json::{Value, json};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
 
 #[test]
 fn test_provider_creation() {
@@ -68,3 +79,359 @@ fn test_provider_debug() {
     assert!(debug_str.contains("http://localhost:8080"));
     assert!(debug_str.contains("test-model"));
 }
+
+/// Serve `/v1/embeddings`, answering the first `failures` requests with `status`
+async fn flaky_server(
+    failures: usize,
+    status: StatusCode,
+    retry_after: Option<&'static str>,
+) -> (String, Arc<AtomicUsize>) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let app = Router::new().route(
+        "/v1/embeddings",
+        post(move || {
+            let counter = counter.clone();
+            async move {
+                if counter.fetch_add(1, Ordering::SeqCst) < failures {
+                    let mut headers = HeaderMap::new();
+                    if let Some(value) = retry_after {
+                        headers.insert("retry-after", value.parse().unwrap());
+                    }
+                    return (status, headers, "busy").into_response();
+                }
+                let body = json!({"data": [{"index": 0, "embedding": [0.5, 0.5]}]});
+                Response::new(body.to_string().into())
+            }
+        }),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    (format!("http://{}/v1/embeddings", address), calls)
+}
+
+fn fast_retry(max_retries: u32) -> RetryPolicy {
+    RetryPolicy {
+        max_retries,
+        initial_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(50),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_backoff_grows_and_is_capped() {
+    let policy = RetryPolicy {
+        initial_backoff: Duration::from_millis(100),
+        max_backoff: Duration::from_secs(1),
+        ..Default::default()
+    };
+    assert_eq!(policy.backoff(0), Duration::from_millis(100));
+    assert_eq!(policy.backoff(2), Duration::from_millis(400));
+    assert_eq!(policy.backoff(10), Duration::from_secs(1));
+    for attempt in 0..6 {
+        let delay = policy.delay(attempt, None).unwrap();
+        assert!(delay >= policy.backoff(attempt) / 2 && delay <= policy.backoff(attempt));
+    }
+
+    assert!(RetryPolicy::is_retryable(StatusCode::TOO_MANY_REQUESTS));
+    assert!(RetryPolicy::is_retryable(StatusCode::BAD_GATEWAY));
+    assert!(!RetryPolicy::is_retryable(StatusCode::UNAUTHORIZED));
+}
+
+#[test]
+fn test_retry_after_header() {
+    let mut headers = HeaderMap::new();
+    assert_eq!(retry_after(&headers), None);
+    headers.insert("retry-after", "2".parse().unwrap());
+    assert_eq!(retry_after(&headers), Some(Duration::from_secs(2)));
+
+    let mut policy = fast_retry(1);
+    // Retry-After wins over the computed backoff, even past max_backoff, up to a limit
+    assert_eq!(
+        policy.delay(0, Some(&headers)),
+        Some(Duration::from_secs(2))
+    );
+    policy.max_retry_after = Duration::from_secs(1);
+    assert_eq!(policy.delay(0, Some(&headers)), None);
+    headers.insert(
+        "retry-after",
+        "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap(),
+    );
+    assert_eq!(retry_after(&headers), None);
+}
+
+#[tokio::test]
+async fn test_retries_throttled_and_failing_requests() {
+    let (url, calls) = flaky_server(2, StatusCode::TOO_MANY_REQUESTS, Some("0")).await;
+    let provider = Provider::new(url, "m").with_retry(fast_retry(3));
+    let embeddings = provider.fetch_embedding("hello").await.unwrap();
+    assert_eq!(embeddings.data[0].embedding, vec![0.5, 0.5]);
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+    let (url, calls) = flaky_server(5, StatusCode::SERVICE_UNAVAILABLE, None).await;
+    let provider = Provider::new(url, "m").with_retry(fast_retry(2));
+    let error = provider.fetch_embedding("hello").await.unwrap_err();
+    assert!(format!("{:#}", error).contains("503"));
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_long_retry_after_and_refused_connections() {
+    let (url, calls) = flaky_server(1, StatusCode::TOO_MANY_REQUESTS, Some("3600")).await;
+    let provider = Provider::new(url, "m").with_retry(fast_retry(3));
+    let error = provider.fetch_embedding("hello").await.unwrap_err();
+    assert!(format!("{:#}", error).contains("retry after more than 300s"));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    // Nothing listens on a port that was just released
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/v1/embeddings", listener.local_addr().unwrap());
+    drop(listener);
+    let provider = Provider::new(url, "m").with_retry(fast_retry(2));
+    let error = provider.fetch_embedding("hello").await.unwrap_err();
+    assert!(format!("{:#}", error).contains("Giving up after 3 attempts"));
+}
+
+#[tokio::test]
+async fn test_client_errors_are_not_retried() {
+    let (url, calls) = flaky_server(1, StatusCode::UNAUTHORIZED, None).await;
+    let provider = Provider::new(url, "m").with_retry(fast_retry(3));
+    assert!(provider.fetch_embedding("hello").await.is_err());
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_request_timeout() {
+    let app = Router::new().route(
+        "/v1/embeddings",
+        post(|| async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            "late"
+        }),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/v1/embeddings", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let provider = Provider::new(url, "m")
+        .with_timeouts(Duration::from_secs(1), Duration::from_millis(100))
+        .with_retry(fast_retry(1));
+    let started = Instant::now();
+    assert!(provider.fetch_embedding("hello").await.is_err());
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert_eq!(provider.timeout(), Duration::from_millis(100));
+}
+
+#[tokio::test]
+async fn test_rate_limiter_spreads_requests() {
+    // 1200 requests per minute: a burst of 1200, then one every 50ms
+    let limiter = RateLimiter::new(Some(1200), None);
+    let started = Instant::now();
+    for _ in 0..1200 {
+        limiter.acquire(1).await;
+    }
+    assert!(started.elapsed() < Duration::from_millis(50));
+    for _ in 0..3 {
+        limiter.acquire(1).await;
+    }
+    assert!(started.elapsed() >= Duration::from_millis(120));
+
+    // 6000 tokens per minute refill at 100 per second
+    let limiter = RateLimiter::new(None, Some(6000));
+    limiter.acquire(6000).await;
+    let started = Instant::now();
+    limiter.acquire(30).await;
+    assert!(started.elapsed() >= Duration::from_millis(250));
+    // Larger than a whole minute is clamped instead of waiting forever
+    let limiter = RateLimiter::new(None, Some(100));
+    limiter.acquire(1_000_000).await;
+
+    assert_eq!(estimate_tokens(&["abcdefgh".to_string(), String::new()]), 3);
+}
+
+/// Serve `path`, remembering every request body and answering it with `respond`
+async fn adapter_server(
+    path: &'static str,
+    respond: fn(&Value) -> Value,
+) -> (String, Arc<Mutex<Vec<Value>>>) {
+    let bodies = Arc::new(Mutex::new(Vec::new()));
+    let seen = bodies.clone();
+    let app = Router::new().route(
+        path,
+        post(move |Json(body): Json<Value>| {
+            let seen = seen.clone();
+            async move {
+                let response = respond(&body);
+                seen.lock().unwrap().push(body);
+                Json(response)
+            }
+        }),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    (format!("http://{}{}", address, path), bodies)
+}
+
+/// One embedding per input text: its length and its position
+fn vectors(texts: &Value) -> Vec<Value> {
+    texts
+        .as_array()
+        .unwrap()
+        .iter()
+        .enumerate()
+        .map(|(i, text)| json!([text.as_str().unwrap().len() as f32, i as f32]))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_openai_adapter_sends_dimensions() {
+    let (url, bodies) = adapter_server("/v1/embeddings", |body| {
+        // Answer out of order; results are matched to chunks by index
+        let data: Vec<Value> = vectors(&body["input"])
+            .into_iter()
+            .enumerate()
+            .rev()
+            .map(|(index, embedding)| json!({"index": index, "embedding": embedding}))
+            .collect();
+        json!({ "data": data })
+    })
+    .await;
+    let provider: Arc<dyn EmbeddingProvider> = Arc::new(
+        Provider::new(url, "text-embedding-3-small")
+            .with_api_key("sk-test")
+            .with_dimensions(256),
+    );
+
+    let chunks = vec!["a".to_string(), "bcd".to_string()];
+    let mut embeddings = provider.fetch_embeddings(&chunks).await.unwrap().data;
+    embeddings.sort_by_key(|e| e.index);
+    assert_eq!(embeddings[1].chunk, "bcd");
+    assert_eq!(embeddings[1].embedding, vec![3.0, 1.0]);
+    assert_eq!(embeddings[1].dimensions, 2);
+    assert_eq!(provider.model(), "text-embedding-3-small");
+
+    let body = bodies.lock().unwrap()[0].clone();
+    assert_eq!(body["model"], "text-embedding-3-small");
+    assert_eq!(body["dimensions"], 256);
+    assert_eq!(body["input"], json!(["a", "bcd"]));
+}
+
+#[tokio::test]
+async fn test_ollama_and_tei_adapters() {
+    let (url, bodies) = adapter_server(
+        "/api/embed",
+        |body| json!({"model": body["model"], "embeddings": vectors(&body["input"])}),
+    )
+    .await;
+    let ollama = Provider::new(url, "nomic-embed-text").with_kind(ProviderKind::Ollama);
+    let embeddings = ollama
+        .fetch_embeddings(&["hi".to_string(), "there".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(embeddings.data.len(), 2);
+    assert_eq!(embeddings.data[1].embedding, vec![5.0, 1.0]);
+    assert_eq!(embeddings.data[1].chunk, "there");
+    let body = bodies.lock().unwrap()[0].clone();
+    assert_eq!(
+        body,
+        json!({"model": "nomic-embed-text", "input": ["hi", "there"]})
+    );
+    // A response in another provider's format is an error, not an empty result
+    let wrong =
+        Provider::new(ollama.url.clone(), "nomic-embed-text").with_retry(RetryPolicy::none());
+    let error = wrong.fetch_embedding("hi").await.unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("Unexpected openai embeddings response")
+    );
+
+    let (url, bodies) = adapter_server("/embed", |body| json!(vectors(&body["inputs"]))).await;
+    let tei = Provider::new(url, "bge-small").with_kind(ProviderKind::Tei);
+    let embeddings = tei.fetch_query("query").await.unwrap();
+    assert_eq!(embeddings.data[0].embedding, vec![5.0, 0.0]);
+    assert_eq!(bodies.lock().unwrap()[0], json!({"inputs": ["query"]}));
+}
+
+#[tokio::test]
+async fn test_cohere_adapter_input_types() {
+    let (url, bodies) = adapter_server(
+        "/v2/embed",
+        |body| json!({"id": "x", "embeddings": {"float": vectors(&body["texts"])}}),
+    )
+    .await;
+    let provider = Provider::new(url, "embed-english-v3.0").with_kind(ProviderKind::Cohere);
+
+    let documents = provider
+        .fetch_embeddings(&["doc one".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(documents.data[0].embedding, vec![7.0, 0.0]);
+    let query = EmbeddingProvider::fetch_query(&provider, "q")
+        .await
+        .unwrap();
+    assert_eq!(query.data[0].chunk, "q");
+
+    let bodies = bodies.lock().unwrap().clone();
+    assert_eq!(bodies[0]["texts"], json!(["doc one"]));
+    assert_eq!(bodies[0]["model"], "embed-english-v3.0");
+    assert_eq!(bodies[0]["input_type"], "search_document");
+    assert_eq!(bodies[1]["input_type"], "search_query");
+
+    // Cohere v1 returns a plain list
+    let (url, _) = adapter_server(
+        "/v1/embed",
+        |body| json!({"embeddings": vectors(&body["texts"])}),
+    )
+    .await;
+    let provider = Provider::new(url, "embed-english-v3.0")
+        .with_kind(ProviderKind::Cohere)
+        .with_input_type("clustering");
+    let embeddings = provider.fetch_embedding("abc").await.unwrap();
+    assert_eq!(embeddings.data[0].embedding, vec![3.0, 0.0]);
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+#[tokio::test]
+async fn test_hashing_provider_is_deterministic() {
+    let provider = HashingProvider::new(128);
+    let chunks = vec![
+        "War and Peace".to_string(),
+        "peace and war!".to_string(),
+        "Quantum chromodynamics".to_string(),
+        String::new(),
+    ];
+    let embeddings = provider.fetch_embeddings(&chunks).await.unwrap().data;
+    assert_eq!(embeddings.len(), 4);
+    assert_eq!(provider.model(), "hashing-128");
+    for (i, embedding) in embeddings.iter().enumerate() {
+        assert_eq!(embedding.index, i);
+        assert_eq!(embedding.chunk, chunks[i]);
+        assert_eq!(embedding.dimensions, 128);
+        let norm = cosine(&embedding.embedding, &embedding.embedding);
+        assert!((norm - 1.0).abs() < 1e-5, "norm {}", norm);
+    }
+
+    // Same words in another order and case give the same bag of features
+    let same = cosine(&embeddings[0].embedding, &embeddings[1].embedding);
+    let unrelated = cosine(&embeddings[0].embedding, &embeddings[2].embedding);
+    assert!((same - 1.0).abs() < 1e-5);
+    assert!(unrelated < 0.5, "unrelated {}", unrelated);
+    // Shared trigrams pull inflections together
+    let inflected = cosine(&provider.embed("wars"), &provider.embed("war"));
+    assert!(inflected > unrelated);
+
+    // Another instance, or a query, embeds identically
+    let query = provider.fetch_query("War and Peace").await.unwrap();
+    assert_eq!(query.data[0].embedding, embeddings[0].embedding);
+    assert_eq!(
+        HashingProvider::new(128).embed("War and Peace"),
+        embeddings[0].embedding
+    );
+}
@@ -0,0 +1,167 @@
+mod common;
+
+use blaze_db::prelude::{
+    Filter, Metrics, QuantizedIndex, ScalarQuantizer, SearchMode, SearchQuery, Segment, VectorData,
+    VectorIndex, VectorSource, recall_at_k,
+};
+use blaze_db::utils::MetadataValue;
+use common::{data_of, items, random_vectors};
+use tempfile::tempdir;
+
+/// Random vectors, every other one tagged `even`
+fn tagged_data(count: usize, dimensions: usize) -> VectorData {
+    let items = items(random_vectors(count, dimensions, 42))
+        .into_iter()
+        .map(|mut item| {
+            item.metadata = [("even".to_string(), MetadataValue::Bool(item.index % 2 == 0))].into();
+            item
+        })
+        .collect();
+    data_of(items)
+}
+
+#[test]
+fn test_scalar_quantizer_roundtrip_error_is_bounded() {
+    let data = tagged_data(100, 8);
+    let quantizer = ScalarQuantizer::train(&data);
+
+    assert_eq!(quantizer.dimensions(), 8);
+    for idx in 0..data.len() {
+        let original = data.vector(idx);
+        let decoded = quantizer.decode(&quantizer.encode(original));
+        for (d, (a, b)) in original.iter().zip(&decoded).enumerate() {
+            assert!((a - b).abs() <= quantizer.scale[d] / 2.0 + 1e-6);
+        }
+    }
+}
+
+#[test]
+fn test_quantized_scores_track_exact_scores() {
+    let data = tagged_data(50, 32);
+    let index = QuantizedIndex::build(&data);
+    let query = random_vectors(1, 32, 7).remove(0);
+
+    for metric in [Metrics::Cosine, Metrics::Euclidean, Metrics::DotProduct] {
+        for idx in 0..data.len() {
+            let exact = metric.calculate(&query, data.vector(idx));
+            let approx = index.score(metric, &query, idx);
+            assert!(
+                (exact - approx).abs() < 0.05,
+                "{:?} at {}: {} vs {}",
+                metric,
+                idx,
+                exact,
+                approx
+            );
+        }
+    }
+}
+
+#[test]
+fn test_quantized_recall_and_rescoring() {
+    let data = tagged_data(1000, 32);
+    let queries = random_vectors(20, 32, 99);
+
+    for metric in [Metrics::Cosine, Metrics::Euclidean, Metrics::DotProduct] {
+        let index = QuantizedIndex::build(&data).with_rescore(Some(4));
+        let report = index.report(&data, &queries, 10, metric).unwrap();
+
+        assert!(
+            report.recall >= 0.85,
+            "{:?} recall {}",
+            metric,
+            report.recall
+        );
+        let rescored = report.rescored_recall.unwrap();
+        assert!(rescored >= report.recall);
+        assert!(
+            rescored >= 0.98,
+            "{:?} rescored recall {}",
+            metric,
+            rescored
+        );
+        // Four bytes per dimension against one, plus a norm per vector and two floats
+        // of range per dimension
+        let index_bytes = 1000 * 32 + 1000 * 4 + 32 * 8;
+        assert_eq!(
+            report.compression_ratio,
+            (1000 * 32 * 4) as f32 / index_bytes as f32
+        );
+        assert!(report.compression_ratio < 4.0);
+    }
+}
+
+#[test]
+fn test_quantized_rescored_scores_are_exact() {
+    let data = tagged_data(300, 16);
+    let index = QuantizedIndex::build(&data).with_rescore(Some(3));
+    let query = SearchQuery::new(5, random_vectors(1, 16, 3).remove(0), Metrics::Cosine)
+        .with_mode(SearchMode::Approximate);
+
+    let results = query.search_with_index(&data, &index).unwrap();
+    assert_eq!(results.len(), 5);
+    // Rescored candidates carry the same score as the exact scan gives them
+    let mut exact = query.clone();
+    exact.top_k = data.len();
+    let exact = exact.search(&data).unwrap();
+    for result in &results {
+        let expected = exact.iter().find(|e| e.id == result.id).unwrap();
+        assert_eq!(result.score, expected.score);
+    }
+    assert!(results.windows(2).all(|w| w[0].score >= w[1].score));
+}
+
+#[test]
+fn test_quantized_search_applies_filters_and_rejects_bad_dimensions() {
+    let data = tagged_data(200, 8);
+    let index = QuantizedIndex::build(&data);
+
+    let query = SearchQuery::new(10, vec![0.5; 8], Metrics::DotProduct)
+        .with_filter(Filter::eq("even", true));
+    let results = index.search(&query, &data).unwrap();
+    assert_eq!(results.len(), 10);
+    assert!(
+        results
+            .iter()
+            .all(|r| r.metadata.get("even") == Some(&MetadataValue::Bool(true)))
+    );
+
+    let wrong = SearchQuery::new(10, vec![0.5; 3], Metrics::Cosine);
+    assert!(index.search(&wrong, &data).is_err());
+}
+
+#[test]
+fn test_quantized_index_rescores_from_segment_on_disk() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("0.seg");
+    let data = tagged_data(500, 16);
+    Segment::write(&path, &data, None).unwrap();
+    let segment = Segment::open(&path).unwrap();
+
+    let index = QuantizedIndex::build(&segment).with_rescore(Some(4));
+    let query = SearchQuery::new(10, random_vectors(1, 16, 11).remove(0), Metrics::Euclidean);
+
+    let exact = query.search_source(&segment).unwrap();
+    let approx = index.search_source(&query, &segment).unwrap();
+    assert_eq!(recall_at_k(&exact, &approx), 1.0);
+    assert!(index.memory_usage_mb() * 3.0 < data.memory_usage_mb());
+}
+
+#[tokio::test]
+async fn test_quantized_index_write_read_binary() {
+    let dir = tempdir().unwrap();
+    let data = tagged_data(100, 8);
+    let index = QuantizedIndex::build(&data).with_rescore(Some(2));
+    index
+        .write_binary(dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+
+    let loaded = QuantizedIndex::read_binary(dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(loaded.len(), 100);
+    assert_eq!(loaded.rescore, Some(2));
+    assert_eq!(loaded.quantizer, index.quantizer);
+    assert_eq!(loaded.codes(42), index.codes(42));
+}
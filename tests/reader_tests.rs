@@ -0,0 +1,189 @@
+use blaze_db::config::Config;
+use blaze_db::prelude::{Ingestor, MetadataValue};
+use blaze_db::utils::{Chunker, Format, Reader, html_to_text};
+use std::path::Path;
+use tempfile::tempdir;
+
+#[test]
+fn test_format_from_extension() {
+    assert_eq!(
+        Format::from_path(Path::new("a/README.md")),
+        Format::Markdown
+    );
+    assert_eq!(Format::from_path(Path::new("page.HTM")), Format::Html);
+    assert_eq!(
+        Format::from_path(Path::new("tickets.ndjson")),
+        Format::Jsonl
+    );
+    assert_eq!(Format::from_path(Path::new("export.tsv")), Format::Csv);
+    assert_eq!(Format::from_path(Path::new("book.txt")), Format::Text);
+    assert_eq!(Format::from_path(Path::new("LICENSE")), Format::Text);
+
+    let explicit = Reader::default().with_format(Format::Jsonl);
+    assert_eq!(explicit.format_of(Path::new("dump.txt")), Format::Jsonl);
+    assert!("docx".parse::<Format>().is_err());
+}
+
+#[test]
+fn test_html_drops_scripts_and_styles() {
+    let html = r#"<!DOCTYPE html>
+<html><head><title>Release &amp; notes</title>
+<style>body { color: red; }</style>
+<script type="text/javascript">if (a < b) { alert("<p>hi</p>"); }</script></head>
+<body>
+  <h1>Version   2.0</h1>
+  <!-- <p>hidden comment</p> -->
+  <p>Fixes <b>crash</b> on&nbsp;start &lt;urgent&gt; &#8212; see <a href="/t?a=1&amp;b=2">#12</a>.</p>
+  <ul><li>One</li><li>Two</li></ul>
+  <NOSCRIPT>Enable JavaScript</NOSCRIPT>
+</body></html>"#;
+    let (text, title) = html_to_text(html);
+    assert_eq!(title.as_deref(), Some("Release & notes"));
+    assert_eq!(
+        text,
+        "Version 2.0\n\nFixes crash on start <urgent> \u{2014} see #12.\n\nOne\nTwo"
+    );
+
+    let documents = Reader::default().parse(html, Format::Html).unwrap();
+    assert_eq!(
+        documents[0].metadata["title"],
+        MetadataValue::from("Release & notes")
+    );
+}
+
+#[test]
+fn test_html_unclosed_hidden_elements() {
+    assert_eq!(html_to_text("<p>hi</p><script>var x").0, "hi");
+    assert_eq!(html_to_text("<p>hi</p><style>p { color").0, "hi");
+    assert_eq!(html_to_text("<p>hi</p><noscript>").0, "hi");
+    let (text, title) = html_to_text("<title>Draft");
+    assert_eq!((text.as_str(), title.as_deref()), ("", Some("Draft")));
+    // A closing tag cut off before its `>`
+    assert_eq!(html_to_text("<p>hi</p><script>x()</script").0, "hi");
+}
+
+#[test]
+fn test_markdown_front_matter() {
+    let markdown = "---\ntitle: \"Onboarding\"\nowner: platform\n---\n# Welcome\nHello.\n";
+    let documents = Reader::default().parse(markdown, Format::Markdown).unwrap();
+    assert_eq!(documents.len(), 1);
+    assert_eq!(documents[0].text, "# Welcome\nHello.\n");
+    assert_eq!(
+        documents[0].metadata["title"],
+        MetadataValue::from("Onboarding")
+    );
+    assert_eq!(
+        documents[0].metadata["owner"],
+        MetadataValue::from("platform")
+    );
+
+    // A horizontal rule is not front matter
+    let plain = "Intro\n---\nMore\n";
+    let documents = Reader::default().parse(plain, Format::Markdown).unwrap();
+    assert_eq!(documents[0].text, plain);
+    assert!(documents[0].metadata.is_empty());
+}
+
+#[test]
+fn test_jsonl_text_fields_and_metadata() {
+    let jsonl = r#"{"id": 7, "title": "Login fails", "fields": {"body": "SSO loops", "priority": 2}}
+
+{"id": 8, "title": "", "fields": {"body": ""}}
+{"id": 9, "title": "Slow search", "tags": ["perf"], "fields": {"body": "p99 is 3s"}}
+"#;
+    let reader = Reader::default().with_text_fields(vec!["title".into(), "fields.body".into()]);
+    let documents = reader.parse(jsonl, Format::Jsonl).unwrap();
+    assert_eq!(documents.len(), 2);
+    assert_eq!(documents[0].text, "Login fails\n\nSSO loops");
+    assert_eq!(documents[0].metadata["id"], MetadataValue::Int(7));
+    assert_eq!(documents[0].metadata["record"], MetadataValue::Int(0));
+    // The object holding the text is not copied into metadata
+    assert!(!documents[0].metadata.contains_key("fields"));
+    assert_eq!(
+        documents[1].metadata["tags"],
+        MetadataValue::from(vec!["perf"])
+    );
+
+    let reader = reader.with_metadata_fields(vec!["id".into(), "fields.priority".into()]);
+    let documents = reader.parse(jsonl, Format::Jsonl).unwrap();
+    assert_eq!(documents[0].metadata.len(), 3);
+    assert_eq!(
+        documents[0].metadata["fields.priority"],
+        MetadataValue::Int(2)
+    );
+
+    let error = reader
+        .parse("{\"title\": \"ok\"}\nnot json\n", Format::Jsonl)
+        .unwrap_err();
+    assert!(error.to_string().contains("line 2"));
+}
+
+#[test]
+fn test_csv_quoting_and_columns() {
+    let csv = "\u{feff}key,summary,description,votes\r\n\
+        T-1,Crash,\"Fails on start, \"\"always\"\"\nwith trace\",3\r\n\
+        \r\n\
+        T-2,Typo,,0.5\r\n\
+        T-3,,,\n";
+    let reader = Reader::default().with_text_fields(vec!["summary".into(), "description".into()]);
+    let documents = reader.parse(csv, Format::Csv).unwrap();
+    assert_eq!(documents.len(), 2);
+    assert_eq!(
+        documents[0].text,
+        "Crash\n\nFails on start, \"always\"\nwith trace"
+    );
+    assert_eq!(documents[0].metadata["key"], MetadataValue::from("T-1"));
+    assert_eq!(documents[0].metadata["votes"], MetadataValue::Int(3));
+    assert_eq!(documents[1].text, "Typo");
+    assert_eq!(documents[1].metadata["votes"], MetadataValue::Float(0.5));
+    assert_eq!(documents[1].metadata["record"], MetadataValue::Int(1));
+
+    let missing = Reader::default().parse(csv, Format::Csv).unwrap_err();
+    assert!(missing.to_string().contains("no column text"));
+}
+
+#[test]
+fn test_ingestor_reads_tsv_rows_as_chunks() {
+    let dir = tempdir().unwrap();
+    let source = dir.path().join("tickets.tsv");
+    std::fs::write(
+        &source,
+        "text\tteam\nFirst ticket. It has two sentences.\tcore\nSecond ticket\tweb\n",
+    )
+    .unwrap();
+
+    let batches = Ingestor::new(&source, 8)
+        .with_chunker(Chunker::Sentence)
+        .read_chunks()
+        .unwrap();
+    let chunks: Vec<_> = batches.into_iter().flatten().collect();
+    assert_eq!(chunks.len(), 3);
+    assert_eq!(chunks[1].text, "It has two sentences.");
+    assert_eq!(chunks[1].start, 14);
+    assert_eq!(chunks[1].metadata["team"], MetadataValue::from("core"));
+    assert_eq!(chunks[2].metadata["team"], MetadataValue::from("web"));
+}
+
+#[test]
+fn test_reader_from_config() {
+    let config = Config::parse(
+        "[ingest]\nformat = \"csv\"\ntext_fields = [\"summary\"]\ndelimiter = \";\"\nbatch_size = 16\n",
+    )
+    .unwrap();
+    assert_eq!(config.ingest.batch_size, 16);
+    assert_eq!(config.ingest.reader.format, Some(Format::Csv));
+    assert_eq!(config.ingest.reader.text_fields, vec!["summary"]);
+    assert_eq!(config.ingest.reader.delimiter, Some(';'));
+    assert_eq!(Config::default().ingest.reader, Reader::default());
+
+    let mut config = Config::default();
+    config
+        .apply_overrides(|key| match key {
+            "BLAZE_INGEST_FORMAT" => Some("jsonl".into()),
+            "BLAZE_INGEST_TEXT_FIELDS" => Some("title, body".into()),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(config.ingest.reader.format, Some(Format::Jsonl));
+    assert_eq!(config.ingest.reader.text_fields, vec!["title", "body"]);
+}
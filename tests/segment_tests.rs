@@ -0,0 +1,214 @@
+mod common;
+use blaze_db::prelude::{
+    EmbeddingStore, Filter, MappedStore, Metrics, SearchQuery, Segment, VectorData, VectorSource,
+};
+use blaze_db::utils::{EmbeddingData, MetadataValue};
+use common::data_of;
+use tempfile::tempdir;
+
+fn sample_data() -> VectorData {
+    let items = (0..20)
+        .map(|i| EmbeddingData {
+            id: format!("doc-{}", i),
+            chunk: format!("chunk {} – naïve café ✓", i),
+            embedding: vec![(i as f32).sin(), (i as f32).cos(), i as f32 / 10.0],
+            dimensions: 3,
+            metadata: [("n".to_string(), MetadataValue::Int(i))].into(),
+            ..Default::default()
+        })
+        .collect();
+    data_of(items)
+}
+
+#[test]
+fn test_segment_roundtrip() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("0.seg");
+    let data = sample_data();
+
+    Segment::write(&path, &data, Some(Metrics::Euclidean)).unwrap();
+    let segment = Segment::open(&path).unwrap();
+
+    assert_eq!(segment.len(), 20);
+    assert_eq!(segment.dimensions(), 3);
+    assert_eq!(segment.header().metric_hint, Some(Metrics::Euclidean));
+    assert!(!segment.header().normalized);
+    assert_eq!(segment.vectors().len(), 60);
+    for idx in 0..20 {
+        assert_eq!(segment.vector(idx), data.get_vector(idx).unwrap());
+        assert_eq!(segment.chunk(idx), data.get_chunk(idx).unwrap());
+        assert_eq!(segment.id(idx), format!("doc-{}", idx));
+        assert_eq!(
+            segment.metadata(idx).unwrap().get("n"),
+            Some(&MetadataValue::Int(idx as i64))
+        );
+    }
+}
+
+#[test]
+fn test_segment_search_matches_in_memory() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("0.seg");
+    let data = sample_data();
+    Segment::write(&path, &data, None).unwrap();
+    let segment = Segment::open(&path).unwrap();
+    for idx in 0..segment.len() {
+        assert_eq!(segment.norm(idx), data.norm(idx));
+    }
+
+    for metric in [Metrics::Cosine, Metrics::Euclidean, Metrics::DotProduct] {
+        let query =
+            SearchQuery::new(5, vec![0.3, -0.2, 0.9], metric).with_filter(Filter::gte("n", 5));
+        let expected = query.search(&data).unwrap();
+        let actual = query.search_source(&segment).unwrap();
+
+        assert_eq!(expected.len(), actual.len());
+        for (a, b) in expected.iter().zip(&actual) {
+            assert_eq!(a.id, b.id);
+            assert_eq!(a.score, b.score);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_segment_skips_deleted_vectors() {
+    let dir = tempdir().unwrap();
+    let store = dir.path().to_str().unwrap();
+    let mut data = sample_data();
+    EmbeddingStore::delete(store, &mut data, vec!["doc-3".to_string()])
+        .await
+        .unwrap();
+
+    let path = dir.path().join("0.seg");
+    Segment::write(&path, &data, None).unwrap();
+    let segment = Segment::open(&path).unwrap();
+
+    assert_eq!(segment.len(), 19);
+    assert!((0..segment.len()).all(|idx| segment.id(idx) != "doc-3"));
+    let copy = segment.to_vector_data();
+    assert_eq!(copy.total_vectors, 19);
+    assert_eq!(copy.position("doc-4"), Some(3));
+}
+
+#[test]
+fn test_segment_empty() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("empty.seg");
+
+    Segment::write(&path, &VectorData::default(), None).unwrap();
+    let segment = Segment::open(&path).unwrap();
+
+    assert!(segment.is_empty());
+    assert!(segment.vectors().is_empty());
+    assert!(
+        SearchQuery::new(3, vec![1.0], Metrics::Cosine)
+            .search_source(&segment)
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_segment_rejects_corrupt_files() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("bad.seg");
+
+    std::fs::write(&path, b"not a segment at all").unwrap();
+    assert!(Segment::open(&path).is_err());
+
+    // Valid header followed by a truncated body
+    Segment::write(&path, &sample_data(), None).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+    assert!(Segment::open(&path).is_err());
+
+    // Unknown version
+    let mut bytes = bytes.clone();
+    bytes[8] = 99;
+    std::fs::write(&path, &bytes).unwrap();
+    let error = Segment::open(&path).err().unwrap();
+    assert!(format!("{:#}", error).contains("version"));
+
+    // Sizes that overflow when added up are errors, not panics
+    let mut huge_count = bytes.clone();
+    huge_count[12..16].copy_from_slice(&0u32.to_le_bytes());
+    huge_count[16..24].copy_from_slice(&u64::MAX.to_le_bytes());
+    huge_count[32..40].copy_from_slice(&64u64.to_le_bytes());
+    std::fs::write(&path, &huge_count).unwrap();
+    assert!(Segment::open(&path).is_err());
+    let mut huge_metadata = bytes.clone();
+    huge_metadata[56..64].copy_from_slice(&u64::MAX.to_le_bytes());
+    std::fs::write(&path, &huge_metadata).unwrap();
+    assert!(Segment::open(&path).is_err());
+}
+
+#[test]
+fn test_segment_open_dir() {
+    let dir = tempdir().unwrap();
+    let data = sample_data();
+    Segment::write(dir.path().join("b.seg"), &data, None).unwrap();
+    Segment::write(dir.path().join("a.seg"), &VectorData::default(), None).unwrap();
+    std::fs::write(dir.path().join("other.bin"), b"ignored").unwrap();
+
+    let segments = Segment::open_dir(dir.path()).unwrap();
+    assert_eq!(segments.len(), 2);
+    assert!(segments[0].is_empty());
+    assert_eq!(segments[1].len(), 20);
+}
+
+#[tokio::test]
+async fn test_sealed_store_is_searched_through_its_segment() {
+    let dir = tempdir().unwrap();
+    let store = dir.path().to_str().unwrap();
+    let item = |id: &str, embedding: Vec<f32>| EmbeddingData {
+        id: id.to_string(),
+        chunk: format!("chunk {}", id),
+        dimensions: embedding.len(),
+        embedding,
+        ..Default::default()
+    };
+    let mut data = EmbeddingStore::open(store).await.unwrap();
+    EmbeddingStore::upsert(
+        store,
+        &mut data,
+        vec![
+            item("a", vec![1.0, 0.0]),
+            item("b", vec![0.0, 1.0]),
+            item("c", vec![0.7, 0.7]),
+        ],
+    )
+    .await
+    .unwrap();
+    assert_eq!(EmbeddingStore::seal(store).await.unwrap(), 3);
+
+    // Written after sealing: read on top of the segment
+    EmbeddingStore::upsert(
+        store,
+        &mut data,
+        vec![item("b", vec![-1.0, 0.0]), item("d", vec![0.5, 0.5])],
+    )
+    .await
+    .unwrap();
+    EmbeddingStore::delete(store, &mut data, vec!["c".to_string()])
+        .await
+        .unwrap();
+
+    let mapped = MappedStore::open(store).await.unwrap();
+    assert_eq!(mapped.segment().unwrap().len(), 3);
+    assert_eq!(mapped.delta().len(), 2);
+    assert_eq!(mapped.total_vectors(), 3);
+    assert_eq!(mapped.dimensions(), 2);
+
+    let loaded = EmbeddingStore::read_binary(store).await.unwrap();
+    for metric in [Metrics::Cosine, Metrics::Euclidean, Metrics::DotProduct] {
+        let query = SearchQuery::new(10, vec![0.6, 0.4], metric);
+        let expected = query.search(&loaded).unwrap();
+        let actual = query.search_source(&mapped).unwrap();
+        assert_eq!(actual.len(), 3);
+        for (a, b) in expected.iter().zip(&actual) {
+            assert_eq!(a.id, b.id);
+            assert_eq!(a.chunk, b.chunk);
+            assert!((a.score - b.score).abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,316 @@
+mod common;
+use axum::Router;
+use blaze_db::config::Config;
+use blaze_db::prelude::{EmbeddingStore, IndexType, Metrics, Provider};
+use blaze_db::server::{AppState, serve};
+use blaze_db::utils::{CollectionSchema, Collections, EmbeddingData, VectorData};
+use common::items;
+use serde_json::{Value, json};
+use std::sync::Arc;
+use tempfile::{TempDir, tempdir};
+use tokio::net::TcpListener;
+
+/// Start a server over a fresh store and return its base URL
+async fn spawn_server(dir: &TempDir) -> String {
+    // Nothing listens on port 9, so text requests fail fast
+    spawn_server_with(
+        dir,
+        Provider::new("http://127.0.0.1:9/v1/embeddings", "test-model"),
+    )
+    .await
+}
+
+async fn spawn_server_with(dir: &TempDir, provider: Provider) -> String {
+    let state = AppState::load(dir.path().to_str().unwrap(), provider)
+        .await
+        .unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(serve(listener, Arc::new(state)));
+
+    format!("http://{}", address)
+}
+
+/// Mock embeddings endpoint that answers in reverse order and leaves out every input
+/// starting with "drop"; each embedding is `[input length, 1]`
+async fn spawn_provider() -> Provider {
+    let app = Router::new().route(
+        "/v1/embeddings",
+        axum::routing::post(|axum::Json(body): axum::Json<Value>| async move {
+            let input: Vec<String> = serde_json::from_value(body["input"].clone()).unwrap();
+            let data: Vec<Value> = input
+                .iter()
+                .enumerate()
+                .rev()
+                .filter(|(_, text)| !text.starts_with("drop"))
+                .map(|(i, text)| json!({"index": i, "embedding": [text.len() as f32, 1.0]}))
+                .collect();
+            axum::Json(json!({ "data": data }))
+        }),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/v1/embeddings", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    Provider::new(url, "mock")
+}
+
+async fn post(url: String, body: Value) -> (u16, Value) {
+    let response = reqwest::Client::new()
+        .post(url)
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    let status = response.status().as_u16();
+    (status, response.json().await.unwrap())
+}
+
+#[tokio::test]
+async fn test_server_starts_with_empty_store() {
+    let dir = tempdir().unwrap();
+    let base = spawn_server(&dir).await;
+
+    let stats: Value = reqwest::get(format!("{}/stats", base))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(stats["total_vectors"], 0);
+    assert_eq!(stats["dimensions"], 0);
+}
+
+#[tokio::test]
+async fn test_server_loads_existing_store() {
+    let dir = tempdir().unwrap();
+    let items = vec![EmbeddingData {
+        id: "existing".to_string(),
+        chunk: "already on disk".to_string(),
+        embedding: vec![1.0, 0.0, 0.0],
+        dimensions: 3,
+        ..Default::default()
+    }];
+    EmbeddingStore::new(0, items)
+        .write_batch(dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+
+    let base = spawn_server(&dir).await;
+    let (status, body) = post(
+        format!("{}/search", base),
+        json!({"vector": [1.0, 0.0, 0.0], "top_k": 1}),
+    )
+    .await;
+
+    assert_eq!(status, 200);
+    assert_eq!(body["results"][0]["id"], "existing");
+    assert_eq!(body["results"][0]["chunk"], "already on disk");
+}
+
+#[tokio::test]
+async fn test_server_insert_search_delete() {
+    let dir = tempdir().unwrap();
+    let base = spawn_server(&dir).await;
+
+    let (status, body) = post(
+        format!("{}/insert", base),
+        json!({"items": [
+            {"id": "a", "chunk": "alpha", "vector": [1.0, 0.0], "metadata": {"tenant": "acme"}},
+            {"id": "b", "chunk": "beta", "vector": [0.0, 1.0], "metadata": {"tenant": "globex"}},
+            {"id": "c", "chunk": "gamma", "vector": [0.7, 0.7], "metadata": {"tenant": "acme"}}
+        ]}),
+    )
+    .await;
+    assert_eq!(status, 200);
+    assert_eq!(body["inserted"], 3);
+
+    let (status, body) = post(
+        format!("{}/search", base),
+        json!({
+            "vector": [0.0, 1.0],
+            "top_k": 2,
+            "metric": "DotProduct",
+            "filter": {"eq": {"field": "tenant", "value": "acme"}}
+        }),
+    )
+    .await;
+    assert_eq!(status, 200);
+    let results = body["results"].as_array().unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0]["id"], "c");
+    assert_eq!(results[0]["metadata"]["tenant"], "acme");
+
+    let (status, body) = post(format!("{}/delete", base), json!({"ids": ["c"]})).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["deleted"], 1);
+
+    let stats: Value = reqwest::get(format!("{}/stats", base))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(stats["total_vectors"], 2);
+    assert_eq!(stats["dimensions"], 2);
+
+    // Inserts and deletes are persisted in the store directory
+    let reloaded = EmbeddingStore::read_binary(dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(reloaded.total_vectors, 2);
+    assert_eq!(reloaded.position("c"), None);
+}
+
+#[tokio::test]
+async fn test_server_rejects_bad_requests() {
+    let dir = tempdir().unwrap();
+    let base = spawn_server(&dir).await;
+
+    let (status, body) = post(format!("{}/search", base), json!({"top_k": 3})).await;
+    assert_eq!(status, 400);
+    assert!(body["error"].as_str().unwrap().contains("vector or text"));
+
+    post(
+        format!("{}/insert", base),
+        json!({"items": [{"chunk": "alpha", "vector": [1.0, 0.0]}]}),
+    )
+    .await;
+    let (status, _) = post(
+        format!("{}/insert", base),
+        json!({"items": [{"chunk": "wrong", "vector": [1.0, 0.0, 0.0]}]}),
+    )
+    .await;
+    assert_eq!(status, 400);
+
+    let (status, _) = post(format!("{}/search", base), json!({"vector": [1.0]})).await;
+    assert_eq!(status, 400);
+}
+
+#[tokio::test]
+async fn test_server_text_search_without_provider() {
+    let dir = tempdir().unwrap();
+    let base = spawn_server(&dir).await;
+
+    let (status, body) = post(format!("{}/search", base), json!({"text": "war"})).await;
+
+    assert_eq!(status, 502);
+    assert!(
+        body["error"]
+            .as_str()
+            .unwrap()
+            .contains("Embedding provider failed")
+    );
+}
+
+#[tokio::test]
+async fn test_server_insert_matches_embeddings_by_index() {
+    let dir = tempdir().unwrap();
+    let base = spawn_server_with(&dir, spawn_provider().await).await;
+
+    let (status, body) = post(
+        format!("{}/insert", base),
+        json!({"items": [
+            {"id": "a", "chunk": "a"},
+            {"id": "given", "chunk": "with vector", "vector": [0.0, 9.0]},
+            {"id": "bbb", "chunk": "bbb"}
+        ]}),
+    )
+    .await;
+    assert_eq!(status, 200);
+    assert_eq!(body["inserted"], 3);
+    let data = EmbeddingStore::read_binary(dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+    let vector = |id: &str| {
+        data.get_vector(data.position(id).unwrap())
+            .unwrap()
+            .to_vec()
+    };
+    assert_eq!(vector("a"), [1.0, 1.0]);
+    assert_eq!(vector("given"), [0.0, 9.0]);
+    assert_eq!(vector("bbb"), [3.0, 1.0]);
+
+    // A chunk left out of the response is an error, not a shifted embedding
+    let (status, body) = post(
+        format!("{}/insert", base),
+        json!({"items": [{"id": "x", "chunk": "drop me"}, {"id": "y", "chunk": "kept"}]}),
+    )
+    .await;
+    assert_eq!(status, 502);
+    assert!(
+        body["error"]
+            .as_str()
+            .unwrap()
+            .contains("no embedding for item 0")
+    );
+}
+
+#[tokio::test]
+async fn test_server_serves_configured_collection() {
+    let dir = tempdir().unwrap();
+    let mut collections = Collections::open(dir.path()).await.unwrap();
+    let schema =
+        CollectionSchema::new("docs", 2, Metrics::Cosine, "mock").with_index(IndexType::Hnsw);
+    collections.create(schema).await.unwrap();
+    let items = items((0..20).map(|i| vec![i as f32, 1.0]).collect());
+    collections
+        .upsert("docs", &mut VectorData::default(), items)
+        .await
+        .unwrap();
+
+    let mut config = Config::default();
+    config.storage.path = dir.path().to_str().unwrap().to_string();
+    config.storage.collection = Some("docs".to_string());
+    config.search.metric = Metrics::DotProduct;
+    let state = AppState::from_config(&config).await.unwrap();
+    assert_eq!(state.search.metric, Metrics::Cosine);
+    assert_eq!(state.collection.as_ref().unwrap().index, IndexType::Hnsw);
+    assert_eq!(state.data.read().await.total_vectors, 20);
+    assert!(dir.path().join("docs").join("index.hnsw").exists());
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(serve(listener, Arc::new(state)));
+
+    let (status, _) = post(
+        format!("{}/insert", base),
+        json!({"items": [{"id": "wide", "chunk": "wrong", "vector": [1.0, 0.0, 0.0]}]}),
+    )
+    .await;
+    assert_eq!(status, 400);
+
+    let (status, _) = post(
+        format!("{}/insert", base),
+        json!({"items": [{"id": "new", "chunk": "inserted", "vector": [-1.0, 0.0]}]}),
+    )
+    .await;
+    assert_eq!(status, 200);
+    let (status, body) = post(
+        format!("{}/search", base),
+        json!({"vector": [-1.0, 0.0], "top_k": 1}),
+    )
+    .await;
+    assert_eq!(status, 200);
+    assert_eq!(body["results"][0]["id"], "new");
+
+    let data = collections.load("docs").await.unwrap();
+    assert_eq!(data.total_vectors, 21);
+    assert_eq!(data.model, "mock");
+}
+
+#[test]
+fn test_server_binary_fails_on_invalid_config() {
+    let dir = tempdir().unwrap();
+    let config = dir.path().join("blaze.toml");
+    std::fs::write(&config, "[storage\npath = 1\n").unwrap();
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_blaze-server"))
+        .args(["--config", config.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Failed to load config"));
+}
@@ -0,0 +1,172 @@
+mod common;
+
+use blaze_db::prelude::{
+    EmbeddingStore, HnswConfig, HnswIndex, IvfConfig, IvfIndex, Kernel, Metrics, PqConfig, PqIndex,
+    QuantizedIndex, SearchQuery, VectorData, VectorIndex,
+};
+use common::{random_values, vector_data};
+
+fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() <= 1e-4 * (1.0 + a.abs().max(b.abs()))
+}
+
+#[test]
+fn test_detected_kernel_is_available() {
+    let available = Kernel::available();
+    assert_eq!(available[0], Kernel::scalar());
+    assert!(available.contains(&Kernel::detect()));
+}
+
+#[test]
+fn test_every_kernel_matches_reference() {
+    for len in (0..70).chain([127, 128, 129, 1024, 1031]) {
+        let a = random_values(len, len as u64);
+        let b = random_values(len, len as u64 + 1000);
+        let dot: f32 = a.iter().zip(&b).map(|(x, y)| x * y).sum();
+        let l2: f32 = a.iter().zip(&b).map(|(x, y)| (x - y) * (x - y)).sum();
+
+        for kernel in Kernel::available() {
+            assert!(
+                close(kernel.dot(&a, &b), dot),
+                "{} dot at {}",
+                kernel.name(),
+                len
+            );
+            assert!(
+                close(kernel.squared_l2(&a, &b), l2),
+                "{} l2 at {}",
+                kernel.name(),
+                len
+            );
+            assert!(close(
+                kernel.norm_squared(&a),
+                a.iter().map(|x| x * x).sum()
+            ));
+        }
+    }
+}
+
+#[test]
+fn test_kernels_use_common_prefix_of_mismatched_lengths() {
+    let a = random_values(40, 1);
+    let b = random_values(33, 2);
+    for kernel in Kernel::available() {
+        assert!(close(kernel.dot(&a, &b), kernel.dot(&a[..33], &b)));
+        assert!(close(
+            kernel.squared_l2(&b, &a),
+            kernel.squared_l2(&b, &a[..33])
+        ));
+    }
+}
+
+#[test]
+fn test_search_rejects_mismatched_dimensions() {
+    let a = random_values(40, 1);
+    let b = random_values(33, 2);
+    let data = VectorData {
+        chunk: vec!["chunk".to_string()],
+        embedding: vec![b],
+        dimensions: 33,
+        total_vectors: 1,
+        ..Default::default()
+    };
+    let query = SearchQuery::new(1, a, Metrics::Cosine);
+    assert!(query.check_dimensions(data.dimensions).is_err());
+    assert!(query.check_dimensions(0).is_ok());
+    let error = query.search(&data).unwrap_err();
+    assert!(error.to_string().contains("Query has 40 dimensions"));
+}
+
+#[test]
+fn test_vector_data_caches_norms() {
+    let mut data = vector_data((0..20).map(|i| random_values(48, i)).collect());
+
+    assert_eq!(data.norms.len(), 20);
+    for (vector, norm) in data.embedding.iter().zip(&data.norms) {
+        assert!(close(
+            *norm,
+            vector.iter().map(|x| x * x).sum::<f32>().sqrt()
+        ));
+    }
+
+    data.apply(EmbeddingStore::deletion(1, vec!["doc-3".to_string()]));
+    data.compact();
+    assert_eq!(data.norms.len(), 19);
+    assert!(close(
+        data.norms[3],
+        data.embedding[3].iter().map(|x| x * x).sum::<f32>().sqrt()
+    ));
+}
+
+#[test]
+fn test_cached_norms_do_not_change_results() {
+    let embedding: Vec<Vec<f32>> = (0..100).map(|i| random_values(64, i)).collect();
+    let hand_built = VectorData {
+        chunk: (0..100).map(|i| format!("chunk {}", i)).collect(),
+        embedding,
+        dimensions: 64,
+        total_vectors: 100,
+        ..Default::default()
+    };
+    let mut cached = hand_built.clone();
+    cached.cache_norms();
+    assert_eq!(cached.norms.len(), 100);
+
+    let query = SearchQuery::new(10, random_values(64, 999), Metrics::Cosine);
+    let expected = query.search(&hand_built).unwrap();
+    let actual = query.search(&cached).unwrap();
+    for (a, b) in expected.iter().zip(&actual) {
+        assert_eq!(a.chunk, b.chunk);
+        assert!(close(a.score, b.score));
+        let idx: usize = a.chunk.trim_start_matches("chunk ").parse().unwrap();
+        assert!(close(
+            a.score,
+            Metrics::Cosine.calculate(&query.query_vector, &hand_built.embedding[idx])
+        ));
+    }
+}
+
+#[test]
+fn test_indexes_score_with_cached_norms() {
+    let mut data = VectorData {
+        chunk: (0..100).map(|i| format!("chunk {}", i)).collect(),
+        embedding: (0..100).map(|i| random_values(64, i)).collect(),
+        dimensions: 64,
+        total_vectors: 100,
+        ..Default::default()
+    };
+    data.cache_norms();
+    // Doubled norms halve every cosine score, which shows the cache is read
+    let mut doubled = data.clone();
+    doubled.norms.iter_mut().for_each(|norm| *norm *= 2.0);
+
+    let ivf = IvfConfig {
+        lists: 4,
+        nprobe: 4,
+        ..Default::default()
+    };
+    let indexes: Vec<Box<dyn VectorIndex>> = vec![
+        Box::new(HnswIndex::build(
+            &data,
+            Metrics::Cosine,
+            HnswConfig::default(),
+        )),
+        Box::new(IvfIndex::build(&data, Metrics::Cosine, ivf).unwrap()),
+        Box::new(
+            PqIndex::build(&data, PqConfig::default())
+                .unwrap()
+                .with_rescore(Some(4)),
+        ),
+        Box::new(QuantizedIndex::build(&data).with_rescore(Some(4))),
+    ];
+    let query = SearchQuery::new(10, random_values(64, 999), Metrics::Cosine);
+    for index in &indexes {
+        let expected = index.search(&query, &data).unwrap();
+        let actual = index.search(&query, &doubled).unwrap();
+        assert_eq!(expected.len(), 10);
+        for (a, b) in expected.iter().zip(&actual) {
+            assert_eq!(a.chunk, b.chunk);
+            assert!(close(a.score, b.score * 2.0));
+        }
+    }
+}
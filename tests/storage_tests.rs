@@ -1,5 +1,10 @@
-use blaze_db::prelude::{EmbeddingStore, VectorData};
-use blaze_db::utils::EmbeddingData;
+mod common;
+use blaze_db::prelude::{
+    ConsistencyError, EmbeddingStore, HnswConfig, HnswIndex, Metrics, SearchQuery, VectorData,
+    VectorIndex,
+};
+use blaze_db::utils::{BATCH_MAGIC, BATCH_VERSION, EmbeddingData};
+use common::item;
 use tempfile::tempdir;
 
 #[tokio::test]
@@ -10,12 +15,14 @@ async fn test_embedding_store_creation() {
             chunk: "test chunk 1".to_string(),
             embedding: vec![1.0, 2.0, 3.0],
             dimensions: 3,
+            ..Default::default()
         },
         EmbeddingData {
             index: 1,
             chunk: "test chunk 2".to_string(),
             embedding: vec![4.0, 5.0, 6.0],
             dimensions: 3,
+            ..Default::default()
         },
     ];
 
@@ -37,6 +44,7 @@ async fn test_write_read_binary() {
         chunk: "test chunk".to_string(),
         embedding: vec![1.0, 2.0, 3.0],
         dimensions: 3,
+        ..Default::default()
     }];
 
     let store = EmbeddingStore::new(0, embedding_data);
@@ -73,6 +81,7 @@ async fn test_read_binary_multiple_files() {
             chunk: format!("chunk {}", i),
             embedding: vec![i as f32, (i + 1) as f32],
             dimensions: 2,
+            ..Default::default()
         }];
 
         let store = EmbeddingStore::new(i, embedding_data);
@@ -131,6 +140,7 @@ fn test_vector_data_get_vector() {
         embedding: vec![vec![1.0, 2.0], vec![3.0, 4.0]],
         dimensions: 2,
         total_vectors: 2,
+        ..Default::default()
     };
 
     assert_eq!(vector_data.get_vector(0), Some([1.0, 2.0].as_slice()));
@@ -145,6 +155,7 @@ fn test_vector_data_get_chunk() {
         embedding: vec![vec![1.0, 2.0], vec![3.0, 4.0]],
         dimensions: 2,
         total_vectors: 2,
+        ..Default::default()
     };
 
     assert_eq!(vector_data.get_chunk(0), Some("chunk1"));
@@ -159,6 +170,7 @@ fn test_vector_data_memory_usage() {
         embedding: vec![vec![1.0; 100]], // 100 f32 values
         dimensions: 100,
         total_vectors: 1,
+        ..Default::default()
     };
 
     let memory_mb = vector_data.memory_usage_mb();
@@ -174,6 +186,7 @@ fn test_vector_data_empty() {
         embedding: vec![],
         dimensions: 0,
         total_vectors: 0,
+        ..Default::default()
     };
 
     assert_eq!(vector_data.get_vector(0), None);
@@ -188,6 +201,7 @@ async fn test_embedding_store_debug_print() {
         chunk: "test chunk for debugging".to_string(),
         embedding: vec![1.0, 2.0, 3.0, 4.0, 5.0],
         dimensions: 5,
+        ..Default::default()
     }];
 
     let store = EmbeddingStore::new(42, embedding_data);
@@ -198,3 +212,333 @@ async fn test_embedding_store_debug_print() {
     assert_eq!(store.batch_index, 42);
     assert_eq!(store.items[0].dimensions, 5);
 }
+
+#[test]
+fn test_embedding_store_generates_missing_ids() {
+    let embedding_data = vec![
+        EmbeddingData {
+            index: 0,
+            chunk: "generated".to_string(),
+            embedding: vec![1.0],
+            dimensions: 1,
+            ..Default::default()
+        },
+        item("custom", "explicit", vec![2.0]),
+    ];
+
+    let store = EmbeddingStore::new(7, embedding_data);
+
+    assert_eq!(store.items[0].id, "7-0");
+    assert_eq!(store.items[1].id, "custom");
+}
+
+#[tokio::test]
+async fn test_read_binary_later_batches_win() {
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path().to_str().unwrap();
+
+    EmbeddingStore::new(
+        0,
+        vec![
+            item("a", "old a", vec![1.0, 0.0]),
+            item("b", "old b", vec![0.0, 1.0]),
+        ],
+    )
+    .write_batch(dir_path)
+    .await
+    .unwrap();
+    EmbeddingStore::new(1, vec![item("a", "new a", vec![0.5, 0.5])])
+        .write_batch(dir_path)
+        .await
+        .unwrap();
+    EmbeddingStore::deletion(2, vec!["b".to_string()])
+        .write_batch(dir_path)
+        .await
+        .unwrap();
+
+    let vector_data = EmbeddingStore::read_binary(dir_path).await.unwrap();
+
+    // Replaced and deleted vectors stay tombstoned, so positions match every load
+    assert_eq!(vector_data.total_vectors, 1);
+    let position = vector_data.position("a").unwrap();
+    assert_eq!(position, 2);
+    assert_eq!(vector_data.chunk[position], "new a");
+    assert_eq!(vector_data.embedding[position], vec![0.5, 0.5]);
+    assert!(vector_data.is_deleted(0) && vector_data.is_deleted(1));
+    assert_eq!(vector_data.position("b"), None);
+    assert_eq!(vector_data.next_batch_index, 3);
+}
+
+#[tokio::test]
+async fn test_upsert_and_delete_persist() {
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path().to_str().unwrap();
+
+    let mut vector_data = VectorData::default();
+    EmbeddingStore::upsert(
+        dir_path,
+        &mut vector_data,
+        vec![
+            item("x", "chunk x", vec![1.0, 0.0]),
+            item("y", "chunk y", vec![0.0, 1.0]),
+        ],
+    )
+    .await
+    .unwrap();
+    EmbeddingStore::upsert(
+        dir_path,
+        &mut vector_data,
+        vec![item("x", "chunk x v2", vec![1.0, 1.0])],
+    )
+    .await
+    .unwrap();
+
+    // The replaced vector is tombstoned, in memory and across loads
+    assert_eq!(vector_data.total_vectors, 2);
+    assert!(vector_data.is_deleted(0));
+    assert_eq!(vector_data.position("x"), Some(2));
+
+    let removed = EmbeddingStore::delete(
+        dir_path,
+        &mut vector_data,
+        vec!["y".to_string(), "missing".to_string()],
+    )
+    .await
+    .unwrap();
+    assert_eq!(removed, 1);
+    assert_eq!(vector_data.total_vectors, 1);
+    assert_eq!(vector_data.position("y"), None);
+
+    let reloaded = EmbeddingStore::read_binary(dir_path).await.unwrap();
+    assert_eq!(reloaded.total_vectors, 1);
+    assert_eq!(reloaded.position("x"), Some(2));
+    assert_eq!(reloaded.get_chunk(2), Some("chunk x v2"));
+    assert_eq!(reloaded.deleted, vector_data.deleted);
+}
+
+#[tokio::test]
+async fn test_saved_index_matches_reloaded_positions() {
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path().to_str().unwrap();
+    let mut data = VectorData::default();
+    let items = (0..20)
+        .map(|i| {
+            let angle = i as f32 / 10.0;
+            item(
+                &format!("v{}", i),
+                &format!("chunk {}", i),
+                vec![angle.cos(), angle.sin()],
+            )
+        })
+        .collect();
+    EmbeddingStore::upsert(dir_path, &mut data, items)
+        .await
+        .unwrap();
+    EmbeddingStore::delete(dir_path, &mut data, vec!["v3".to_string()])
+        .await
+        .unwrap();
+    let index = HnswIndex::build(&data, Metrics::Cosine, HnswConfig::default());
+    index.write_binary(dir_path).await.unwrap();
+
+    let reloaded = EmbeddingStore::read_binary(dir_path).await.unwrap();
+    let index = HnswIndex::read_binary(dir_path).await.unwrap();
+    let target = reloaded.position("v7").unwrap();
+    let query = SearchQuery::new(3, reloaded.embedding[target].clone(), Metrics::Cosine);
+    let results = index.search(&query, &reloaded).unwrap();
+    assert_eq!(results[0].id, "v7");
+    assert_eq!(results[0].chunk, "chunk 7");
+    assert!(results.iter().all(|result| result.id != "v3"));
+}
+
+#[test]
+fn test_vector_data_compact() {
+    let mut vector_data = VectorData::default();
+    vector_data.apply(EmbeddingStore::new(
+        0,
+        vec![
+            item("a", "a", vec![1.0]),
+            item("b", "b", vec![2.0]),
+            item("c", "c", vec![3.0]),
+        ],
+    ));
+    vector_data.apply(EmbeddingStore::deletion(1, vec!["b".to_string()]));
+
+    assert_eq!(vector_data.embedding.len(), 3);
+    assert_eq!(vector_data.total_vectors, 2);
+
+    vector_data.compact();
+
+    assert_eq!(vector_data.ids, vec!["a".to_string(), "c".to_string()]);
+    assert_eq!(vector_data.embedding, vec![vec![1.0], vec![3.0]]);
+    assert_eq!(vector_data.total_vectors, 2);
+    assert!(vector_data.deleted.is_empty());
+}
+
+#[tokio::test]
+async fn test_batch_header_records_dimensions_and_model() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let store = EmbeddingStore::new(0, vec![item("a", "a", vec![1.0, 2.0, 3.0])])
+        .with_model("nomic-embed-text");
+    assert_eq!(store.dimensions, 3);
+    store.write_batch(path).await.unwrap();
+
+    let loaded = EmbeddingStore::read_binary_file(&dir.path().join("embeddings_batch_0.bin"))
+        .await
+        .unwrap();
+    assert_eq!(loaded.dimensions, 3);
+    assert_eq!(loaded.model, "nomic-embed-text");
+
+    let data = EmbeddingStore::read_binary(path).await.unwrap();
+    assert_eq!(data.model, "nomic-embed-text");
+    assert_eq!(EmbeddingStore::deletion(1, vec![]).dimensions, 0);
+}
+
+/// A batch in the bincode layout of stores written before batches had ids or a header
+fn legacy_batch(batch_index: u64, chunks: &[(&str, &[f32])]) -> Vec<u8> {
+    let mut bytes = batch_index.to_le_bytes().to_vec();
+    bytes.extend((chunks.len() as u64).to_le_bytes());
+    for (index, (chunk, embedding)) in chunks.iter().enumerate() {
+        bytes.extend((index as u64).to_le_bytes());
+        bytes.extend((chunk.len() as u64).to_le_bytes());
+        bytes.extend(chunk.as_bytes());
+        bytes.extend((embedding.len() as u64).to_le_bytes());
+        embedding.iter().for_each(|x| bytes.extend(x.to_le_bytes()));
+        bytes.extend((embedding.len() as u64).to_le_bytes());
+    }
+    bytes
+}
+
+#[tokio::test]
+async fn test_read_binary_loads_legacy_batches() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    std::fs::write(
+        dir.path().join("embeddings_batch_0.bin"),
+        legacy_batch(0, &[("first", &[1.0, 0.0]), ("second", &[0.0, 1.0])]),
+    )
+    .unwrap();
+
+    let loaded = EmbeddingStore::read_binary_file(&dir.path().join("embeddings_batch_0.bin"))
+        .await
+        .unwrap();
+    assert_eq!(loaded.dimensions, 2);
+    assert_eq!(loaded.items[1].id, "0-1");
+    assert_eq!(loaded.items[1].chunk, "second");
+
+    // New batches are written with a header and load next to legacy ones
+    let mut data = EmbeddingStore::open(path).await.unwrap();
+    assert_eq!(data.position("0-0"), Some(0));
+    EmbeddingStore::new(1, vec![item("c", "third", vec![1.0, 1.0])])
+        .write_batch(path)
+        .await
+        .unwrap();
+    let bytes = std::fs::read(dir.path().join("embeddings_batch_1.bin")).unwrap();
+    assert_eq!(bytes[..4], BATCH_MAGIC);
+    assert_eq!(bytes[4..8], BATCH_VERSION.to_le_bytes());
+    data = EmbeddingStore::read_binary(path).await.unwrap();
+    assert_eq!(data.total_vectors, 3);
+    assert_eq!(data.chunk, vec!["first", "second", "third"]);
+
+    // A header from a newer build is refused rather than misread
+    let mut newer = bytes.clone();
+    newer[4..8].copy_from_slice(&(BATCH_VERSION + 1).to_le_bytes());
+    std::fs::write(dir.path().join("embeddings_batch_1.bin"), newer).unwrap();
+    let error = EmbeddingStore::read_binary(path).await.unwrap_err();
+    assert!(format!("{:#}", error).contains("newer"), "{:#}", error);
+}
+
+#[tokio::test]
+async fn test_upsert_rejects_mismatched_batches() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let mut data = VectorData::default();
+    EmbeddingStore::upsert_with_model(path, &mut data, "nomic", vec![item("a", "a", vec![1.0; 4])])
+        .await
+        .unwrap();
+
+    let error = EmbeddingStore::upsert(path, &mut data, vec![item("b", "b", vec![1.0; 8])])
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<ConsistencyError>(),
+        Some(&ConsistencyError::Dimensions {
+            id: "b".to_string(),
+            expected: 4,
+            found: 8
+        })
+    );
+
+    // Vectors that disagree within one batch are caught before the store is consulted
+    let ragged = vec![item("c", "c", vec![1.0; 4]), item("d", "d", vec![1.0; 3])];
+    let error = EmbeddingStore::upsert(path, &mut data, ragged)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<ConsistencyError>(),
+        Some(ConsistencyError::Dimensions { found: 3, .. })
+    ));
+
+    let error = EmbeddingStore::upsert_with_model(
+        path,
+        &mut data,
+        "qwen3",
+        vec![item("e", "e", vec![1.0; 4])],
+    )
+    .await
+    .unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<ConsistencyError>(),
+        Some(ConsistencyError::Model { expected, found }) if expected == "nomic" && found == "qwen3"
+    ));
+
+    // Unknown models are accepted and nothing rejected reached the store
+    EmbeddingStore::upsert(path, &mut data, vec![item("f", "f", vec![1.0; 4])])
+        .await
+        .unwrap();
+    let reloaded = EmbeddingStore::read_binary(path).await.unwrap();
+    assert_eq!(reloaded.total_vectors, 2);
+    assert_eq!(reloaded.model, "nomic");
+}
+
+#[tokio::test]
+async fn test_read_binary_reports_inconsistent_files() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    EmbeddingStore::new(0, vec![item("a", "a", vec![1.0; 4])])
+        .with_model("nomic")
+        .write_batch(path)
+        .await
+        .unwrap();
+    EmbeddingStore::new(1, vec![item("b", "b", vec![1.0; 4])])
+        .write_batch(path)
+        .await
+        .unwrap();
+    EmbeddingStore::new(2, vec![item("c", "c", vec![1.0; 8])])
+        .with_model("qwen3")
+        .write_batch(path)
+        .await
+        .unwrap();
+    EmbeddingStore::new(3, vec![item("d", "d", vec![1.0; 4])])
+        .with_model("qwen3")
+        .write_batch(path)
+        .await
+        .unwrap();
+
+    let error = EmbeddingStore::read_binary(path).await.unwrap_err();
+    let Some(ConsistencyError::Files {
+        expected_dimensions,
+        expected_model,
+        files,
+    }) = error.downcast_ref::<ConsistencyError>()
+    else {
+        panic!("unexpected error: {}", error);
+    };
+    assert_eq!(*expected_dimensions, 4);
+    assert_eq!(expected_model, "nomic");
+    let batches: Vec<usize> = files.iter().map(|f| f.batch_index).collect();
+    assert_eq!(batches, vec![2, 3]);
+    assert!(files[0].source.ends_with("embeddings_batch_2.bin"));
+    assert_eq!(files[0].dimensions, 8);
+    assert!(error.to_string().contains("embeddings_batch_3.bin"));
+}
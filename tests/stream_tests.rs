@@ -0,0 +1,222 @@
+use blaze_db::cli::{Cli, run_with};
+use blaze_db::prelude::{EmbeddingStore, HashingProvider, Ingestor, MetadataValue};
+use blaze_db::utils::{
+    BatchOutcome, Checkpoint, Chunk, Chunker, EmbeddingFuture, EmbeddingProvider, Format, Pipeline,
+    Reader,
+};
+use clap::Parser;
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+use tempfile::tempdir;
+
+/// Hashing embeddings after a short delay, tracking concurrent requests and rejecting
+/// any batch whose first chunk starts with "bad" while `failing` is set
+#[derive(Debug, Default)]
+struct SlowProvider {
+    inner: HashingProvider,
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+    failing: AtomicBool,
+}
+
+impl EmbeddingProvider for SlowProvider {
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    fn fetch_embeddings<'a>(&'a self, chunks: &'a [String]) -> EmbeddingFuture<'a> {
+        Box::pin(async move {
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            if self.failing.load(Ordering::SeqCst) && chunks[0].starts_with("bad") {
+                anyhow::bail!("rejected");
+            }
+            self.inner.fetch_embeddings(chunks).await
+        })
+    }
+}
+
+fn flatten(batches: impl IntoIterator<Item = anyhow::Result<Vec<Chunk>>>) -> Vec<Chunk> {
+    batches
+        .into_iter()
+        .flat_map(|batch| batch.unwrap())
+        .collect()
+}
+
+#[test]
+fn test_streamed_chunks_match_whole_file() {
+    let dir = tempdir().unwrap();
+    let source = dir.path().join("dump.log");
+    let mut text = String::new();
+    for i in 0..200 {
+        text.push_str(&format!("event {} happened. It was logged.\n", i));
+        if i % 7 == 0 {
+            text.push('\n');
+        }
+    }
+    std::fs::write(&source, &text).unwrap();
+
+    for chunker in [Chunker::Line, Chunker::Paragraph, Chunker::Sentence] {
+        let ingestor = Ingestor::new(&source, 8).with_chunker(chunker.clone());
+        let whole: Vec<Chunk> = ingestor.read_chunks().unwrap().concat();
+        let batches = ingestor.batches().unwrap().with_block_bytes(300);
+        let streamed = flatten(batches);
+        assert_eq!(streamed, whole, "{:?}", chunker);
+        for chunk in &streamed {
+            assert_eq!(&text[chunk.start..chunk.end], chunk.text);
+        }
+    }
+
+    let batches: Vec<Vec<Chunk>> = Ingestor::new(&source, 16)
+        .batches()
+        .unwrap()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(batches.len(), 13);
+    assert!(batches[..12].iter().all(|batch| batch.len() == 16));
+    assert_eq!(batches[12].len(), 8);
+}
+
+#[test]
+fn test_streamed_jsonl_keeps_line_numbers() {
+    let dir = tempdir().unwrap();
+    let source = dir.path().join("tickets.jsonl");
+    let mut file = std::fs::File::create(&source).unwrap();
+    for i in 0..50 {
+        writeln!(file, "{{\"id\": {}, \"text\": \"ticket {}\"}}", i, i).unwrap();
+    }
+    writeln!(file, "{{broken").unwrap();
+    drop(file);
+
+    let mut batches = Ingestor::new(&source, 8)
+        .batches()
+        .unwrap()
+        .with_block_bytes(64);
+    let first = batches.next().unwrap().unwrap();
+    assert_eq!(first[3].text, "ticket 3");
+    assert_eq!(first[3].metadata["id"], MetadataValue::Int(3));
+
+    let results: Vec<_> = batches.collect();
+    let error = results.last().unwrap().as_ref().unwrap_err();
+    assert!(format!("{:#}", error).contains("line 51"), "{:#}", error);
+    let chunks: Vec<Chunk> = results
+        .into_iter()
+        .filter_map(Result::ok)
+        .flatten()
+        .collect();
+    // Batches before the broken line come in order, nothing comes after it
+    assert!(chunks.len() >= 32);
+    for (record, chunk) in chunks.iter().enumerate() {
+        assert_eq!(
+            chunk.metadata["record"],
+            MetadataValue::Int(8 + record as i64)
+        );
+    }
+
+    // Formats that need the whole file are not streamed
+    let html = dir.path().join("page.html");
+    std::fs::write(&html, "<p>hi</p>").unwrap();
+    assert!(Ingestor::new(&html, 8).batches().is_err());
+    let forced = Ingestor::new(&html, 8).with_reader(Reader::default().with_format(Format::Text));
+    assert_eq!(flatten(forced.batches().unwrap())[0].text, "<p>hi</p>");
+}
+
+#[tokio::test]
+async fn test_run_stream_bounds_concurrency_and_resumes() {
+    let dir = tempdir().unwrap();
+    let source = dir.path().join("dump.log");
+    let lines: Vec<String> = (0..80)
+        .map(|i| {
+            // The first line of batch 3 is rejected while the provider is failing
+            if i == 24 {
+                "bad line".to_string()
+            } else {
+                format!("line {}", i)
+            }
+        })
+        .collect();
+    std::fs::write(&source, lines.join("\n")).unwrap();
+    let store = dir.path().join("store");
+    let store = store.to_str().unwrap();
+
+    let provider = Arc::new(SlowProvider::default());
+    provider.failing.store(true, Ordering::SeqCst);
+    let pipeline = Pipeline::new(provider.clone(), store).with_concurrency(3);
+    let ingestor = Ingestor::new(&source, 8);
+    let mut data = EmbeddingStore::open(store).await.unwrap();
+
+    let report = pipeline
+        .run_stream("dump", ingestor.stream(1).unwrap(), &mut data, |_| {})
+        .await
+        .unwrap();
+    assert_eq!(report.batches, 10);
+    assert_eq!(report.stored, 9);
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(report.failed[0].0, 3);
+    assert!(provider.max_in_flight.load(Ordering::SeqCst) <= 3);
+    assert!(Checkpoint::path(store, "dump").exists());
+
+    provider.failing.store(false, Ordering::SeqCst);
+    let mut outcomes = Vec::new();
+    let report = pipeline
+        .run_stream("dump", ingestor.stream(1).unwrap(), &mut data, |outcome| {
+            outcomes.push(outcome.clone())
+        })
+        .await
+        .unwrap();
+    assert_eq!((report.skipped, report.stored), (9, 1));
+    assert!(outcomes.contains(&BatchOutcome::Stored { batch: 3, items: 8 }));
+    assert!(!Checkpoint::path(store, "dump").exists());
+
+    let data = EmbeddingStore::read_binary(store).await.unwrap();
+    assert_eq!(data.total_vectors, 80);
+    let position = data.position("3-0").unwrap();
+    assert_eq!(data.chunk[position], "bad line");
+    let start: usize = lines[..24].iter().map(|line| line.len() + 1).sum();
+    assert_eq!(
+        data.metadata[position]["start"],
+        MetadataValue::Int(start as i64)
+    );
+}
+
+#[tokio::test]
+async fn test_cli_streams_large_files() {
+    let dir = tempdir().unwrap();
+    let store = dir.path().join("store");
+    let source = dir.path().join("dump.log");
+    let lines: Vec<String> = (0..100).map(|i| format!("request {} served", i)).collect();
+    std::fs::write(&source, lines.join("\n")).unwrap();
+
+    let config = dir.path().join("blaze.toml");
+    std::fs::write(
+        &config,
+        format!(
+            "[storage]\npath = {:?}\n\n[provider]\noffline = true\ndimensions = 32\n",
+            store.to_str().unwrap()
+        ),
+    )
+    .unwrap();
+
+    let cli = Cli::try_parse_from([
+        "blaze",
+        "--config",
+        config.to_str().unwrap(),
+        "ingest",
+        source.to_str().unwrap(),
+        "--stream",
+        "--batch-size",
+        "16",
+    ])
+    .unwrap();
+    run_with(cli).await.unwrap();
+
+    let data = EmbeddingStore::read_binary(store.to_str().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(data.total_vectors, 100);
+    assert_eq!(data.dimensions, 32);
+}
@@ -0,0 +1,234 @@
+use blaze_db::cli::{Cli, run_with};
+use blaze_db::prelude::{EmbeddingStore, HashingProvider, Ingestor, MetadataValue, VectorData};
+use blaze_db::utils::{
+    Chunker, Dedup, DedupMethod, DedupPolicy, FileOutcome, Pipeline, SyncManifest,
+};
+use clap::Parser;
+use std::path::Path;
+use tempfile::tempdir;
+
+fn write(root: &Path, path: &str, content: &str) {
+    let path = root.join(path);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, content).unwrap();
+}
+
+/// Live ids of vectors of the file called `name`
+fn ids_of(data: &VectorData, name: &str) -> Vec<String> {
+    let mut ids: Vec<String> = (0..data.ids.len())
+        .filter(|&index| !data.is_deleted(index))
+        .map(|index| data.ids[index].clone())
+        .filter(|id| id.contains(&format!("{}:", name)))
+        .collect();
+    ids.sort();
+    ids
+}
+
+#[tokio::test]
+async fn test_sync_embeds_only_changes() {
+    let dir = tempdir().unwrap();
+    let docs = dir.path().join("docs");
+    let store = dir.path().join("store");
+    let store = store.to_str().unwrap();
+    write(&docs, "guide.md", "Install it\nRun it\n");
+    write(&docs, "faq.txt", "Why?\nBecause.\nWhen?\n");
+    write(&docs, "old/notes.txt", "Obsolete\n");
+    write(&docs, "drafts/wip.txt", "Not yet\n");
+
+    let ingestor = Ingestor::new(&docs, 8).with_exclude(vec!["drafts".into()]);
+    let pipeline = Pipeline::new(HashingProvider::new(16), store);
+    let mut data = EmbeddingStore::open(store).await.unwrap();
+
+    let report = pipeline.sync(&ingestor, &mut data, |_| {}).await.unwrap();
+    assert_eq!((report.added, report.updated, report.unchanged), (3, 0, 0));
+    assert_eq!(data.total_vectors, 6);
+    let docs = docs.canonicalize().unwrap();
+    let faq = docs.join("faq.txt").to_string_lossy().to_string();
+    assert_eq!(ids_of(&data, "faq.txt").len(), 3);
+    assert!(data.position(&format!("{}:0-2", faq)).is_some());
+
+    // Nothing changed: no file is read or embedded again
+    let report = pipeline.sync(&ingestor, &mut data, |_| {}).await.unwrap();
+    assert_eq!((report.added, report.updated, report.unchanged), (0, 0, 3));
+
+    // A shorter file drops its extra vectors, an identical rewrite is only touched and a
+    // deleted file loses its vectors
+    write(&docs, "faq.txt", "Why not?\n");
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    write(&docs, "guide.md", "Install it\nRun it\n");
+    std::fs::remove_dir_all(docs.join("old")).unwrap();
+    let mut outcomes = Vec::new();
+    let report = pipeline
+        .sync(&ingestor, &mut data, |outcome| {
+            outcomes.push(outcome.clone())
+        })
+        .await
+        .unwrap();
+    assert_eq!(
+        (report.updated, report.unchanged, report.removed),
+        (1, 1, 1)
+    );
+    assert!(outcomes.contains(&FileOutcome::Updated {
+        path: "faq.txt".to_string(),
+        chunks: 1
+    }));
+    assert_eq!(ids_of(&data, "faq.txt"), vec![format!("{}:0-0", faq)]);
+    assert!(ids_of(&data, "notes.txt").is_empty());
+    assert_eq!(data.total_vectors, 3);
+
+    // The store on disk agrees, and the manifest lists the current files
+    let reopened = EmbeddingStore::read_binary(store).await.unwrap();
+    assert_eq!(reopened.total_vectors, 3);
+    let chunk = reopened.position(&format!("{}:0-0", faq)).unwrap();
+    assert_eq!(reopened.chunk[chunk], "Why not?");
+    let manifest = SyncManifest::load(store, &docs.to_string_lossy())
+        .await
+        .unwrap();
+    assert_eq!(manifest.files.len(), 2);
+
+    // Other chunking settings re-embed everything
+    let paragraphs = ingestor.clone().with_chunker(Chunker::Paragraph);
+    let report = pipeline.sync(&paragraphs, &mut data, |_| {}).await.unwrap();
+    assert_eq!((report.updated, report.unchanged), (2, 0));
+    assert_eq!(data.total_vectors, 2);
+}
+
+#[tokio::test]
+async fn test_sync_keys_files_by_canonical_source() {
+    let dir = tempdir().unwrap();
+    let docs = dir.path().join("docs");
+    let store = dir.path().join("store");
+    let store = store.to_str().unwrap();
+    write(&docs, "a.txt", "Alpha\nBeta\n");
+    write(&docs, "sub/b.txt", "Gamma\n");
+
+    let pipeline = Pipeline::new(HashingProvider::new(16), store);
+    let mut data = EmbeddingStore::open(store).await.unwrap();
+    let report = pipeline
+        .sync(&Ingestor::new(&docs, 8), &mut data, |_| {})
+        .await
+        .unwrap();
+    assert_eq!(report.added, 2);
+
+    // The same directory spelled differently is the same source
+    let alias = dir.path().join("docs/sub/..");
+    let report = pipeline
+        .sync(&Ingestor::new(&alias, 8), &mut data, |_| {})
+        .await
+        .unwrap();
+    assert_eq!((report.added, report.unchanged), (0, 2));
+    assert_eq!(data.total_vectors, 3);
+
+    let canonical = docs.canonicalize().unwrap();
+    let manifest = SyncManifest::load(store, &canonical.to_string_lossy())
+        .await
+        .unwrap();
+    assert_eq!(
+        manifest.files.keys().collect::<Vec<_>>(),
+        ["a.txt", "sub/b.txt"]
+    );
+    let b = data
+        .position(&format!("{}:0-0", canonical.join("sub/b.txt").display()))
+        .unwrap();
+    assert_eq!(data.metadata[b]["source"], MetadataValue::from("sub/b.txt"));
+}
+
+#[tokio::test]
+async fn test_sync_dedups_across_files() {
+    let dir = tempdir().unwrap();
+    let docs = dir.path().join("docs");
+    let store = dir.path().join("store");
+    let store = store.to_str().unwrap();
+    write(&docs, "a.txt", "Copyright ACME\nAlpha\n");
+    write(&docs, "b.txt", "Copyright ACME\nBeta\n");
+
+    let exact = Dedup::new(DedupMethod::Exact, DedupPolicy::KeepFirst);
+    let ingestor = Ingestor::new(&docs, 8).with_dedup(exact);
+    let pipeline = Pipeline::new(HashingProvider::new(16), store);
+    let mut data = EmbeddingStore::open(store).await.unwrap();
+    pipeline.sync(&ingestor, &mut data, |_| {}).await.unwrap();
+    assert_eq!(data.total_vectors, 3);
+    assert_eq!(ids_of(&data, "a.txt").len(), 2);
+    assert_eq!(ids_of(&data, "b.txt").len(), 1);
+
+    // A changed file is compared with what unchanged files already stored
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    write(&docs, "b.txt", "Copyright ACME\nBeta\nGamma\n");
+    let report = pipeline.sync(&ingestor, &mut data, |_| {}).await.unwrap();
+    assert_eq!((report.updated, report.unchanged), (1, 1));
+    assert_eq!(ids_of(&data, "b.txt").len(), 2);
+    let copies = (0..data.chunk.len())
+        .filter(|&index| !data.is_deleted(index) && data.chunk[index] == "Copyright ACME")
+        .count();
+    assert_eq!(copies, 1);
+}
+
+#[tokio::test]
+async fn test_sync_leaves_failed_files_for_next_run() {
+    let dir = tempdir().unwrap();
+    let docs = dir.path().join("docs");
+    let store = dir.path().join("store");
+    let store = store.to_str().unwrap();
+    write(&docs, "good.txt", "fine\n");
+    write(&docs, "bad.jsonl", "{\"text\": \"ok\"}\nnot json\n");
+
+    let ingestor = Ingestor::new(&docs, 8);
+    let pipeline = Pipeline::new(HashingProvider::new(16), store);
+    let mut data = EmbeddingStore::open(store).await.unwrap();
+    let report = pipeline.sync(&ingestor, &mut data, |_| {}).await.unwrap();
+    assert_eq!(report.added, 1);
+    assert_eq!(report.failed.len(), 1);
+    assert!(report.failed[0].1.contains("line 2"));
+
+    write(
+        &docs,
+        "bad.jsonl",
+        "{\"text\": \"ok\"}\n{\"text\": \"fixed\"}\n",
+    );
+    let report = pipeline.sync(&ingestor, &mut data, |_| {}).await.unwrap();
+    assert!(report.is_complete());
+    assert_eq!((report.added, report.unchanged), (1, 1));
+    assert_eq!(data.total_vectors, 3);
+}
+
+#[tokio::test]
+async fn test_cli_syncs_directory() {
+    let dir = tempdir().unwrap();
+    let docs = dir.path().join("docs");
+    let store = dir.path().join("store");
+    let config = dir.path().join("blaze.toml");
+    std::fs::write(
+        &config,
+        format!(
+            "[storage]\npath = {:?}\n\n[provider]\noffline = true\ndimensions = 32\n\n\
+             [ingest]\nbatch_size = 8\nexclude = [\"*.log\"]\n",
+            store.to_str().unwrap()
+        ),
+    )
+    .unwrap();
+    write(&docs, "a.md", "# A\nAlpha\n");
+    write(&docs, "b.html", "<p>Beta</p><script>x()</script>");
+    write(&docs, "debug.log", "noise\n");
+    let run = |args: &[&str]| {
+        let mut argv = vec!["blaze", "--config", config.to_str().unwrap()];
+        argv.extend_from_slice(args);
+        run_with(Cli::try_parse_from(argv).unwrap())
+    };
+
+    run(&["ingest", docs.to_str().unwrap(), "--chunker", "paragraph"])
+        .await
+        .unwrap();
+    std::fs::remove_file(docs.join("a.md")).unwrap();
+    run(&["ingest", docs.to_str().unwrap(), "--chunker", "paragraph"])
+        .await
+        .unwrap();
+
+    let data = EmbeddingStore::read_binary(store.to_str().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(data.total_vectors, 1);
+    let live = (0..data.chunk.len())
+        .find(|&i| !data.is_deleted(i))
+        .unwrap();
+    assert_eq!(data.chunk[live], "Beta");
+}
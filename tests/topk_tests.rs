@@ -0,0 +1,81 @@
+mod common;
+
+use blaze_db::prelude::{Metrics, SearchQuery, SearchResult, VectorData};
+use common::{random_values, vector_data};
+use std::cmp::Ordering;
+
+/// The previous behaviour: score everything, stable sort descending with NaN last
+fn full_sort(query: &SearchQuery, data: &VectorData) -> Vec<String> {
+    let mut scored: Vec<(usize, f32)> = data
+        .embedding
+        .iter()
+        .enumerate()
+        .map(|(idx, v)| (idx, query.metric.calculate(&query.query_vector, v)))
+        .collect();
+    scored.sort_by(|a, b| match a.1.is_nan().cmp(&b.1.is_nan()) {
+        Ordering::Equal => b.1.total_cmp(&a.1),
+        other => other,
+    });
+    scored
+        .into_iter()
+        .take(query.top_k)
+        .map(|(idx, _)| data.chunk[idx].clone())
+        .collect()
+}
+
+fn chunks(results: &[SearchResult]) -> Vec<String> {
+    results.iter().map(|r| r.chunk.clone()).collect()
+}
+
+#[test]
+fn test_topk_matches_full_sort() {
+    let data = vector_data((0..5000).map(|i| random_values(32, i)).collect());
+
+    for metric in [Metrics::Cosine, Metrics::Euclidean, Metrics::DotProduct] {
+        for top_k in [1, 7, 100, 4999, 5000, 6000] {
+            let query = SearchQuery::new(top_k, random_values(32, 12345), metric);
+            let results = query.search(&data).unwrap();
+
+            assert_eq!(results.len(), top_k.min(5000));
+            assert_eq!(chunks(&results), full_sort(&query, &data));
+            assert!(results.windows(2).all(|w| w[0].score >= w[1].score));
+        }
+    }
+}
+
+#[test]
+fn test_topk_zero_returns_nothing() {
+    let data = vector_data((0..10).map(|i| random_values(4, i)).collect());
+    let query = SearchQuery::new(0, vec![1.0; 4], Metrics::Cosine);
+    assert!(query.search(&data).unwrap().is_empty());
+}
+
+#[test]
+fn test_topk_ties_keep_earliest_positions() {
+    // Identical vectors score identically; the earliest positions win, as with a stable sort
+    let data = vector_data(vec![vec![1.0, 0.0]; 1000]);
+    let query = SearchQuery::new(5, vec![1.0, 0.0], Metrics::DotProduct);
+
+    assert_eq!(
+        chunks(&query.search(&data).unwrap()),
+        vec!["chunk 0", "chunk 1", "chunk 2", "chunk 3", "chunk 4"]
+    );
+}
+
+#[test]
+fn test_topk_ranks_nan_scores_last() {
+    let mut embedding: Vec<Vec<f32>> = (0..50).map(|i| random_values(4, i)).collect();
+    embedding[3] = vec![f32::NAN; 4];
+    embedding[40] = vec![f32::NAN; 4];
+    let data = vector_data(embedding);
+
+    let query = SearchQuery::new(10, vec![0.5; 4], Metrics::Euclidean);
+    let results = query.search(&data).unwrap();
+    assert!(results.iter().all(|r| !r.score.is_nan()));
+
+    let everything = SearchQuery::new(50, vec![0.5; 4], Metrics::Euclidean)
+        .search(&data)
+        .unwrap();
+    assert!(everything[48].score.is_nan() && everything[49].score.is_nan());
+    assert_eq!(everything[48].chunk, "chunk 3");
+}
@@ -0,0 +1,243 @@
+mod common;
+use blaze_db::prelude::{EmbeddingStore, VectorData};
+use blaze_db::utils::{WAL_FILE_NAME, Wal};
+use common::item;
+use std::io::Write;
+use tempfile::tempdir;
+
+fn bin_files(dir: &std::path::Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".bin"))
+        .collect();
+    names.sort();
+    names
+}
+
+#[tokio::test]
+async fn test_upsert_is_logged_and_replayed_on_open() {
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path().to_str().unwrap();
+
+    let mut data = EmbeddingStore::open(dir_path).await.unwrap();
+    EmbeddingStore::upsert(dir_path, &mut data, vec![item("a", "a", vec![1.0, 0.0])])
+        .await
+        .unwrap();
+    EmbeddingStore::delete(dir_path, &mut data, vec!["a".to_string()])
+        .await
+        .unwrap();
+    EmbeddingStore::upsert(dir_path, &mut data, vec![item("b", "b", vec![0.0, 1.0])])
+        .await
+        .unwrap();
+
+    // Small writes stay in the log until a checkpoint
+    assert!(bin_files(dir.path()).is_empty());
+    assert_eq!(Wal::replay(dir_path).await.unwrap().stores.len(), 3);
+
+    let reopened = EmbeddingStore::open(dir_path).await.unwrap();
+    assert_eq!(reopened.total_vectors, 1);
+    assert_eq!(reopened.position("b"), Some(1));
+    assert!(reopened.is_deleted(0));
+    assert_eq!(reopened.next_batch_index, 3);
+}
+
+#[tokio::test]
+async fn test_checkpoint_writes_batches_and_empties_log() {
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path().to_str().unwrap();
+
+    let mut data = VectorData::default();
+    EmbeddingStore::upsert(dir_path, &mut data, vec![item("a", "a", vec![1.0])])
+        .await
+        .unwrap();
+    EmbeddingStore::upsert(dir_path, &mut data, vec![item("b", "b", vec![2.0])])
+        .await
+        .unwrap();
+
+    assert_eq!(Wal::checkpoint(dir_path).await.unwrap(), 2);
+    assert_eq!(
+        bin_files(dir.path()),
+        vec!["embeddings_batch_0.bin", "embeddings_batch_1.bin"]
+    );
+    assert!(Wal::replay(dir_path).await.unwrap().stores.is_empty());
+
+    // A second checkpoint has nothing left to do
+    assert_eq!(Wal::checkpoint(dir_path).await.unwrap(), 0);
+    let reloaded = EmbeddingStore::read_binary(dir_path).await.unwrap();
+    assert_eq!(reloaded.total_vectors, 2);
+}
+
+#[tokio::test]
+async fn test_replay_skips_batches_already_checkpointed() {
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path().to_str().unwrap();
+
+    // Simulate a crash after the batch file was written but before the log was emptied
+    let mut store = EmbeddingStore::new(0, vec![item("a", "a", vec![1.0])]);
+    Wal::append(dir_path, &mut store).await.unwrap();
+    store.write_batch(dir_path).await.unwrap();
+
+    let data = EmbeddingStore::open(dir_path).await.unwrap();
+    assert_eq!(data.total_vectors, 1);
+    assert_eq!(data.embedding.len(), 1);
+}
+
+#[tokio::test]
+async fn test_torn_log_tail_is_discarded() {
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path().to_str().unwrap();
+
+    let mut data = VectorData::default();
+    EmbeddingStore::upsert(dir_path, &mut data, vec![item("a", "a", vec![1.0])])
+        .await
+        .unwrap();
+    let complete_len = std::fs::metadata(dir.path().join(WAL_FILE_NAME))
+        .unwrap()
+        .len();
+
+    // A record cut short by a crash mid-append
+    let mut log = std::fs::OpenOptions::new()
+        .append(true)
+        .open(dir.path().join(WAL_FILE_NAME))
+        .unwrap();
+    log.write_all(&[200, 0, 0, 0, 1, 2, 3]).unwrap();
+    drop(log);
+
+    let log_len = || {
+        std::fs::metadata(dir.path().join(WAL_FILE_NAME))
+            .unwrap()
+            .len()
+    };
+
+    // Readers skip the tail but leave the log alone: it may be an append in progress
+    let read = EmbeddingStore::read_binary(dir_path).await.unwrap();
+    assert_eq!(read.total_vectors, 1);
+    assert_eq!(read.discarded_log_bytes, 7);
+    assert_eq!(log_len(), complete_len + 7);
+    assert_eq!(Wal::replay(dir_path).await.unwrap().discarded, 7);
+
+    // Writers truncate it when opening the store
+    let mut data = EmbeddingStore::open(dir_path).await.unwrap();
+    assert_eq!(data.total_vectors, 1);
+    assert_eq!(data.discarded_log_bytes, 7);
+    assert_eq!(log_len(), complete_len);
+
+    // Appends after recovery are readable again
+    EmbeddingStore::upsert(dir_path, &mut data, vec![item("b", "b", vec![2.0])])
+        .await
+        .unwrap();
+    let reopened = EmbeddingStore::open(dir_path).await.unwrap();
+    assert_eq!(reopened.total_vectors, 2);
+    assert_eq!(reopened.discarded_log_bytes, 0);
+}
+
+#[tokio::test]
+async fn test_concurrent_appends_survive_checkpoints() {
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path().to_str().unwrap().to_string();
+
+    let mut tasks = Vec::new();
+    for batch in 0..40 {
+        let dir_path = dir_path.clone();
+        tasks.push(tokio::spawn(async move {
+            let mut store =
+                EmbeddingStore::new(batch, vec![item(&format!("v{}", batch), "v", vec![1.0])]);
+            Wal::append(&dir_path, &mut store).await.unwrap();
+            if batch % 10 == 0 {
+                Wal::checkpoint(&dir_path).await.unwrap();
+            }
+        }));
+    }
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    // Every record is either in a batch file or still in the log
+    let data = EmbeddingStore::read_binary(&dir_path).await.unwrap();
+    assert_eq!(data.total_vectors, 40);
+    assert_eq!(data.discarded_log_bytes, 0);
+}
+
+#[tokio::test]
+async fn test_open_removes_temp_files_and_rejects_corrupt_batches() {
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path().to_str().unwrap();
+
+    EmbeddingStore::new(0, vec![item("a", "a", vec![1.0])])
+        .write_batch(dir_path)
+        .await
+        .unwrap();
+    std::fs::write(dir.path().join("embeddings_batch_1.bin.tmp"), b"partial").unwrap();
+
+    let data = EmbeddingStore::open(dir_path).await.unwrap();
+    assert_eq!(data.total_vectors, 1);
+    assert!(!dir.path().join("embeddings_batch_1.bin.tmp").exists());
+
+    // A damaged batch is an error rather than silently dropped data
+    std::fs::write(dir.path().join("embeddings_batch_1.bin"), b"garbage").unwrap();
+    let error = EmbeddingStore::open(dir_path).await.unwrap_err();
+    assert!(format!("{:#}", error).contains("embeddings_batch_1.bin"));
+}
+
+#[tokio::test]
+async fn test_writers_with_stale_state_get_distinct_batch_indexes() {
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path().to_str().unwrap();
+
+    // e.g. a server and a CLI ingest, each holding its own view of the store
+    let mut server = EmbeddingStore::open(dir_path).await.unwrap();
+    let mut cli = EmbeddingStore::open(dir_path).await.unwrap();
+    EmbeddingStore::upsert(dir_path, &mut server, vec![item("a", "a", vec![1.0])])
+        .await
+        .unwrap();
+    EmbeddingStore::upsert(dir_path, &mut cli, vec![item("b", "b", vec![2.0])])
+        .await
+        .unwrap();
+    assert_eq!(server.next_batch_index, 1);
+    assert_eq!(cli.next_batch_index, 2);
+
+    assert_eq!(Wal::checkpoint(dir_path).await.unwrap(), 2);
+    EmbeddingStore::delete(dir_path, &mut server, vec!["b".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(server.next_batch_index, 3);
+
+    let reloaded = EmbeddingStore::read_binary(dir_path).await.unwrap();
+    assert_eq!(reloaded.total_vectors, 1);
+    assert!(reloaded.position("a").is_some());
+    assert_eq!(reloaded.position("b"), None);
+}
+
+#[tokio::test]
+async fn test_logged_batch_colliding_with_a_file_is_kept() {
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path().to_str().unwrap();
+
+    // A log written before indexes were allocated under the lock
+    EmbeddingStore::new(0, vec![item("a", "a", vec![1.0])])
+        .write_batch(dir_path)
+        .await
+        .unwrap();
+    let payload =
+        bincode::serialize(&EmbeddingStore::new(0, vec![item("b", "b", vec![2.0])])).unwrap();
+    let checksum = payload.iter().fold(0xcbf29ce484222325u64, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    let mut record = (payload.len() as u32).to_le_bytes().to_vec();
+    record.extend_from_slice(&checksum.to_le_bytes());
+    record.extend_from_slice(&payload);
+    std::fs::write(dir.path().join(WAL_FILE_NAME), record).unwrap();
+
+    let read = EmbeddingStore::read_binary(dir_path).await.unwrap();
+    assert_eq!(read.total_vectors, 2);
+
+    assert_eq!(Wal::checkpoint(dir_path).await.unwrap(), 1);
+    assert_eq!(
+        bin_files(dir.path()),
+        vec!["embeddings_batch_0.bin", "embeddings_batch_1.bin"]
+    );
+    let reloaded = EmbeddingStore::read_binary(dir_path).await.unwrap();
+    assert_eq!(reloaded.total_vectors, 2);
+    assert!(reloaded.position("b").is_some());
+}